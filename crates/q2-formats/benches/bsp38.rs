@@ -0,0 +1,114 @@
+//! Parser and mesh building benchmarks.
+//!
+//! Runs against every `.bsp` found in the workspace `assets/` directory (the
+//! maps aren't checked in, see `make ensure-data`). Run with `cargo bench`.
+
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use q2_formats::bsp38::{
+    prelude::{MeshBuilder, OcclusionOptions},
+    BSP38,
+};
+
+fn load_maps() -> Vec<(String, Vec<u8>)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../assets");
+    let mut maps = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("bsp") {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            maps.push((name, std::fs::read(&path).unwrap()));
+        }
+    }
+    maps.sort_by(|a, b| a.0.cmp(&b.0));
+    if maps.is_empty() {
+        eprintln!(
+            "No .bsp files found in {}; nothing to benchmark",
+            dir.display()
+        );
+    }
+    maps
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let maps = load_maps();
+
+    let mut group = c.benchmark_group("from_bytes");
+    for (name, bytes) in &maps {
+        group.bench_with_input(BenchmarkId::from_parameter(name), bytes, |b, bytes| {
+            b.iter(|| BSP38::from_bytes(black_box(bytes.clone())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_readers(c: &mut Criterion) {
+    let maps = load_maps();
+    let bsps: Vec<_> = maps
+        .into_iter()
+        .map(|(name, bytes)| (name, BSP38::from_bytes(bytes).unwrap()))
+        .collect();
+
+    let mut group = c.benchmark_group("read");
+    for (name, bsp) in &bsps {
+        group.bench_with_input(BenchmarkId::new("vertices", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_vertices().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("edges", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_edges().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("edge_points", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_edge_points().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("face_edges", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_face_edges().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("planes", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_planes().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("texture_info", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_texture_info().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("vis_matrix", name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_vis_matrix().unwrap())
+        });
+    }
+    group.finish();
+
+    // Full triangulation, including the readers it depends on
+    let mut group = c.benchmark_group("read_faces");
+    group.sample_size(20);
+    for (name, bsp) in &bsps {
+        group.bench_with_input(BenchmarkId::from_parameter(name), bsp, |b, bsp| {
+            b.iter(|| bsp.read_faces().unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("reused_builder", name), bsp, |b, bsp| {
+            let mut builder = MeshBuilder::with_capacity_for(bsp);
+            b.iter(|| {
+                builder.build(bsp);
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("bake_occlusion");
+    group.sample_size(10);
+    for (name, bsp) in &bsps {
+        let tracer = bsp.tracer();
+        let data = bsp.read_faces().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+            b.iter(|| {
+                data.clone()
+                    .bake_occlusion(&tracer, &OcclusionOptions::default())
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_readers);
+criterion_main!(benches);
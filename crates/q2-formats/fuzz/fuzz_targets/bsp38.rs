@@ -0,0 +1,62 @@
+//! Parses arbitrary bytes as a BSP and runs every reader over the result.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run bsp38`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::bsp38::{
+    prelude::{FaceMetric, MeshBuilder, Strip},
+    LumpIndex, BSP38,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(bsp) = BSP38::from_bytes(data.to_vec()) else {
+        return;
+    };
+    let _ = bsp.lump_table();
+    let _ = bsp.read_vertices();
+    let _ = bsp.read_planes();
+    let _ = bsp.read_edges();
+    let _ = bsp.read_edge_points();
+    let _ = bsp.read_face_edges();
+    let _ = bsp.read_texture_info();
+    let _ = bsp.read_face_records();
+    let _ = bsp.read_faces();
+    let _ = bsp.read_entities();
+    let _ = bsp.read_vis_matrix();
+    let _ = bsp.read_nodes();
+    let _ = bsp.read_leafs();
+    let _ = bsp.read_models();
+    let _ = bsp.model_collision(-1);
+    for metric in FaceMetric::ALL {
+        let _ = bsp.face_metric(metric);
+    }
+    let _ = bsp.area_graph().areas_connected(1, 2, &[]);
+    let bounds = bsp.bounds();
+    let _ = bsp.faces_in_bounds(bounds);
+    let tracer = bsp.tracer();
+    let _ = tracer.trace_line(bounds.min, bounds.max, -1);
+    let _ = tracer.trace_box(bounds.min, bounds.max, [16.0; 3], -1);
+    let _ = tracer.trace_ray(bounds.max, bounds.min, -1);
+    let _ = tracer.area_at(bounds.min);
+    let _ = bsp.read_vis_matrix().map(|vis| vis.stats());
+    let _ = bsp.leaf_visibility();
+    let _ = bsp.area_portal_state();
+    let _ = bsp.cluster_areas();
+    let _ = bsp.collision_proxy(-1);
+    let _ = bsp.read_lights();
+    let _ = bsp.texture_animations();
+    let _ = bsp.check_references();
+    let _ = bsp.report();
+    let _ = bsp.flow_report();
+    let atlas = bsp.read_lightmaps();
+    let mut builder = MeshBuilder::new().with_lightmaps(&atlas).with_chunks(512.0);
+    let _ = builder.build_batches(&bsp, 4);
+    let _ = builder.build_translucent_batches(&bsp);
+    let _ = builder.build_model_batches(&bsp, 1);
+    let stripped = bsp.write_stripped(&[(LumpIndex::Visibility, Strip::Zero)]);
+    if let Ok(stripped) = BSP38::from_bytes(stripped) {
+        let _ = stripped.read_vis_matrix();
+    }
+});
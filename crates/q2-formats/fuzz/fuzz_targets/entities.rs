@@ -0,0 +1,12 @@
+//! Tokenizes and parses arbitrary text as an entity string.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run entities`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::bsp38::prelude::parse_entities;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse_entities(&String::from_utf8_lossy(data));
+});
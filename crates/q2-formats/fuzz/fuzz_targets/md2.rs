@@ -0,0 +1,22 @@
+//! Reads arbitrary bytes as an MD2 model and builds the mesh of every
+//! frame.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run md2`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::md2::Md2;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(md2) = Md2::from_bytes(data) else {
+        return;
+    };
+    let layout = md2.mesh_layout();
+    let _ = md2.mesh_uvs(&layout);
+    for frame in 0..md2.frames.len() {
+        let _ = md2.mesh_positions(frame, &layout);
+        let _ = md2.mesh_normals(frame, &layout);
+    }
+    let _ = md2.animations();
+});
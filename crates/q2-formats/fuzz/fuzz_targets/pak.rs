@@ -0,0 +1,16 @@
+//! Reads arbitrary bytes as a PAK archive and extracts every entry.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run pak`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::pak::PakArchive;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut archive) = PakArchive::from_bytes(data.to_vec()) {
+        for entry in archive.entries().to_vec() {
+            let _ = archive.read_entry(&entry);
+        }
+    }
+});
@@ -0,0 +1,14 @@
+//! Decodes arbitrary bytes as a PCX image and converts it to RGBA.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run pcx`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::pcx::Pcx;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pcx) = Pcx::from_bytes(data) {
+        let _ = pcx.to_rgba();
+    }
+});
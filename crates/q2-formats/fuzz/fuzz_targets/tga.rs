@@ -0,0 +1,12 @@
+//! Decodes arbitrary bytes as a TGA image.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run tga`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::tga::Tga;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Tga::from_bytes(data);
+});
@@ -0,0 +1,18 @@
+//! Reads arbitrary bytes as a WAL texture and as a palette, and converts
+//! every mip level to RGBA.
+//!
+//! Run from `crates/q2-formats` with `cargo +nightly fuzz run wal`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use q2_formats::wal::{Palette, Wal, MIP_LEVELS};
+
+fuzz_target!(|data: &[u8]| {
+    let palette = Palette::from_pcx(data).unwrap_or_else(|_| Palette::grayscale());
+    if let Ok(wal) = Wal::from_bytes(data) {
+        for level in 0..MIP_LEVELS {
+            let _ = wal.to_rgba(level, &palette);
+        }
+    }
+});
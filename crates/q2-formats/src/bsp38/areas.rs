@@ -0,0 +1,261 @@
+use std::collections::{HashSet, VecDeque};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// An area from the Areas lump: a region of the map closed off from the
+/// others except through area portals. Area 0 is unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub num_area_portals: u32,
+    pub first_area_portal: u32,
+}
+
+/// An entry of the AreaPortals lump: a portal leading out of an area.
+///
+/// Each portal is listed once by each of the two areas it connects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AreaPortal {
+    /// Number of the portal, matching the `style` of the `func_areaportal`
+    /// entity that opens and closes it.
+    pub portal: u32,
+    /// The area on the other side.
+    pub other_area: u32,
+}
+
+/// Which areas are connected through which portals.
+#[derive(Debug, Clone, Default)]
+pub struct AreaGraph {
+    portals: Vec<Vec<AreaPortal>>,
+}
+
+impl AreaGraph {
+    pub fn num_areas(&self) -> usize {
+        self.portals.len()
+    }
+
+    /// The portals leading out of `area`.
+    pub fn portals(&self, area: usize) -> &[AreaPortal] {
+        self.portals
+            .get(area)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether `a` can be reached from `b` without passing through any of the
+    /// `closed` portals. An area is always connected to itself.
+    pub fn areas_connected(&self, a: usize, b: usize, closed: &[u32]) -> bool {
+        if a == b {
+            return true;
+        }
+        let mut seen = vec![false; self.num_areas()];
+        let mut queue = VecDeque::from([a]);
+        while let Some(area) = queue.pop_front() {
+            for portal in self.portals(area) {
+                if closed.contains(&portal.portal) {
+                    continue;
+                }
+                let other = portal.other_area as usize;
+                if other == b {
+                    return true;
+                }
+                if other < seen.len() && !std::mem::replace(&mut seen[other], true) {
+                    queue.push_back(other);
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Which area portals are open, with the areas they connect, as the game
+/// keeps them: every portal starts closed and is opened while the door (or
+/// other entity) on it is open. Areas only see into each other while
+/// connected.
+///
+/// ```
+/// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+///
+/// let bytes = TestMapBuilder::room([0.0; 3], [64.0; 3])
+///     .with_area_portal(1, 2)
+///     .build();
+/// let mut state = BSP38::from_bytes(bytes).unwrap().area_portal_state();
+/// assert!(!state.areas_connected(1, 2));
+/// state.set_portal_open(1, true);
+/// assert!(state.areas_connected(1, 2));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AreaPortalState {
+    graph: AreaGraph,
+    /// Numbers of the portals in the map. Kept as a set, since the numbers
+    /// come from the file and can be anything.
+    portals: HashSet<u32>,
+    /// Numbers of the open portals.
+    open: HashSet<u32>,
+    /// The group of connected areas each area is in, None for area 0.
+    groups: Vec<Option<usize>>,
+}
+
+impl AreaPortalState {
+    /// The state of `graph` with every portal closed.
+    pub fn new(graph: AreaGraph) -> Self {
+        let portals = (0..graph.num_areas())
+            .flat_map(|area| graph.portals(area))
+            .map(|portal| portal.portal)
+            .collect();
+        let mut state = Self {
+            graph,
+            portals,
+            open: HashSet::new(),
+            groups: Vec::new(),
+        };
+        state.flood();
+        state
+    }
+
+    pub fn is_portal_open(&self, portal: u32) -> bool {
+        self.open.contains(&portal)
+    }
+
+    /// Opens or closes `portal`, as a door on it opens or closes. Portals
+    /// missing from the map are ignored.
+    pub fn set_portal_open(&mut self, portal: u32, open: bool) {
+        if !self.portals.contains(&portal) {
+            return;
+        }
+        let changed = match open {
+            true => self.open.insert(portal),
+            false => self.open.remove(&portal),
+        };
+        if changed {
+            self.flood();
+        }
+    }
+
+    /// Whether `a` and `b` are connected through open portals. An area is
+    /// connected to itself, and area 0 (outside of any area) to every area.
+    pub fn areas_connected(&self, a: usize, b: usize) -> bool {
+        if a == b || a == 0 || b == 0 {
+            return true;
+        }
+        match (self.groups.get(a), self.groups.get(b)) {
+            (Some(Some(a)), Some(Some(b))) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Numbers the groups of areas connected through open portals.
+    fn flood(&mut self) {
+        let num_areas = self.graph.num_areas();
+        self.groups = vec![None; num_areas];
+        let mut group = 0;
+        for start in 1..num_areas {
+            if self.groups[start].is_some() {
+                continue;
+            }
+            self.groups[start] = Some(group);
+            let mut stack = vec![start];
+            while let Some(area) = stack.pop() {
+                for portal in self.graph.portals(area) {
+                    let other = portal.other_area as usize;
+                    if !self.is_portal_open(portal.portal) || other == 0 || other >= num_areas {
+                        continue;
+                    }
+                    if self.groups[other].is_none() {
+                        self.groups[other] = Some(group);
+                        stack.push(other);
+                    }
+                }
+            }
+            group += 1;
+        }
+    }
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn read_areas(&self) -> Result<Vec<Area>, BspError> {
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Areas)?;
+        let num_areas = cursor.get_ref().len() / 8;
+        (0..num_areas)
+            .map(|_| {
+                Ok(Area {
+                    num_area_portals: cursor.read_u32::<LittleEndian>()?,
+                    first_area_portal: cursor.read_u32::<LittleEndian>()?,
+                })
+            })
+            .collect()
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_area_portals(&self) -> Result<Vec<AreaPortal>, BspError> {
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::AreaPortals)?;
+        let num_portals = cursor.get_ref().len() / 8;
+        (0..num_portals)
+            .map(|_| {
+                Ok(AreaPortal {
+                    portal: cursor.read_u32::<LittleEndian>()?,
+                    other_area: cursor.read_u32::<LittleEndian>()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Builds the area adjacency graph from the Areas and AreaPortals lumps.
+    ///
+    /// Portal ranges reaching past the end of the AreaPortals lump are cut
+    /// short.
+    pub fn area_graph(&self) -> AreaGraph {
+        let portals = self.read_area_portals().unwrap_or_default();
+        let portals = self
+            .read_areas()
+            .unwrap_or_default()
+            .iter()
+            .map(|area| {
+                let first = (area.first_area_portal as usize).min(portals.len());
+                let end = first
+                    .saturating_add(area.num_area_portals as usize)
+                    .min(portals.len());
+                portals[first..end].to_vec()
+            })
+            .collect();
+        AreaGraph { portals }
+    }
+
+    /// Whether areas `a` and `b` are connected with every area portal open,
+    /// which is whether they can ever see each other. See
+    /// [BSP38::area_portal_state] for portals opening and closing.
+    pub fn areas_connected(&self, a: usize, b: usize) -> bool {
+        self.area_graph().areas_connected(a, b, &[])
+    }
+
+    /// The [AreaPortalState] of the map as it starts, with every portal
+    /// closed.
+    pub fn area_portal_state(&self) -> AreaPortalState {
+        AreaPortalState::new(self.area_graph())
+    }
+
+    /// The area of each PVS cluster, that of its first leaf, or 0 for
+    /// clusters without leafs. A cluster doesn't span areas, as area portals
+    /// split the clusters too.
+    pub fn cluster_areas(&self) -> Vec<usize> {
+        let leafs = self.read_leafs().unwrap_or_default();
+        let clusters = leafs
+            .iter()
+            .filter_map(|leaf| usize::try_from(leaf.cluster).ok())
+            .map(|cluster| cluster + 1)
+            .max()
+            .unwrap_or(0);
+        let mut areas = vec![0; clusters];
+        for leaf in leafs.iter().rev() {
+            if let (Ok(cluster), Ok(area)) =
+                (usize::try_from(leaf.cluster), usize::try_from(leaf.area))
+            {
+                areas[cluster] = area;
+            }
+        }
+        areas
+    }
+}
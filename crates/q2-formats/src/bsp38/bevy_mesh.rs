@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use bevy_render::{
+    mesh::{Indices, Mesh, PrimitiveTopology},
+    render_asset::RenderAssetUsages,
+};
+
+use super::FaceData;
+
+/// Controls the conversion of [FaceData] into a Bevy [Mesh].
+#[derive(Debug, Clone, Copy)]
+pub struct MeshOptions {
+    /// Include the per-vertex debug colors.
+    pub colors: bool,
+    /// Merge identical vertices and add an index buffer. Fan triangulation
+    /// repeats every polygon corner, so this roughly halves the vertex count
+    /// at the cost of a hash lookup per vertex.
+    pub indexed: bool,
+    pub asset_usage: RenderAssetUsages,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        Self {
+            colors: true,
+            indexed: false,
+            asset_usage: RenderAssetUsages::default(),
+        }
+    }
+}
+
+impl FaceData {
+    /// Builds a triangle list mesh with positions, normals, texture UVs and,
+    /// when present, lightmap UVs.
+    pub fn into_mesh(self, options: &MeshOptions) -> Mesh {
+        let vertices = self.points.len() / 3;
+        let vec3 = |data: &[f32], i: usize| [data[i * 3], data[i * 3 + 1], data[i * 3 + 2]];
+        let vec2 = |data: &[f32], i: usize| [data[i * 2], data[i * 2 + 1]];
+        let colors = options.colors && self.colors.len() == self.points.len();
+        let uv1 = self.uv1.len() / 2 == vertices;
+
+        // Vertices to keep, in output order, and the index of each input vertex
+        let (kept, indices): (Vec<usize>, Option<Vec<u32>>) = if options.indexed {
+            let mut kept = Vec::new();
+            let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
+            let indices = (0..vertices)
+                .map(|i| {
+                    let mut key: Vec<u32> = vec3(&self.points, i)
+                        .into_iter()
+                        .chain(vec3(&self.normals, i))
+                        .chain(vec2(&self.uv, i))
+                        .map(f32::to_bits)
+                        .collect();
+                    if uv1 {
+                        key.extend(vec2(&self.uv1, i).map(f32::to_bits));
+                    }
+                    if colors {
+                        key.extend(vec3(&self.colors, i).map(f32::to_bits));
+                    }
+                    *seen.entry(key).or_insert_with(|| {
+                        kept.push(i);
+                        (kept.len() - 1) as u32
+                    })
+                })
+                .collect();
+            (kept, Some(indices))
+        } else {
+            ((0..vertices).collect(), None)
+        };
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, options.asset_usage);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            kept.iter()
+                .map(|&i| vec3(&self.points, i))
+                .collect::<Vec<_>>(),
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            kept.iter()
+                .map(|&i| vec3(&self.normals, i))
+                .collect::<Vec<_>>(),
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            kept.iter().map(|&i| vec2(&self.uv, i)).collect::<Vec<_>>(),
+        );
+        if uv1 {
+            mesh.insert_attribute(
+                Mesh::ATTRIBUTE_UV_1,
+                kept.iter().map(|&i| vec2(&self.uv1, i)).collect::<Vec<_>>(),
+            );
+        }
+        if colors {
+            mesh.insert_attribute(
+                Mesh::ATTRIBUTE_COLOR,
+                kept.iter()
+                    .map(|&i| {
+                        let [r, g, b] = vec3(&self.colors, i);
+                        [r, g, b, 1.0]
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        if let Some(indices) = indices {
+            mesh.insert_indices(Indices::U32(indices));
+        }
+        mesh
+    }
+}
+
+impl From<FaceData> for Mesh {
+    fn from(data: FaceData) -> Self {
+        data.into_mesh(&MeshOptions::default())
+    }
+}
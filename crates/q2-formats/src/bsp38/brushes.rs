@@ -0,0 +1,62 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// A brush from the Brushes lump: a convex volume bounded by its sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Brush {
+    pub first_side: u32,
+    pub num_sides: u32,
+    /// Content flags, see [contents](super::contents).
+    pub contents: i32,
+}
+
+impl Brush {
+    /// Range of the BrushSides lump holding the sides of this brush.
+    pub fn sides(&self) -> std::ops::Range<usize> {
+        let first = self.first_side as usize;
+        first..first.saturating_add(self.num_sides as usize)
+    }
+}
+
+/// A side of a brush, from the BrushSides lump. The plane's normal points out
+/// of the brush.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrushSide {
+    pub plane: u16,
+    /// Texinfo of the side, or -1 for none.
+    pub texinfo: i16,
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn read_brushes(&self) -> Result<Vec<Brush>, BspError> {
+        const BRUSH_SIZE: usize = 12;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Brushes)?;
+        let num_brushes = cursor.get_ref().len() / BRUSH_SIZE;
+        (0..num_brushes)
+            .map(|_| {
+                Ok(Brush {
+                    first_side: cursor.read_u32::<LittleEndian>()?,
+                    num_sides: cursor.read_u32::<LittleEndian>()?,
+                    contents: cursor.read_i32::<LittleEndian>()?,
+                })
+            })
+            .collect()
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_brush_sides(&self) -> Result<Vec<BrushSide>, BspError> {
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::BrushSides)?;
+        let num_sides = cursor.get_ref().len() / 4;
+        (0..num_sides)
+            .map(|_| {
+                Ok(BrushSide {
+                    plane: cursor.read_u16::<LittleEndian>()?,
+                    texinfo: cursor.read_i16::<LittleEndian>()?,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{contents::CONTENTS_DETAIL, FaceData, LumpIndex, MeshBuilder, BSP38};
+
+/// How far outside of a brush plane a corner may be and still count as on
+/// the brush, to absorb rounding in the plane intersections.
+const HULL_EPSILON: f32 = 0.01;
+
+/// Most sides of a brush [BSP38::model_collision] builds a hull for. The
+/// game's brushes have a few dozen at most, even with their bevel planes,
+/// and finding the corners takes the fourth power of the count.
+const MAX_BRUSH_SIDES: usize = 64;
+
+/// Grid the corners of [BSP38::collision_proxy] snap to, in steps per unit,
+/// so brushes that meet share corners after rounding.
+const PROXY_GRID: f32 = 32.0;
+
+/// Faces as an indexed triangle mesh, with shared corners welded, for physics
+/// and ray queries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollisionMesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl CollisionMesh {
+    pub fn from_face_data(data: &FaceData) -> Self {
+        Self::from_points(&data.points)
+    }
+
+    /// Welds a flat list of triangle corners, three floats each.
+    pub fn from_points(points: &[f32]) -> Self {
+        let mut mesh = Self::default();
+        let mut ids: HashMap<[u32; 3], u32> = HashMap::new();
+        let corners: Vec<u32> = points
+            .chunks_exact(3)
+            .map(|p| {
+                let p = [p[0], p[1], p[2]];
+                *ids.entry(p.map(f32::to_bits)).or_insert_with(|| {
+                    mesh.vertices.push(p);
+                    (mesh.vertices.len() - 1) as u32
+                })
+            })
+            .collect();
+        mesh.triangles = corners
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        mesh
+    }
+}
+
+/// A brush as a convex volume: the points behind all of its planes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvexHull {
+    /// Content flags of the brush, see [contents](super::contents).
+    pub contents: i32,
+    /// Planes as (normal, distance), normals pointing out of the volume.
+    pub planes: Vec<[f32; 4]>,
+    /// Corners of the volume, for engines that build hulls from points.
+    pub vertices: Vec<[f32; 3]>,
+}
+
+/// Collision shapes of one model, in map coordinates.
+///
+/// Both representations use plain arrays, so they can be handed to any
+/// physics engine: `hulls` as convex colliders (exact, and what the game
+/// itself collides against) or `mesh` as a single triangle mesh collider.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelCollision {
+    pub hulls: Vec<ConvexHull>,
+    pub mesh: CollisionMesh,
+}
+
+impl BSP38 {
+    /// Collision shapes of every model, in Models lump order.
+    ///
+    /// The hulls are the brushes with any of the `mask` contents (see
+    /// [contents](super::contents)) in the leafs under the model's head node;
+    /// brushes whose sides don't enclose a volume, or with more than
+    /// [MAX_BRUSH_SIDES], are skipped. The mesh holds the model's faces.
+    #[instrument(skip_all)]
+    pub fn model_collision(&self, mask: i32) -> Vec<ModelCollision> {
+        let nodes = self.read_nodes().unwrap_or_default();
+        let leafs = self.read_leafs().unwrap_or_default();
+        let leaf_brushes = self.read_leaf_brushes().unwrap_or_default();
+        let brushes = self.read_brushes().unwrap_or_default();
+        let sides = self.read_brush_sides().unwrap_or_default();
+        let planes = self.read_planes().unwrap_or_default();
+        let num_faces = self.lump_info(LumpIndex::Faces).count.unwrap_or(0);
+        let mut builder = MeshBuilder::new();
+
+        self.read_models()
+            .unwrap_or_default()
+            .iter()
+            .map(|model| {
+                // Malformed trees can contain cycles, so each node is only
+                // entered once
+                let mut visited = vec![false; nodes.len()];
+                let mut in_model = vec![false; brushes.len()];
+                let mut stack = vec![model.head_node];
+                while let Some(child) = stack.pop() {
+                    if child < 0 {
+                        let Some(leaf) = leafs.get((-1 - child) as usize) else {
+                            continue;
+                        };
+                        for brush in leaf.brushes(&leaf_brushes) {
+                            if let Some(slot) = in_model.get_mut(brush) {
+                                *slot = true;
+                            }
+                        }
+                        continue;
+                    }
+                    let Some(node) = nodes.get(child as usize) else {
+                        continue;
+                    };
+                    if !std::mem::replace(&mut visited[child as usize], true) {
+                        stack.extend(node.children);
+                    }
+                }
+
+                let hulls = brushes
+                    .iter()
+                    .zip(in_model)
+                    .filter(|(brush, in_model)| *in_model && brush.contents & mask != 0)
+                    .filter_map(|(brush, _)| {
+                        let planes = sides
+                            .get(brush.sides())
+                            .filter(|sides| sides.len() <= MAX_BRUSH_SIDES)?
+                            .iter()
+                            .map(|side| planes.get(side.plane as usize).copied())
+                            .collect::<Option<Vec<_>>>()?;
+                        let vertices = hull_vertices(&planes);
+                        (vertices.len() >= 4).then_some(ConvexHull {
+                            contents: brush.contents,
+                            planes,
+                            vertices,
+                        })
+                    })
+                    .collect();
+
+                // The face count comes from the file, so only faces in the
+                // Faces lump are collected
+                let faces = model.faces();
+                let faces: Vec<usize> = (faces.start..faces.end.min(num_faces)).collect();
+                ModelCollision {
+                    hulls,
+                    mesh: CollisionMesh::from_face_data(builder.build_faces(self, &faces)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl BSP38 {
+    /// A simplified stand-in for the world, for physics or occlusion in
+    /// engines that can't read BSP files: the sides of the world's brushes
+    /// with any of the `mask` contents, as one welded triangle mesh in map
+    /// coordinates.
+    ///
+    /// Unlike the drawn faces it is made of closed brush volumes, without
+    /// detail brushes ([CONTENTS_DETAIL]) or sides buried in another brush,
+    /// so it has few holes and no decoration. Inline models aren't included.
+    #[instrument(skip_all)]
+    pub fn collision_proxy(&self, mask: i32) -> CollisionMesh {
+        let Some(world) = self.model_collision(mask).into_iter().next() else {
+            return CollisionMesh::default();
+        };
+        let hulls: Vec<&ConvexHull> = world
+            .hulls
+            .iter()
+            .filter(|hull| hull.contents & CONTENTS_DETAIL == 0)
+            .collect();
+
+        let mut points = Vec::new();
+        for (i, hull) in hulls.iter().enumerate() {
+            for &plane in &hull.planes {
+                let side = hull_side(hull, plane);
+                if side.len() < 3 {
+                    continue;
+                }
+                let center = side.iter().sum::<Vec3A>() / side.len() as f32;
+                let buried = hulls
+                    .iter()
+                    .enumerate()
+                    .any(|(j, other)| i != j && buries(other, center, plane));
+                if buried {
+                    continue;
+                }
+                // Adding zero turns -0.0 into 0.0, which welds apart
+                let snap = |p: Vec3A| (p * PROXY_GRID).round() / PROXY_GRID + Vec3A::ZERO;
+                for k in 2..side.len() {
+                    for p in [side[0], side[k - 1], side[k]] {
+                        points.extend_from_slice(&snap(p).to_array());
+                    }
+                }
+            }
+        }
+        CollisionMesh::from_points(&points)
+    }
+}
+
+/// The corners of `hull` on `plane`, counterclockwise seen from outside.
+fn hull_side(hull: &ConvexHull, [x, y, z, dist]: [f32; 4]) -> Vec<Vec3A> {
+    let normal = Vec3A::new(x, y, z);
+    let mut corners: Vec<Vec3A> = hull
+        .vertices
+        .iter()
+        .map(|&v| Vec3A::from(v))
+        .filter(|v| (normal.dot(*v) - dist).abs() <= HULL_EPSILON)
+        .collect();
+    if corners.is_empty() {
+        return corners;
+    }
+    let center = corners.iter().sum::<Vec3A>() / corners.len() as f32;
+    let u = normal.any_orthonormal_vector();
+    let v = normal.cross(u);
+    corners.sort_by(|a, b| {
+        let angle = |p: &Vec3A| (*p - center).dot(v).atan2((*p - center).dot(u));
+        angle(a).total_cmp(&angle(b))
+    });
+    corners
+}
+
+/// Whether `point`, on a side of another brush facing along `plane`, is
+/// inside `hull` or on its surface, so the side is hidden by it. Lying on a
+/// side of `hull` facing the same way counts as outside.
+fn buries(hull: &ConvexHull, point: Vec3A, [x, y, z, _]: [f32; 4]) -> bool {
+    let normal = Vec3A::new(x, y, z);
+    hull.planes.iter().all(|&[hx, hy, hz, dist]| {
+        let hull_normal = Vec3A::new(hx, hy, hz);
+        let distance = hull_normal.dot(point) - dist;
+        match hull_normal.dot(normal) > 1.0 - 1e-4 {
+            true => distance < -HULL_EPSILON,
+            false => distance <= HULL_EPSILON,
+        }
+    })
+}
+
+/// Corners of the convex volume behind all of `planes`: the intersections
+/// of every three planes that lie on or behind the others.
+fn hull_vertices(planes: &[[f32; 4]]) -> Vec<[f32; 3]> {
+    let planes: Vec<(Vec3A, f32)> = planes
+        .iter()
+        .map(|&[x, y, z, dist]| (Vec3A::new(x, y, z), dist))
+        .collect();
+    let mut vertices: Vec<[f32; 3]> = Vec::new();
+    for (i, &(n1, d1)) in planes.iter().enumerate() {
+        for (j, &(n2, d2)) in planes.iter().enumerate().skip(i + 1) {
+            for &(n3, d3) in planes.iter().skip(j + 1) {
+                let denom = n1.dot(n2.cross(n3));
+                if denom.abs() < 1e-6 {
+                    continue;
+                }
+                let p = (d1 * n2.cross(n3) + d2 * n3.cross(n1) + d3 * n1.cross(n2)) / denom;
+                let inside = planes.iter().all(|&(n, d)| n.dot(p) - d <= HULL_EPSILON);
+                let distinct = vertices
+                    .iter()
+                    .all(|&v| Vec3A::from(v).distance_squared(p) > HULL_EPSILON);
+                if inside && distinct {
+                    vertices.push(p.to_array());
+                }
+            }
+        }
+    }
+    vertices
+}
@@ -0,0 +1,22 @@
+//! Content flags of leafs and brushes, see [Leaf::contents](super::Leaf).
+
+/// An opaque wall.
+pub const CONTENTS_SOLID: i32 = 0x1;
+/// A wall that can be seen through.
+pub const CONTENTS_WINDOW: i32 = 0x2;
+pub const CONTENTS_AUX: i32 = 0x4;
+pub const CONTENTS_LAVA: i32 = 0x8;
+pub const CONTENTS_SLIME: i32 = 0x10;
+pub const CONTENTS_WATER: i32 = 0x20;
+pub const CONTENTS_MIST: i32 = 0x40;
+/// Invisible walls that only stop players.
+pub const CONTENTS_PLAYERCLIP: i32 = 0x10000;
+/// Small brushes, such as trim and pipes, that don't split the BSP tree.
+pub const CONTENTS_DETAIL: i32 = 0x8000000;
+
+/// Contents that stop movement.
+pub const MASK_SOLID: i32 = CONTENTS_SOLID | CONTENTS_WINDOW;
+/// Contents that stop player movement.
+pub const MASK_PLAYERSOLID: i32 = MASK_SOLID | CONTENTS_PLAYERCLIP;
+/// Contents that block light, as used by the light compiler.
+pub const MASK_OPAQUE: i32 = CONTENTS_SOLID | CONTENTS_SLIME | CONTENTS_LAVA;
@@ -0,0 +1,97 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// An edge between two vertices, by index into the Vertices lump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub v0: u16,
+    pub v1: u16,
+}
+
+/// An entry of the FaceEdges lump: a reference from a face to an edge.
+///
+/// Faces list their edges in winding order. Edges are shared between the two
+/// faces meeting at them, so one face walks each edge from `v0` to `v1` and
+/// the other walks it backwards, marked by a negative index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfEdge(pub i32);
+
+impl SurfEdge {
+    /// Index of the edge in the Edges lump.
+    pub fn edge(self) -> usize {
+        self.0.unsigned_abs() as usize
+    }
+
+    pub fn is_reversed(self) -> bool {
+        self.0 < 0
+    }
+
+    /// The vertex this edge starts at when walking the face, or None if the
+    /// edge is missing from `edges`.
+    pub fn start(self, edges: &[Edge]) -> Option<u16> {
+        let edge = edges.get(self.edge())?;
+        Some(if self.is_reversed() { edge.v1 } else { edge.v0 })
+    }
+
+    /// The vertex this edge ends at when walking the face, or None if the
+    /// edge is missing from `edges`.
+    pub fn end(self, edges: &[Edge]) -> Option<u16> {
+        let edge = edges.get(self.edge())?;
+        Some(if self.is_reversed() { edge.v0 } else { edge.v1 })
+    }
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn read_edges(&self) -> Result<Vec<Edge>, BspError> {
+        if let Some(edges) = &self.decoded.edges {
+            return Ok(edges.clone());
+        }
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Edges)?;
+        let num_edges = cursor.get_ref().len() / 4;
+        let mut buffer = Vec::with_capacity(num_edges);
+        for _ in 0..num_edges {
+            let v0 = cursor.read_u16::<LittleEndian>()?;
+            let v1 = cursor.read_u16::<LittleEndian>()?;
+            buffer.push(Edge { v0, v1 });
+        }
+        Ok(buffer)
+    }
+
+    // Returns all the edges in the BSP as a series of point pairs.
+    //
+    // The return buffer has 6 * N floats, where N is the number of edges.
+    // The first 3 floats are the position of the edge start, and the next
+    // 3 are the position of the edge end. Edges referencing vertices missing
+    // from the Vertices lump are left out.
+    #[instrument(skip_all)]
+    pub fn read_edge_points(&self) -> Result<Vec<f32>, BspError> {
+        let vertices = self.read_vertices()?;
+        let edges = self.read_edges()?;
+        let mut buffer = Vec::with_capacity(6 * edges.len());
+        let point = |v: u16| vertices.get(v as usize * 3..v as usize * 3 + 3);
+        for edge in edges {
+            if let (Some(start), Some(end)) = (point(edge.v0), point(edge.v1)) {
+                buffer.extend_from_slice(start);
+                buffer.extend_from_slice(end);
+            }
+        }
+        Ok(buffer)
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_face_edges(&self) -> Result<Vec<SurfEdge>, BspError> {
+        if let Some(face_edges) = &self.decoded.face_edges {
+            return Ok(face_edges.clone());
+        }
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::FaceEdges)?;
+        let num_edges = cursor.get_ref().len() / 4;
+        let mut buffer = Vec::with_capacity(num_edges);
+        for _ in 0..num_edges {
+            buffer.push(SurfEdge(cursor.read_i32::<LittleEndian>()?));
+        }
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,145 @@
+use tracing::{instrument, warn};
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// An entity from the Entities lump: a classname and its other key/value
+/// pairs, in file order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entity {
+    pub classname: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl Entity {
+    /// Returns the value of `key`, including the classname.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        if key == "classname" {
+            return Some(&self.classname);
+        }
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses `key` as a number. Quake reads malformed numbers as 0, but
+    /// here they count as missing.
+    pub fn get_f32(&self, key: &str) -> Option<f32> {
+        self.get(key)?.trim().parse().ok()
+    }
+
+    /// Parses `key` as three space-separated numbers, e.g. `origin` or
+    /// `_color`.
+    pub fn get_vec3(&self, key: &str) -> Option<[f32; 3]> {
+        let mut parts = self.get(key)?.split_whitespace().map(str::parse);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(x)), Some(Ok(y)), Some(Ok(z))) => Some([x, y, z]),
+            _ => None,
+        }
+    }
+
+    /// The entity's position, from its `origin` key.
+    pub fn origin(&self) -> Option<[f32; 3]> {
+        self.get_vec3("origin")
+    }
+
+    /// The yaw from the `angle` key, in degrees. As in Quake 2, -1 means
+    /// straight up and -2 straight down.
+    pub fn angle(&self) -> Option<f32> {
+        self.get_f32("angle")
+    }
+
+    /// The inline model the entity is drawn with, from its `model` key:
+    /// `*1` is model 1 of the Models lump. Entities using an external model
+    /// file have none.
+    pub fn model(&self) -> Option<usize> {
+        self.get("model")?.strip_prefix('*')?.parse().ok()
+    }
+
+    /// The name other entities target this one by.
+    pub fn targetname(&self) -> Option<&str> {
+        self.get("targetname")
+    }
+}
+
+impl BSP38 {
+    /// Parses the entity string of the Entities lump.
+    #[instrument(skip_all)]
+    pub fn read_entities(&self) -> Result<Vec<Entity>, BspError> {
+        if let Some(entities) = &self.decoded.entities {
+            return Ok(entities.clone());
+        }
+        let cursor = self.read_lump_as_cursor(LumpIndex::Entities)?;
+        let bytes = *cursor.get_ref();
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(parse_entities(&String::from_utf8_lossy(&bytes[..end])))
+    }
+}
+
+/// Parses an entity string of the form `{ "key" "value" ... } { ... }`.
+///
+/// Parsing stops with a warning at the first malformed entity; the entities
+/// read up to that point are returned.
+pub fn parse_entities(text: &str) -> Vec<Entity> {
+    let mut tokens = Tokenizer { rest: text };
+    let mut entities = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        if token != "{" {
+            warn!("Expected '{{' in entity string, found {:?}", token);
+            break;
+        }
+        let mut entity = Entity::default();
+        loop {
+            let Some(key) = tokens.next() else {
+                warn!("Unterminated entity in entity string");
+                return entities;
+            };
+            if key == "}" {
+                break;
+            }
+            let Some(value) = tokens.next().filter(|v| *v != "}") else {
+                warn!("Missing value for key {:?} in entity string", key);
+                return entities;
+            };
+            if key == "classname" {
+                entity.classname = value.to_string();
+            } else {
+                entity.properties.push((key.to_string(), value.to_string()));
+            }
+        }
+        entities.push(entity);
+    }
+    entities
+}
+
+/// Splits an entity string into braces and (optionally quoted) strings.
+struct Tokenizer<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let s = self.rest.trim_start();
+        if s.is_empty() {
+            self.rest = s;
+            return None;
+        }
+        if let Some(quoted) = s.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            self.rest = quoted.get(end + 1..).unwrap_or("");
+            return Some(&quoted[..end]);
+        }
+        if s.starts_with('{') || s.starts_with('}') {
+            self.rest = &s[1..];
+            return Some(&s[..1]);
+        }
+        let end = s
+            .find(|c: char| c.is_whitespace() || c == '"' || c == '{' || c == '}')
+            .unwrap_or(s.len());
+        self.rest = &s[end..];
+        Some(&s[..end])
+    }
+}
@@ -0,0 +1,90 @@
+use thiserror::Error;
+
+use super::{LumpIndex, BSP38, LUMP_INFO};
+
+pub(super) const HEADER_SIZE: usize = 8 + 8 * LumpIndex::COUNT as usize;
+
+#[derive(Debug, Error)]
+pub enum BspError {
+    #[error("File too short for a BSP header ({0} bytes)")]
+    Truncated(usize),
+    #[error("Not a BSP file (magic {0:?})")]
+    InvalidMagic([u8; 4]),
+    #[error("Unsupported BSP version {0}, expected 38")]
+    UnsupportedVersion(u32),
+    #[error("Lump {name} (offset {offset}, length {length}) is outside of the file")]
+    InvalidLump {
+        name: &'static str,
+        offset: i32,
+        length: i32,
+    },
+    #[error("Lump {name} length {length} is not a multiple of its {record_size} byte records")]
+    InvalidLumpSize {
+        name: &'static str,
+        length: i32,
+        record_size: usize,
+    },
+    #[error("Lump ended in the middle of a record: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl BSP38 {
+    /// Checks the header and lump table of `bytes` without parsing any lump.
+    ///
+    /// Stricter than [BSP38::from_bytes], which accepts other versions and
+    /// cuts short lumps outside of the file.
+    pub fn check_header(bytes: &[u8]) -> Result<(), BspError> {
+        Self::check_magic(bytes)?;
+        let word = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        let version = word(4) as u32;
+        if version != 38 {
+            return Err(BspError::UnsupportedVersion(version));
+        }
+
+        for (i, (name, _)) in LUMP_INFO.iter().enumerate() {
+            let offset = word(8 + 8 * i);
+            let length = word(12 + 8 * i);
+            let end = (offset as i64) + (length as i64);
+            if offset < 0 || length < 0 || end > bytes.len() as i64 {
+                return Err(BspError::InvalidLump {
+                    name,
+                    offset,
+                    length,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BSP38 {
+    /// Checks that `bytes` is long enough for a header and starts with the
+    /// BSP magic.
+    pub(super) fn check_magic(bytes: &[u8]) -> Result<(), BspError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(BspError::Truncated(bytes.len()));
+        }
+        if &bytes[0..4] != b"IBSP" {
+            return Err(BspError::InvalidMagic(bytes[0..4].try_into().unwrap()));
+        }
+        Ok(())
+    }
+
+    /// [BSP38::check_header], plus fixed-size lumps holding a whole number of
+    /// records.
+    pub(super) fn check_lumps(bytes: &[u8]) -> Result<(), BspError> {
+        Self::check_header(bytes)?;
+        for (i, &(name, record_size)) in LUMP_INFO.iter().enumerate() {
+            let length = i32::from_le_bytes(bytes[12 + 8 * i..16 + 8 * i].try_into().unwrap());
+            if record_size > 0 && !(length as usize).is_multiple_of(record_size) {
+                return Err(BspError::InvalidLumpSize {
+                    name,
+                    length,
+                    record_size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
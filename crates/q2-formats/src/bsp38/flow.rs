@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use tracing::instrument;
+
+use super::{Entity, BSP38};
+
+/// Spacing of the [NavGrid](super::NavGrid) the report walks on, in map
+/// units.
+const NAV_SPACING: f32 = 32.0;
+
+/// Classnames players spawn at, in order of preference: deathmatch spawns,
+/// or the single player start of maps without any.
+const SPAWN_CLASSNAMES: [&str; 2] = ["info_player_deathmatch", "info_player_start"];
+
+/// What a pickup entity gives the player, by the prefix of its classname.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PickupKind {
+    Weapon,
+    Ammo,
+    /// Health, armor and powerups.
+    Item,
+    Key,
+}
+
+impl PickupKind {
+    /// ```
+    /// # use q2_formats::bsp38::prelude::PickupKind;
+    /// assert_eq!(PickupKind::of("weapon_railgun"), Some(PickupKind::Weapon));
+    /// assert_eq!(PickupKind::of("item_quad"), Some(PickupKind::Item));
+    /// assert_eq!(PickupKind::of("misc_teleporter"), None);
+    /// ```
+    pub fn of(classname: &str) -> Option<Self> {
+        let (prefix, _) = classname.split_once('_')?;
+        match prefix {
+            "weapon" => Some(PickupKind::Weapon),
+            "ammo" => Some(PickupKind::Ammo),
+            "item" => Some(PickupKind::Item),
+            "key" => Some(PickupKind::Key),
+            _ => None,
+        }
+    }
+}
+
+/// Pickups and spawns in one area of a map.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AreaPickups {
+    pub spawns: usize,
+    pub weapons: usize,
+    pub ammo: usize,
+    pub items: usize,
+    pub keys: usize,
+}
+
+/// The walk from a spawn point to the weapons, see [FlowReport].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnFlow {
+    /// Index of the spawn in the entity list.
+    pub entity: usize,
+    pub origin: [f32; 3],
+    /// The nearest weapon by walking distance, as its classname and the
+    /// distance, or None if no weapon can be walked to.
+    pub nearest_weapon: Option<(String, f32)>,
+    /// Walking distance to each weapon that can be reached, on average.
+    pub average_weapon_distance: Option<f32>,
+}
+
+/// How well the walkable parts of a map hang together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Connectivity {
+    /// Points of the nav grid.
+    pub nav_nodes: usize,
+    /// Separate walkable regions, which can only be left by teleporting or
+    /// jumping.
+    pub components: usize,
+    /// Share of the nav grid in the largest region, from 0 to 1.
+    pub largest_component: f32,
+    /// Spawns not on the nav grid, or from which no weapon can be walked to.
+    pub stranded_spawns: usize,
+    /// Areas of the map, and area portals joining them, each counted once.
+    pub areas: usize,
+    pub area_portals: usize,
+}
+
+/// Pickup distribution and gameplay flow of a map, for reviewing deathmatch
+/// maps: where the items are, how far the weapons are from the spawns, and
+/// whether the walkable space is in one piece. See [BSP38::flow_report].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowReport {
+    /// Pickups and spawns by the area of the leaf they are in. Area 0 is
+    /// for entities outside of the world, or maps without areas.
+    pub areas: BTreeMap<usize, AreaPickups>,
+    pub spawns: Vec<SpawnFlow>,
+    /// Walking distance from a spawn to its nearest weapon, on average over
+    /// the spawns that have one.
+    pub average_spawn_to_weapon: Option<f32>,
+    pub connectivity: Connectivity,
+}
+
+impl BSP38 {
+    /// Builds the [FlowReport] of the map, walking on a nav grid of its
+    /// floors.
+    ///
+    /// ```
+    /// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+    ///
+    /// let bytes = TestMapBuilder::room([0.0; 3], [512.0; 3])
+    ///     .with_entity("info_player_deathmatch", &[("origin", "48 48 24")])
+    ///     .with_entity("weapon_railgun", &[("origin", "464 48 16")])
+    ///     .build();
+    /// let report = BSP38::from_bytes(bytes).unwrap().flow_report();
+    /// let (weapon, distance) = report.spawns[0].nearest_weapon.clone().unwrap();
+    /// assert_eq!(weapon, "weapon_railgun");
+    /// assert_eq!(distance, 416.0);
+    /// ```
+    #[instrument(skip_all)]
+    pub fn flow_report(&self) -> FlowReport {
+        let entities = self.read_entities().unwrap_or_default();
+        let tracer = self.tracer();
+        let leafs = self.read_leafs().unwrap_or_default();
+        let area_of = |origin: [f32; 3]| {
+            tracer
+                .leaf_at(origin)
+                .and_then(|leaf| leafs.get(leaf))
+                .and_then(|leaf| usize::try_from(leaf.area).ok())
+                .unwrap_or(0)
+        };
+
+        let spawn_class = SPAWN_CLASSNAMES
+            .iter()
+            .find(|&&class| entities.iter().any(|e| e.classname == class))
+            .copied();
+        let is_spawn = |entity: &Entity| Some(entity.classname.as_str()) == spawn_class;
+
+        let mut report = FlowReport::default();
+        for entity in &entities {
+            let Some(origin) = entity.origin() else {
+                continue;
+            };
+            let area = report.areas.entry(area_of(origin)).or_default();
+            if is_spawn(entity) {
+                area.spawns += 1;
+            }
+            match PickupKind::of(&entity.classname) {
+                Some(PickupKind::Weapon) => area.weapons += 1,
+                Some(PickupKind::Ammo) => area.ammo += 1,
+                Some(PickupKind::Item) => area.items += 1,
+                Some(PickupKind::Key) => area.keys += 1,
+                None => {}
+            }
+        }
+
+        let nav = self.nav_grid(NAV_SPACING);
+        let weapons: Vec<(&str, Option<usize>)> = entities
+            .iter()
+            .filter(|e| PickupKind::of(&e.classname) == Some(PickupKind::Weapon))
+            .filter_map(|e| Some((e.classname.as_str(), nav.nearest(e.origin()?))))
+            .collect();
+        for (i, entity) in entities.iter().enumerate().filter(|(_, e)| is_spawn(e)) {
+            let Some(origin) = entity.origin() else {
+                continue;
+            };
+            let distances = nav.nearest(origin).map(|node| nav.distances_from(node));
+            let reached: Vec<(&str, f32)> = weapons
+                .iter()
+                .filter_map(|&(classname, node)| {
+                    let distance = distances.as_ref()?[node?];
+                    distance.is_finite().then_some((classname, distance))
+                })
+                .collect();
+            report.spawns.push(SpawnFlow {
+                entity: i,
+                origin,
+                nearest_weapon: reached
+                    .iter()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|&(classname, distance)| (classname.to_string(), distance)),
+                average_weapon_distance: (!reached.is_empty())
+                    .then(|| reached.iter().map(|(_, d)| d).sum::<f32>() / reached.len() as f32),
+            });
+        }
+        let nearest: Vec<f32> = report
+            .spawns
+            .iter()
+            .filter_map(|spawn| Some(spawn.nearest_weapon.as_ref()?.1))
+            .collect();
+        report.average_spawn_to_weapon =
+            (!nearest.is_empty()).then(|| nearest.iter().sum::<f32>() / nearest.len() as f32);
+
+        let components = nav.components();
+        let mut sizes = vec![0usize; components.iter().map(|&c| c + 1).max().unwrap_or(0)];
+        for &component in &components {
+            sizes[component] += 1;
+        }
+        report.connectivity = Connectivity {
+            nav_nodes: components.len(),
+            components: sizes.len(),
+            largest_component: match components.len() {
+                0 => 0.0,
+                n => sizes.iter().copied().max().unwrap_or(0) as f32 / n as f32,
+            },
+            stranded_spawns: report.spawns.len() - nearest.len(),
+            // Area 0 is unused
+            areas: self
+                .read_areas()
+                .unwrap_or_default()
+                .len()
+                .saturating_sub(1),
+            area_portals: self.read_area_portals().unwrap_or_default().len() / 2,
+        };
+        report
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+use super::{LumpIndex, LumpInfo, BSP38};
+
+/// The lump table as a map from lump name to summary.
+struct LumpTable(Vec<LumpInfo>);
+
+impl fmt::Debug for LumpTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|lump| (lump.name, LumpSummary(lump))))
+            .finish()
+    }
+}
+
+/// Lump table entry printed without its (already shown) name.
+struct LumpSummary<'a>(&'a LumpInfo);
+
+impl fmt::Debug for LumpSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Lump");
+        s.field("offset", &self.0.offset)
+            .field("length", &self.0.length);
+        if let Some(count) = self.0.count {
+            s.field("count", &count);
+        }
+        s.finish()
+    }
+}
+
+impl fmt::Debug for BSP38 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BSP38")
+            .field("magic", &self.magic)
+            .field("version", &self.version)
+            .field("bytes", &self.bytes.len())
+            .field("bounds", &self.bounds)
+            .field("lumps", &LumpTable(self.lump_table()))
+            .finish()
+    }
+}
+
+impl fmt::Display for BSP38 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |index: LumpIndex| self.lump_info(index).count.unwrap_or(0);
+        write!(
+            f,
+            "{} v{} ({} bytes): {} vertices, {} faces, {} texinfo, {} leafs, {} models",
+            self.magic,
+            self.version,
+            self.bytes.len(),
+            count(LumpIndex::Vertices),
+            count(LumpIndex::Faces),
+            count(LumpIndex::Texinfo),
+            count(LumpIndex::Leafs),
+            count(LumpIndex::Models),
+        )
+    }
+}
@@ -0,0 +1,215 @@
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{LumpIndex, BSP38};
+
+/// Spacing of lightmap samples in texture space, in texels.
+pub const LUXEL_SIZE: f32 = 16.0;
+
+/// Smallest and largest edge of the square atlas pages, in luxels. Pages grow
+/// until every lightmap fits on one, and past the largest size further pages
+/// are added.
+const MIN_PAGE_SIZE: u32 = 128;
+const MAX_PAGE_SIZE: u32 = 2048;
+
+/// Bytes per luxel in the Lighting lump (RGB) and in the atlas pages (RGBA).
+const LIGHTING_BYTES: usize = 3;
+const PAGE_BYTES: usize = 4;
+
+/// Where the lightmap of one face lies in a [LightmapAtlas].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FaceLightmap {
+    /// Index into [LightmapAtlas::pages].
+    pub page: usize,
+    /// Position of the first luxel in the page.
+    pub x: u32,
+    pub y: u32,
+    /// Size in luxels.
+    pub width: u32,
+    pub height: u32,
+    /// Texture coordinates of the first luxel, divided by [LUXEL_SIZE].
+    pub min_s: f32,
+    pub min_t: f32,
+    /// Edge of the page, in luxels.
+    pub page_size: u32,
+}
+
+impl FaceLightmap {
+    /// Atlas UV of the point with texture coordinates `s`, `t`: the center of
+    /// a luxel at each luxel position, so filtering never reaches the
+    /// lightmaps next to this one.
+    pub fn uv(&self, s: f32, t: f32) -> [f32; 2] {
+        let size = self.page_size as f32;
+        [
+            (self.x as f32 + s / LUXEL_SIZE - self.min_s + 0.5) / size,
+            (self.y as f32 + t / LUXEL_SIZE - self.min_t + 0.5) / size,
+        ]
+    }
+}
+
+/// The lightmaps of all faces, packed into square RGBA pages.
+///
+/// Only the first light style of each face is packed: switchable and
+/// flickering lights show in their default state.
+#[derive(Debug, Clone, Default)]
+pub struct LightmapAtlas {
+    /// Edge of every page, in luxels.
+    pub page_size: u32,
+    /// 8-bit RGBA luxels of each page, row by row.
+    pub pages: Vec<Vec<u8>>,
+    /// Lightmap of each face, in face order. None for faces without one,
+    /// such as sky and warp faces.
+    pub faces: Vec<Option<FaceLightmap>>,
+}
+
+impl LightmapAtlas {
+    /// UV of a white luxel kept at the corner of the first page, for drawing
+    /// faces without a lightmap fully lit, as the game does.
+    pub fn unlit_uv(&self) -> [f32; 2] {
+        [0.5 / self.page_size as f32; 2]
+    }
+}
+
+impl BSP38 {
+    /// Extracts the lightmap of every face from the Lighting lump and packs
+    /// them into atlas pages.
+    ///
+    /// The size of a lightmap follows from the face's texture extents the way
+    /// the light compiler lays it out, one luxel every [LUXEL_SIZE] texels.
+    /// Faces whose lightmap is missing from a corrupt file, or has no luxels,
+    /// get none.
+    #[instrument(skip_all)]
+    pub fn read_lightmaps(&self) -> LightmapAtlas {
+        let tex_info = self.read_texture_info().unwrap_or_default();
+        let records = self.read_face_records().unwrap_or_default();
+        let polygons = self.face_polygons();
+        let lighting = self
+            .read_lump_as_cursor(LumpIndex::Lighting)
+            .map_or(&[][..], std::io::Cursor::into_inner);
+
+        // Size and lighting data of each face with a lightmap
+        let lightmaps: Vec<Option<(FaceLightmap, &[u8])>> = records
+            .iter()
+            .zip(&polygons)
+            .map(|(face, points)| {
+                let tex = tex_info.get(face.texinfo as usize)?;
+                if face.lightmap_offset < 0 || face.lightmap_styles[0] == 255 {
+                    return None;
+                }
+                let (min_s, width) = luxel_range(points, Vec3A::from(tex.u), tex.u0)?;
+                let (min_t, height) = luxel_range(points, Vec3A::from(tex.v), tex.v0)?;
+                // Must fit on a page next to the white corner. Extents that
+                // aren't finite give no luxels at all.
+                if width == 0 || height == 0 || width >= MAX_PAGE_SIZE || height >= MAX_PAGE_SIZE {
+                    return None;
+                }
+                let start = face.lightmap_offset as usize;
+                let length = (width * height) as usize * LIGHTING_BYTES;
+                let data = lighting.get(start..start + length)?;
+                let lightmap = FaceLightmap {
+                    page: 0,
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                    min_s,
+                    min_t,
+                    page_size: 0,
+                };
+                Some((lightmap, data))
+            })
+            .collect();
+
+        // Tallest first packs shelves tightly
+        let mut order: Vec<usize> = (0..lightmaps.len())
+            .filter(|&k| lightmaps[k].is_some())
+            .collect();
+        order.sort_by_key(|&k| {
+            let (lightmap, _) = lightmaps[k].as_ref().unwrap();
+            (std::cmp::Reverse(lightmap.height), k)
+        });
+        let sizes: Vec<(u32, u32)> = order
+            .iter()
+            .map(|&k| {
+                let (lightmap, _) = lightmaps[k].as_ref().unwrap();
+                (lightmap.width, lightmap.height)
+            })
+            .collect();
+        let largest = sizes.iter().map(|&(w, h)| w.max(h)).max().unwrap_or(0);
+        let mut page_size = MIN_PAGE_SIZE.max((largest + 1).next_power_of_two());
+        let mut places = pack(&sizes, page_size);
+        while page_size < MAX_PAGE_SIZE && places.last().is_some_and(|&(page, ..)| page > 0) {
+            page_size *= 2;
+            places = pack(&sizes, page_size);
+        }
+
+        let num_pages = places.last().map_or(1, |&(page, ..)| page + 1);
+        let page_bytes = (page_size * page_size) as usize * PAGE_BYTES;
+        let mut pages = vec![vec![0u8; page_bytes]; num_pages];
+        pages[0][..PAGE_BYTES].copy_from_slice(&[255; PAGE_BYTES]);
+
+        let mut faces = vec![None; lightmaps.len()];
+        for (&k, &(page, x, y)) in order.iter().zip(&places) {
+            let (mut lightmap, data) = lightmaps[k].unwrap();
+            lightmap.page = page;
+            lightmap.x = x;
+            lightmap.y = y;
+            lightmap.page_size = page_size;
+            let rows = data.chunks_exact(lightmap.width as usize * LIGHTING_BYTES);
+            for (row, luxels) in rows.enumerate() {
+                let start = ((y as usize + row) * page_size as usize + x as usize) * PAGE_BYTES;
+                let out = pages[page][start..].chunks_exact_mut(PAGE_BYTES);
+                for (out, rgb) in out.zip(luxels.chunks_exact(LIGHTING_BYTES)) {
+                    out.copy_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+                }
+            }
+            faces[k] = Some(lightmap);
+        }
+
+        LightmapAtlas {
+            page_size,
+            pages,
+            faces,
+        }
+    }
+}
+
+/// Places rectangles of `sizes` on shelves of square pages of `page_size`,
+/// left to right, returning the page and position of each. The first luxel
+/// of the first page is left free for the white corner.
+fn pack(sizes: &[(u32, u32)], page_size: u32) -> Vec<(usize, u32, u32)> {
+    let (mut page, mut x, mut y, mut shelf) = (0, 1, 0, 1);
+    sizes
+        .iter()
+        .map(|&(width, height)| {
+            if x + width > page_size {
+                (x, y) = (0, y + shelf);
+                shelf = 0;
+            }
+            if y + height > page_size {
+                (page, x, y, shelf) = (page + 1, 0, 0, 0);
+            }
+            let place = (page, x, y);
+            x += width;
+            shelf = shelf.max(height);
+            place
+        })
+        .collect()
+}
+
+/// The first luxel and the number of luxels along texture `axis`, counted the
+/// way the light compiler does: the texture extents snapped outwards to
+/// [LUXEL_SIZE], plus one. None for a face without points.
+pub(super) fn luxel_range(points: &[Vec3A], axis: Vec3A, offset: f32) -> Option<(f32, u32)> {
+    let (min, max) = points
+        .iter()
+        .map(|p| p.dot(axis) + offset)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), s| {
+            (min.min(s), max.max(s))
+        });
+    if min > max {
+        return None;
+    }
+    let first = (min / LUXEL_SIZE).floor();
+    Some((first, ((max / LUXEL_SIZE).ceil() - first + 1.0) as u32))
+}
@@ -0,0 +1,206 @@
+use std::f32::consts::PI;
+
+use super::{Entity, TargetGraph, BSP38};
+
+/// Meters per map unit. Quake 2 maps are built at roughly one unit per inch.
+pub const METERS_PER_UNIT: f32 = 0.0254;
+
+/// Candela per point of Quake light value, chosen so the default light of 300
+/// is about as bright as a 60 W incandescent bulb (800 lm).
+const CANDELA_PER_LIGHT: f32 = 800.0 / (4.0 * PI) / 300.0;
+
+/// Light value qrad3 uses when a light has none.
+const DEFAULT_LIGHT: f32 = 300.0;
+
+/// Spotlight cone qrad3 uses when a spotlight has no `_cone`, in degrees.
+const DEFAULT_CONE: f32 = 10.0;
+
+/// Sun light value arghrad uses when the worldspawn has no `_sun_light`.
+const DEFAULT_SUN_LIGHT: f32 = 200.0;
+
+/// Worldspawn keys giving the sun's direction as angles, in order of
+/// preference.
+const SUN_ANGLE_KEYS: [&str; 3] = ["_sun_angle", "_sun_mangle", "_sun"];
+
+/// A `light` or `light_spot` entity, with its keys interpreted the way qrad3
+/// does when baking lightmaps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapLight {
+    /// Index of the light in the entity list.
+    pub entity: usize,
+    pub origin: [f32; 3],
+    /// The `light` value: brightness, and also the distance in map units
+    /// where the light's linear falloff reaches zero.
+    pub value: f32,
+    /// The `_color`, scaled so its largest component is 1.
+    pub color: [f32; 3],
+    /// Lightstyle index for switchable and flickering lights.
+    pub style: u8,
+    pub spot: Option<Spot>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spot {
+    /// Unit vector the spotlight points along.
+    pub direction: [f32; 3],
+    /// Half-angle of the cone, in degrees.
+    pub cone: f32,
+}
+
+impl MapLight {
+    /// Luminous intensity in candela.
+    pub fn candela(&self) -> f32 {
+        self.value * CANDELA_PER_LIGHT
+    }
+
+    /// Luminous power in lumens of a point light of the same intensity, as
+    /// used by Bevy's point and spot lights.
+    ///
+    /// Physical falloff assumes meters: for a scene in map units, multiply by
+    /// `(1.0 / METERS_PER_UNIT).powi(2)`.
+    pub fn lumens(&self) -> f32 {
+        4.0 * PI * self.candela()
+    }
+
+    /// Distance in map units beyond which the light has no effect.
+    pub fn range(&self) -> f32 {
+        self.value
+    }
+}
+
+/// The sun a light compiler such as arghrad baked into a map, from the keys
+/// of its worldspawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sun {
+    /// Unit vector the sunlight travels along.
+    pub direction: [f32; 3],
+    /// The `_sun_light` value.
+    pub value: f32,
+    /// The `_sun_color`, scaled so its largest component is 1.
+    pub color: [f32; 3],
+}
+
+/// The sun of a map, if its worldspawn has one: `_sun_vector` gives the
+/// direction the light travels in, or `_sun_angle`, `_sun_mangle` or `_sun`
+/// give it as a yaw and a pitch in degrees, negative pitches pointing down.
+///
+/// ```
+/// # use q2_formats::bsp38::prelude::{extract_sun, parse_entities};
+/// let entities = parse_entities(r#"{ "classname" "worldspawn" "_sun" "0 -90" }"#);
+/// let sun = extract_sun(&entities).unwrap();
+/// assert!((sun.direction[2] + 1.0).abs() < 1e-6);
+/// assert_eq!(sun.value, 200.0);
+/// ```
+pub fn extract_sun(entities: &[Entity]) -> Option<Sun> {
+    let worldspawn = entities.iter().find(|e| e.classname == "worldspawn")?;
+    let direction = worldspawn.get_vec3("_sun_vector").or_else(|| {
+        SUN_ANGLE_KEYS.iter().find_map(|key| {
+            let mut angles = worldspawn.get(key)?.split_whitespace();
+            let yaw: f32 = angles.next()?.parse().ok()?;
+            let pitch: f32 = angles.next()?.parse().ok()?;
+            let (sin_yaw, cos_yaw) = yaw.to_radians().sin_cos();
+            let (sin_pitch, cos_pitch) = pitch.to_radians().sin_cos();
+            Some([cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch])
+        })
+    })?;
+    let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if length == 0.0 {
+        return None;
+    }
+    Some(Sun {
+        direction: direction.map(|c| c / length),
+        value: worldspawn
+            .get_f32("_sun_light")
+            .unwrap_or(DEFAULT_SUN_LIGHT),
+        color: normalize_color(worldspawn.get_vec3("_sun_color")),
+    })
+}
+
+/// Extracts the lights of a map from its entities.
+///
+/// A light is a spotlight when it is a `light_spot` or has a `target`. It
+/// points at its target, or along its `angle` when it has none (-1 is up,
+/// -2 is down).
+pub fn extract_lights(entities: &[Entity]) -> Vec<MapLight> {
+    let graph = TargetGraph::new(entities);
+    entities
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.classname == "light" || e.classname == "light_spot")
+        .filter_map(|(i, entity)| {
+            let origin = entity.origin()?;
+            let value = entity
+                .get_f32("light")
+                .or_else(|| entity.get_f32("_light"))
+                .filter(|&v| v != 0.0)
+                .unwrap_or(DEFAULT_LIGHT);
+
+            let spot =
+                (entity.classname == "light_spot" || entity.get("target").is_some()).then(|| {
+                    Spot {
+                        direction: spot_direction(entities, &graph, i, origin),
+                        cone: entity
+                            .get_f32("_cone")
+                            .filter(|&c| c != 0.0)
+                            .unwrap_or(DEFAULT_CONE),
+                    }
+                });
+
+            Some(MapLight {
+                entity: i,
+                origin,
+                value,
+                color: normalize_color(entity.get_vec3("_color")),
+                style: entity.get_f32("style").unwrap_or(0.0) as u8,
+                spot,
+            })
+        })
+        .collect()
+}
+
+fn spot_direction(
+    entities: &[Entity],
+    graph: &TargetGraph,
+    i: usize,
+    origin: [f32; 3],
+) -> [f32; 3] {
+    let target_origin = entities[i]
+        .get("target")
+        .and_then(|name| graph.named(name).first())
+        .and_then(|&t| entities[t].origin());
+    let direction = match target_origin {
+        Some(target) => [0, 1, 2].map(|k| target[k] - origin[k]),
+        None => match entities[i].angle().unwrap_or(0.0) {
+            -1.0 => [0.0, 0.0, 1.0],
+            -2.0 => [0.0, 0.0, -1.0],
+            angle => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                [cos, sin, 0.0]
+            }
+        },
+    };
+    let length = direction.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if length == 0.0 {
+        return [0.0, 0.0, -1.0];
+    }
+    direction.map(|c| c / length)
+}
+
+fn normalize_color(color: Option<[f32; 3]>) -> [f32; 3] {
+    let color = color.unwrap_or([1.0; 3]);
+    let max = color.iter().cloned().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return [1.0; 3];
+    }
+    color.map(|c| c.max(0.0) / max)
+}
+
+impl BSP38 {
+    pub fn read_lights(&self) -> Vec<MapLight> {
+        extract_lights(&self.read_entities().unwrap_or_default())
+    }
+
+    pub fn read_sun(&self) -> Option<Sun> {
+        extract_sun(&self.read_entities().unwrap_or_default())
+    }
+}
@@ -0,0 +1,581 @@
+use glam::Vec3A;
+use std::collections::BTreeMap;
+use tracing::instrument;
+
+use super::{
+    palette::DebugPalette, surface::SurfaceFlags, Edge, Face, FaceData, FaceLightmap,
+    LightmapAtlas, LumpIndex, SurfEdge, TextureInfo, BSP38,
+};
+
+const FACE_BYTES: usize = 20;
+
+/// The grid cell of a face, see [MeshBuilder::with_chunks].
+type Chunk = Option<[i32; 3]>;
+
+/// Triangulated geometry for one texture within one or more PVS clusters.
+#[derive(Debug)]
+pub struct FaceBatch {
+    /// Clusters the faces belong to. Faces outside of any leaf (such as inline
+    /// model faces) use cluster -1.
+    pub clusters: Vec<i16>,
+    pub texture: String,
+    /// Whether the faces are see-through, see [MeshBuilder::build_translucent_batches].
+    pub translucent: bool,
+    /// The grid cell the faces are in, see [MeshBuilder::with_chunks].
+    pub chunk: Option<[i32; 3]>,
+    pub data: FaceData,
+}
+
+/// Triangulates the faces of a BSP into flat vertex buffers.
+///
+/// The builder keeps its buffers between calls to [MeshBuilder::build], so
+/// rebuilding a map (e.g. on hot-reload) reuses the previous allocations
+/// rather than growing fresh vectors from zero.
+#[derive(Default)]
+pub struct MeshBuilder {
+    data: FaceData,
+    face_points: Vec<Vec3A>,
+    lightmaps: Option<LightmapUvs>,
+    palette: DebugPalette,
+    chunk_size: Option<f32>,
+}
+
+/// The layout of a [LightmapAtlas], for laying out lightmap UVs.
+struct LightmapUvs {
+    faces: Vec<Option<FaceLightmap>>,
+    unlit: [f32; 2],
+}
+
+/// Lumps decoded once per build and shared by every face.
+struct Lumps {
+    planes: Vec<[f32; 4]>,
+    vertices: Vec<f32>,
+    face_edges: Vec<SurfEdge>,
+    edges: Vec<Edge>,
+    tex_info: Vec<TextureInfo>,
+}
+
+impl Lumps {
+    fn read(bsp: &BSP38) -> Self {
+        Self {
+            planes: bsp.read_planes().unwrap_or_default(),
+            vertices: bsp.read_vertices().unwrap_or_default(),
+            face_edges: bsp.read_face_edges().unwrap_or_default(),
+            edges: bsp.read_edges().unwrap_or_default(),
+            tex_info: bsp.read_texture_info().unwrap_or_default(),
+        }
+    }
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a builder with buffers already sized for the given map.
+    pub fn with_capacity_for(bsp: &BSP38) -> Self {
+        let mut builder = Self::new();
+        builder.reserve_for(bsp);
+        builder
+    }
+
+    /// Lays out lightmap UVs ([FaceData::uv1]) in `atlas` in the following
+    /// builds. Faces without a lightmap get the atlas' white luxel.
+    pub fn with_lightmaps(mut self, atlas: &LightmapAtlas) -> Self {
+        self.lightmaps = Some(LightmapUvs {
+            faces: atlas.faces.clone(),
+            unlit: atlas.unlit_uv(),
+        });
+        self
+    }
+
+    /// Tints the faces of the following builds ([FaceData::colors]) with
+    /// `palette` instead of the classic one.
+    pub fn with_palette(mut self, palette: DebugPalette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Splits the world batches of the following builds along a grid of
+    /// `size` unit cubes, each face going to the cell holding the center of
+    /// its bounds. A batch then stays within about one cell, so its bounding
+    /// box ([FaceData::bounds]) is small enough to be frustum culled, where
+    /// one spanning the map would always be in view.
+    ///
+    /// ```
+    /// use q2_formats::{
+    ///     bsp38::{prelude::MeshBuilder, BSP38},
+    ///     test_utils::TestMapBuilder,
+    /// };
+    ///
+    /// let bytes = TestMapBuilder::room([0.0; 3], [512.0; 3]).build();
+    /// let bsp = BSP38::from_bytes(bytes).unwrap();
+    /// assert_eq!(MeshBuilder::new().build_batches(&bsp, 0).len(), 1);
+    ///
+    /// // The six sides of the room, each in the cell of its center
+    /// let batches = MeshBuilder::new().with_chunks(128.0).build_batches(&bsp, 0);
+    /// assert_eq!(batches.len(), 6);
+    /// let floor = batches.iter().find(|b| b.data.bounds().max[2] == 0.0).unwrap();
+    /// assert_eq!(floor.chunk, Some([2, 2, 0]));
+    /// ```
+    pub fn with_chunks(mut self, size: f32) -> Self {
+        self.chunk_size = Some(size);
+        self
+    }
+
+    /// Grows the scratch buffers to fit the triangulated faces of `bsp`.
+    ///
+    /// The estimate comes from the lump sizes: every face edge contributes one
+    /// polygon vertex and a fan over N vertices yields N - 2 triangles.
+    pub fn reserve_for(&mut self, bsp: &BSP38) {
+        let num_faces = bsp.lumps[LumpIndex::Faces as usize].length as usize / FACE_BYTES;
+        let num_face_edges = bsp.lumps[LumpIndex::FaceEdges as usize].length as usize / 4;
+        let num_vertices = 3 * num_face_edges.saturating_sub(2 * num_faces);
+
+        let data = &mut self.data;
+        data.points.reserve(3 * num_vertices);
+        data.normals.reserve(3 * num_vertices);
+        data.colors.reserve(3 * num_vertices);
+        data.uv.reserve(2 * num_vertices);
+        if self.lightmaps.is_some() {
+            data.uv1.reserve(2 * num_vertices);
+        }
+    }
+
+    /// Triangulates all faces of `bsp`, replacing the output of any previous
+    /// build.
+    #[instrument(skip_all)]
+    pub fn build(&mut self, bsp: &BSP38) -> &FaceData {
+        let lumps = Lumps::read(bsp);
+
+        self.data.clear();
+        for (k, face) in bsp
+            .read_face_records()
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            triangulate(
+                &lumps,
+                k,
+                face,
+                self.lightmaps.as_ref(),
+                self.palette.colors(),
+                &mut self.face_points,
+                &mut self.data,
+            );
+        }
+        &self.data
+    }
+
+    /// Triangulates the faces listed in `faces`, in the given order, replacing
+    /// the output of any previous build.
+    ///
+    /// Each face is a fan of `num_edges - 2` triangles, so triangles can be
+    /// traced back to their face from the face records. Indices past the end
+    /// of the Faces lump are skipped.
+    #[instrument(skip_all, fields(faces = faces.len()))]
+    pub fn build_faces(&mut self, bsp: &BSP38, faces: &[usize]) -> &FaceData {
+        let lumps = Lumps::read(bsp);
+        let records = bsp.read_face_records().unwrap_or_default();
+
+        self.data.clear();
+        for &k in faces {
+            if let Some(face) = records.get(k) {
+                triangulate(
+                    &lumps,
+                    k,
+                    face,
+                    self.lightmaps.as_ref(),
+                    self.palette.colors(),
+                    &mut self.face_points,
+                    &mut self.data,
+                );
+            }
+        }
+        &self.data
+    }
+
+    /// Triangulates the opaque faces of the world model of `bsp` into one
+    /// batch per (cluster, texture) pair. Sky and nodraw faces are left out,
+    /// translucent ones go in [MeshBuilder::build_translucent_batches] instead
+    /// and those of inline models in [MeshBuilder::build_model_batches].
+    ///
+    /// Batches with fewer than `merge_threshold` triangles are merged with the
+    /// other small batches of the same texture (and chunk, see
+    /// [MeshBuilder::with_chunks]), trading some culling granularity for
+    /// fewer draw calls.
+    #[instrument(skip_all, fields(merge_threshold = merge_threshold))]
+    pub fn build_batches(&mut self, bsp: &BSP38, merge_threshold: usize) -> Vec<FaceBatch> {
+        let lumps = Lumps::read(bsp);
+        let face_clusters = bsp.face_clusters();
+
+        let world = world_faces(bsp);
+
+        let mut groups: BTreeMap<(&str, Chunk, i16), FaceData> = BTreeMap::new();
+        for (k, face) in bsp
+            .read_face_records()
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            if !world.contains(&k) {
+                continue;
+            }
+            let Some(tex) = lumps.tex_info.get(face.texinfo as usize) else {
+                continue;
+            };
+            if layer(tex.surface_flags()) != Some(false) {
+                continue;
+            }
+            let texture = tex.texture.as_str();
+            let chunk = self.chunk_of(&lumps, face);
+            let cluster = face_clusters[k];
+            let data = groups.entry((texture, chunk, cluster)).or_default();
+            triangulate(
+                &lumps,
+                k,
+                face,
+                self.lightmaps.as_ref(),
+                self.palette.colors(),
+                &mut self.face_points,
+                data,
+            );
+        }
+
+        let mut batches: Vec<FaceBatch> = Vec::new();
+        let mut small: Option<FaceBatch> = None;
+        for ((texture, chunk, cluster), data) in groups {
+            if data.points.is_empty() {
+                continue;
+            }
+            if small
+                .as_ref()
+                .is_some_and(|s| s.texture != texture || s.chunk != chunk)
+            {
+                batches.extend(small.take());
+            }
+            if data.triangle_count() >= merge_threshold {
+                batches.push(FaceBatch {
+                    clusters: vec![cluster],
+                    texture: texture.to_string(),
+                    translucent: false,
+                    chunk,
+                    data,
+                });
+                continue;
+            }
+            match &mut small {
+                Some(batch) => {
+                    batch.clusters.push(cluster);
+                    batch.data.append(data);
+                }
+                None => {
+                    small = Some(FaceBatch {
+                        clusters: vec![cluster],
+                        texture: texture.to_string(),
+                        translucent: false,
+                        chunk,
+                        data,
+                    })
+                }
+            }
+        }
+        batches.extend(small);
+        batches
+    }
+
+    /// Triangulates the see-through faces of the world model of `bsp`, those with
+    /// [SurfaceFlags::TRANS33] or [SurfaceFlags::TRANS66], into one batch per
+    /// texture (and chunk, see [MeshBuilder::with_chunks]), to be drawn
+    /// blended after the opaque ones.
+    #[instrument(skip_all)]
+    pub fn build_translucent_batches(&mut self, bsp: &BSP38) -> Vec<FaceBatch> {
+        let lumps = Lumps::read(bsp);
+        let face_clusters = bsp.face_clusters();
+        let world = world_faces(bsp);
+
+        // Same order as build_batch: by cluster, then by face
+        let mut faces: BTreeMap<(&str, Chunk), Vec<(i16, usize)>> = BTreeMap::new();
+        let records = bsp.read_face_records().unwrap_or_default();
+        for (k, face) in records.iter().enumerate() {
+            if !world.contains(&k) {
+                continue;
+            }
+            let Some(tex) = lumps.tex_info.get(face.texinfo as usize) else {
+                continue;
+            };
+            if layer(tex.surface_flags()) == Some(true) {
+                let chunk = self.chunk_of(&lumps, face);
+                let texture = faces.entry((tex.texture.as_str(), chunk)).or_default();
+                texture.push((face_clusters[k], k));
+            }
+        }
+
+        let mut batches = Vec::new();
+        for ((texture, chunk), mut faces) in faces {
+            faces.sort_unstable();
+            let mut batch = FaceBatch {
+                clusters: faces.iter().map(|&(cluster, _)| cluster).collect(),
+                texture: texture.to_string(),
+                translucent: true,
+                chunk,
+                data: FaceData::default(),
+            };
+            batch.clusters.dedup();
+            for (_, k) in faces {
+                triangulate(
+                    &lumps,
+                    k,
+                    &records[k],
+                    self.lightmaps.as_ref(),
+                    self.palette.colors(),
+                    &mut self.face_points,
+                    &mut batch.data,
+                );
+            }
+            if !batch.data.points.is_empty() {
+                batches.push(batch);
+            }
+        }
+        batches
+    }
+
+    /// Triangulates the faces of one batch of [MeshBuilder::build_batches],
+    /// or of [MeshBuilder::build_translucent_batches] if `translucent`: the
+    /// faces with `texture` in any of `clusters` and in `chunk` (built with
+    /// the same [MeshBuilder::with_chunks]), skipping those `keep` returns
+    /// false for. Replaces the output of any previous build.
+    ///
+    /// With every face kept, this rebuilds the batch's triangles exactly, so
+    /// a change to a few faces only needs their batches rebuilt.
+    #[instrument(skip_all, fields(texture = texture))]
+    pub fn build_batch(
+        &mut self,
+        bsp: &BSP38,
+        texture: &str,
+        clusters: &[i16],
+        chunk: Option<[i32; 3]>,
+        translucent: bool,
+        keep: impl Fn(usize) -> bool,
+    ) -> &FaceData {
+        let lumps = Lumps::read(bsp);
+        let face_clusters = bsp.face_clusters();
+        let records = bsp.read_face_records().unwrap_or_default();
+        let world = world_faces(bsp);
+
+        // Same order as build_batches: by cluster, then by face
+        let mut faces: Vec<(i16, usize)> = (0..records.len())
+            .filter(|k| world.contains(k))
+            .filter(|&k| clusters.contains(&face_clusters[k]))
+            .filter(|&k| self.chunk_of(&lumps, &records[k]) == chunk)
+            .filter(|&k| {
+                lumps
+                    .tex_info
+                    .get(records[k].texinfo as usize)
+                    .is_some_and(|tex| {
+                        tex.texture == texture && layer(tex.surface_flags()) == Some(translucent)
+                    })
+            })
+            .filter(|&k| keep(k))
+            .map(|k| (face_clusters[k], k))
+            .collect();
+        faces.sort_unstable();
+
+        self.data.clear();
+        for (_, k) in faces {
+            triangulate(
+                &lumps,
+                k,
+                &records[k],
+                self.lightmaps.as_ref(),
+                self.palette.colors(),
+                &mut self.face_points,
+                &mut self.data,
+            );
+        }
+        &self.data
+    }
+
+    /// Triangulates the faces of model `model` of `bsp` into one batch per
+    /// texture, opaque batches first, then translucent ones. Model 0 is the
+    /// world; the others are the inline models of doors, platforms and the
+    /// like, referenced as `*1`, `*2`, ... by their entities.
+    ///
+    /// The faces stay in map coordinates and out of any cluster. Returns no
+    /// batches for a model missing from the Models lump.
+    #[instrument(skip_all, fields(model = model))]
+    pub fn build_model_batches(&mut self, bsp: &BSP38, model: usize) -> Vec<FaceBatch> {
+        let Some(model) = bsp.read_models().unwrap_or_default().get(model).copied() else {
+            return Vec::new();
+        };
+        let lumps = Lumps::read(bsp);
+        let records = bsp.read_face_records().unwrap_or_default();
+
+        let mut groups: BTreeMap<(bool, &str), FaceData> = BTreeMap::new();
+        for k in model.faces() {
+            let Some(face) = records.get(k) else {
+                break;
+            };
+            let Some(tex) = lumps.tex_info.get(face.texinfo as usize) else {
+                continue;
+            };
+            let Some(translucent) = layer(tex.surface_flags()) else {
+                continue;
+            };
+            let data = groups
+                .entry((translucent, tex.texture.as_str()))
+                .or_default();
+            triangulate(
+                &lumps,
+                k,
+                face,
+                self.lightmaps.as_ref(),
+                self.palette.colors(),
+                &mut self.face_points,
+                data,
+            );
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, data)| !data.points.is_empty())
+            .map(|((translucent, texture), data)| FaceBatch {
+                clusters: vec![-1],
+                texture: texture.to_string(),
+                translucent,
+                chunk: None,
+                data,
+            })
+            .collect()
+    }
+
+    /// The texture, cluster and chunk (see [MeshBuilder::with_chunks]) of each
+    /// face of `bsp`, which together pick the batch of
+    /// [MeshBuilder::build_batches] the face ends up in.
+    pub fn face_batch_keys(&self, bsp: &BSP38) -> Vec<(String, i16, Option<[i32; 3]>)> {
+        let lumps = Lumps::read(bsp);
+        bsp.read_face_records()
+            .unwrap_or_default()
+            .iter()
+            .zip(bsp.face_clusters())
+            .map(|(face, cluster)| {
+                let texture = lumps
+                    .tex_info
+                    .get(face.texinfo as usize)
+                    .map_or(String::new(), |tex| tex.texture.clone());
+                (texture, cluster, self.chunk_of(&lumps, face))
+            })
+            .collect()
+    }
+
+    /// The grid cell of [MeshBuilder::with_chunks] the center of a face's
+    /// bounds is in, or None when not chunking or for a broken face.
+    fn chunk_of(&self, lumps: &Lumps, face: &Face) -> Option<[i32; 3]> {
+        let size = self.chunk_size?;
+        let first = face.first_edge as usize;
+        let surf_edges = lumps
+            .face_edges
+            .get(first..first + face.num_edges as usize)?;
+        let (mut min, mut max) = (Vec3A::splat(f32::INFINITY), Vec3A::splat(f32::NEG_INFINITY));
+        for surf_edge in surf_edges {
+            let i = surf_edge.start(&lumps.edges)? as usize * 3;
+            let point = Vec3A::from_slice(lumps.vertices.get(i..i + 3)?);
+            (min, max) = (min.min(point), max.max(point));
+        }
+        let cell = ((min + max) * 0.5 / size).floor();
+        Some([cell.x as i32, cell.y as i32, cell.z as i32])
+    }
+
+    /// Consumes the builder, returning the output of the last build.
+    pub fn into_face_data(self) -> FaceData {
+        self.data
+    }
+}
+
+/// The faces of the world model, or all faces if the Models lump is empty.
+fn world_faces(bsp: &BSP38) -> std::ops::Range<usize> {
+    bsp.read_models()
+        .unwrap_or_default()
+        .first()
+        .map_or(0..usize::MAX, |world| world.faces())
+}
+
+/// Which batches faces with `flags` go in: None for faces that aren't drawn,
+/// such as sky, otherwise whether they are translucent.
+fn layer(flags: SurfaceFlags) -> Option<bool> {
+    flags.is_drawn().then(|| flags.is_translucent())
+}
+
+/// Fan-triangulates face `k` and appends the triangles to `out`. Faces
+/// referring to planes, edges, vertices or texinfo missing from a corrupt
+/// file are skipped.
+fn triangulate(
+    lumps: &Lumps,
+    k: usize,
+    face: &Face,
+    lightmaps: Option<&LightmapUvs>,
+    palette: &[[f32; 3]],
+    face_pts: &mut Vec<Vec3A>,
+    out: &mut FaceData,
+) {
+    let (Some(plane), Some(tex)) = (
+        lumps.planes.get(face.plane as usize),
+        lumps.tex_info.get(face.texinfo as usize),
+    ) else {
+        return;
+    };
+    let mut normal = Vec3A::new(plane[0], plane[1], plane[2]);
+    if face.side == 0 {
+        normal = -normal;
+    }
+
+    let first = face.first_edge as usize;
+    let Some(surf_edges) = lumps.face_edges.get(first..first + face.num_edges as usize) else {
+        return;
+    };
+    face_pts.clear();
+    for surf_edge in surf_edges {
+        let Some(i) = surf_edge.start(&lumps.edges).map(|v| v as usize * 3) else {
+            return;
+        };
+        let Some(point) = lumps.vertices.get(i..i + 3) else {
+            return;
+        };
+        face_pts.push(Vec3A::from_slice(point));
+    }
+
+    let u_axis = Vec3A::from(tex.u);
+    let v_axis = Vec3A::from(tex.v);
+    let normal_array = normal.to_array();
+    let lightmap = lightmaps.map(|uvs| (uvs.faces.get(k).copied().flatten(), uvs.unlit));
+
+    for i in 2..face_pts.len() {
+        let a = face_pts[0];
+        let b = face_pts[i - 1];
+        let c = face_pts[i];
+
+        // Only the sign of the winding relative to the plane normal matters,
+        // so the cross product doesn't need normalizing.
+        let wind = (b - a).cross(c - a);
+        let tri = if wind.dot(normal) < 0.0 {
+            [a, b, c]
+        } else {
+            [c, b, a]
+        };
+
+        let color = &palette[(k + i) % palette.len()];
+        for p in tri {
+            out.points.extend_from_slice(&p.to_array());
+            out.normals.extend_from_slice(&normal_array);
+            let (s, t) = (tex.u0 + p.dot(u_axis), tex.v0 + p.dot(v_axis));
+            out.uv.extend_from_slice(&[s, t]);
+            match lightmap {
+                Some((Some(lightmap), _)) => out.uv1.extend_from_slice(&lightmap.uv(s, t)),
+                Some((None, unlit)) => out.uv1.extend_from_slice(&unlit),
+                None => {}
+            }
+            out.colors.extend_from_slice(color);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{
+    lightmaps::luxel_range,
+    surface::{SURF_NODRAW, SURF_SKY, SURF_WARP},
+    BSP38,
+};
+
+/// A per-face measure for analysis views, see [BSP38::face_metric].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaceMetric {
+    /// Lightmap samples per square unit, as laid out by the light compiler.
+    /// Faces that get no lightmap (sky, warp and nodraw) are 0.
+    LuxelDensity,
+    /// World units per texel, averaged over the two texture axes; 1 is the
+    /// default editor scale.
+    TextureScale,
+    /// Area in square units.
+    Area,
+    /// PVS cluster of a leaf holding the face, or -1 for none.
+    Cluster,
+    /// Faces drawn when standing in the face's cluster: the faces of every
+    /// cluster in its PVS. An estimate of overdraw around the face.
+    Overdraw,
+}
+
+impl FaceMetric {
+    pub const ALL: [FaceMetric; 5] = [
+        FaceMetric::LuxelDensity,
+        FaceMetric::TextureScale,
+        FaceMetric::Area,
+        FaceMetric::Cluster,
+        FaceMetric::Overdraw,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FaceMetric::LuxelDensity => "luxel density",
+            FaceMetric::TextureScale => "texture scale",
+            FaceMetric::Area => "face area",
+            FaceMetric::Cluster => "cluster",
+            FaceMetric::Overdraw => "overdraw",
+        }
+    }
+}
+
+impl BSP38 {
+    /// Measures `metric` for every face, in face order.
+    #[instrument(skip(self))]
+    pub fn face_metric(&self, metric: FaceMetric) -> Vec<f32> {
+        match metric {
+            FaceMetric::Cluster => self.face_clusters().into_iter().map(f32::from).collect(),
+            FaceMetric::Overdraw => self.face_overdraw(),
+            _ => {
+                let tex_info = self.read_texture_info().unwrap_or_default();
+                let records = self.read_face_records().unwrap_or_default();
+                let polygons = self.face_polygons();
+                records
+                    .iter()
+                    .zip(&polygons)
+                    .map(|(face, points)| {
+                        let Some(tex) = tex_info.get(face.texinfo as usize) else {
+                            return 0.0;
+                        };
+                        let (u, v) = (Vec3A::from(tex.u), Vec3A::from(tex.v));
+                        match metric {
+                            FaceMetric::Area => polygon_area(points),
+                            FaceMetric::TextureScale => {
+                                (1.0 / u.length().max(f32::EPSILON)
+                                    + 1.0 / v.length().max(f32::EPSILON))
+                                    / 2.0
+                            }
+                            _ if tex.flags & (SURF_SKY | SURF_WARP | SURF_NODRAW) != 0 => 0.0,
+                            _ => {
+                                let area = polygon_area(points);
+                                let luxels = luxel_extent(points, u, tex.u0)
+                                    * luxel_extent(points, v, tex.v0);
+                                if area > 0.0 {
+                                    luxels / area
+                                } else {
+                                    0.0
+                                }
+                            }
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The corners of every face, in winding order.
+    pub(super) fn face_polygons(&self) -> Vec<Vec<Vec3A>> {
+        let vertices = self.read_vertices().unwrap_or_default();
+        let face_edges = self.read_face_edges().unwrap_or_default();
+        let edges = self.read_edges().unwrap_or_default();
+        self.read_face_records()
+            .unwrap_or_default()
+            .iter()
+            .map(|face| {
+                let first = face.first_edge as usize;
+                face_edges
+                    .get(first..first + face.num_edges as usize)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|surf_edge| {
+                        let i = surf_edge.start(&edges)? as usize * 3;
+                        vertices.get(i..i + 3).map(Vec3A::from_slice)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn face_overdraw(&self) -> Vec<f32> {
+        let clusters = self.face_clusters();
+        let vis = self.read_vis_matrix().unwrap_or_default();
+        let num_clusters = clusters
+            .iter()
+            .filter_map(|&c| usize::try_from(c).ok())
+            .map(|c| c + 1)
+            .max()
+            .unwrap_or(0);
+        let mut faces_in = vec![0usize; num_clusters];
+        for &cluster in &clusters {
+            if let Ok(cluster) = usize::try_from(cluster) {
+                faces_in[cluster] += 1;
+            }
+        }
+        // Clusters missing from the vis matrix see and are seen by all
+        let in_vis = num_clusters.min(vis.num_clusters());
+        let past_vis: usize = faces_in[in_vis..].iter().sum();
+        let all: usize = faces_in.iter().sum();
+        let drawn: Vec<usize> = (0..num_clusters)
+            .map(|from| match from < in_vis {
+                true => {
+                    let seen: usize = (0..in_vis)
+                        .filter(|&to| vis.is_visible(from, to))
+                        .map(|to| faces_in[to])
+                        .sum();
+                    seen + past_vis
+                }
+                false => all,
+            })
+            .collect();
+        clusters
+            .iter()
+            .map(|&cluster| {
+                usize::try_from(cluster)
+                    .ok()
+                    .map_or(0.0, |c| drawn[c] as f32)
+            })
+            .collect()
+    }
+}
+
+/// Area of a planar convex polygon.
+fn polygon_area(points: &[Vec3A]) -> f32 {
+    let Some(&first) = points.first() else {
+        return 0.0;
+    };
+    points
+        .windows(2)
+        .skip(1)
+        .map(|pair| (pair[0] - first).cross(pair[1] - first))
+        .sum::<Vec3A>()
+        .length()
+        / 2.0
+}
+
+/// Number of lightmap samples along texture `axis`, see [luxel_range].
+fn luxel_extent(points: &[Vec3A], axis: Vec3A, offset: f32) -> f32 {
+    luxel_range(points, axis, offset).map_or(0.0, |(_, count)| count as f32)
+}
@@ -0,0 +1,557 @@
+mod areas;
+#[cfg(feature = "bevy")]
+mod bevy_mesh;
+mod bounds;
+mod brushes;
+mod collision;
+pub mod contents;
+mod edges;
+mod entities;
+mod error;
+mod flow;
+mod fmt;
+mod lightmaps;
+mod lights;
+mod mesh_builder;
+mod metrics;
+mod models;
+mod nav;
+mod occlusion;
+mod options;
+mod palette;
+mod references;
+mod report;
+mod spatial;
+pub mod surface;
+mod targets;
+mod textures;
+mod trace;
+mod tree;
+mod vis;
+mod writer;
+
+pub mod prelude {
+    pub use super::areas::*;
+    #[cfg(feature = "bevy")]
+    pub use super::bevy_mesh::*;
+    pub use super::bounds::*;
+    pub use super::brushes::*;
+    pub use super::collision::*;
+    pub use super::edges::*;
+    pub use super::entities::*;
+    pub use super::error::*;
+    pub use super::flow::*;
+    pub use super::lightmaps::*;
+    pub use super::lights::*;
+    pub use super::mesh_builder::*;
+    pub use super::metrics::*;
+    pub use super::models::*;
+    pub use super::nav::*;
+    pub use super::occlusion::*;
+    pub use super::options::*;
+    pub use super::palette::*;
+    pub use super::references::*;
+    pub use super::report::*;
+    pub use super::targets::*;
+    pub use super::textures::*;
+    pub use super::trace::*;
+    pub use super::tree::*;
+    pub use super::vis::*;
+    pub use super::writer::*;
+}
+
+use options::Decoded;
+use prelude::*;
+pub use report::analyze;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use glam::Vec3A;
+use std::{collections::HashMap, io::Cursor};
+use surface::SurfaceFlags;
+use tracing::instrument;
+
+pub struct BSP38 {
+    pub magic: String,
+    pub version: u32,
+    lumps: Vec<BSP38Lump>,
+    pub bytes: Vec<u8>,
+
+    bounds: Bounds,
+    decoded: Decoded,
+}
+
+#[derive(Debug)]
+struct BSP38Lump {
+    offset: i32,
+    length: i32,
+    /// The offset and length in the lump table, when they reached outside of
+    /// the file and were cut to it.
+    outside: Option<(i32, i32)>,
+}
+
+/// The lumps of a BSP38 file, in file order.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LumpIndex {
+    Entities = 0,
+    Planes = 1,
+    Vertices = 2,
+    Visibility = 3,
+    Nodes = 4,
+    Texinfo = 5,
+    Faces = 6,
+    Lighting = 7,
+    Leafs = 8,
+    LeafFaces = 9,
+    LeafBrushes = 10,
+    Edges = 11,
+    FaceEdges = 12,
+    Models = 13,
+    Brushes = 14,
+    BrushSides = 15,
+    Pop = 16,
+    Areas = 17,
+    AreaPortals = 18,
+    COUNT = 19,
+}
+
+impl LumpIndex {
+    /// Every lump, in file order.
+    pub const ALL: [LumpIndex; LumpIndex::COUNT as usize] = [
+        LumpIndex::Entities,
+        LumpIndex::Planes,
+        LumpIndex::Vertices,
+        LumpIndex::Visibility,
+        LumpIndex::Nodes,
+        LumpIndex::Texinfo,
+        LumpIndex::Faces,
+        LumpIndex::Lighting,
+        LumpIndex::Leafs,
+        LumpIndex::LeafFaces,
+        LumpIndex::LeafBrushes,
+        LumpIndex::Edges,
+        LumpIndex::FaceEdges,
+        LumpIndex::Models,
+        LumpIndex::Brushes,
+        LumpIndex::BrushSides,
+        LumpIndex::Pop,
+        LumpIndex::Areas,
+        LumpIndex::AreaPortals,
+    ];
+
+    /// The lump's name in [LumpInfo], such as `leaf_faces`.
+    pub fn name(self) -> &'static str {
+        LUMP_INFO[self as usize].0
+    }
+
+    /// The lump with [LumpIndex::name] `name`.
+    ///
+    /// ```
+    /// # use q2_formats::bsp38::LumpIndex;
+    /// assert_eq!(LumpIndex::from_name("lighting"), Some(LumpIndex::Lighting));
+    /// assert_eq!(LumpIndex::from_name("lightmaps"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        LumpIndex::ALL.into_iter().find(|lump| lump.name() == name)
+    }
+}
+
+/// Name and record size in bytes of each lump, in lump order. Lumps without
+/// fixed-size records use a size of 0.
+const LUMP_INFO: [(&str, usize); LumpIndex::COUNT as usize] = [
+    ("entities", 0),
+    ("planes", 20),
+    ("vertices", 12),
+    ("visibility", 0),
+    ("nodes", 28),
+    ("texinfo", 76),
+    ("faces", 20),
+    ("lighting", 0),
+    ("leafs", 28),
+    ("leaf_faces", 2),
+    ("leaf_brushes", 2),
+    ("edges", 4),
+    ("face_edges", 4),
+    ("models", 48),
+    ("brushes", 12),
+    ("brush_sides", 4),
+    ("pop", 0),
+    ("areas", 8),
+    ("area_portals", 8),
+];
+
+/// An entry of the lump table with its name and record count.
+#[derive(Debug, Clone, Copy)]
+pub struct LumpInfo {
+    pub name: &'static str,
+    pub offset: i32,
+    pub length: i32,
+    /// Number of fixed-size records, or None for lumps of raw bytes.
+    pub count: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureInfo {
+    pub u: [f32; 3],
+    pub u0: f32,
+    pub v: [f32; 3],
+    pub v0: f32,
+    pub flags: u32,
+    pub value: u32,
+    pub texture: String,
+    pub next: u32,
+}
+
+impl TextureInfo {
+    pub fn surface_flags(&self) -> SurfaceFlags {
+        SurfaceFlags::from_bits_retain(self.flags)
+    }
+}
+
+/// A face record from the Faces lump.
+#[derive(Debug, Clone, Copy)]
+pub struct Face {
+    pub plane: u16,
+    pub side: u16,
+    pub first_edge: u32,
+    pub num_edges: u16,
+    pub texinfo: u16,
+    pub lightmap_styles: [u8; 4],
+    pub lightmap_offset: i32,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct FaceData {
+    pub points: Vec<f32>,
+    pub normals: Vec<f32>,
+    pub colors: Vec<f32>,
+    pub uv: Vec<f32>,
+    /// Lightmap texture coordinates, two per vertex. Empty unless lightmaps
+    /// have been laid out.
+    pub uv1: Vec<f32>,
+}
+
+impl FaceData {
+    pub fn triangle_count(&self) -> usize {
+        self.points.len() / 9
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.normals.clear();
+        self.colors.clear();
+        self.uv.clear();
+        self.uv1.clear();
+    }
+
+    /// The box around all the vertices, empty for no vertices.
+    pub fn bounds(&self) -> Bounds {
+        let mut bounds = Bounds::default();
+        for point in self.points.chunks_exact(3) {
+            for (i, &x) in point.iter().enumerate() {
+                bounds.min[i] = bounds.min[i].min(x);
+                bounds.max[i] = bounds.max[i].max(x);
+            }
+        }
+        bounds
+    }
+
+    /// Moves all the vertices of `other` onto the end of this data.
+    pub fn append(&mut self, mut other: FaceData) {
+        self.points.append(&mut other.points);
+        self.normals.append(&mut other.normals);
+        self.colors.append(&mut other.colors);
+        self.uv.append(&mut other.uv);
+        self.uv1.append(&mut other.uv1);
+    }
+}
+
+impl BSP38 {
+    /// Parses a BSP with the default [ParseOptions].
+    ///
+    /// Only files that aren't BSPs at all are rejected. Lumps reaching past
+    /// the end of the file are cut short while parsing, and their readers,
+    /// like those of lumps ending in a partial record, return an error.
+    ///
+    /// Indices and counts in the records are returned as stored. The
+    /// builders and queries using them treat lumps their reader rejects as
+    /// empty, skip records referring to data the file lacks, and bound what
+    /// corrupt counts make them allocate.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, BspError> {
+        Self::parse_with(bytes, &ParseOptions::default())
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    /// Describes every lump of the file, in lump order.
+    pub fn lump_table(&self) -> Vec<LumpInfo> {
+        (0..LumpIndex::COUNT as usize)
+            .map(|i| self.lump_info_at(i))
+            .collect()
+    }
+
+    /// Whether lump `index` holds any data. Stripped maps (see
+    /// [BSP38::write_stripped]) may lack the optional ones, such as the
+    /// Lighting and Visibility lumps.
+    pub fn has_lump(&self, index: LumpIndex) -> bool {
+        self.lumps[index as usize].length > 0
+    }
+
+    fn lump_info(&self, index: LumpIndex) -> LumpInfo {
+        self.lump_info_at(index as usize)
+    }
+
+    fn lump_info_at(&self, i: usize) -> LumpInfo {
+        let (name, record_size) = LUMP_INFO[i];
+        let lump = &self.lumps[i];
+        LumpInfo {
+            name,
+            offset: lump.offset,
+            length: lump.length,
+            count: (record_size > 0).then(|| lump.length as usize / record_size),
+        }
+    }
+
+    fn compute_bounds(&self) -> Bounds {
+        let vertices = self.read_vertices().unwrap_or_default();
+        let (min, max) = vertices
+            .chunks_exact(3)
+            .map(Vec3A::from_slice)
+            .fold((Vec3A::INFINITY, Vec3A::NEG_INFINITY), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        Bounds {
+            min: min.to_array(),
+            max: max.to_array(),
+        }
+    }
+
+    // Convert the above JavaScript function to Rust
+    #[instrument(skip_all)]
+    pub fn read_vertices(&self) -> Result<Vec<f32>, BspError> {
+        if let Some(vertices) = &self.decoded.vertices {
+            return Ok(vertices.clone());
+        }
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Vertices)?;
+        let num_vertices = cursor.get_ref().len() / 12;
+
+        let mut buffer = Vec::with_capacity(3 * num_vertices);
+        for _ in 0..num_vertices {
+            buffer.push(cursor.read_f32::<LittleEndian>()?);
+            buffer.push(cursor.read_f32::<LittleEndian>()?);
+            buffer.push(cursor.read_f32::<LittleEndian>()?);
+        }
+        Ok(buffer)
+    }
+
+    /// The bytes of a lump, checked the way [Validation::Strict] checks them
+    /// up front: the lump must lie within the file, and a lump of fixed-size
+    /// records must hold a whole number of them.
+    fn read_lump_as_cursor(&self, lump_index: LumpIndex) -> Result<Cursor<&[u8]>, BspError> {
+        let (name, record_size) = LUMP_INFO[lump_index as usize];
+        let lump = &self.lumps[lump_index as usize];
+        if let Some((offset, length)) = lump.outside {
+            return Err(BspError::InvalidLump {
+                name,
+                offset,
+                length,
+            });
+        }
+        if record_size > 0 && !(lump.length as usize).is_multiple_of(record_size) {
+            return Err(BspError::InvalidLumpSize {
+                name,
+                length: lump.length,
+                record_size,
+            });
+        }
+        Ok(Cursor::new(
+            &self.bytes[lump.offset as usize..(lump.offset + lump.length) as usize],
+        ))
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_planes(&self) -> Result<Vec<[f32; 4]>, BspError> {
+        if let Some(planes) = &self.decoded.planes {
+            return Ok(planes.clone());
+        }
+        const PLANE_SIZE: usize = 20;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Planes)?;
+        let num_planes = cursor.get_ref().len() / PLANE_SIZE;
+        let mut buffer = Vec::with_capacity(num_planes);
+        for _ in 0..num_planes {
+            let normal_x = cursor.read_f32::<LittleEndian>()?;
+            let normal_y = cursor.read_f32::<LittleEndian>()?;
+            let normal_z = cursor.read_f32::<LittleEndian>()?;
+            let distance = cursor.read_f32::<LittleEndian>()?;
+            let _type = cursor.read_u32::<LittleEndian>()?;
+            buffer.push([normal_x, normal_y, normal_z, distance]);
+        }
+        Ok(buffer)
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_face_records(&self) -> Result<Vec<Face>, BspError> {
+        if let Some(faces) = &self.decoded.faces {
+            return Ok(faces.clone());
+        }
+        const FACE_BYTES: usize = 20;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Faces)?;
+        let num_faces = cursor.get_ref().len() / FACE_BYTES;
+        let mut buffer = Vec::with_capacity(num_faces);
+        for _ in 0..num_faces {
+            let plane = cursor.read_u16::<LittleEndian>()?;
+            let side = cursor.read_u16::<LittleEndian>()?;
+            let first_edge = cursor.read_u32::<LittleEndian>()?;
+            let num_edges = cursor.read_u16::<LittleEndian>()?;
+            let texinfo = cursor.read_u16::<LittleEndian>()?;
+            let mut lightmap_styles = [0u8; 4];
+            for style in lightmap_styles.iter_mut() {
+                *style = cursor.read_u8()?;
+            }
+            let lightmap_offset = cursor.read_i32::<LittleEndian>()?;
+            buffer.push(Face {
+                plane,
+                side,
+                first_edge,
+                num_edges,
+                texinfo,
+                lightmap_styles,
+                lightmap_offset,
+            });
+        }
+        Ok(buffer)
+    }
+
+    // Returns the PVS cluster of each face, taken from the first leaf that
+    // references it, or -1 for faces not referenced by any leaf.
+    #[instrument(skip_all)]
+    fn face_clusters(&self) -> Vec<i16> {
+        let num_faces = self.lumps[LumpIndex::Faces as usize].length as usize / 20;
+        let mut clusters = vec![-1; num_faces];
+
+        let leaf_faces = self.read_leaf_faces().unwrap_or_default();
+        for leaf in self.read_leafs().unwrap_or_default() {
+            if leaf.cluster < 0 {
+                continue;
+            }
+            for face in leaf.faces(&leaf_faces) {
+                let Some(slot) = clusters.get_mut(face) else {
+                    continue;
+                };
+                if *slot < 0 {
+                    *slot = leaf.cluster;
+                }
+            }
+        }
+        clusters
+    }
+
+    /// Triangulates all faces into a single set of vertex buffers.
+    ///
+    /// Use a [MeshBuilder] directly to reuse buffers across repeated builds.
+    pub fn read_faces(&self) -> Result<FaceData, BspError> {
+        self.check_mesh_lumps()?;
+        let mut builder = MeshBuilder::with_capacity_for(self);
+        builder.build(self);
+        Ok(builder.into_face_data())
+    }
+
+    /// Triangulates all drawn faces into one set of vertex buffers per
+    /// texture, keyed by texture name, so each can be drawn with its own
+    /// material. Sky and nodraw faces, and textures no face uses, are left
+    /// out.
+    pub fn read_faces_by_texture(&self) -> Result<HashMap<String, FaceData>, BspError> {
+        self.check_mesh_lumps()?;
+        // Batches under the threshold merge per texture, across all clusters
+        let mut builder = MeshBuilder::new();
+        let mut batches = builder.build_batches(self, usize::MAX);
+        batches.extend(builder.build_translucent_batches(self));
+
+        let mut by_texture: HashMap<String, FaceData> = HashMap::new();
+        for batch in batches {
+            by_texture
+                .entry(batch.texture)
+                .or_default()
+                .append(batch.data);
+        }
+        Ok(by_texture)
+    }
+
+    /// Reads the lumps faces are triangulated from, for the errors a
+    /// [MeshBuilder] would skip over by treating them as empty.
+    fn check_mesh_lumps(&self) -> Result<(), BspError> {
+        for lump in [
+            LumpIndex::Planes,
+            LumpIndex::Vertices,
+            LumpIndex::Texinfo,
+            LumpIndex::Faces,
+            LumpIndex::Edges,
+            LumpIndex::FaceEdges,
+            LumpIndex::Leafs,
+            LumpIndex::LeafFaces,
+            LumpIndex::Models,
+        ] {
+            self.read_lump_as_cursor(lump)?;
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_texture_info(&self) -> Result<Vec<TextureInfo>, BspError> {
+        if let Some(tex_info) = &self.decoded.tex_info {
+            return Ok(tex_info.clone());
+        }
+        const TEXTUREINFO_SIZE: usize = 76;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Texinfo)?;
+        let num_tex_info = cursor.get_ref().len() / TEXTUREINFO_SIZE;
+
+        let mut buffer = Vec::with_capacity(num_tex_info);
+
+        for _ in 0..num_tex_info {
+            let u = [
+                cursor.read_f32::<LittleEndian>()?,
+                cursor.read_f32::<LittleEndian>()?,
+                cursor.read_f32::<LittleEndian>()?,
+            ];
+            let u0 = cursor.read_f32::<LittleEndian>()?;
+            let v = [
+                cursor.read_f32::<LittleEndian>()?,
+                cursor.read_f32::<LittleEndian>()?,
+                cursor.read_f32::<LittleEndian>()?,
+            ];
+            let v0 = cursor.read_f32::<LittleEndian>()?;
+
+            let flags = cursor.read_u32::<LittleEndian>()?;
+            let value = cursor.read_u32::<LittleEndian>()?;
+
+            let mut buf = Vec::new();
+            for _ in 0..32 {
+                buf.push(cursor.read_u8()?);
+            }
+            let texture = buf
+                .iter()
+                .filter(|&&b| b != 0)
+                .map(|&b| b as char)
+                .collect::<String>()
+                .trim()
+                .to_string();
+
+            let next = cursor.read_u32::<LittleEndian>()?;
+
+            buffer.push(TextureInfo {
+                u,
+                u0,
+                v,
+                v0,
+                flags,
+                value,
+                texture,
+                next,
+            });
+        }
+
+        Ok(buffer)
+    }
+}
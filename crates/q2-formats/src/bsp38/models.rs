@@ -0,0 +1,55 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// A model from the Models lump. Model 0 is the world; the others are the
+/// inline brush models of entities such as doors and platforms, referenced
+/// as `*1`, `*2`, ... by their `model` key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Model {
+    pub mins: [f32; 3],
+    pub maxs: [f32; 3],
+    pub origin: [f32; 3],
+    /// Root of the model's subtree in the Nodes lump.
+    pub head_node: i32,
+    pub first_face: u32,
+    pub num_faces: u32,
+}
+
+impl Model {
+    /// Range of the Faces lump holding the faces of this model.
+    pub fn faces(&self) -> std::ops::Range<usize> {
+        let first = self.first_face as usize;
+        first..first.saturating_add(self.num_faces as usize)
+    }
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn read_models(&self) -> Result<Vec<Model>, BspError> {
+        const MODEL_SIZE: usize = 48;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Models)?;
+        let num_models = cursor.get_ref().len() / MODEL_SIZE;
+        (0..num_models)
+            .map(|_| {
+                Ok(Model {
+                    mins: read_f32s(&mut cursor)?,
+                    maxs: read_f32s(&mut cursor)?,
+                    origin: read_f32s(&mut cursor)?,
+                    head_node: cursor.read_i32::<LittleEndian>()?,
+                    first_face: cursor.read_u32::<LittleEndian>()?,
+                    num_faces: cursor.read_u32::<LittleEndian>()?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn read_f32s(cursor: &mut std::io::Cursor<&[u8]>) -> std::io::Result<[f32; 3]> {
+    Ok([
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+        cursor.read_f32::<LittleEndian>()?,
+    ])
+}
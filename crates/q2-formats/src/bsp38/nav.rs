@@ -0,0 +1,314 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{contents::MASK_PLAYERSOLID, surface::SURF_SKY, Tracer, BSP38};
+
+/// Lowest normal Z of a floor a player can stand on, as in the game.
+const MIN_FLOOR_NORMAL: f32 = 0.7;
+/// Height a player walks up without jumping.
+const STEP_HEIGHT: f32 = 18.0;
+/// The player's box, relative to its origin, which is this far above the
+/// floor.
+const PLAYER_MINS: [f32; 3] = [-16.0, -16.0, -24.0];
+const PLAYER_MAXS: [f32; 3] = [16.0, 16.0, 32.0];
+const ORIGIN_HEIGHT: f32 = 24.0;
+/// Floors closer than this in height are the same spot.
+const SAME_FLOOR: f32 = 4.0;
+/// How far [NavGrid::nearest] looks for a node.
+const MAX_SNAP: f32 = 96.0;
+/// Most grid cells a [NavGrid] looks at over all of its floors, far more
+/// than the 8192 unit maps of the game need at any sensible spacing, so
+/// corrupt coordinates can't run it out of memory or time.
+const MAX_CELLS: usize = 1 << 20;
+
+/// Where a player can stand in a map and walk between, as points on a
+/// horizontal grid over the world's floors, linked to the points of the
+/// neighboring cells they can walk up to or drop down to. A coarse stand-in
+/// for a navmesh, enough for distances along the ground.
+#[derive(Debug, Clone, Default)]
+pub struct NavGrid {
+    spacing: f32,
+    /// Floor points, a player's origin [ORIGIN_HEIGHT] above them.
+    nodes: Vec<[f32; 3]>,
+    /// The nodes reachable from each node, with the distance.
+    links: Vec<Vec<(usize, f32)>>,
+    /// Nodes by grid cell.
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl BSP38 {
+    /// Builds the [NavGrid] of the world's floors, with a point every
+    /// `spacing` units where the player's box fits.
+    ///
+    /// ```
+    /// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+    ///
+    /// let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3]).build();
+    /// let nav = BSP38::from_bytes(bytes).unwrap().nav_grid(32.0);
+    /// assert_eq!(nav.nodes().len(), 8 * 8);
+    /// ```
+    #[instrument(skip(self))]
+    pub fn nav_grid(&self, spacing: f32) -> NavGrid {
+        let tracer = self.tracer();
+        let planes = self.read_planes().unwrap_or_default();
+        let tex_info = self.read_texture_info().unwrap_or_default();
+        let records = self.read_face_records().unwrap_or_default();
+        let polygons = self.face_polygons();
+        let models = self.read_models().unwrap_or_default();
+        let world = models
+            .first()
+            .map_or(0..records.len(), |world| world.faces());
+        let world = world.start..world.end.min(records.len());
+        // Floors are cut to the world model's box, if the map has one
+        let limits = models
+            .first()
+            .map_or((Vec3A::NEG_INFINITY, Vec3A::INFINITY), |world| {
+                (Vec3A::from(world.mins), Vec3A::from(world.maxs))
+            });
+
+        let mut nav = NavGrid {
+            spacing,
+            ..Default::default()
+        };
+        let mut budget = MAX_CELLS;
+        for k in world {
+            let (Some(face), Some(polygon)) = (records.get(k), polygons.get(k)) else {
+                continue;
+            };
+            let sky = tex_info
+                .get(face.texinfo as usize)
+                .is_some_and(|tex| tex.flags & SURF_SKY != 0);
+            let Some(&[x, y, z, dist]) = planes.get(face.plane as usize) else {
+                continue;
+            };
+            // Facing the same way as the mesh builder's normals
+            let (normal, dist) = match face.side {
+                0 => (-Vec3A::new(x, y, z), -dist),
+                _ => (Vec3A::new(x, y, z), dist),
+            };
+            if sky || normal.z < MIN_FLOOR_NORMAL || polygon.len() < 3 {
+                continue;
+            }
+            nav.add_floor(&tracer, polygon, normal, dist, limits, &mut budget);
+        }
+        nav.link(&tracer);
+        nav
+    }
+}
+
+impl NavGrid {
+    pub fn spacing(&self) -> f32 {
+        self.spacing
+    }
+
+    /// The floor points, in map coordinates.
+    pub fn nodes(&self) -> &[[f32; 3]] {
+        &self.nodes
+    }
+
+    /// The nodes reachable in a step from `node`, with their distance.
+    pub fn links(&self, node: usize) -> &[(usize, f32)] {
+        self.links.get(node).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.spacing).floor() as i32,
+            (y / self.spacing).floor() as i32,
+        )
+    }
+
+    /// The node nearest to `point`, such as an entity's origin, within a
+    /// few cells of it.
+    pub fn nearest(&self, point: [f32; 3]) -> Option<usize> {
+        let point = Vec3A::from(point);
+        let (cx, cy) = self.cell(point.x, point.y);
+        let reach = (MAX_SNAP / self.spacing).ceil() as i32;
+        (cx.saturating_sub(reach)..=cx.saturating_add(reach))
+            .flat_map(|x| {
+                (cy.saturating_sub(reach)..=cy.saturating_add(reach)).map(move |y| (x, y))
+            })
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .map(|&node| (node, Vec3A::from(self.nodes[node]).distance(point)))
+            .filter(|&(_, distance)| distance <= MAX_SNAP)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(node, _)| node)
+    }
+
+    /// Walking distance from `start` to every node, infinite for the nodes
+    /// it can't reach.
+    pub fn distances_from(&self, start: usize) -> Vec<f32> {
+        let mut distances = vec![f32::INFINITY; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        distances[start] = 0.0;
+        // Bits of non-negative floats sort as the floats do
+        queue.push(Reverse((0f32.to_bits(), start)));
+        while let Some(Reverse((bits, node))) = queue.pop() {
+            let distance = f32::from_bits(bits);
+            if distance > distances[node] {
+                continue;
+            }
+            for &(next, step) in self.links(node) {
+                let next_distance = distance + step;
+                if next_distance < distances[next] {
+                    distances[next] = next_distance;
+                    queue.push(Reverse((next_distance.to_bits(), next)));
+                }
+            }
+        }
+        distances
+    }
+
+    /// The connected part of the grid each node belongs to, numbered from
+    /// 0, counting a drop as a way back up.
+    pub fn components(&self) -> Vec<usize> {
+        let mut neighbors = vec![Vec::new(); self.nodes.len()];
+        for (node, links) in self.links.iter().enumerate() {
+            for &(next, _) in links {
+                neighbors[node].push(next);
+                neighbors[next].push(node);
+            }
+        }
+        let mut components = vec![usize::MAX; self.nodes.len()];
+        let mut count = 0;
+        for start in 0..self.nodes.len() {
+            if components[start] != usize::MAX {
+                continue;
+            }
+            components[start] = count;
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                for &next in &neighbors[node] {
+                    if components[next] == usize::MAX {
+                        components[next] = count;
+                        stack.push(next);
+                    }
+                }
+            }
+            count += 1;
+        }
+        components
+    }
+
+    /// Adds the grid points inside a floor polygon where the player fits,
+    /// within the `limits` box. Floors are skipped if the cells of their
+    /// bounds don't fit in what is left of the `budget`.
+    fn add_floor(
+        &mut self,
+        tracer: &Tracer,
+        polygon: &[Vec3A],
+        normal: Vec3A,
+        dist: f32,
+        limits: (Vec3A, Vec3A),
+        budget: &mut usize,
+    ) {
+        let (min, max) = polygon.iter().fold(
+            (Vec3A::splat(f32::INFINITY), Vec3A::splat(f32::NEG_INFINITY)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        );
+        let (min, max) = (min.max(limits.0), max.min(limits.1));
+        if !min.is_finite() || !max.is_finite() || min.cmpgt(max).any() {
+            return;
+        }
+        let (x0, y0) = self.cell(min.x, min.y);
+        let (x1, y1) = self.cell(max.x, max.y);
+        let cells = (x1 as i64 - x0 as i64 + 1).checked_mul(y1 as i64 - y0 as i64 + 1);
+        match cells
+            .and_then(|cells| usize::try_from(cells).ok())
+            .filter(|&cells| cells <= *budget)
+        {
+            Some(cells) => *budget -= cells,
+            None => return,
+        }
+        for cx in x0..=x1 {
+            for cy in y0..=y1 {
+                // Cell centers, so floors meeting at an edge share no point
+                let x = (cx as f32 + 0.5) * self.spacing;
+                let y = (cy as f32 + 0.5) * self.spacing;
+                if !inside(polygon, x, y) {
+                    continue;
+                }
+                let z = (dist - normal.x * x - normal.y * y) / normal.z;
+                let cell = self.cells.entry((cx, cy)).or_default();
+                if cell
+                    .iter()
+                    .any(|&node| (self.nodes[node][2] - z).abs() < SAME_FLOOR)
+                {
+                    continue;
+                }
+                let origin = [x, y, z + ORIGIN_HEIGHT + 1.0];
+                let fits = !tracer
+                    .trace(origin, origin, PLAYER_MINS, PLAYER_MAXS, MASK_PLAYERSOLID)
+                    .start_solid;
+                if fits {
+                    cell.push(self.nodes.len());
+                    self.nodes.push([x, y, z]);
+                }
+            }
+        }
+    }
+
+    /// Links each node to those of the 8 cells around it that the player
+    /// can step up to or drop down to.
+    fn link(&mut self, tracer: &Tracer) {
+        self.links = vec![Vec::new(); self.nodes.len()];
+        let clear = |from: Vec3A, to: Vec3A| {
+            tracer
+                .trace(
+                    from.into(),
+                    to.into(),
+                    PLAYER_MINS,
+                    PLAYER_MAXS,
+                    MASK_PLAYERSOLID,
+                )
+                .fraction
+                >= 1.0
+        };
+        let lift = Vec3A::Z * (ORIGIN_HEIGHT + 1.0);
+        for (&(cx, cy), nodes) in &self.cells {
+            for &node in nodes {
+                let a = Vec3A::from(self.nodes[node]);
+                let neighbors = (-1..=1)
+                    .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                    .filter(|&step| step != (0, 0))
+                    .filter_map(|(dx, dy)| Some((cx.checked_add(dx)?, cy.checked_add(dy)?)))
+                    .filter_map(|cell| self.cells.get(&cell))
+                    .flatten();
+                for &next in neighbors {
+                    let b = Vec3A::from(self.nodes[next]);
+                    if b.z - a.z > STEP_HEIGHT {
+                        continue;
+                    }
+                    // Over the step, then down onto the floor below
+                    let step = Vec3A::Z * STEP_HEIGHT;
+                    let over = Vec3A::new(b.x, b.y, a.z.max(b.z)) + lift + step;
+                    if clear(a + lift + step, over) && clear(over, b + lift) {
+                        self.links[node].push((next, a.distance(b)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the point at `x`, `y` is inside the convex `polygon` seen from
+/// above, whichever way it winds.
+fn inside(polygon: &[Vec3A], x: f32, y: f32) -> bool {
+    let n = polygon.len();
+    let crosses = (0..n).map(|i| {
+        let (a, b) = (polygon[i], polygon[(i + 1) % n]);
+        (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+    });
+    let (mut left, mut right) = (false, false);
+    for cross in crosses {
+        left |= cross > 0.0;
+        right |= cross < 0.0;
+    }
+    !(left && right)
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{contents::MASK_OPAQUE, FaceData, Tracer};
+
+/// Lifts the start of occlusion rays off the surface, so they don't
+/// immediately hit the wall the vertex is on.
+const SURFACE_OFFSET: f32 = 1.0;
+
+/// Settings of [FaceData::bake_occlusion].
+#[derive(Debug, Clone)]
+pub struct OcclusionOptions {
+    /// Rays cast over the hemisphere of each vertex.
+    pub rays: usize,
+    /// Length of the rays; walls further away don't occlude.
+    pub distance: f32,
+}
+
+impl Default for OcclusionOptions {
+    fn default() -> Self {
+        Self {
+            rays: 16,
+            distance: 96.0,
+        }
+    }
+}
+
+impl FaceData {
+    /// Replaces the vertex colors with baked ambient occlusion: white where
+    /// the hemisphere around the vertex normal is open, darker the more of it
+    /// is blocked by nearby walls.
+    ///
+    /// Rays are spread over the hemisphere with a cosine-weighted spiral and
+    /// traced with `tracer` against [MASK_OPAQUE] contents. A hit counts more
+    /// the closer it is. Vertices are baked in parallel on native targets.
+    #[instrument(skip_all, fields(vertices = self.points.len() / 3))]
+    pub fn bake_occlusion(&mut self, tracer: &Tracer, options: &OcclusionOptions) {
+        // Fan triangulation repeats polygon corners, so each distinct
+        // (position, normal) pair is only traced once
+        let mut ids: HashMap<[u32; 6], usize> = HashMap::new();
+        let mut samples: Vec<(Vec3A, Vec3A)> = Vec::new();
+        let vertex_ids: Vec<usize> = self
+            .points
+            .chunks_exact(3)
+            .zip(self.normals.chunks_exact(3))
+            .map(|(p, n)| {
+                let key = [p[0], p[1], p[2], n[0], n[1], n[2]].map(f32::to_bits);
+                *ids.entry(key).or_insert_with(|| {
+                    samples.push((Vec3A::from_slice(p), Vec3A::from_slice(n)));
+                    samples.len() - 1
+                })
+            })
+            .collect();
+
+        let rays = hemisphere_rays(options.rays);
+        let bake = |&(point, normal): &(Vec3A, Vec3A)| {
+            let (tangent, bitangent) = normal.any_orthonormal_pair();
+            let start = point + normal * SURFACE_OFFSET;
+            let blocked: f32 = rays
+                .iter()
+                .filter_map(|ray| {
+                    let dir = tangent * ray.x + bitangent * ray.y + normal * ray.z;
+                    let end = start + dir * options.distance;
+                    tracer.trace_line(start.to_array(), end.to_array(), MASK_OPAQUE)
+                })
+                .map(|fraction| 1.0 - fraction)
+                .sum();
+            1.0 - blocked / rays.len().max(1) as f32
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let occlusion: Vec<f32> = {
+            use rayon::prelude::*;
+            samples.par_iter().map(bake).collect()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let occlusion: Vec<f32> = samples.iter().map(bake).collect();
+
+        self.colors.clear();
+        for id in vertex_ids {
+            self.colors.extend_from_slice(&[occlusion[id]; 3]);
+        }
+    }
+}
+
+/// `count` directions over the hemisphere around +Z, denser towards the pole
+/// as in cosine-weighted sampling, laid out on a golden-angle spiral.
+fn hemisphere_rays(count: usize) -> Vec<Vec3A> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let r = ((i as f32 + 0.5) / count as f32).sqrt();
+            let phi = i as f32 * golden_angle;
+            Vec3A::new(r * phi.cos(), r * phi.sin(), (1.0 - r * r).sqrt())
+        })
+        .collect()
+}
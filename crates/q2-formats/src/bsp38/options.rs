@@ -0,0 +1,215 @@
+use tracing::{instrument, warn, Level};
+
+use super::{
+    BSP38Lump, Bounds, BspError, Edge, Entity, Face, Leaf, LumpIndex, Node, SurfEdge, TextureInfo,
+    VisMatrix, BSP38,
+};
+
+/// How [BSP38::parse_with] treats files that don't match the format exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Validation {
+    /// Rejects any header or lump table problem, including fixed-size lumps
+    /// with a partial trailing record.
+    Strict,
+    /// Only rejects files that aren't BSPs at all. Other problems are logged,
+    /// and left for the readers of the lumps they affect to return.
+    #[default]
+    Lenient,
+}
+
+/// Controls how much work [BSP38::parse_with] does up front.
+///
+/// ```
+/// use q2_formats::bsp38::{prelude::*, LumpIndex};
+///
+/// let options = ParseOptions::new()
+///     .validation(Validation::Strict)
+///     .compute_bounds(false)
+///     .eager(&[LumpIndex::Entities, LumpIndex::Visibility]);
+/// # let _ = options;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    eager: Vec<LumpIndex>,
+    compute_bounds: bool,
+    validation: Validation,
+    log_level: Option<Level>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            eager: Vec::new(),
+            compute_bounds: true,
+            validation: Validation::Lenient,
+            log_level: Some(Level::DEBUG),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Lenient validation, bounds computed, nothing decoded eagerly: the
+    /// behavior of [BSP38::from_bytes].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes these lumps while parsing and keeps the result, so later reads
+    /// are copies instead of decodes. Lumps without a reader are ignored, and
+    /// lumps their reader rejects are read, and rejected, on each call.
+    pub fn eager(mut self, lumps: &[LumpIndex]) -> Self {
+        self.eager.extend_from_slice(lumps);
+        self
+    }
+
+    /// Whether to compute the bounds of all vertices. When off,
+    /// [BSP38::bounds] returns empty (inverted infinite) bounds.
+    pub fn compute_bounds(mut self, compute_bounds: bool) -> Self {
+        self.compute_bounds = compute_bounds;
+        self
+    }
+
+    pub fn validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Level of the summary logged after parsing, or None for no summary.
+    pub fn log_level(mut self, log_level: Option<Level>) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    pub fn parse(&self, bytes: Vec<u8>) -> Result<BSP38, BspError> {
+        BSP38::parse_with(bytes, self)
+    }
+}
+
+/// Lumps decoded while parsing, see [ParseOptions::eager].
+#[derive(Default)]
+pub(super) struct Decoded {
+    pub entities: Option<Vec<Entity>>,
+    pub planes: Option<Vec<[f32; 4]>>,
+    pub vertices: Option<Vec<f32>>,
+    pub vis: Option<VisMatrix>,
+    pub tex_info: Option<Vec<TextureInfo>>,
+    pub faces: Option<Vec<Face>>,
+    pub edges: Option<Vec<Edge>>,
+    pub face_edges: Option<Vec<SurfEdge>>,
+    pub nodes: Option<Vec<Node>>,
+    pub leafs: Option<Vec<Leaf>>,
+}
+
+impl BSP38 {
+    /// Parses a BSP, doing as much validation and decoding as `options` ask
+    /// for.
+    #[instrument(skip_all, fields(bytes = bytes.len()))]
+    pub fn parse_with(bytes: Vec<u8>, options: &ParseOptions) -> Result<Self, BspError> {
+        match options.validation {
+            Validation::Strict => Self::check_lumps(&bytes)?,
+            Validation::Lenient => Self::check_magic(&bytes)?,
+        }
+
+        let word = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let version = word(4) as u32;
+        if version != 38 {
+            warn!("Unexpected BSP version {}, parsing as 38", version);
+        }
+
+        let lumps = (0..LumpIndex::COUNT as usize)
+            .map(|i| {
+                let (offset, length) = (word(8 + 8 * i), word(12 + 8 * i));
+                // Strict validation already rejected lumps outside the file
+                let start = offset.clamp(0, bytes.len() as i32);
+                let end = (start as i64 + length.max(0) as i64).min(bytes.len() as i64) as i32;
+                let outside = (start, end - start) != (offset, length);
+                if outside {
+                    warn!(
+                        "Lump {} (offset {}, length {}) is outside of the file, truncating",
+                        i, offset, length
+                    );
+                }
+                BSP38Lump {
+                    offset: start,
+                    length: end - start,
+                    outside: outside.then_some((offset, length)),
+                }
+            })
+            .collect();
+
+        let mut bsp38 = BSP38 {
+            magic: "IBSP".to_string(),
+            version,
+            lumps,
+            bytes,
+            bounds: Bounds::default(),
+            decoded: Decoded::default(),
+        };
+        for &lump in &options.eager {
+            bsp38.decode(lump);
+        }
+        if options.compute_bounds {
+            bsp38.bounds = bsp38.compute_bounds();
+        }
+
+        match options.log_level {
+            Some(Level::ERROR) => tracing::error!("Parsed {}", bsp38),
+            Some(Level::WARN) => tracing::warn!("Parsed {}", bsp38),
+            Some(Level::INFO) => tracing::info!("Parsed {}", bsp38),
+            Some(Level::DEBUG) => tracing::debug!("Parsed {}", bsp38),
+            Some(_) => tracing::trace!("Parsed {}", bsp38),
+            None => {}
+        }
+        Ok(bsp38)
+    }
+
+    fn decode(&mut self, lump: LumpIndex) {
+        match lump {
+            LumpIndex::Entities => self.decoded.entities = self.read_entities().ok(),
+            LumpIndex::Planes => self.decoded.planes = self.read_planes().ok(),
+            LumpIndex::Vertices => self.decoded.vertices = self.read_vertices().ok(),
+            LumpIndex::Visibility => self.decoded.vis = self.read_vis_matrix().ok(),
+            LumpIndex::Texinfo => self.decoded.tex_info = self.read_texture_info().ok(),
+            LumpIndex::Faces => self.decoded.faces = self.read_face_records().ok(),
+            LumpIndex::Edges => self.decoded.edges = self.read_edges().ok(),
+            LumpIndex::FaceEdges => self.decoded.face_edges = self.read_face_edges().ok(),
+            LumpIndex::Nodes => self.decoded.nodes = self.read_nodes().ok(),
+            LumpIndex::Leafs => self.decoded.leafs = self.read_leafs().ok(),
+            _ => {}
+        }
+    }
+
+    /// Heap bytes held by the lumps decoded up front, on top of the raw
+    /// [BSP38::bytes]. Lumps decoded on each call aren't counted.
+    pub fn decoded_bytes(&self) -> usize {
+        fn vec_bytes<T>(lump: &Option<Vec<T>>) -> usize {
+            lump.as_ref()
+                .map_or(0, |lump| lump.capacity() * std::mem::size_of::<T>())
+        }
+        let decoded = &self.decoded;
+        let entity_strings = decoded.entities.iter().flatten().map(|entity| {
+            entity.classname.capacity()
+                + entity
+                    .properties
+                    .iter()
+                    .map(|(key, value)| key.capacity() + value.capacity())
+                    .sum::<usize>()
+                + entity.properties.capacity() * std::mem::size_of::<(String, String)>()
+        });
+        let texture_names = decoded.tex_info.iter().flatten();
+        vec_bytes(&decoded.entities)
+            + entity_strings.sum::<usize>()
+            + vec_bytes(&decoded.planes)
+            + vec_bytes(&decoded.vertices)
+            + decoded.vis.as_ref().map_or(0, VisMatrix::memory_bytes)
+            + vec_bytes(&decoded.tex_info)
+            + texture_names
+                .map(|info| info.texture.capacity())
+                .sum::<usize>()
+            + vec_bytes(&decoded.faces)
+            + vec_bytes(&decoded.edges)
+            + vec_bytes(&decoded.face_edges)
+            + vec_bytes(&decoded.nodes)
+            + vec_bytes(&decoded.leafs)
+    }
+}
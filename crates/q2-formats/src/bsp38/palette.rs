@@ -0,0 +1,128 @@
+/// The original debug palette used to tint adjacent faces differently.
+const CLASSIC: [[f32; 3]; 9] = [
+    [0.949, 0.6314, 0.5569],
+    [0.3098, 0.7333, 0.7765],
+    [0.9451, 0.6902, 0.1451],
+    [0.7647, 0.8588, 0.3961],
+    [0.7216, 0.1373, 0.0941],
+    [0.4353, 0.5725, 0.1569],
+    [0.4549, 0.1647, 0.0078],
+    [0.5686, 0.3059, 0.6196],
+    [0.2824, 0.4039, 0.1569],
+];
+
+/// Okabe and Ito's palette, with grey in place of black.
+const OKABE_ITO: [[f32; 3]; 8] = [
+    [0.7913, 0.3467, 0.0000], // #E69F00
+    [0.0931, 0.4564, 0.8148], // #56B4E9
+    [0.0000, 0.3419, 0.1714], // #009E73
+    [0.8714, 0.7758, 0.0545], // #F0E442
+    [0.0000, 0.1683, 0.4452], // #0072B2
+    [0.6654, 0.1119, 0.0000], // #D55E00
+    [0.6038, 0.1912, 0.3864], // #CC79A7
+    [0.3185, 0.3185, 0.3185], // #999999
+];
+
+/// Paul Tol's bright qualitative palette.
+const TOL_BRIGHT: [[f32; 3]; 7] = [
+    [0.0578, 0.1845, 0.4020], // #4477AA
+    [0.8550, 0.1329, 0.1845], // #EE6677
+    [0.0160, 0.2462, 0.0331], // #228833
+    [0.6038, 0.4969, 0.0578], // #CCBB44
+    [0.1329, 0.6038, 0.8550], // #66CCEE
+    [0.4020, 0.0331, 0.1845], // #AA3377
+    [0.4969, 0.4969, 0.4969], // #BBBBBB
+];
+
+/// Blue to green to yellow to red.
+const CLASSIC_RAMP: [[f32; 3]; 4] = [
+    [0.1, 0.2, 0.9],
+    [0.1, 0.8, 0.3],
+    [0.95, 0.85, 0.1],
+    [0.9, 0.1, 0.1],
+];
+
+/// Viridis, which stays ordered by lightness for every kind of color
+/// blindness.
+const VIRIDIS: [[f32; 3]; 5] = [
+    [0.0578, 0.0003, 0.0887], // #440154
+    [0.0437, 0.0844, 0.2582], // #3B528B
+    [0.0152, 0.2831, 0.2623], // #21918C
+    [0.1119, 0.5841, 0.1221], // #5EC962
+    [0.9823, 0.7991, 0.0185], // #FDE725
+];
+
+/// Colors of debug views: the tints telling adjacent faces apart, and the
+/// ramp of continuous values. All colors are linear RGB.
+///
+/// Besides the original palette there are two that stay distinct with
+/// protanopia, deuteranopia and tritanopia; both pair with the viridis ramp.
+///
+/// ```
+/// # use q2_formats::bsp38::prelude::DebugPalette;
+/// let palette: DebugPalette = "okabe_ito".parse().unwrap();
+/// assert_eq!(palette, DebugPalette::OkabeIto);
+/// assert_eq!(palette.color(0), palette.color(palette.colors().len()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugPalette {
+    #[default]
+    Classic,
+    OkabeIto,
+    TolBright,
+}
+
+impl DebugPalette {
+    pub const ALL: [DebugPalette; 3] = [
+        DebugPalette::Classic,
+        DebugPalette::OkabeIto,
+        DebugPalette::TolBright,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DebugPalette::Classic => "classic",
+            DebugPalette::OkabeIto => "okabe_ito",
+            DebugPalette::TolBright => "tol_bright",
+        }
+    }
+
+    /// The colors given to faces or categories in turn.
+    pub fn colors(&self) -> &'static [[f32; 3]] {
+        match self {
+            DebugPalette::Classic => &CLASSIC,
+            DebugPalette::OkabeIto => &OKABE_ITO,
+            DebugPalette::TolBright => &TOL_BRIGHT,
+        }
+    }
+
+    /// Color of category `i`, cycling through [DebugPalette::colors].
+    pub fn color(&self, i: usize) -> [f32; 3] {
+        let colors = self.colors();
+        colors[i % colors.len()]
+    }
+
+    /// Color of `t` on the palette's ramp, low at 0 and high at 1.
+    pub fn ramp(&self, t: f32) -> [f32; 3] {
+        let stops: &[[f32; 3]] = match self {
+            DebugPalette::Classic => &CLASSIC_RAMP,
+            DebugPalette::OkabeIto | DebugPalette::TolBright => &VIRIDIS,
+        };
+        let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (x as usize).min(stops.len() - 2);
+        let f = x - i as f32;
+        let (a, b) = (stops[i], stops[i + 1]);
+        [0, 1, 2].map(|c| a[c] + (b[c] - a[c]) * f)
+    }
+}
+
+impl std::str::FromStr for DebugPalette {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|palette| palette.name() == name)
+            .ok_or_else(|| format!("unknown palette {:?}", name))
+    }
+}
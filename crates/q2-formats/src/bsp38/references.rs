@@ -0,0 +1,157 @@
+use std::fmt;
+
+use tracing::instrument;
+
+use super::BSP38;
+
+/// A record pointing past the end of the lump it refers to, see
+/// [BSP38::check_references].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenReference {
+    /// Lump and index of the record holding the reference.
+    pub lump: &'static str,
+    pub index: usize,
+    /// The lump referred to, the index (or end of the range) referred to
+    /// and how many records that lump has.
+    pub target: &'static str,
+    pub value: i64,
+    pub count: usize,
+}
+
+impl fmt::Display for BrokenReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}] refers to {} {}, of {}",
+            self.lump, self.index, self.target, self.value, self.count
+        )
+    }
+}
+
+impl BSP38 {
+    /// Checks that every index from one lump into another is in range: those
+    /// of faces, edges, nodes, leafs, models and brushes, and of leafs into
+    /// PVS clusters and areas if the map has any. Readers and the mesh
+    /// builder trust these, so a map failing here may not load.
+    ///
+    /// ```
+    /// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+    ///
+    /// let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3]).build();
+    /// let bsp = BSP38::from_bytes(bytes).unwrap();
+    /// assert!(bsp.check_references().is_empty());
+    /// ```
+    #[instrument(skip_all)]
+    pub fn check_references(&self) -> Vec<BrokenReference> {
+        let planes = self.read_planes().unwrap_or_default().len();
+        let vertices = self.read_vertices().unwrap_or_default().len() / 3;
+        let edges = self.read_edges().unwrap_or_default();
+        let face_edges = self.read_face_edges().unwrap_or_default();
+        let tex_info = self.read_texture_info().unwrap_or_default().len();
+        let faces = self.read_face_records().unwrap_or_default();
+        let nodes = self.read_nodes().unwrap_or_default();
+        let leafs = self.read_leafs().unwrap_or_default();
+        let leaf_faces = self.read_leaf_faces().unwrap_or_default();
+        let leaf_brushes = self.read_leaf_brushes().unwrap_or_default();
+        let brushes = self.read_brushes().unwrap_or_default();
+        let brush_sides = self.read_brush_sides().unwrap_or_default();
+        let clusters = self.read_vis_matrix().unwrap_or_default().num_clusters();
+        let areas = self.read_areas().unwrap_or_default().len();
+
+        let mut broken = Vec::new();
+        let mut check = |lump, index, target, value: i64, count: usize| {
+            if value < 0 || value as u64 >= count as u64 {
+                broken.push(BrokenReference {
+                    lump,
+                    index,
+                    target,
+                    value,
+                    count,
+                });
+            }
+        };
+        // Ranges are checked by their last record
+        let last = |range: std::ops::Range<usize>| match range.is_empty() {
+            true => None,
+            false => Some(range.end as i64 - 1),
+        };
+
+        for (i, face) in faces.iter().enumerate() {
+            check("faces", i, "planes", face.plane as i64, planes);
+            check("faces", i, "texinfo", face.texinfo as i64, tex_info);
+            let first = face.first_edge as usize;
+            if let Some(end) = last(first..first + face.num_edges as usize) {
+                check("faces", i, "face_edges", end, face_edges.len());
+            }
+        }
+        for (i, face_edge) in face_edges.iter().enumerate() {
+            check(
+                "face_edges",
+                i,
+                "edges",
+                face_edge.edge() as i64,
+                edges.len(),
+            );
+        }
+        for (i, edge) in edges.iter().enumerate() {
+            check("edges", i, "vertices", edge.v0 as i64, vertices);
+            check("edges", i, "vertices", edge.v1 as i64, vertices);
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            check("nodes", i, "planes", node.plane as i64, planes);
+            for child in node.children {
+                match child {
+                    0.. => check("nodes", i, "nodes", child as i64, nodes.len()),
+                    _ => check("nodes", i, "leafs", -1 - child as i64, leafs.len()),
+                }
+            }
+            let first = node.first_face as usize;
+            if let Some(end) = last(first..first + node.num_faces as usize) {
+                check("nodes", i, "faces", end, faces.len());
+            }
+        }
+        for (i, leaf) in leafs.iter().enumerate() {
+            if let Some(end) = last(leaf.leaf_faces()) {
+                check("leafs", i, "leaf_faces", end, leaf_faces.len());
+            }
+            if let Some(end) = last(leaf.leaf_brushes()) {
+                check("leafs", i, "leaf_brushes", end, leaf_brushes.len());
+            }
+            // Maps without vis or areas leave these unchecked
+            if clusters > 0 && leaf.cluster >= 0 {
+                check("leafs", i, "clusters", leaf.cluster as i64, clusters);
+            }
+            if areas > 0 {
+                check("leafs", i, "areas", leaf.area as i64, areas);
+            }
+        }
+        for (i, &face) in leaf_faces.iter().enumerate() {
+            check("leaf_faces", i, "faces", face as i64, faces.len());
+        }
+        for (i, &brush) in leaf_brushes.iter().enumerate() {
+            check("leaf_brushes", i, "brushes", brush as i64, brushes.len());
+        }
+        for (i, model) in self.read_models().unwrap_or_default().iter().enumerate() {
+            if let Some(end) = last(model.faces()) {
+                check("models", i, "faces", end, faces.len());
+            }
+            // Models without a tree of their own point at a leaf
+            match model.head_node {
+                0.. => check("models", i, "nodes", model.head_node as i64, nodes.len()),
+                head => check("models", i, "leafs", -1 - head as i64, leafs.len()),
+            }
+        }
+        for (i, brush) in brushes.iter().enumerate() {
+            if let Some(end) = last(brush.sides()) {
+                check("brushes", i, "brush_sides", end, brush_sides.len());
+            }
+        }
+        for (i, side) in brush_sides.iter().enumerate() {
+            check("brush_sides", i, "planes", side.plane as i64, planes);
+            if side.texinfo >= 0 {
+                check("brush_sides", i, "texinfo", side.texinfo as i64, tex_info);
+            }
+        }
+        broken
+    }
+}
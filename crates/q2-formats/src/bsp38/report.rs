@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use tracing::instrument;
+
+use super::{
+    surface::{SURF_NODRAW, SURF_SKY},
+    Bounds, BspError, LumpIndex, ParseOptions, TextureUsage, BSP38,
+};
+
+/// Everything a map index needs to know about a map, see [analyze].
+#[derive(Debug, Clone)]
+pub struct MapReport {
+    pub version: u32,
+    /// Size of the file in bytes.
+    pub size: usize,
+    pub stats: MapStats,
+    /// Textures used by the map, sorted by name.
+    pub textures: Vec<TextureUsage>,
+    /// Number of entities of each classname.
+    pub classnames: BTreeMap<String, usize>,
+    /// The `message` of the worldspawn, usually the map's title.
+    pub title: Option<String>,
+    pub vis: VisCost,
+    /// Bounds of all vertices.
+    pub bounds: Bounds,
+    /// Bounds of the faces that are drawn, without the sky and nodraw faces.
+    /// The sky box usually surrounds the whole map, so these frame the
+    /// playable space much better for thumbnails and previews.
+    pub view_bounds: Bounds,
+}
+
+/// Record counts of a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MapStats {
+    pub vertices: usize,
+    pub faces: usize,
+    pub triangles: usize,
+    pub leafs: usize,
+    pub clusters: usize,
+    pub models: usize,
+    pub brushes: usize,
+    pub entities: usize,
+}
+
+/// How much the PVS cuts down on what is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VisCost {
+    /// Clusters potentially visible from a cluster, on average.
+    pub average_visible_clusters: f32,
+    /// Faces drawn when standing in a cluster, on average over clusters.
+    /// Without vis, every face is drawn from everywhere.
+    pub average_drawn_faces: f32,
+    /// The cluster drawing the most faces, and how many.
+    pub worst_cluster: Option<(usize, usize)>,
+}
+
+/// Parses a map and reports its stats, entities, textures, vis cost and
+/// bounds, for indexing uploads on a map hosting site or similar server
+/// side use.
+///
+/// ```
+/// use q2_formats::{bsp38::analyze, test_utils::TestMapBuilder};
+///
+/// let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+///     .with_entity("info_player_deathmatch", &[("origin", "128 128 24")])
+///     .build();
+/// let report = analyze(bytes).unwrap();
+/// assert_eq!(report.stats.faces, 6);
+/// assert_eq!(report.classnames["info_player_deathmatch"], 1);
+/// ```
+#[instrument(skip_all)]
+pub fn analyze(bytes: Vec<u8>) -> Result<MapReport, BspError> {
+    let bsp = ParseOptions::new().log_level(None).parse(bytes)?;
+    Ok(bsp.report())
+}
+
+impl BSP38 {
+    /// The [MapReport] of an already parsed map.
+    pub fn report(&self) -> MapReport {
+        let records = self.read_face_records().unwrap_or_default();
+        let entities = self.read_entities().unwrap_or_default();
+        let vis = self.read_vis_matrix().unwrap_or_default();
+        let count = |lump: LumpIndex| self.lump_info(lump).count.unwrap_or(0);
+
+        let mut classnames = BTreeMap::new();
+        for entity in &entities {
+            *classnames.entry(entity.classname.clone()).or_insert(0) += 1;
+        }
+        let title = entities
+            .iter()
+            .find(|entity| entity.classname == "worldspawn")
+            .and_then(|entity| entity.get("message"))
+            .map(str::to_string);
+
+        MapReport {
+            version: self.version,
+            size: self.bytes.len(),
+            stats: MapStats {
+                vertices: count(LumpIndex::Vertices),
+                faces: records.len(),
+                triangles: records
+                    .iter()
+                    .map(|face| (face.num_edges as usize).saturating_sub(2))
+                    .sum(),
+                leafs: count(LumpIndex::Leafs),
+                clusters: vis.num_clusters(),
+                models: count(LumpIndex::Models),
+                brushes: count(LumpIndex::Brushes),
+                entities: entities.len(),
+            },
+            textures: self.unique_textures(),
+            classnames,
+            title,
+            vis: self.vis_cost(),
+            bounds: self.bounds(),
+            view_bounds: self.view_bounds(),
+        }
+    }
+
+    fn vis_cost(&self) -> VisCost {
+        let vis = self.read_vis_matrix().unwrap_or_default();
+        let clusters = self.face_clusters();
+        let num_clusters = vis.num_clusters();
+        if num_clusters == 0 {
+            return VisCost {
+                average_drawn_faces: clusters.len() as f32,
+                ..Default::default()
+            };
+        }
+
+        let mut faces_in = vec![0usize; num_clusters];
+        for &cluster in &clusters {
+            if let Some(count) = usize::try_from(cluster)
+                .ok()
+                .and_then(|c| faces_in.get_mut(c))
+            {
+                *count += 1;
+            }
+        }
+        let (mut visible, mut drawn, mut worst) = (0, 0, None);
+        for from in 0..num_clusters {
+            let to: Vec<usize> = (0..num_clusters)
+                .filter(|&to| vis.is_visible(from, to))
+                .collect();
+            let faces = to.iter().map(|&to| faces_in[to]).sum();
+            visible += to.len();
+            drawn += faces;
+            if worst.is_none_or(|(_, most)| faces > most) {
+                worst = Some((from, faces));
+            }
+        }
+        VisCost {
+            average_visible_clusters: visible as f32 / num_clusters as f32,
+            average_drawn_faces: drawn as f32 / num_clusters as f32,
+            worst_cluster: worst,
+        }
+    }
+
+    fn view_bounds(&self) -> Bounds {
+        let tex_info = self.read_texture_info().unwrap_or_default();
+        let mut bounds = Bounds::default();
+        for (face, points) in self
+            .read_face_records()
+            .unwrap_or_default()
+            .iter()
+            .zip(self.face_polygons())
+        {
+            let hidden = tex_info
+                .get(face.texinfo as usize)
+                .is_none_or(|tex| tex.flags & (SURF_SKY | SURF_NODRAW) != 0);
+            if hidden {
+                continue;
+            }
+            for point in points {
+                for i in 0..3 {
+                    bounds.min[i] = bounds.min[i].min(point[i]);
+                    bounds.max[i] = bounds.max[i].max(point[i]);
+                }
+            }
+        }
+        bounds
+    }
+}
@@ -0,0 +1,115 @@
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{Bounds, Edge, Face, LumpIndex, SurfEdge, BSP38};
+
+impl BSP38 {
+    /// Indices of the leafs whose volume may touch `bounds`, sorted.
+    ///
+    /// Walks the node tree from the head node of the world model, only
+    /// descending into the sides of each splitting plane the box reaches.
+    #[instrument(skip_all)]
+    pub fn leafs_in_bounds(&self, bounds: Bounds) -> Vec<usize> {
+        let nodes = self.read_nodes().unwrap_or_default();
+        let planes = self.read_planes().unwrap_or_default();
+        let num_leafs = self.lump_info(LumpIndex::Leafs).count.unwrap_or(0);
+
+        let mut leafs = Vec::new();
+        if nodes.is_empty() {
+            return leafs;
+        }
+        // Malformed trees can contain cycles, so each node is only entered once
+        let mut visited = vec![false; nodes.len()];
+        let mut stack = vec![0];
+        while let Some(child) = stack.pop() {
+            if child < 0 {
+                let leaf = (-1 - child) as usize;
+                if leaf < num_leafs {
+                    leafs.push(leaf);
+                }
+                continue;
+            }
+            let Some(node) = nodes.get(child as usize) else {
+                continue;
+            };
+            if std::mem::replace(&mut visited[child as usize], true) {
+                continue;
+            }
+            let Some(&plane) = planes.get(node.plane as usize) else {
+                continue;
+            };
+            let (front, back) = box_on_plane_sides(&bounds, plane);
+            if back {
+                stack.push(node.children[1]);
+            }
+            if front {
+                stack.push(node.children[0]);
+            }
+        }
+        leafs.sort_unstable();
+        leafs.dedup();
+        leafs
+    }
+
+    /// Indices of the faces overlapping `bounds`, sorted.
+    ///
+    /// Only faces listed by the leafs of [BSP38::leafs_in_bounds] are
+    /// considered, so faces outside of any leaf (e.g. those of brush models)
+    /// are never returned.
+    #[instrument(skip_all)]
+    pub fn faces_in_bounds(&self, bounds: Bounds) -> Vec<usize> {
+        let leafs = self.read_leafs().unwrap_or_default();
+        let leaf_faces = self.read_leaf_faces().unwrap_or_default();
+        let mut faces: Vec<usize> = self
+            .leafs_in_bounds(bounds)
+            .into_iter()
+            .flat_map(|leaf| leafs[leaf].faces(&leaf_faces))
+            .collect();
+        faces.sort_unstable();
+        faces.dedup();
+
+        let records = self.read_face_records().unwrap_or_default();
+        let vertices = self.read_vertices().unwrap_or_default();
+        let edges = self.read_edges().unwrap_or_default();
+        let face_edges = self.read_face_edges().unwrap_or_default();
+        faces.retain(|&face| {
+            records
+                .get(face)
+                .and_then(|face| face_bounds(face, &face_edges, &edges, &vertices))
+                .is_some_and(|face_bounds| face_bounds.intersects(&bounds))
+        });
+        faces
+    }
+}
+
+/// Which sides of `plane` the box reaches: (front, back). A box touching the
+/// plane counts as in front, as in Quake's `BoxOnPlaneSide`.
+fn box_on_plane_sides(bounds: &Bounds, [x, y, z, dist]: [f32; 4]) -> (bool, bool) {
+    let normal = Vec3A::new(x, y, z);
+    let positive = normal.cmpge(Vec3A::ZERO);
+    let (min, max) = (Vec3A::from(bounds.min), Vec3A::from(bounds.max));
+    // The corners of the box furthest along and against the normal
+    let far = Vec3A::select(positive, max, min);
+    let near = Vec3A::select(positive, min, max);
+    (normal.dot(far) >= dist, normal.dot(near) < dist)
+}
+
+/// Bounds of the vertices of `face`, or None if it references missing data.
+fn face_bounds(
+    face: &Face,
+    face_edges: &[SurfEdge],
+    edges: &[Edge],
+    vertices: &[f32],
+) -> Option<Bounds> {
+    let first = face.first_edge as usize;
+    let mut bounds = Bounds::default();
+    for surf_edge in face_edges.get(first..first + face.num_edges as usize)? {
+        let v = surf_edge.start(edges)? as usize;
+        let point = vertices.get(3 * v..3 * v + 3)?;
+        for (axis, &v) in point.iter().enumerate() {
+            bounds.min[axis] = bounds.min[axis].min(v);
+            bounds.max[axis] = bounds.max[axis].max(v);
+        }
+    }
+    Some(bounds)
+}
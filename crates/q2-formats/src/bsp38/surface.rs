@@ -0,0 +1,67 @@
+//! Surface flags of texinfo records, see [TextureInfo::flags](super::TextureInfo).
+
+/// Emits light, with the texinfo `value` as its intensity.
+pub const SURF_LIGHT: u32 = 0x1;
+/// Frictionless.
+pub const SURF_SLICK: u32 = 0x2;
+/// Draws the sky box instead of the texture.
+pub const SURF_SKY: u32 = 0x4;
+/// Turbulent warp, used by water, slime and lava.
+pub const SURF_WARP: u32 = 0x8;
+/// 33% opaque.
+pub const SURF_TRANS33: u32 = 0x10;
+/// 66% opaque.
+pub const SURF_TRANS66: u32 = 0x20;
+/// Texture scrolls along its u axis.
+pub const SURF_FLOWING: u32 = 0x40;
+/// Not drawn at all.
+pub const SURF_NODRAW: u32 = 0x80;
+
+bitflags::bitflags! {
+    /// The surface flags of a texinfo record as a set, see
+    /// [TextureInfo::surface_flags](super::TextureInfo::surface_flags).
+    /// Unknown bits are kept.
+    ///
+    /// ```
+    /// # use q2_formats::bsp38::surface::SurfaceFlags;
+    /// let flags = SurfaceFlags::from_bits_retain(0x14);
+    /// assert!(!flags.is_drawn());
+    /// assert_eq!(SurfaceFlags::TRANS33.alpha(), 0.33);
+    /// ```
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct SurfaceFlags: u32 {
+        const LIGHT = SURF_LIGHT;
+        const SLICK = SURF_SLICK;
+        const SKY = SURF_SKY;
+        const WARP = SURF_WARP;
+        const TRANS33 = SURF_TRANS33;
+        const TRANS66 = SURF_TRANS66;
+        const FLOWING = SURF_FLOWING;
+        const NODRAW = SURF_NODRAW;
+    }
+}
+
+impl SurfaceFlags {
+    /// Whether the surface is drawn with its texture: sky surfaces show the
+    /// sky box instead, and nodraw surfaces nothing.
+    pub fn is_drawn(&self) -> bool {
+        !self.intersects(Self::SKY | Self::NODRAW)
+    }
+
+    /// Whether the surface is drawn see-through, with [SurfaceFlags::alpha].
+    pub fn is_translucent(&self) -> bool {
+        self.intersects(Self::TRANS33 | Self::TRANS66)
+    }
+
+    /// Opacity of the surface. With both translucency flags set, as the game
+    /// does, 33% wins.
+    pub fn alpha(&self) -> f32 {
+        if self.contains(Self::TRANS33) {
+            0.33
+        } else if self.contains(Self::TRANS66) {
+            0.66
+        } else {
+            1.0
+        }
+    }
+}
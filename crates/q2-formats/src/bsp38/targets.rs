@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use super::Entity;
+
+/// The key an entity uses to refer to another entity's `targetname`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// `target`: fired when the entity is used or triggered.
+    Target,
+    /// `killtarget`: removed when the entity is used.
+    KillTarget,
+    /// `pathtarget`: fired when a train reaches this path corner.
+    PathTarget,
+}
+
+impl LinkKind {
+    pub const ALL: [LinkKind; 3] = [LinkKind::Target, LinkKind::KillTarget, LinkKind::PathTarget];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            LinkKind::Target => "target",
+            LinkKind::KillTarget => "killtarget",
+            LinkKind::PathTarget => "pathtarget",
+        }
+    }
+}
+
+/// A resolved reference from one entity to another, by index into the
+/// entity list the graph was built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub from: usize,
+    pub to: usize,
+    pub kind: LinkKind,
+}
+
+/// A reference to a `targetname` no entity has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedLink {
+    pub from: usize,
+    pub kind: LinkKind,
+    pub name: String,
+}
+
+/// The `target`/`targetname` relationships between map entities.
+///
+/// Several entities may share a targetname, in which case a reference to it
+/// links to all of them.
+#[derive(Debug, Clone, Default)]
+pub struct TargetGraph {
+    links: Vec<Link>,
+    unresolved: Vec<UnresolvedLink>,
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+impl TargetGraph {
+    pub fn new(entities: &[Entity]) -> Self {
+        let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entity) in entities.iter().enumerate() {
+            if let Some(name) = entity.targetname() {
+                by_name.entry(name.to_string()).or_default().push(i);
+            }
+        }
+
+        let mut links = Vec::new();
+        let mut unresolved = Vec::new();
+        for (from, entity) in entities.iter().enumerate() {
+            for kind in LinkKind::ALL {
+                let Some(name) = entity.get(kind.key()) else {
+                    continue;
+                };
+                match by_name.get(name) {
+                    Some(targets) => {
+                        links.extend(targets.iter().map(|&to| Link { from, to, kind }));
+                    }
+                    None => unresolved.push(UnresolvedLink {
+                        from,
+                        kind,
+                        name: name.to_string(),
+                    }),
+                }
+            }
+        }
+
+        Self {
+            links,
+            unresolved,
+            by_name,
+        }
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// References to targetnames that no entity has, usually mapping errors.
+    pub fn unresolved(&self) -> &[UnresolvedLink] {
+        &self.unresolved
+    }
+
+    /// The entities with the given targetname.
+    pub fn named(&self, targetname: &str) -> &[usize] {
+        self.by_name.get(targetname).map_or(&[], Vec::as_slice)
+    }
+
+    /// What entity `from` triggers, kills or moves along to.
+    pub fn targets_of(&self, from: usize) -> impl Iterator<Item = &Link> {
+        self.links.iter().filter(move |link| link.from == from)
+    }
+
+    /// What triggers, kills or leads to entity `to`.
+    pub fn sources_of(&self, to: usize) -> impl Iterator<Item = &Link> {
+        self.links.iter().filter(move |link| link.to == to)
+    }
+}
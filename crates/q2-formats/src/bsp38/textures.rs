@@ -0,0 +1,74 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::BSP38;
+
+/// A texture used by the map's faces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureUsage {
+    /// Texture path without extension, e.g. `e1u1/floor1_3`.
+    pub name: String,
+    /// Number of faces drawn with the texture.
+    pub faces: usize,
+    /// Surface flags of all texinfo records naming the texture, or'ed
+    /// together.
+    pub flags: u32,
+}
+
+impl BSP38 {
+    /// Lists each texture named by the texinfo records once, sorted by name.
+    ///
+    /// Textures only referenced by unused texinfo records are included with
+    /// a face count of 0.
+    pub fn unique_textures(&self) -> Vec<TextureUsage> {
+        let tex_info = self.read_texture_info().unwrap_or_default();
+        let mut textures: BTreeMap<&str, TextureUsage> = BTreeMap::new();
+        for info in &tex_info {
+            let usage = textures
+                .entry(info.texture.as_str())
+                .or_insert_with(|| TextureUsage {
+                    name: info.texture.clone(),
+                    faces: 0,
+                    flags: 0,
+                });
+            usage.flags |= info.flags;
+        }
+        for face in self.read_face_records().unwrap_or_default() {
+            if let Some(info) = tex_info.get(face.texinfo as usize) {
+                if let Some(usage) = textures.get_mut(info.texture.as_str()) {
+                    usage.faces += 1;
+                }
+            }
+        }
+        textures.into_values().collect()
+    }
+
+    /// The frames of each animated texture, keyed by texture name: the
+    /// textures along the `next` chain of its texinfo, starting with itself,
+    /// until the chain loops back or ends. Quake 2 draws a face with frame
+    /// `n` by following its texinfo's chain `n` times.
+    ///
+    /// Textures whose chain has a single frame aren't listed. When several
+    /// texinfo records name a texture, the first with a chain wins.
+    pub fn texture_animations(&self) -> BTreeMap<String, Vec<String>> {
+        let tex_info = self.read_texture_info().unwrap_or_default();
+        let mut animations = BTreeMap::new();
+        for (i, info) in tex_info.iter().enumerate() {
+            if animations.contains_key(&info.texture) {
+                continue;
+            }
+            let mut frames = vec![info.texture.clone()];
+            let mut seen = HashSet::from([i]);
+            let mut next = info.next as usize;
+            // Malformed chains may loop back past their start, or point
+            // out of the lump
+            while let Some(frame) = tex_info.get(next).filter(|_| seen.insert(next)) {
+                frames.push(frame.texture.clone());
+                next = frame.next as usize;
+            }
+            if frames.len() > 1 {
+                animations.insert(info.texture.clone(), frames);
+            }
+        }
+        animations
+    }
+}
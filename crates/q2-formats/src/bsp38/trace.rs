@@ -0,0 +1,411 @@
+use glam::Vec3A;
+use tracing::instrument;
+
+use super::{Brush, BrushSide, Leaf, Node, BSP38};
+
+/// How far a [Trace] stops short of the brush it hits, in map units, so
+/// the end position is never inside the brush because of rounding.
+const DIST_EPSILON: f32 = 0.03125;
+
+/// Line traces through the BSP tree, testing the contents of the leafs the
+/// line passes through, and box traces against the brushes of those leafs.
+///
+/// Holds the nodes, planes, leafs and brushes it needs, so repeated traces
+/// don't decode the lumps again.
+#[derive(Debug, Clone)]
+pub struct Tracer {
+    nodes: Vec<Node>,
+    planes: Vec<[f32; 4]>,
+    leafs: Vec<Leaf>,
+    leaf_brushes: Vec<u16>,
+    brushes: Vec<Brush>,
+    brush_sides: Vec<BrushSide>,
+    /// Surface flags of each texinfo.
+    surface_flags: Vec<u32>,
+}
+
+/// Result of sweeping a box through the brushes of a map, see
+/// [Tracer::trace].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trace {
+    /// Fraction of the sweep travelled before hitting a brush, 1.0 when
+    /// nothing was hit.
+    pub fraction: f32,
+    /// Where the box stopped.
+    pub end: [f32; 3],
+    /// Plane of the brush side hit, as normal and distance, with the normal
+    /// pointing out of the brush.
+    pub plane: Option<[f32; 4]>,
+    /// Surface flags of the brush side hit, see [surface](super::surface).
+    pub surface_flags: u32,
+    /// Contents of the brush hit, see [contents](super::contents).
+    pub contents: i32,
+    /// The box started inside a brush.
+    pub start_solid: bool,
+    /// The box never left the brush it started in. The fraction is 0.0.
+    pub all_solid: bool,
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn tracer(&self) -> Tracer {
+        Tracer {
+            nodes: self.read_nodes().unwrap_or_default(),
+            planes: self.read_planes().unwrap_or_default(),
+            leafs: self.read_leafs().unwrap_or_default(),
+            leaf_brushes: self.read_leaf_brushes().unwrap_or_default(),
+            brushes: self.read_brushes().unwrap_or_default(),
+            brush_sides: self.read_brush_sides().unwrap_or_default(),
+            surface_flags: self
+                .read_texture_info()
+                .unwrap_or_default()
+                .iter()
+                .map(|tex| tex.flags)
+                .collect(),
+        }
+    }
+
+    /// Index of the leaf containing `point`, see [Tracer::leaf_at]. Points
+    /// the tree of a corrupt file leads nowhere from are in leaf 0, the solid
+    /// leaf.
+    ///
+    /// Decodes the tree on every call; use a [BSP38::tracer] for repeated
+    /// lookups.
+    pub fn leaf_at_point(&self, point: [f32; 3]) -> usize {
+        self.tracer().leaf_at(point).unwrap_or(0)
+    }
+}
+
+impl Tracer {
+    /// Index of the leaf containing `point`, found by walking the tree of the
+    /// world model. Points on a plane count as in front of it.
+    pub fn leaf_at(&self, point: [f32; 3]) -> Option<usize> {
+        let point = Vec3A::from(point);
+        let mut child = 0i32;
+        // Each node is passed at most once on the way down a valid tree
+        for _ in 0..=self.nodes.len() {
+            if child < 0 {
+                let leaf = (-1 - child) as usize;
+                return (leaf < self.leafs.len()).then_some(leaf);
+            }
+            let node = self.nodes.get(child as usize)?;
+            let [x, y, z, dist] = *self.planes.get(node.plane as usize)?;
+            let front = Vec3A::new(x, y, z).dot(point) >= dist;
+            child = node.children[usize::from(!front)];
+        }
+        None
+    }
+
+    /// PVS cluster of the leaf containing `point`, or None in leafs outside
+    /// of any cluster, such as the solid leaf.
+    pub fn cluster_at(&self, point: [f32; 3]) -> Option<usize> {
+        let leaf = &self.leafs[self.leaf_at(point)?];
+        usize::try_from(leaf.cluster).ok()
+    }
+
+    /// Area of the leaf containing `point`, or None outside of any area.
+    pub fn area_at(&self, point: [f32; 3]) -> Option<usize> {
+        let leaf = &self.leafs[self.leaf_at(point)?];
+        usize::try_from(leaf.area).ok().filter(|&area| area > 0)
+    }
+
+    /// Fraction of the segment from `start` to `end` travelled before it
+    /// first enters a leaf with any of the `mask` contents (see
+    /// [contents](super::contents)), or None if it never does.
+    ///
+    /// Walks the tree of the world model from its head node, visiting the
+    /// side of each plane nearest to `start` first.
+    pub fn trace_line(&self, start: [f32; 3], end: [f32; 3], mask: i32) -> Option<f32> {
+        let (start, end) = (Vec3A::from(start), Vec3A::from(end));
+        if self.nodes.is_empty() {
+            return None;
+        }
+        // A segment visits each node of a valid tree at most once, so a
+        // longer walk means the tree is malformed
+        let mut budget = self.nodes.len();
+        let mut stack = vec![(0i32, 0.0f32, 1.0f32)];
+        while let Some((child, f1, f2)) = stack.pop() {
+            if child < 0 {
+                let leaf = self.leafs.get((-1 - child) as usize)?;
+                if leaf.contents & mask != 0 {
+                    return Some(f1);
+                }
+                continue;
+            }
+            budget = budget.checked_sub(1)?;
+            let node = self.nodes.get(child as usize)?;
+            let [x, y, z, dist] = *self.planes.get(node.plane as usize)?;
+            let normal = Vec3A::new(x, y, z);
+            let p1 = start.lerp(end, f1);
+            let p2 = start.lerp(end, f2);
+            let t1 = normal.dot(p1) - dist;
+            let t2 = normal.dot(p2) - dist;
+
+            if t1 >= 0.0 && t2 >= 0.0 {
+                stack.push((node.children[0], f1, f2));
+            } else if t1 < 0.0 && t2 < 0.0 {
+                stack.push((node.children[1], f1, f2));
+            } else {
+                // The segment crosses the plane: the near side is walked
+                // first, so it goes on the stack last
+                let near = usize::from(t1 < 0.0);
+                let mid = f1 + (f2 - f1) * t1 / (t1 - t2);
+                stack.push((node.children[1 - near], mid, f2));
+                stack.push((node.children[near], f1, mid));
+            }
+        }
+        None
+    }
+
+    /// Sweeps the box from `mins` to `maxs`, relative to its origin, from
+    /// `start` to `end` through the brushes with any of the `mask` contents,
+    /// as Quake 2 moves players and projectiles.
+    ///
+    /// Brushes are only tested in the leafs the swept box passes through, so
+    /// the world model's tree must list them in its leafs. When `start` and
+    /// `end` are the same, only tests whether the box is inside a brush.
+    pub fn trace(
+        &self,
+        start: [f32; 3],
+        end: [f32; 3],
+        mins: [f32; 3],
+        maxs: [f32; 3],
+        mask: i32,
+    ) -> Trace {
+        let (mins, maxs) = (Vec3A::from(mins), Vec3A::from(maxs));
+        let (p1, p2) = (Vec3A::from(start), Vec3A::from(end));
+        let mut sweep = Sweep {
+            tracer: self,
+            start: p1,
+            end: p2,
+            mins,
+            maxs,
+            // The box grows both ways by this much when crossing node planes
+            extents: mins.abs().max(maxs.abs()),
+            is_point: mins == Vec3A::ZERO && maxs == Vec3A::ZERO,
+            mask,
+            checked: vec![false; self.brushes.len()],
+            trace: Trace {
+                fraction: 1.0,
+                end,
+                plane: None,
+                surface_flags: 0,
+                contents: 0,
+                start_solid: false,
+                all_solid: false,
+            },
+        };
+        if !self.nodes.is_empty() {
+            sweep.hull_check(p1, p2);
+        }
+
+        let mut trace = sweep.trace;
+        if trace.fraction < 1.0 {
+            trace.end = p1.lerp(p2, trace.fraction).into();
+        }
+        trace
+    }
+
+    /// [Tracer::trace] of a point.
+    pub fn trace_ray(&self, start: [f32; 3], end: [f32; 3], mask: i32) -> Trace {
+        self.trace(start, end, [0.0; 3], [0.0; 3], mask)
+    }
+
+    /// [Tracer::trace] of a box from `-half_extents` to `half_extents`.
+    pub fn trace_box(
+        &self,
+        start: [f32; 3],
+        end: [f32; 3],
+        half_extents: [f32; 3],
+        mask: i32,
+    ) -> Trace {
+        let mins = (-Vec3A::from(half_extents)).into();
+        self.trace(start, end, mins, half_extents, mask)
+    }
+}
+
+/// State of a single [Tracer::trace].
+struct Sweep<'a> {
+    tracer: &'a Tracer,
+    start: Vec3A,
+    end: Vec3A,
+    mins: Vec3A,
+    maxs: Vec3A,
+    extents: Vec3A,
+    is_point: bool,
+    mask: i32,
+    /// Brushes already clipped against, as a brush is listed in every leaf
+    /// it touches.
+    checked: Vec<bool>,
+    trace: Trace,
+}
+
+impl Sweep<'_> {
+    /// Walks the sweep from `p1` to `p2` down the tree of the world model,
+    /// near side first, splitting it where the box straddles node planes.
+    fn hull_check(&mut self, p1: Vec3A, p2: Vec3A) {
+        let tracer = self.tracer;
+        // As in [Tracer::trace_line], a sweep visits each node of a valid
+        // tree at most once, however it splits, so a longer walk means the
+        // tree is malformed
+        let mut budget = tracer.nodes.len();
+        let mut stack = vec![(0i32, 0.0f32, 1.0f32, p1, p2)];
+        while let Some((child, f1, f2, p1, p2)) = stack.pop() {
+            // Already hit something closer
+            if self.trace.fraction <= f1 {
+                continue;
+            }
+            if child < 0 {
+                self.trace_to_leaf((-1 - child) as usize);
+                continue;
+            }
+            let Some(left) = budget.checked_sub(1) else {
+                return;
+            };
+            budget = left;
+            let Some(node) = tracer.nodes.get(child as usize) else {
+                continue;
+            };
+            let Some(&[x, y, z, dist]) = tracer.planes.get(node.plane as usize) else {
+                continue;
+            };
+            let normal = Vec3A::new(x, y, z);
+            let t1 = normal.dot(p1) - dist;
+            let t2 = normal.dot(p2) - dist;
+            let offset = match self.is_point {
+                true => 0.0,
+                false => (self.extents * normal).abs().element_sum(),
+            };
+
+            if t1 >= offset && t2 >= offset {
+                stack.push((node.children[0], f1, f2, p1, p2));
+                continue;
+            }
+            if t1 < -offset && t2 < -offset {
+                stack.push((node.children[1], f1, f2, p1, p2));
+                continue;
+            }
+
+            // The swept box straddles the plane: split it where the box
+            // starts and stops touching each side, overlapping a little
+            let (near, frac, frac2) = if t1 < t2 {
+                let inv = 1.0 / (t1 - t2);
+                (
+                    1,
+                    (t1 - offset + DIST_EPSILON) * inv,
+                    (t1 + offset + DIST_EPSILON) * inv,
+                )
+            } else if t1 > t2 {
+                let inv = 1.0 / (t1 - t2);
+                (
+                    0,
+                    (t1 + offset + DIST_EPSILON) * inv,
+                    (t1 - offset - DIST_EPSILON) * inv,
+                )
+            } else {
+                (0, 1.0, 0.0)
+            };
+
+            // The near side is walked first, so it goes on the stack last
+            let frac2 = frac2.clamp(0.0, 1.0);
+            let mid = p1.lerp(p2, frac2);
+            let midf = f1 + (f2 - f1) * frac2;
+            stack.push((node.children[near ^ 1], midf, f2, mid, p2));
+
+            let frac = frac.clamp(0.0, 1.0);
+            let mid = p1.lerp(p2, frac);
+            let midf = f1 + (f2 - f1) * frac;
+            stack.push((node.children[near], f1, midf, p1, mid));
+        }
+    }
+
+    fn trace_to_leaf(&mut self, leaf: usize) {
+        let tracer = self.tracer;
+        let Some(leaf) = tracer.leafs.get(leaf) else {
+            return;
+        };
+        for brush in leaf.brushes(&tracer.leaf_brushes) {
+            match self.checked.get_mut(brush) {
+                Some(checked) if !*checked => *checked = true,
+                _ => continue,
+            }
+            let brush = &tracer.brushes[brush];
+            if brush.contents & self.mask != 0 {
+                self.clip_to_brush(brush);
+                if self.trace.fraction == 0.0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Clips the whole sweep against the planes of `brush`, keeping the hit
+    /// if it's closer than the current one.
+    fn clip_to_brush(&mut self, brush: &Brush) {
+        let tracer = self.tracer;
+        let (p1, p2) = (self.start, self.end);
+        let mut enter = -1.0f32;
+        let mut leave = 1.0f32;
+        let mut hit = None;
+        let mut starts_out = false;
+        let mut gets_out = false;
+        for side in tracer.brush_sides.get(brush.sides()).unwrap_or(&[]) {
+            let Some(&[x, y, z, dist]) = tracer.planes.get(side.plane as usize) else {
+                return;
+            };
+            let normal = Vec3A::new(x, y, z);
+            // Push the plane out to the corner of the box nearest to it
+            let offset_dist = match self.is_point {
+                true => dist,
+                false => {
+                    let corner = Vec3A::select(normal.cmplt(Vec3A::ZERO), self.maxs, self.mins);
+                    dist - corner.dot(normal)
+                }
+            };
+            let d1 = normal.dot(p1) - offset_dist;
+            let d2 = normal.dot(p2) - offset_dist;
+            gets_out |= d2 > 0.0;
+            starts_out |= d1 > 0.0;
+
+            // Entirely in front of this side, so outside of the brush
+            if d1 > 0.0 && d2 >= d1 {
+                return;
+            }
+            // Entirely behind it
+            if d1 <= 0.0 && d2 <= 0.0 {
+                continue;
+            }
+            if d1 > d2 {
+                let f = (d1 - DIST_EPSILON) / (d1 - d2);
+                if f > enter {
+                    enter = f;
+                    hit = Some(([x, y, z, dist], side));
+                }
+            } else {
+                leave = leave.min((d1 + DIST_EPSILON) / (d1 - d2));
+            }
+        }
+
+        if !starts_out {
+            self.trace.start_solid = true;
+            if !gets_out {
+                self.trace.all_solid = true;
+                self.trace.fraction = 0.0;
+                self.trace.contents = brush.contents;
+            }
+            return;
+        }
+        if enter < leave && enter > -1.0 && enter < self.trace.fraction {
+            if let Some((plane, side)) = hit {
+                self.trace.fraction = enter.max(0.0);
+                self.trace.plane = Some(plane);
+                self.trace.surface_flags = usize::try_from(side.texinfo)
+                    .ok()
+                    .and_then(|tex| tracer.surface_flags.get(tex))
+                    .copied()
+                    .unwrap_or(0);
+                self.trace.contents = brush.contents;
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// A node of the BSP tree, from the Nodes lump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node {
+    /// Index of the splitting plane in the Planes lump.
+    pub plane: u32,
+    /// Front and back child. Negative values are leafs: `-1 - child` is the
+    /// index into the Leafs lump.
+    pub children: [i32; 2],
+    pub mins: [i16; 3],
+    pub maxs: [i16; 3],
+    pub first_face: u16,
+    pub num_faces: u16,
+}
+
+/// A leaf of the BSP tree, from the Leafs lump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Leaf {
+    /// Content flags, e.g. 1 for solid.
+    pub contents: i32,
+    /// PVS cluster, or -1 for leafs outside of any cluster.
+    pub cluster: i16,
+    pub area: i16,
+    pub mins: [i16; 3],
+    pub maxs: [i16; 3],
+    /// Range of the LeafFaces lump listing the faces in this leaf.
+    pub first_leaf_face: u16,
+    pub num_leaf_faces: u16,
+    /// Range of the LeafBrushes lump listing the brushes in this leaf.
+    pub first_leaf_brush: u16,
+    pub num_leaf_brushes: u16,
+}
+
+impl Leaf {
+    /// Range of the LeafFaces lump listing the faces in this leaf.
+    pub fn leaf_faces(&self) -> std::ops::Range<usize> {
+        let first = self.first_leaf_face as usize;
+        first..first + self.num_leaf_faces as usize
+    }
+
+    /// Range of the LeafBrushes lump listing the brushes in this leaf.
+    pub fn leaf_brushes(&self) -> std::ops::Range<usize> {
+        let first = self.first_leaf_brush as usize;
+        first..first + self.num_leaf_brushes as usize
+    }
+
+    /// The faces in this leaf, as indices into the Faces lump, looked up in
+    /// the LeafFaces lump from [BSP38::read_leaf_faces]. A range reaching
+    /// past the end of the lump lists no faces.
+    ///
+    /// ```
+    /// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+    ///
+    /// let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [64.0; 3]).build()).unwrap();
+    /// let leaf_faces = bsp.read_leaf_faces().unwrap();
+    /// let faces: Vec<usize> = bsp.read_leafs().unwrap()[1].faces(&leaf_faces).collect();
+    /// assert_eq!(faces, [0, 1, 2, 3, 4, 5]);
+    /// ```
+    pub fn faces<'a>(&self, leaf_faces: &'a [u16]) -> impl Iterator<Item = usize> + 'a {
+        let faces = leaf_faces.get(self.leaf_faces()).unwrap_or_default();
+        faces.iter().map(|&face| face as usize)
+    }
+
+    /// The brushes in this leaf, as indices into the Brushes lump, looked up
+    /// in the LeafBrushes lump from [BSP38::read_leaf_brushes]. A range
+    /// reaching past the end of the lump lists no brushes.
+    pub fn brushes<'a>(&self, leaf_brushes: &'a [u16]) -> impl Iterator<Item = usize> + 'a {
+        let brushes = leaf_brushes.get(self.leaf_brushes()).unwrap_or_default();
+        brushes.iter().map(|&brush| brush as usize)
+    }
+}
+
+impl BSP38 {
+    #[instrument(skip_all)]
+    pub fn read_nodes(&self) -> Result<Vec<Node>, BspError> {
+        if let Some(nodes) = &self.decoded.nodes {
+            return Ok(nodes.clone());
+        }
+        const NODE_SIZE: usize = 28;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Nodes)?;
+        let num_nodes = cursor.get_ref().len() / NODE_SIZE;
+        let mut buffer = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes {
+            let plane = cursor.read_u32::<LittleEndian>()?;
+            let children = [
+                cursor.read_i32::<LittleEndian>()?,
+                cursor.read_i32::<LittleEndian>()?,
+            ];
+            let mins = read_i16s(&mut cursor)?;
+            let maxs = read_i16s(&mut cursor)?;
+            let first_face = cursor.read_u16::<LittleEndian>()?;
+            let num_faces = cursor.read_u16::<LittleEndian>()?;
+            buffer.push(Node {
+                plane,
+                children,
+                mins,
+                maxs,
+                first_face,
+                num_faces,
+            });
+        }
+        Ok(buffer)
+    }
+
+    #[instrument(skip_all)]
+    pub fn read_leafs(&self) -> Result<Vec<Leaf>, BspError> {
+        if let Some(leafs) = &self.decoded.leafs {
+            return Ok(leafs.clone());
+        }
+        const LEAF_SIZE: usize = 28;
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Leafs)?;
+        let num_leafs = cursor.get_ref().len() / LEAF_SIZE;
+        let mut buffer = Vec::with_capacity(num_leafs);
+        for _ in 0..num_leafs {
+            let contents = cursor.read_i32::<LittleEndian>()?;
+            let cluster = cursor.read_i16::<LittleEndian>()?;
+            let area = cursor.read_i16::<LittleEndian>()?;
+            let mins = read_i16s(&mut cursor)?;
+            let maxs = read_i16s(&mut cursor)?;
+            buffer.push(Leaf {
+                contents,
+                cluster,
+                area,
+                mins,
+                maxs,
+                first_leaf_face: cursor.read_u16::<LittleEndian>()?,
+                num_leaf_faces: cursor.read_u16::<LittleEndian>()?,
+                first_leaf_brush: cursor.read_u16::<LittleEndian>()?,
+                num_leaf_brushes: cursor.read_u16::<LittleEndian>()?,
+            });
+        }
+        Ok(buffer)
+    }
+
+    /// Reads the LeafFaces lump: face indices, referenced by ranges of
+    /// [Leaf::leaf_faces].
+    #[instrument(skip_all)]
+    pub fn read_leaf_faces(&self) -> Result<Vec<u16>, BspError> {
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::LeafFaces)?;
+        let count = cursor.get_ref().len() / 2;
+        (0..count)
+            .map(|_| Ok(cursor.read_u16::<LittleEndian>()?))
+            .collect()
+    }
+
+    /// Reads the LeafBrushes lump: brush indices, referenced by ranges of
+    /// [Leaf::leaf_brushes].
+    #[instrument(skip_all)]
+    pub fn read_leaf_brushes(&self) -> Result<Vec<u16>, BspError> {
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::LeafBrushes)?;
+        let count = cursor.get_ref().len() / 2;
+        (0..count)
+            .map(|_| Ok(cursor.read_u16::<LittleEndian>()?))
+            .collect()
+    }
+}
+
+fn read_i16s(cursor: &mut std::io::Cursor<&[u8]>) -> std::io::Result<[i16; 3]> {
+    Ok([
+        cursor.read_i16::<LittleEndian>()?,
+        cursor.read_i16::<LittleEndian>()?,
+        cursor.read_i16::<LittleEndian>()?,
+    ])
+}
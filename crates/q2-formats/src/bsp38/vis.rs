@@ -0,0 +1,247 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use tracing::instrument;
+
+use super::{BspError, LumpIndex, BSP38};
+
+/// Most PVS clusters a matrix is decoded for, far more than the maps of the
+/// game have, so a corrupt count can't make it take more than 32 MB.
+const MAX_CLUSTERS: usize = 16384;
+
+/// The fully decompressed potentially visible set of a map.
+///
+/// Stores one bit per (cluster, cluster) pair, row-major: bit `to` of row
+/// `from` is set when cluster `to` may be visible from cluster `from`. Maps
+/// compiled without vis have no clusters and report everything as visible.
+#[derive(Debug, Clone, Default)]
+pub struct VisMatrix {
+    num_clusters: usize,
+    row_bytes: usize,
+    bits: Vec<u8>,
+}
+
+impl VisMatrix {
+    pub fn num_clusters(&self) -> usize {
+        self.num_clusters
+    }
+
+    /// Whether cluster `to` is potentially visible from cluster `from`.
+    pub fn is_visible(&self, from: usize, to: usize) -> bool {
+        match self.row(from) {
+            Some(row) if to < self.num_clusters => row[to >> 3] & (1 << (to & 7)) != 0,
+            _ => true,
+        }
+    }
+
+    /// The packed visibility bits of `cluster`, one bit per cluster, or None
+    /// for a cluster the matrix doesn't have.
+    pub fn row(&self, cluster: usize) -> Option<&[u8]> {
+        (cluster < self.num_clusters)
+            .then(|| &self.bits[cluster * self.row_bytes..(cluster + 1) * self.row_bytes])
+    }
+
+    /// Size of the decompressed matrix in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// The clusters potentially visible from `cluster`, in order.
+    pub fn visible_from(&self, cluster: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_clusters).filter(move |&to| self.is_visible(cluster, to))
+    }
+
+    /// The matrix with each pair of clusters visible both ways if it was
+    /// either way. A vis compiler can leave pairs one-sided, which shows up
+    /// as geometry popping in depending on which side the camera is on.
+    ///
+    /// ```
+    /// use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+    ///
+    /// let bytes = TestMapBuilder::room([0.0; 3], [64.0; 3])
+    ///     .with_vis(vec![vec![true, true], vec![false, true]])
+    ///     .build();
+    /// let vis = BSP38::from_bytes(bytes).unwrap().read_vis_matrix().unwrap();
+    /// assert!(!vis.is_visible(1, 0));
+    /// assert!(vis.symmetric().is_visible(1, 0));
+    /// ```
+    pub fn symmetric(&self) -> VisMatrix {
+        let mut matrix = self.clone();
+        for from in 0..self.num_clusters {
+            for to in self.visible_from(from) {
+                matrix.bits[to * self.row_bytes + (from >> 3)] |= 1 << (from & 7);
+            }
+        }
+        matrix
+    }
+
+    /// How much each cluster sees, see [VisStats].
+    pub fn stats(&self) -> VisStats {
+        let mut stats = VisStats {
+            clusters: self.num_clusters,
+            ..Default::default()
+        };
+        let mut visible = 0;
+        for from in 0..self.num_clusters {
+            let seen = self.visible_from(from).count();
+            visible += seen;
+            if stats.worst_cluster.is_none_or(|(_, most)| seen > most) {
+                stats.worst_cluster = Some((from, seen));
+            }
+            stats.one_sided_pairs += (from + 1..self.num_clusters)
+                .filter(|&to| self.is_visible(from, to) != self.is_visible(to, from))
+                .count();
+        }
+        if self.num_clusters > 0 {
+            stats.average_visible = visible as f32 / self.num_clusters as f32;
+        }
+        stats
+    }
+}
+
+/// Summary of a [VisMatrix], for finding the clusters that cost the most
+/// to draw from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VisStats {
+    pub clusters: usize,
+    /// Clusters potentially visible from a cluster, itself included, on
+    /// average.
+    pub average_visible: f32,
+    /// The cluster seeing the most clusters, and how many.
+    pub worst_cluster: Option<(usize, usize)>,
+    /// Pairs of clusters where only one sees the other, see
+    /// [VisMatrix::symmetric].
+    pub one_sided_pairs: usize,
+}
+
+/// The PVS by leaf: leafs see each other when their clusters do.
+///
+/// Leafs outside of any cluster, such as the solid leaf, see nothing and are
+/// seen from nowhere.
+#[derive(Debug, Clone, Default)]
+pub struct LeafVisibility {
+    /// Cluster of each leaf.
+    clusters: Vec<i16>,
+    matrix: VisMatrix,
+}
+
+impl LeafVisibility {
+    pub fn num_leafs(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Whether leaf `to` is potentially visible from leaf `from`.
+    pub fn is_visible(&self, from: usize, to: usize) -> bool {
+        let cluster = |leaf: usize| {
+            self.clusters
+                .get(leaf)
+                .and_then(|&cluster| usize::try_from(cluster).ok())
+        };
+        match (cluster(from), cluster(to)) {
+            (Some(from), Some(to)) => self.matrix.is_visible(from, to),
+            _ => false,
+        }
+    }
+
+    /// The leafs potentially visible from `leaf`, in leaf order.
+    pub fn visible_leafs(&self, leaf: usize) -> impl Iterator<Item = usize> + '_ {
+        (0..self.clusters.len()).filter(move |&to| self.is_visible(leaf, to))
+    }
+}
+
+impl BSP38 {
+    /// The PVS by leaf, for answering many visibility queries.
+    pub fn leaf_visibility(&self) -> LeafVisibility {
+        LeafVisibility {
+            clusters: self
+                .read_leafs()
+                .unwrap_or_default()
+                .iter()
+                .map(|leaf| leaf.cluster)
+                .collect(),
+            matrix: self.read_vis_matrix().unwrap_or_default(),
+        }
+    }
+
+    /// Whether leaf `leaf` is potentially visible from leaf `from`.
+    ///
+    /// Decodes the PVS on every call unless it was decoded eagerly, see
+    /// [ParseOptions::eager](super::prelude::ParseOptions::eager); use
+    /// [BSP38::leaf_visibility] for repeated queries.
+    pub fn leaf_visible_from(&self, leaf: usize, from: usize) -> bool {
+        self.leaf_visibility().is_visible(from, leaf)
+    }
+
+    /// The leafs potentially visible from `leaf`, in leaf order.
+    pub fn visible_leafs(&self, leaf: usize) -> impl Iterator<Item = usize> {
+        let vis = self.leaf_visibility();
+        (0..vis.num_leafs()).filter(move |&to| vis.is_visible(leaf, to))
+    }
+
+    /// Decompresses the PVS row of every cluster in the Visibility lump.
+    ///
+    /// Rows are decoded in parallel on native targets.
+    #[instrument(skip_all)]
+    pub fn read_vis_matrix(&self) -> Result<VisMatrix, BspError> {
+        if let Some(vis) = &self.decoded.vis {
+            return Ok(vis.clone());
+        }
+        let mut cursor = self.read_lump_as_cursor(LumpIndex::Visibility)?;
+        let data = *cursor.get_ref();
+        if data.len() < 4 {
+            return Ok(VisMatrix::default());
+        }
+
+        // The count is untrusted, so it's capped at the offsets that fit
+        let num_clusters = cursor.read_u32::<LittleEndian>()? as usize;
+        let num_clusters = num_clusters.min((data.len() - 4) / 8);
+        // A zeroed lump, as written by Strip::Zero, or one too large to be
+        // real
+        if num_clusters == 0 || num_clusters > MAX_CLUSTERS {
+            return Ok(VisMatrix::default());
+        }
+        // Each cluster's PVS offset, followed by its unused PHS offset
+        let offsets: Vec<usize> = data[4..4 + num_clusters * 8]
+            .chunks_exact(8)
+            .map(|entry| u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize)
+            .collect();
+
+        let row_bytes = num_clusters.div_ceil(8);
+        let mut bits = vec![0u8; num_clusters * row_bytes];
+        let decode = |(cluster, row): (usize, &mut [u8])| {
+            decompress_row(&data[offsets[cluster].min(data.len())..], row);
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use rayon::prelude::*;
+            bits.par_chunks_mut(row_bytes).enumerate().for_each(decode);
+        }
+        #[cfg(target_arch = "wasm32")]
+        bits.chunks_mut(row_bytes).enumerate().for_each(decode);
+
+        Ok(VisMatrix {
+            num_clusters,
+            row_bytes,
+            bits,
+        })
+    }
+}
+
+/// Expands a run-length encoded PVS row into `row`. A zero byte is followed by
+/// the number of zero bytes it stands for; any other byte is copied as is.
+fn decompress_row(data: &[u8], row: &mut [u8]) {
+    let mut input = data.iter();
+    let mut out = 0;
+    while out < row.len() {
+        match input.next() {
+            Some(0) => {
+                let count = input.next().copied().unwrap_or(0) as usize;
+                out += count;
+            }
+            Some(&b) => {
+                row[out] = b;
+                out += 1;
+            }
+            None => break,
+        }
+    }
+}
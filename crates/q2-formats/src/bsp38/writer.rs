@@ -0,0 +1,63 @@
+use super::{error::HEADER_SIZE, LumpIndex, BSP38};
+
+/// What [BSP38::write_stripped] does with a lump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strip {
+    /// Keep the lump's size, so anything pointing into it stays in bounds,
+    /// but fill it with zeros, which compress to almost nothing.
+    Zero,
+    /// Leave the lump out of the file, empty in the lump table.
+    Remove,
+}
+
+impl BSP38 {
+    /// Writes the map as a BSP file: the header, then the lumps in file
+    /// order, each starting on a 4 byte boundary.
+    ///
+    /// Lumps cut short while parsing a truncated file are written as they
+    /// were read.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.write_stripped(&[])
+    }
+
+    /// Writes the map as [BSP38::to_bytes] does, with each lump in `strip`
+    /// zeroed or removed, such as the Lighting and Visibility lumps of maps
+    /// previewed on the web.
+    ///
+    /// ```
+    /// use q2_formats::{
+    ///     bsp38::{prelude::Strip, LumpIndex, BSP38},
+    ///     test_utils::TestMapBuilder,
+    /// };
+    ///
+    /// let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+    /// let bytes = bsp.write_stripped(&[(LumpIndex::Visibility, Strip::Remove)]);
+    /// let stripped = BSP38::from_bytes(bytes).unwrap();
+    /// assert!(!stripped.has_lump(LumpIndex::Visibility));
+    /// assert_eq!(stripped.read_faces().unwrap().points, bsp.read_faces().unwrap().points);
+    /// ```
+    pub fn write_stripped(&self, strip: &[(LumpIndex, Strip)]) -> Vec<u8> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(b"IBSP");
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        for lump in LumpIndex::ALL {
+            let data = &self.lumps[lump as usize];
+            let data = &self.bytes[data.offset as usize..(data.offset + data.length) as usize];
+            // The last edit of a lump wins
+            let edit = strip.iter().rev().find(|(l, _)| *l == lump).map(|e| e.1);
+
+            bytes.resize(bytes.len().next_multiple_of(4), 0);
+            let offset = bytes.len();
+            match edit {
+                None => bytes.extend_from_slice(data),
+                Some(Strip::Zero) => bytes.resize(offset + data.len(), 0),
+                Some(Strip::Remove) => {}
+            }
+            let entry = 8 + 8 * lump as usize;
+            let length = bytes.len() - offset;
+            bytes[entry..entry + 4].copy_from_slice(&(offset as i32).to_le_bytes());
+            bytes[entry + 4..entry + 8].copy_from_slice(&(length as i32).to_le_bytes());
+        }
+        bytes
+    }
+}
@@ -0,0 +1,14 @@
+//! Parsers for the Quake 2 data formats.
+//!
+//! Plain Rust with no engine dependency, so tools and servers can use it as
+//! well as the `q2-viewer` app.
+
+pub mod bsp38;
+pub mod md2;
+pub mod pak;
+pub mod pcx;
+pub mod tga;
+pub mod wal;
+
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
@@ -0,0 +1,419 @@
+//! Quake 2 .md2 models: triangle meshes animated by keyframes, each frame a
+//! full set of compressed vertex positions.
+
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read},
+    ops::Range,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use glam::Vec3;
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"IDP2";
+const VERSION: i32 = 8;
+const SKIN_NAME_SIZE: usize = 64;
+const FRAME_NAME_SIZE: usize = 16;
+/// Scale and translation, then the name, before a frame's vertices.
+const FRAME_HEADER_SIZE: usize = 24 + FRAME_NAME_SIZE;
+
+#[derive(Debug, Error)]
+pub enum Md2Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not an MD2 file (magic {0:?})")]
+    InvalidMagic([u8; 4]),
+    #[error("Unsupported MD2 version {0}, expected 8")]
+    UnsupportedVersion(i32),
+    #[error("{name} (offset {offset}, {count} records) is outside of the file")]
+    InvalidSection {
+        name: &'static str,
+        offset: i32,
+        count: i32,
+    },
+    #[error("Frame size {frame_size} doesn't fit {num_vertices} vertices")]
+    InvalidFrameSize { frame_size: i32, num_vertices: i32 },
+    #[error("Triangle {triangle} indexes vertex {index} of {count}")]
+    InvalidIndex {
+        triangle: usize,
+        index: u16,
+        count: usize,
+    },
+}
+
+/// A triangle of an [Md2], as indices into the frame vertices and into the
+/// texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Md2Triangle {
+    pub vertices: [u16; 3],
+    pub tex_coords: [u16; 3],
+}
+
+/// A keyframe of an [Md2].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Md2Frame {
+    /// Name of the frame, e.g. `run3`, see [Md2::animations].
+    pub name: String,
+    /// Positions of the vertices, in model units.
+    pub positions: Vec<Vec3>,
+    /// Index of each vertex's normal in the game's table of 162 normals,
+    /// which lighting looked up. [Md2::frame_normals] computes normals from
+    /// the triangles instead.
+    pub normal_indices: Vec<u8>,
+}
+
+/// A parsed .md2 model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Md2 {
+    pub skin_width: u32,
+    pub skin_height: u32,
+    /// Paths of the skin images, e.g. `models/monsters/soldier/skin.pcx`.
+    pub skins: Vec<String>,
+    /// Texture coordinates, in skin texels.
+    pub tex_coords: Vec<[i16; 2]>,
+    pub triangles: Vec<Md2Triangle>,
+    pub frames: Vec<Md2Frame>,
+}
+
+/// The animations every Quake 2 player model has, at the frames the game
+/// plays them from. Models follow the same frame layout so any can stand in
+/// for the player; monsters name their frames instead, see
+/// [Md2::animations].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Md2Animation {
+    Stand,
+    Run,
+    Attack,
+    Pain,
+    Death,
+}
+
+impl Md2Animation {
+    pub const ALL: [Md2Animation; 5] = [
+        Md2Animation::Stand,
+        Md2Animation::Run,
+        Md2Animation::Attack,
+        Md2Animation::Pain,
+        Md2Animation::Death,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Md2Animation::Stand => "stand",
+            Md2Animation::Run => "run",
+            Md2Animation::Attack => "attack",
+            Md2Animation::Pain => "pain",
+            Md2Animation::Death => "death",
+        }
+    }
+
+    /// The frames of the animation in a player model. Of the three pain and
+    /// death variants, this is the first.
+    ///
+    /// ```
+    /// # use q2_formats::md2::Md2Animation;
+    /// assert_eq!(Md2Animation::Run.frames(), 40..46);
+    /// assert_eq!(Md2Animation::Death.frames(), 178..184);
+    /// ```
+    pub fn frames(self) -> Range<usize> {
+        match self {
+            Md2Animation::Stand => 0..40,
+            Md2Animation::Run => 40..46,
+            Md2Animation::Attack => 46..54,
+            Md2Animation::Pain => 54..58,
+            Md2Animation::Death => 178..184,
+        }
+    }
+
+    /// Whether the animation repeats; pain and death play once.
+    pub fn looping(self) -> bool {
+        matches!(
+            self,
+            Md2Animation::Stand | Md2Animation::Run | Md2Animation::Attack
+        )
+    }
+}
+
+/// The vertices of the mesh of an [Md2], where each distinct pair of frame
+/// vertex and texture coordinate is its own vertex, since the format
+/// indexes them separately.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Md2MeshLayout {
+    /// The frame vertex and texture coordinate of each mesh vertex.
+    pub vertices: Vec<(u16, u16)>,
+    /// Three mesh vertices per triangle, counter-clockwise seen from the
+    /// front.
+    pub indices: Vec<u32>,
+}
+
+impl Md2 {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Md2Error> {
+        let mut cursor = Cursor::new(bytes);
+        let mut magic = [0u8; 4];
+        cursor.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Md2Error::InvalidMagic(magic));
+        }
+        let version = cursor.read_i32::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(Md2Error::UnsupportedVersion(version));
+        }
+        let mut next = || cursor.read_i32::<LittleEndian>();
+        let skin_width = next()?;
+        let skin_height = next()?;
+        let frame_size = next()?;
+        let num_skins = next()?;
+        let num_vertices = next()?;
+        let num_tex_coords = next()?;
+        let num_triangles = next()?;
+        let _num_gl_commands = next()?;
+        let num_frames = next()?;
+        let ofs_skins = next()?;
+        let ofs_tex_coords = next()?;
+        let ofs_triangles = next()?;
+        let ofs_frames = next()?;
+
+        if num_vertices < 0
+            || frame_size < 0
+            || (frame_size as usize) < FRAME_HEADER_SIZE + 4 * num_vertices as usize
+        {
+            return Err(Md2Error::InvalidFrameSize {
+                frame_size,
+                num_vertices,
+            });
+        }
+        // Bounds every read, and so every allocation, by the file size
+        let section = |name, offset: i32, count: i32, size: usize| {
+            let invalid = Md2Error::InvalidSection {
+                name,
+                offset,
+                count,
+            };
+            let (Ok(start), Ok(count)) = (usize::try_from(offset), usize::try_from(count)) else {
+                return Err(invalid);
+            };
+            count
+                .checked_mul(size)
+                .and_then(|length| bytes.get(start..start.checked_add(length)?))
+                .ok_or(invalid)
+        };
+
+        let skins = section("Skins", ofs_skins, num_skins, SKIN_NAME_SIZE)?
+            .chunks_exact(SKIN_NAME_SIZE)
+            .map(read_name)
+            .collect();
+
+        let mut tex_coords = Vec::new();
+        let mut records = section("Texture coordinates", ofs_tex_coords, num_tex_coords, 4)?;
+        while !records.is_empty() {
+            tex_coords.push([
+                records.read_i16::<LittleEndian>()?,
+                records.read_i16::<LittleEndian>()?,
+            ]);
+        }
+
+        let mut triangles = Vec::new();
+        let mut records = section("Triangles", ofs_triangles, num_triangles, 12)?;
+        while !records.is_empty() {
+            let mut indices = [0u16; 6];
+            records.read_u16_into::<LittleEndian>(&mut indices)?;
+            let triangle = Md2Triangle {
+                vertices: [indices[0], indices[1], indices[2]],
+                tex_coords: [indices[3], indices[4], indices[5]],
+            };
+            let checks = [
+                (triangle.vertices, num_vertices as usize),
+                (triangle.tex_coords, tex_coords.len()),
+            ];
+            for (indices, count) in checks {
+                if let Some(&index) = indices.iter().find(|&&i| i as usize >= count) {
+                    return Err(Md2Error::InvalidIndex {
+                        triangle: triangles.len(),
+                        index,
+                        count,
+                    });
+                }
+            }
+            triangles.push(triangle);
+        }
+
+        let frames = section("Frames", ofs_frames, num_frames, frame_size as usize)?
+            .chunks_exact(frame_size as usize)
+            .map(|frame| read_frame(frame, num_vertices as usize))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            skin_width: skin_width.max(1) as u32,
+            skin_height: skin_height.max(1) as u32,
+            skins,
+            tex_coords,
+            triangles,
+            frames,
+        })
+    }
+
+    /// Vertices and indices of a mesh of the model, see [Md2MeshLayout].
+    /// The game's triangles are clockwise, so each is turned around.
+    pub fn mesh_layout(&self) -> Md2MeshLayout {
+        let mut layout = Md2MeshLayout::default();
+        let mut index = HashMap::new();
+        for triangle in &self.triangles {
+            for corner in [0, 2, 1] {
+                let key = (triangle.vertices[corner], triangle.tex_coords[corner]);
+                let i = *index.entry(key).or_insert_with(|| {
+                    layout.vertices.push(key);
+                    layout.vertices.len() as u32 - 1
+                });
+                layout.indices.push(i);
+            }
+        }
+        layout
+    }
+
+    /// Texture coordinates of the vertices of `layout`, from 0 to 1 across
+    /// the skin.
+    pub fn mesh_uvs(&self, layout: &Md2MeshLayout) -> Vec<[f32; 2]> {
+        let size = [self.skin_width as f32, self.skin_height as f32];
+        layout
+            .vertices
+            .iter()
+            .map(|&(_, st)| {
+                let [s, t] = self.tex_coords[st as usize];
+                [s as f32 / size[0], t as f32 / size[1]]
+            })
+            .collect()
+    }
+
+    /// Positions of the vertices of `layout` in frame `frame`.
+    pub fn mesh_positions(&self, frame: usize, layout: &Md2MeshLayout) -> Vec<[f32; 3]> {
+        let positions = &self.frames[frame].positions;
+        layout
+            .vertices
+            .iter()
+            .map(|&(vertex, _)| positions[vertex as usize].to_array())
+            .collect()
+    }
+
+    /// Smooth normals of the vertices of frame `frame`: the sum of the
+    /// normals of the triangles around each vertex, weighted by their area.
+    pub fn frame_normals(&self, frame: usize) -> Vec<Vec3> {
+        let positions = &self.frames[frame].positions;
+        let mut normals = vec![Vec3::ZERO; positions.len()];
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.vertices.map(|i| positions[i as usize]);
+            // Clockwise triangles, so this faces out
+            let normal = (c - a).cross(b - a);
+            for i in triangle.vertices {
+                normals[i as usize] += normal;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|normal| normal.try_normalize().unwrap_or(Vec3::Z))
+            .collect()
+    }
+
+    /// Normals of the vertices of `layout` in frame `frame`, see
+    /// [Md2::frame_normals].
+    pub fn mesh_normals(&self, frame: usize, layout: &Md2MeshLayout) -> Vec<[f32; 3]> {
+        let normals = self.frame_normals(frame);
+        layout
+            .vertices
+            .iter()
+            .map(|&(vertex, _)| normals[vertex as usize].to_array())
+            .collect()
+    }
+
+    /// The frames of `animation`: the run of frames named after it, see
+    /// [Md2::animations], and otherwise where a player model has it, if the
+    /// model has that many frames. Of variants numbered into the frame
+    /// names, such as `pain101` to `pain104` then `pain201`, only the first
+    /// plays.
+    pub fn animation_frames(&self, animation: Md2Animation) -> Option<Range<usize>> {
+        let named = self
+            .animations()
+            .into_iter()
+            .find(|(name, _)| name == animation.name())
+            .map(|(_, range)| {
+                let first = &self.frames[range.start].name;
+                if first.len() < animation.name().len() + 3 {
+                    return range;
+                }
+                let variant = &first[..first.len() - 2];
+                let end = range
+                    .clone()
+                    .find(|&i| !self.frames[i].name.starts_with(variant))
+                    .unwrap_or(range.end);
+                range.start..end
+            });
+        named.or_else(|| {
+            let frames = animation.frames();
+            (frames.end <= self.frames.len()).then_some(frames)
+        })
+    }
+
+    /// The animations of the model: runs of frames whose names share a
+    /// prefix before their trailing digits, such as `stand01` to `stand40`,
+    /// as the name and the range of frames.
+    ///
+    /// ```
+    /// # use q2_formats::md2::{Md2, Md2Frame};
+    /// let frame = |name: &str| Md2Frame {
+    ///     name: name.to_string(),
+    ///     positions: vec![],
+    ///     normal_indices: vec![],
+    /// };
+    /// let md2 = Md2 {
+    ///     skin_width: 1,
+    ///     skin_height: 1,
+    ///     skins: vec![],
+    ///     tex_coords: vec![],
+    ///     triangles: vec![],
+    ///     frames: ["stand01", "stand02", "run1", "run2", "run3"].map(frame).to_vec(),
+    /// };
+    /// assert_eq!(
+    ///     md2.animations(),
+    ///     [("stand".to_string(), 0..2), ("run".to_string(), 2..5)]
+    /// );
+    /// ```
+    pub fn animations(&self) -> Vec<(String, Range<usize>)> {
+        let mut animations: Vec<(String, Range<usize>)> = Vec::new();
+        for (i, frame) in self.frames.iter().enumerate() {
+            let prefix = frame.name.trim_end_matches(|c: char| c.is_ascii_digit());
+            match animations.last_mut() {
+                Some((name, range)) if name == prefix => range.end = i + 1,
+                _ => animations.push((prefix.to_string(), i..i + 1)),
+            }
+        }
+        animations
+    }
+}
+
+fn read_frame(mut frame: &[u8], num_vertices: usize) -> io::Result<Md2Frame> {
+    let mut transform = [0f32; 6];
+    frame.read_f32_into::<LittleEndian>(&mut transform)?;
+    let (scale, translate) = (
+        Vec3::from_slice(&transform[..3]),
+        Vec3::from_slice(&transform[3..]),
+    );
+    let name = read_name(&frame[..FRAME_NAME_SIZE]);
+    let vertices = &frame[FRAME_NAME_SIZE..FRAME_NAME_SIZE + 4 * num_vertices];
+    let (positions, normal_indices) = vertices
+        .chunks_exact(4)
+        .map(|v| {
+            let packed = Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32);
+            (packed * scale + translate, v[3])
+        })
+        .unzip();
+    Ok(Md2Frame {
+        name,
+        positions,
+        normal_indices,
+    })
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+}
@@ -0,0 +1,198 @@
+//! Quake 2 .pak archives: a flat directory of named files.
+
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"PACK";
+const ENTRY_SIZE: usize = 64;
+const NAME_SIZE: usize = 56;
+
+#[derive(Debug, Error)]
+pub enum PakError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a PAK file")]
+    InvalidMagic,
+    #[error("Invalid directory (offset {offset}, length {length})")]
+    InvalidDirectory { offset: i32, length: i32 },
+    #[error("Entry {name} (offset {offset}, length {length}) is outside of the archive")]
+    InvalidEntry {
+        name: String,
+        offset: u32,
+        length: u32,
+    },
+    #[error("File not found in archive: {0}")]
+    NotFound(String),
+    #[error("File name too long for a PAK entry: {0}")]
+    NameTooLong(String),
+}
+
+/// A directory entry of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry {
+    /// Path of the file inside the archive, e.g. `maps/q2dm1.bsp`.
+    pub name: String,
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// A PAK archive read from any seekable source. File contents are only read
+/// when requested.
+pub struct PakArchive<R> {
+    reader: R,
+    entries: Vec<PakEntry>,
+}
+
+impl PakArchive<File> {
+    /// Opens the archive at `path`, reading only its directory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PakError> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl PakArchive<Cursor<Vec<u8>>> {
+    /// Reads an archive held entirely in memory.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, PakError> {
+        Self::new(Cursor::new(bytes))
+    }
+
+    /// Bytes held in memory: the whole archive and its directory.
+    pub fn memory_bytes(&self) -> usize {
+        self.reader.get_ref().capacity()
+            + self
+                .entries
+                .iter()
+                .map(|entry| std::mem::size_of::<PakEntry>() + entry.name.capacity())
+                .sum::<usize>()
+    }
+}
+
+impl<R: Read + Seek> PakArchive<R> {
+    pub fn new(mut reader: R) -> Result<Self, PakError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PakError::InvalidMagic);
+        }
+        let offset = reader.read_i32::<LittleEndian>()?;
+        let length = reader.read_i32::<LittleEndian>()?;
+        let size = reader.seek(SeekFrom::End(0))?;
+        if offset < 0
+            || length < 0
+            || !(length as usize).is_multiple_of(ENTRY_SIZE)
+            || offset as u64 + length as u64 > size
+        {
+            return Err(PakError::InvalidDirectory { offset, length });
+        }
+
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let mut entries = Vec::with_capacity(length as usize / ENTRY_SIZE);
+        for _ in 0..length as usize / ENTRY_SIZE {
+            let mut name = [0u8; NAME_SIZE];
+            reader.read_exact(&mut name)?;
+            let end = name.iter().position(|&b| b == 0).unwrap_or(NAME_SIZE);
+            let entry = PakEntry {
+                name: String::from_utf8_lossy(&name[..end]).to_string(),
+                offset: reader.read_u32::<LittleEndian>()?,
+                length: reader.read_u32::<LittleEndian>()?,
+            };
+            // Checked up front so a corrupt directory can't trigger huge
+            // allocations when the entry is read
+            if entry.offset as u64 + entry.length as u64 > size {
+                return Err(PakError::InvalidEntry {
+                    name: entry.name,
+                    offset: entry.offset,
+                    length: entry.length,
+                });
+            }
+            entries.push(entry);
+        }
+
+        Ok(Self { reader, entries })
+    }
+
+    pub fn entries(&self) -> &[PakEntry] {
+        &self.entries
+    }
+
+    /// Finds an entry by name, ignoring ASCII case as the game does.
+    pub fn entry(&self, name: &str) -> Option<&PakEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Reads the contents of the file `name`.
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>, PakError> {
+        let entry = self
+            .entry(name)
+            .cloned()
+            .ok_or_else(|| PakError::NotFound(name.to_string()))?;
+        self.read_entry(&entry)
+    }
+
+    /// Reads the contents of `entry`, which must come from this archive.
+    pub fn read_entry(&mut self, entry: &PakEntry) -> Result<Vec<u8>, PakError> {
+        self.reader.seek(SeekFrom::Start(entry.offset as u64))?;
+        let mut data = vec![0u8; entry.length as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Writes a new archive: file data is streamed out as files are added and the
+/// directory is written by [PakWriter::finish].
+pub struct PakWriter<W: Write + Seek> {
+    writer: W,
+    entries: Vec<PakEntry>,
+}
+
+impl<W: Write + Seek> PakWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, PakError> {
+        // Placeholder header, patched with the directory location on finish
+        writer.write_all(MAGIC)?;
+        writer.write_i32::<LittleEndian>(0)?;
+        writer.write_i32::<LittleEndian>(0)?;
+        Ok(Self {
+            writer,
+            entries: Vec::new(),
+        })
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> Result<(), PakError> {
+        if name.len() >= NAME_SIZE {
+            return Err(PakError::NameTooLong(name.to_string()));
+        }
+        let offset = self.writer.stream_position()?;
+        self.writer.write_all(data)?;
+        self.entries.push(PakEntry {
+            name: name.to_string(),
+            offset: offset as u32,
+            length: data.len() as u32,
+        });
+        Ok(())
+    }
+
+    /// Writes the directory and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, PakError> {
+        let offset = self.writer.stream_position()?;
+        for entry in &self.entries {
+            let mut name = [0u8; NAME_SIZE];
+            name[..entry.name.len()].copy_from_slice(entry.name.as_bytes());
+            self.writer.write_all(&name)?;
+            self.writer.write_u32::<LittleEndian>(entry.offset)?;
+            self.writer.write_u32::<LittleEndian>(entry.length)?;
+        }
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_i32::<LittleEndian>(offset as i32)?;
+        self.writer
+            .write_i32::<LittleEndian>((self.entries.len() * ENTRY_SIZE) as i32)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,147 @@
+//! ZSoft .pcx images as Quake 2 uses them, for `pics/`, model skins and the
+//! palette in `pics/colormap.pcx`: 8 bits per pixel in one plane, run-length
+//! encoded, with a 256-color palette at the end of the file.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+use crate::wal::Palette;
+
+const HEADER_SIZE: usize = 128;
+const MANUFACTURER: u8 = 0x0A;
+const ENCODING_RLE: u8 = 1;
+/// Marks a byte as the count of a run, in its low 6 bits.
+const RUN_FLAG: u8 = 0xC0;
+/// The palette's marker byte and 256 RGB triples.
+const PALETTE_SIZE: usize = 1 + 256 * 3;
+
+#[derive(Debug, Error)]
+pub enum PcxError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Not a PCX file (manufacturer {0:#04x})")]
+    InvalidMagic(u8),
+    #[error(
+        "Unsupported PCX: {bits_per_pixel} bits per pixel in {planes} planes, encoding {encoding}"
+    )]
+    Unsupported {
+        bits_per_pixel: u8,
+        planes: u8,
+        encoding: u8,
+    },
+    #[error("Invalid image size {width}x{height} ({bytes_per_line} bytes per line)")]
+    InvalidSize {
+        width: u32,
+        height: u32,
+        bytes_per_line: u16,
+    },
+    #[error("Image data ends after {0} of its pixels")]
+    Truncated(usize),
+    #[error("No 256-color palette at the end of the PCX file")]
+    NoPalette,
+}
+
+/// A decoded .pcx image.
+#[derive(Debug, Clone)]
+pub struct Pcx {
+    pub width: u32,
+    pub height: u32,
+    /// Palette indices, row by row.
+    pub pixels: Vec<u8>,
+    pub palette: Palette,
+}
+
+impl Pcx {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PcxError> {
+        let mut cursor = Cursor::new(bytes);
+        let manufacturer = cursor.read_u8()?;
+        if manufacturer != MANUFACTURER {
+            return Err(PcxError::InvalidMagic(manufacturer));
+        }
+        let _version = cursor.read_u8()?;
+        let encoding = cursor.read_u8()?;
+        let bits_per_pixel = cursor.read_u8()?;
+        let mut window = [0u16; 4];
+        cursor.read_u16_into::<LittleEndian>(&mut window)?;
+        let [x_min, y_min, x_max, y_max] = window;
+        // DPI and the 16-color palette, then a reserved byte
+        cursor.set_position(65);
+        let planes = cursor.read_u8()?;
+        let bytes_per_line = cursor.read_u16::<LittleEndian>()?;
+        if encoding != ENCODING_RLE || bits_per_pixel != 8 || planes != 1 {
+            return Err(PcxError::Unsupported {
+                bits_per_pixel,
+                planes,
+                encoding,
+            });
+        }
+
+        let width = (x_max as u32 + 1).saturating_sub(x_min as u32);
+        let height = (y_max as u32 + 1).saturating_sub(y_min as u32);
+        // A run expands a byte pair to at most 63 pixels, which bounds the
+        // allocation below by the file size
+        if width == 0
+            || height == 0
+            || (bytes_per_line as u32) < width
+            || bytes_per_line as u64 * height as u64 > 32 * bytes.len() as u64
+        {
+            return Err(PcxError::InvalidSize {
+                width,
+                height,
+                bytes_per_line,
+            });
+        }
+
+        let palette = Palette::from_pcx(bytes).map_err(|_| PcxError::NoPalette)?;
+        let data = bytes
+            .get(HEADER_SIZE..bytes.len() - PALETTE_SIZE)
+            .unwrap_or_default();
+        let lines = decode_rle(data, bytes_per_line as usize * height as usize)?;
+        let pixels = lines
+            .chunks_exact(bytes_per_line as usize)
+            .flat_map(|line| &line[..width as usize])
+            .copied()
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+            palette,
+        })
+    }
+
+    /// The image as 8-bit RGBA. As in the game, index 255 is transparent.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&index| self.palette.rgba(index))
+            .collect()
+    }
+}
+
+/// Expands the first `length` bytes of run-length encoded `data`. Runs may
+/// continue from one line into the next.
+fn decode_rle(mut data: &[u8], length: usize) -> Result<Vec<u8>, PcxError> {
+    let mut out = Vec::with_capacity(length);
+    while out.len() < length {
+        let mut byte = [0u8];
+        if data.read_exact(&mut byte).is_err() {
+            return Err(PcxError::Truncated(out.len()));
+        }
+        let (count, value) = match byte[0] {
+            run if run & RUN_FLAG == RUN_FLAG => {
+                let mut value = [0u8];
+                data.read_exact(&mut value)
+                    .map_err(|_| PcxError::Truncated(out.len()))?;
+                ((run & !RUN_FLAG) as usize, value[0])
+            }
+            value => (1, value),
+        };
+        let count = count.min(length - out.len());
+        out.resize(out.len() + count, value);
+    }
+    Ok(out)
+}
@@ -0,0 +1,678 @@
+//! Builds tiny, valid BSP38 files in memory for tests.
+//!
+//! Lets code using the parsers be tested without shipping (copyrighted) map
+//! files. Enabled by the `test-utils` feature.
+//!
+//! ```
+//! use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+//!
+//! let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+//!     .with_entity("info_player_deathmatch", &[("origin", "128 128 24")])
+//!     .build();
+//! let bsp = BSP38::from_bytes(bytes).unwrap();
+//! assert_eq!(bsp.read_entities().unwrap().len(), 2);
+//! ```
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+};
+
+/// A texinfo record as written to the Texinfo lump.
+#[derive(Debug, Clone)]
+pub struct TestTexinfo {
+    pub u: [f32; 3],
+    pub u0: f32,
+    pub v: [f32; 3],
+    pub v0: f32,
+    pub flags: u32,
+    pub value: u32,
+    pub texture: String,
+    pub next: u32,
+}
+
+impl TestTexinfo {
+    /// World-aligned projection onto the XY plane.
+    pub fn named(texture: &str) -> Self {
+        Self {
+            u: [1.0, 0.0, 0.0],
+            u0: 0.0,
+            v: [0.0, 1.0, 0.0],
+            v0: 0.0,
+            flags: 0,
+            value: 0,
+            texture: texture.to_string(),
+            next: u32::MAX,
+        }
+    }
+}
+
+/// A convex polygon face. Points are in order around the polygon.
+#[derive(Debug, Clone)]
+pub struct TestFace {
+    pub points: Vec<[f32; 3]>,
+    pub texinfo: u16,
+    /// PVS cluster of the leaf containing the face, or -1 for none.
+    pub cluster: i16,
+}
+
+/// An axis-aligned box brush.
+#[derive(Debug, Clone)]
+pub struct TestBrush {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub contents: i32,
+}
+
+/// Builder for a BSP38 byte buffer.
+///
+/// Vertices and edges are shared between faces where possible, so faces
+/// meeting at an edge reference it in opposite directions, as real compilers
+/// do.
+#[derive(Debug, Clone, Default)]
+pub struct TestMapBuilder {
+    pub texinfo: Vec<TestTexinfo>,
+    pub faces: Vec<TestFace>,
+    pub entities: Vec<(String, Vec<(String, String)>)>,
+    pub vis: Option<Vec<Vec<bool>>>,
+    /// Pairs of areas joined by a portal, numbered from 1 in order.
+    pub area_portals: Vec<(u32, u32)>,
+    /// Brushes of the world model. Every leaf lists all of them.
+    pub brushes: Vec<TestBrush>,
+    /// Give every face a lightmap filled with [lightmap_color].
+    pub lightmaps: bool,
+    /// Faces of the inline models `*1`, `*2`, ... The world model holds the
+    /// faces before the first of them.
+    pub inline_models: Vec<Range<usize>>,
+}
+
+impl TestMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A closed box from `min` to `max` with all six faces pointing inwards,
+    /// in cluster 0 and using a single texture.
+    pub fn room(min: [f32; 3], max: [f32; 3]) -> Self {
+        let [x0, y0, z0] = min;
+        let [x1, y1, z1] = max;
+        let quads = [
+            [[x0, y0, z0], [x1, y0, z0], [x1, y1, z0], [x0, y1, z0]],
+            [[x0, y0, z1], [x0, y1, z1], [x1, y1, z1], [x1, y0, z1]],
+            [[x0, y0, z0], [x0, y1, z0], [x0, y1, z1], [x0, y0, z1]],
+            [[x1, y0, z0], [x1, y0, z1], [x1, y1, z1], [x1, y1, z0]],
+            [[x0, y0, z0], [x0, y0, z1], [x1, y0, z1], [x1, y0, z0]],
+            [[x0, y1, z0], [x1, y1, z0], [x1, y1, z1], [x0, y1, z1]],
+        ];
+        let mut builder = Self::new().with_texinfo(TestTexinfo::named("e1u1/floor1_3"));
+        for quad in quads {
+            builder = builder.with_face(quad.to_vec(), 0, 0);
+        }
+        builder.with_entity("worldspawn", &[])
+    }
+
+    /// Adds a texinfo record; faces refer to it by index in insertion order.
+    pub fn with_texinfo(mut self, texinfo: TestTexinfo) -> Self {
+        self.texinfo.push(texinfo);
+        self
+    }
+
+    /// Adds a convex face using texinfo `texinfo`, in PVS cluster `cluster`.
+    pub fn with_face(mut self, points: Vec<[f32; 3]>, texinfo: u16, cluster: i16) -> Self {
+        self.faces.push(TestFace {
+            points,
+            texinfo,
+            cluster,
+        });
+        self
+    }
+
+    /// Adds an entity to the entity string.
+    pub fn with_entity(mut self, classname: &str, properties: &[(&str, &str)]) -> Self {
+        self.entities.push((
+            classname.to_string(),
+            properties
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        ));
+        self
+    }
+
+    /// Sets the PVS: `rows[a][b]` is whether cluster `b` is visible from `a`.
+    pub fn with_vis(mut self, rows: Vec<Vec<bool>>) -> Self {
+        self.vis = Some(rows);
+        self
+    }
+
+    /// Adds an area portal between areas `a` and `b` (numbered from 1).
+    /// Portals are numbered from 1 in insertion order.
+    pub fn with_area_portal(mut self, a: u32, b: u32) -> Self {
+        self.area_portals.push((a, b));
+        self
+    }
+
+    /// Adds a box brush from `min` to `max` with the given content flags.
+    pub fn with_brush(mut self, min: [f32; 3], max: [f32; 3], contents: i32) -> Self {
+        self.brushes.push(TestBrush { min, max, contents });
+        self
+    }
+
+    /// Adds an inline model made of `faces`, which should come after the
+    /// world's faces and those of the previous models, as compilers write
+    /// them. It has no subtree of its own: its head node is the solid leaf.
+    pub fn with_inline_model(mut self, faces: Range<usize>) -> Self {
+        self.inline_models.push(faces);
+        self
+    }
+
+    /// Gives every face a lightmap of a single color, see [lightmap_color].
+    pub fn with_lightmaps(mut self) -> Self {
+        self.lightmaps = true;
+        self
+    }
+
+    /// The entity string written to the Entities lump.
+    pub fn entity_string(&self) -> String {
+        let mut s = String::new();
+        for (classname, properties) in &self.entities {
+            s.push_str("{\n");
+            s.push_str(&format!("\"classname\" \"{}\"\n", classname));
+            for (k, v) in properties {
+                s.push_str(&format!("\"{}\" \"{}\"\n", k, v));
+            }
+            s.push_str("}\n");
+        }
+        s
+    }
+
+    /// The Models lump: the world, then the inline models.
+    fn models(&self) -> Vec<u8> {
+        let points = |faces: Range<usize>| -> Vec<[f32; 3]> {
+            self.faces[faces]
+                .iter()
+                .flat_map(|face| face.points.iter().copied())
+                .collect()
+        };
+        let world = 0..self
+            .inline_models
+            .first()
+            .map_or(self.faces.len(), |faces| faces.start);
+
+        let mut out = Vec::new();
+        put_model(&mut out, &points(world.clone()), 0, world);
+        for faces in &self.inline_models {
+            put_model(&mut out, &points(faces.clone()), -1, faces.clone());
+        }
+        out
+    }
+
+    /// Writes the map as BSP38 bytes, ready for `BSP38::from_bytes`.
+    pub fn build(&self) -> Vec<u8> {
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut vertex_ids: HashMap<[u32; 3], u16> = HashMap::new();
+        // Edge 0 is reserved: it can't be referenced in reverse
+        let mut edges: Vec<[u16; 2]> = vec![[0, 0]];
+        let mut edge_ids: HashMap<[u16; 2], i32> = HashMap::new();
+        let mut face_edges: Vec<i32> = Vec::new();
+        let mut planes = Vec::new();
+        let mut faces = Vec::new();
+        let mut lighting = Vec::new();
+
+        for (i, face) in self.faces.iter().enumerate() {
+            let ids: Vec<u16> = face
+                .points
+                .iter()
+                .map(|p| {
+                    *vertex_ids.entry(p.map(f32::to_bits)).or_insert_with(|| {
+                        vertices.push(*p);
+                        (vertices.len() - 1) as u16
+                    })
+                })
+                .collect();
+
+            let first_edge = face_edges.len() as u32;
+            for j in 0..ids.len() {
+                let (a, b) = (ids[j], ids[(j + 1) % ids.len()]);
+                let id = match edge_ids.get(&[b, a]) {
+                    Some(&reverse) => -reverse,
+                    None => *edge_ids.entry([a, b]).or_insert_with(|| {
+                        edges.push([a, b]);
+                        (edges.len() - 1) as i32
+                    }),
+                };
+                face_edges.push(id);
+            }
+
+            let (normal, dist) = plane_of(&face.points);
+            put_f32s(&mut planes, &normal);
+            put_f32s(&mut planes, &[dist]);
+            planes.extend_from_slice(&0u32.to_le_bytes());
+
+            faces.extend_from_slice(&(i as u16).to_le_bytes());
+            faces.extend_from_slice(&1u16.to_le_bytes());
+            faces.extend_from_slice(&first_edge.to_le_bytes());
+            faces.extend_from_slice(&(ids.len() as u16).to_le_bytes());
+            faces.extend_from_slice(&face.texinfo.to_le_bytes());
+            match self.texinfo.get(face.texinfo as usize) {
+                Some(tex) if self.lightmaps => {
+                    let luxels = luxel_count(&face.points, tex.u, tex.u0)
+                        * luxel_count(&face.points, tex.v, tex.v0);
+                    faces.extend_from_slice(&[0, 255, 255, 255]);
+                    faces.extend_from_slice(&(lighting.len() as i32).to_le_bytes());
+                    for _ in 0..luxels {
+                        lighting.extend_from_slice(&lightmap_color(i));
+                    }
+                }
+                _ => {
+                    faces.extend_from_slice(&[0, 255, 255, 255]);
+                    faces.extend_from_slice(&(-1i32).to_le_bytes());
+                }
+            }
+        }
+
+        // Leaf 0 is the solid leaf, then one leaf per cluster
+        let clusters: BTreeSet<i16> = self
+            .faces
+            .iter()
+            .map(|f| f.cluster)
+            .filter(|&c| c >= 0)
+            .collect();
+        let mut leafs = Vec::new();
+        let mut leaf_faces = Vec::new();
+        let mut leaf_bounds = Vec::new();
+        let num_brushes = self.brushes.len() as u16;
+        put_leaf(&mut leafs, 1, -1, ([0; 3], [0; 3]), (0, 0), num_brushes);
+        for &cluster in &clusters {
+            let first = (leaf_faces.len() / 2) as u16;
+            let mut count = 0u16;
+            let mut points = Vec::new();
+            for (i, face) in self.faces.iter().enumerate() {
+                if face.cluster == cluster {
+                    leaf_faces.extend_from_slice(&(i as u16).to_le_bytes());
+                    points.extend_from_slice(&face.points);
+                    count += 1;
+                }
+            }
+            let bounds = short_bounds(&points);
+            leaf_bounds.push(bounds);
+            put_leaf(&mut leafs, 0, cluster, bounds, (first, count), num_brushes);
+        }
+
+        let mut nodes = Vec::new();
+        if !leaf_bounds.is_empty() {
+            let mut tree = NodeWriter {
+                nodes: &mut nodes,
+                planes: &mut planes,
+                leaf_bounds: &leaf_bounds,
+            };
+            let leafs: Vec<usize> = (0..leaf_bounds.len()).collect();
+            if leafs.len() == 1 {
+                // A single leaf still needs a node above it, with the solid
+                // leaf below its floor
+                let (mins, _) = leaf_bounds[0];
+                tree.put_node(2, mins[2] as f32 - 1.0, [-2, -1], leaf_bounds[0]);
+            } else {
+                tree.split(&leafs);
+            }
+        }
+
+        // One plane per box side, pointing out of the brush
+        let mut brushes = Vec::new();
+        let mut brush_sides = Vec::new();
+        let mut leaf_brushes = Vec::new();
+        for (i, brush) in self.brushes.iter().enumerate() {
+            brushes.extend_from_slice(&(brush_sides.len() as u32 / 4).to_le_bytes());
+            brushes.extend_from_slice(&6u32.to_le_bytes());
+            brushes.extend_from_slice(&brush.contents.to_le_bytes());
+            for axis in 0..3 {
+                for (sign, dist) in [(1.0, brush.max[axis]), (-1.0, -brush.min[axis])] {
+                    let mut normal = [0.0; 3];
+                    normal[axis] = sign;
+                    let plane = (planes.len() / 20) as u16;
+                    put_f32s(&mut planes, &normal);
+                    put_f32s(&mut planes, &[dist]);
+                    planes.extend_from_slice(&(axis as u32).to_le_bytes());
+                    brush_sides.extend_from_slice(&plane.to_le_bytes());
+                    brush_sides.extend_from_slice(&(-1i16).to_le_bytes());
+                }
+            }
+            leaf_brushes.extend_from_slice(&(i as u16).to_le_bytes());
+        }
+
+        let mut texinfo = Vec::new();
+        for t in &self.texinfo {
+            put_f32s(&mut texinfo, &t.u);
+            put_f32s(&mut texinfo, &[t.u0]);
+            put_f32s(&mut texinfo, &t.v);
+            put_f32s(&mut texinfo, &[t.v0]);
+            texinfo.extend_from_slice(&t.flags.to_le_bytes());
+            texinfo.extend_from_slice(&t.value.to_le_bytes());
+            let mut name = [0u8; 32];
+            name[..t.texture.len()].copy_from_slice(t.texture.as_bytes());
+            texinfo.extend_from_slice(&name);
+            texinfo.extend_from_slice(&t.next.to_le_bytes());
+        }
+
+        let mut vertex_bytes = Vec::new();
+        for v in &vertices {
+            put_f32s(&mut vertex_bytes, v);
+        }
+
+        let mut edge_bytes = Vec::new();
+        for e in &edges {
+            edge_bytes.extend_from_slice(&e[0].to_le_bytes());
+            edge_bytes.extend_from_slice(&e[1].to_le_bytes());
+        }
+
+        let mut face_edge_bytes = Vec::new();
+        for e in &face_edges {
+            face_edge_bytes.extend_from_slice(&e.to_le_bytes());
+        }
+
+        let mut entities = self.entity_string().into_bytes();
+        entities.push(0);
+
+        let mut lumps: Vec<Vec<u8>> = vec![Vec::new(); 19];
+        lumps[0] = entities;
+        lumps[1] = planes;
+        lumps[2] = vertex_bytes;
+        lumps[3] = self
+            .vis
+            .as_ref()
+            .map(|rows| vis_lump(rows))
+            .unwrap_or_default();
+        lumps[4] = nodes;
+        lumps[5] = texinfo;
+        lumps[6] = faces;
+        lumps[7] = lighting;
+        lumps[8] = leafs;
+        lumps[9] = leaf_faces;
+        lumps[10] = leaf_brushes;
+        lumps[11] = edge_bytes;
+        lumps[12] = face_edge_bytes;
+        lumps[13] = self.models();
+        lumps[14] = brushes;
+        lumps[15] = brush_sides;
+        (lumps[17], lumps[18]) = area_lumps(&self.area_portals);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(b"IBSP");
+        out.extend_from_slice(&38u32.to_le_bytes());
+        let mut offset = 8 + 8 * lumps.len();
+        for lump in &lumps {
+            out.extend_from_slice(&(offset as i32).to_le_bytes());
+            out.extend_from_slice(&(lump.len() as i32).to_le_bytes());
+            offset += lump.len().next_multiple_of(4);
+        }
+        for lump in &lumps {
+            out.extend_from_slice(lump);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+}
+
+/// The color of the lightmap of face `face` written by
+/// [TestMapBuilder::with_lightmaps].
+pub fn lightmap_color(face: usize) -> [u8; 3] {
+    [(face * 40 % 256) as u8, 128, 255]
+}
+
+/// Number of lightmap samples along a texture axis, one every 16 texels over
+/// the texture extents snapped outwards, plus one.
+fn luxel_count(points: &[[f32; 3]], axis: [f32; 3], offset: f32) -> usize {
+    let (min, max) = points
+        .iter()
+        .map(|p| p[0] * axis[0] + p[1] * axis[1] + p[2] * axis[2] + offset)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), s| {
+            (min.min(s), max.max(s))
+        });
+    ((max / 16.0).ceil() - (min / 16.0).floor() + 1.0) as usize
+}
+
+/// Run-length encodes a PVS row the way the vis compiler does.
+pub fn compress_vis_row(row: &[bool]) -> Vec<u8> {
+    let bytes: Vec<u8> = row
+        .chunks(8)
+        .map(|bits| {
+            bits.iter()
+                .enumerate()
+                .fold(0u8, |b, (i, &set)| b | ((set as u8) << i))
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != 0 {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let mut run = 0;
+        while i < bytes.len() && bytes[i] == 0 && run < 255 {
+            run += 1;
+            i += 1;
+        }
+        out.push(0);
+        out.push(run);
+    }
+    out
+}
+
+fn vis_lump(rows: &[Vec<bool>]) -> Vec<u8> {
+    let header = 4 + 8 * rows.len();
+    let mut offsets = Vec::new();
+    let mut data = Vec::new();
+    for row in rows {
+        offsets.push((header + data.len()) as u32);
+        data.extend(compress_vis_row(row));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&(rows.len() as u32).to_le_bytes());
+    for offset in offsets {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+    out.extend(data);
+    out
+}
+
+fn area_lumps(area_portals: &[(u32, u32)]) -> (Vec<u8>, Vec<u8>) {
+    let num_areas = area_portals
+        .iter()
+        .map(|&(a, b)| a.max(b) + 1)
+        .max()
+        .unwrap_or(0);
+    let mut areas = Vec::new();
+    let mut portals = Vec::new();
+    for area in 0..num_areas {
+        let first = (portals.len() / 8) as u32;
+        let mut count = 0u32;
+        for (i, &(a, b)) in area_portals.iter().enumerate() {
+            let other = if area == a {
+                b
+            } else if area == b {
+                a
+            } else {
+                continue;
+            };
+            portals.extend_from_slice(&(i as u32 + 1).to_le_bytes());
+            portals.extend_from_slice(&other.to_le_bytes());
+            count += 1;
+        }
+        areas.extend_from_slice(&count.to_le_bytes());
+        areas.extend_from_slice(&first.to_le_bytes());
+    }
+    (areas, portals)
+}
+
+fn put_model(out: &mut Vec<u8>, points: &[[f32; 3]], head_node: i32, faces: Range<usize>) {
+    let mut min = [0f32; 3];
+    let mut max = [0f32; 3];
+    if let Some(first) = points.first() {
+        (min, max) = (*first, *first);
+    }
+    for v in points {
+        for i in 0..3 {
+            min[i] = min[i].min(v[i]);
+            max[i] = max[i].max(v[i]);
+        }
+    }
+    let origin = [0f32; 3];
+
+    put_f32s(out, &min);
+    put_f32s(out, &max);
+    put_f32s(out, &origin);
+    out.extend_from_slice(&head_node.to_le_bytes());
+    out.extend_from_slice(&(faces.start as i32).to_le_bytes());
+    out.extend_from_slice(&(faces.len() as i32).to_le_bytes());
+}
+
+/// Writes a node tree over the cluster leafs, splitting them with
+/// axis-aligned planes.
+struct NodeWriter<'a> {
+    nodes: &'a mut Vec<u8>,
+    planes: &'a mut Vec<u8>,
+    /// Bounds of leaf `i + 1`; leaf 0 is the solid leaf.
+    leaf_bounds: &'a [([i16; 3], [i16; 3])],
+}
+
+impl NodeWriter<'_> {
+    /// Writes the subtree over `leafs`, returning its child reference.
+    ///
+    /// Leafs are sorted along the axis their bounds spread the most on and
+    /// split in half, with the plane halfway between the two halves. Leafs
+    /// overlapping that plane end up on one side only, so box queries on
+    /// overlapping leafs are approximate.
+    fn split(&mut self, leafs: &[usize]) -> i32 {
+        if let [leaf] = leafs {
+            return -2 - *leaf as i32;
+        }
+        let center = |leaf: usize, axis: usize| {
+            let (mins, maxs) = self.leaf_bounds[leaf];
+            mins[axis] as f32 + maxs[axis] as f32
+        };
+        let spread = |axis: usize| {
+            let centers = leafs.iter().map(|&l| center(l, axis));
+            centers.clone().fold(f32::NEG_INFINITY, f32::max)
+                - centers.fold(f32::INFINITY, f32::min)
+        };
+        let axis = (0..3)
+            .max_by(|&a, &b| spread(a).total_cmp(&spread(b)))
+            .unwrap();
+        let mut sorted = leafs.to_vec();
+        sorted.sort_by(|&a, &b| center(a, axis).total_cmp(&center(b, axis)));
+        let (back, front) = sorted.split_at(sorted.len() / 2);
+
+        let back_max = back
+            .iter()
+            .map(|&l| self.leaf_bounds[l].1[axis])
+            .max()
+            .unwrap();
+        let front_min = front
+            .iter()
+            .map(|&l| self.leaf_bounds[l].0[axis])
+            .min()
+            .unwrap();
+        let dist = (back_max as f32 + front_min as f32) / 2.0;
+
+        let mut bounds = ([i16::MAX; 3], [i16::MIN; 3]);
+        for &leaf in leafs {
+            let (mins, maxs) = self.leaf_bounds[leaf];
+            for i in 0..3 {
+                bounds.0[i] = bounds.0[i].min(mins[i]);
+                bounds.1[i] = bounds.1[i].max(maxs[i]);
+            }
+        }
+
+        // Reserve the node before writing its children, so the root is node 0
+        let node = self.put_node(axis, dist, [0, 0], bounds);
+        let children = [self.split(front), self.split(back)];
+        let at = node * 28 + 4;
+        self.nodes[at..at + 4].copy_from_slice(&children[0].to_le_bytes());
+        self.nodes[at + 4..at + 8].copy_from_slice(&children[1].to_le_bytes());
+        node as i32
+    }
+
+    fn put_node(
+        &mut self,
+        axis: usize,
+        dist: f32,
+        children: [i32; 2],
+        (mins, maxs): ([i16; 3], [i16; 3]),
+    ) -> usize {
+        let mut normal = [0.0; 3];
+        normal[axis] = 1.0;
+        let plane = (self.planes.len() / 20) as u32;
+        put_f32s(self.planes, &normal);
+        put_f32s(self.planes, &[dist]);
+        self.planes.extend_from_slice(&(axis as u32).to_le_bytes());
+
+        let node = self.nodes.len() / 28;
+        self.nodes.extend_from_slice(&plane.to_le_bytes());
+        for child in children {
+            self.nodes.extend_from_slice(&child.to_le_bytes());
+        }
+        for v in mins.iter().chain(&maxs) {
+            self.nodes.extend_from_slice(&v.to_le_bytes());
+        }
+        self.nodes.extend_from_slice(&[0u8; 4]);
+        node
+    }
+}
+
+/// Bounds of `points` rounded outwards to whole units, as stored in leafs.
+fn short_bounds(points: &[[f32; 3]]) -> ([i16; 3], [i16; 3]) {
+    let mut bounds = ([i16::MAX; 3], [i16::MIN; 3]);
+    for p in points {
+        for (i, v) in p.iter().enumerate() {
+            bounds.0[i] = bounds.0[i].min(v.floor() as i16);
+            bounds.1[i] = bounds.1[i].max(v.ceil() as i16);
+        }
+    }
+    bounds
+}
+
+fn put_leaf(
+    out: &mut Vec<u8>,
+    contents: i32,
+    cluster: i16,
+    (mins, maxs): ([i16; 3], [i16; 3]),
+    (first_face, num_faces): (u16, u16),
+    num_brushes: u16,
+) {
+    out.extend_from_slice(&contents.to_le_bytes());
+    out.extend_from_slice(&cluster.to_le_bytes());
+    out.extend_from_slice(&0i16.to_le_bytes());
+    for v in mins.iter().chain(&maxs) {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.extend_from_slice(&first_face.to_le_bytes());
+    out.extend_from_slice(&num_faces.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&num_brushes.to_le_bytes());
+}
+
+fn put_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Plane through a polygon, with the normal following its winding.
+fn plane_of(points: &[[f32; 3]]) -> ([f32; 3], f32) {
+    let sub = |a: [f32; 3], b: [f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    let (e0, e1) = (sub(points[1], points[0]), sub(points[2], points[0]));
+    let n = [
+        e0[1] * e1[2] - e0[2] * e1[1],
+        e0[2] * e1[0] - e0[0] * e1[2],
+        e0[0] * e1[1] - e0[1] * e1[0],
+    ];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    let n = n.map(|c| c / len);
+    let p = points[0];
+    (n, n[0] * p[0] + n[1] * p[1] + n[2] * p[2])
+}
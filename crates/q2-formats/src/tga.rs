@@ -0,0 +1,145 @@
+//! Truevision .tga images, which Quake 2 engines load in place of a .wal or
+//! .pcx of the same name, for high resolution textures, skies and skins.
+//! Covers what those use: true color and grayscale, raw or run-length
+//! encoded, stored bottom-up or top-down.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+const HEADER_SIZE: u64 = 18;
+const TYPE_TRUE_COLOR: u8 = 2;
+const TYPE_GRAYSCALE: u8 = 3;
+/// Added to the image type for run-length encoding.
+const TYPE_RLE: u8 = 8;
+/// Image descriptor bits for rows stored right to left and top to bottom.
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+#[derive(Debug, Error)]
+pub enum TgaError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Unsupported TGA image type {image_type} with {bits_per_pixel} bits per pixel")]
+    Unsupported { image_type: u8, bits_per_pixel: u8 },
+    #[error("Invalid image size {width}x{height}")]
+    InvalidSize { width: u32, height: u32 },
+    #[error("Image data ends after {0} of its pixels")]
+    Truncated(usize),
+}
+
+/// A decoded .tga image.
+#[derive(Debug, Clone)]
+pub struct Tga {
+    pub width: u32,
+    pub height: u32,
+    /// 8-bit RGBA, row by row from the top.
+    pub rgba: Vec<u8>,
+}
+
+impl Tga {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TgaError> {
+        let mut cursor = Cursor::new(bytes);
+        let id_length = cursor.read_u8()?;
+        let color_map_type = cursor.read_u8()?;
+        let image_type = cursor.read_u8()?;
+        let _color_map_start = cursor.read_u16::<LittleEndian>()?;
+        let color_map_length = cursor.read_u16::<LittleEndian>()?;
+        let color_map_bits = cursor.read_u8()?;
+        let _origin = [
+            cursor.read_u16::<LittleEndian>()?,
+            cursor.read_u16::<LittleEndian>()?,
+        ];
+        let width = cursor.read_u16::<LittleEndian>()? as u32;
+        let height = cursor.read_u16::<LittleEndian>()? as u32;
+        let bits_per_pixel = cursor.read_u8()?;
+        let descriptor = cursor.read_u8()?;
+
+        let channels = match (image_type & !TYPE_RLE, bits_per_pixel) {
+            (TYPE_TRUE_COLOR, 24) => 3,
+            (TYPE_TRUE_COLOR, 32) => 4,
+            (TYPE_GRAYSCALE, 8) => 1,
+            _ => {
+                return Err(TgaError::Unsupported {
+                    image_type,
+                    bits_per_pixel,
+                })
+            }
+        };
+        // A run packs up to 128 pixels in a few bytes, which bounds the
+        // allocation below by the file size
+        if width == 0 || height == 0 || width as u64 * height as u64 > 128 * bytes.len() as u64 {
+            return Err(TgaError::InvalidSize { width, height });
+        }
+
+        // Skip the image id and any color map, unused by these types
+        let color_map_size = match color_map_type {
+            0 => 0,
+            _ => color_map_length as u64 * (color_map_bits as u64).div_ceil(8),
+        };
+        cursor.set_position(HEADER_SIZE + id_length as u64 + color_map_size);
+        let data = bytes.get(cursor.position() as usize..).unwrap_or_default();
+
+        let count = (width * height) as usize;
+        let pixels = match image_type & TYPE_RLE != 0 {
+            true => decode_rle(data, count, channels)?,
+            false => data
+                .get(..count * channels)
+                .ok_or(TgaError::Truncated(data.len() / channels))?
+                .to_vec(),
+        };
+
+        let mut rgba = vec![0u8; count * 4];
+        for (i, pixel) in pixels.chunks_exact(channels).enumerate() {
+            let (mut x, mut y) = (i as u32 % width, i as u32 / width);
+            if descriptor & RIGHT_TO_LEFT != 0 {
+                x = width - 1 - x;
+            }
+            if descriptor & TOP_TO_BOTTOM == 0 {
+                y = height - 1 - y;
+            }
+            let color = match *pixel {
+                [b, g, r, a] => [r, g, b, a],
+                [b, g, r] => [r, g, b, 255],
+                [gray] => [gray, gray, gray, 255],
+                _ => unreachable!(),
+            };
+            let at = ((y * width + x) * 4) as usize;
+            rgba[at..at + 4].copy_from_slice(&color);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+}
+
+/// Expands `count` pixels of `channels` bytes from run-length encoded
+/// `data`. Packets may continue from one row into the next.
+fn decode_rle(mut data: &[u8], count: usize, channels: usize) -> Result<Vec<u8>, TgaError> {
+    let length = count * channels;
+    let mut out = Vec::with_capacity(length);
+    let mut pixel = [0u8; 4];
+    while out.len() < length {
+        let truncated = |out: &Vec<u8>| TgaError::Truncated(out.len() / channels);
+        let mut header = [0u8];
+        data.read_exact(&mut header).map_err(|_| truncated(&out))?;
+        let pixels = (header[0] & 0x7f) as usize + 1;
+        let bytes = (pixels * channels).min(length - out.len());
+        if header[0] & 0x80 != 0 {
+            data.read_exact(&mut pixel[..channels])
+                .map_err(|_| truncated(&out))?;
+            out.extend(pixel[..channels].iter().cycle().take(bytes));
+        } else {
+            let raw = data
+                .get(..pixels * channels)
+                .ok_or_else(|| truncated(&out))?;
+            out.extend_from_slice(&raw[..bytes]);
+            data = &data[pixels * channels..];
+        }
+    }
+    Ok(out)
+}
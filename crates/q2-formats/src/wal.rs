@@ -0,0 +1,168 @@
+//! Quake 2 .wal textures: 8-bit palettized images with four mip levels.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
+
+const HEADER_SIZE: usize = 100;
+const NAME_SIZE: usize = 32;
+
+/// Number of mip levels stored in a .wal file, full size first.
+pub const MIP_LEVELS: usize = 4;
+
+/// Size of the palette stored at the end of a 256-color PCX file.
+const PCX_PALETTE_SIZE: usize = 769;
+
+#[derive(Debug, Error)]
+pub enum WalError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid texture size {width}x{height}")]
+    InvalidSize { width: u32, height: u32 },
+    #[error("Mip level {level} (offset {offset}, {length} bytes) is outside of the file")]
+    InvalidMip {
+        level: usize,
+        offset: u32,
+        length: usize,
+    },
+    #[error("No 256-color palette at the end of the PCX file")]
+    NoPalette,
+}
+
+/// A parsed .wal texture.
+#[derive(Debug, Clone)]
+pub struct Wal {
+    /// Texture path without extension, e.g. `e1u1/floor1_3`.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Palette indices of each mip level, row by row. Level `i` is
+    /// `width >> i` by `height >> i` texels.
+    pub mips: [Vec<u8>; MIP_LEVELS],
+    /// Next texture of an animation, empty if none.
+    pub next_name: String,
+    /// Default surface flags, see [crate::bsp38::surface].
+    pub flags: u32,
+    pub contents: u32,
+    pub value: u32,
+}
+
+impl Wal {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WalError> {
+        let mut cursor = Cursor::new(bytes);
+        let name = read_name(&mut cursor)?;
+        let width = cursor.read_u32::<LittleEndian>()?;
+        let height = cursor.read_u32::<LittleEndian>()?;
+        let mut offsets = [0u32; MIP_LEVELS];
+        for offset in offsets.iter_mut() {
+            *offset = cursor.read_u32::<LittleEndian>()?;
+        }
+        let next_name = read_name(&mut cursor)?;
+        let flags = cursor.read_u32::<LittleEndian>()?;
+        let contents = cursor.read_u32::<LittleEndian>()?;
+        let value = cursor.read_u32::<LittleEndian>()?;
+
+        // Every mip level must be at least a texel, which also bounds the
+        // allocation below by the file size
+        if width == 0
+            || height == 0
+            || width % (1 << (MIP_LEVELS - 1)) != 0
+            || height % (1 << (MIP_LEVELS - 1)) != 0
+            || width as u64 * height as u64 > bytes.len() as u64
+        {
+            return Err(WalError::InvalidSize { width, height });
+        }
+
+        let mut mips: [Vec<u8>; MIP_LEVELS] = Default::default();
+        for (level, mip) in mips.iter_mut().enumerate() {
+            let offset = offsets[level];
+            let length = ((width >> level) * (height >> level)) as usize;
+            let start = offset as usize;
+            match bytes.get(start..start + length) {
+                Some(data) if start >= HEADER_SIZE => *mip = data.to_vec(),
+                _ => {
+                    return Err(WalError::InvalidMip {
+                        level,
+                        offset,
+                        length,
+                    })
+                }
+            }
+        }
+
+        Ok(Self {
+            name,
+            width,
+            height,
+            mips,
+            next_name,
+            flags,
+            contents,
+            value,
+        })
+    }
+
+    /// Size in texels of mip level `level`.
+    pub fn mip_size(&self, level: usize) -> (u32, u32) {
+        (self.width >> level, self.height >> level)
+    }
+
+    /// Mip level `level` as 8-bit RGBA through `palette`.
+    pub fn to_rgba(&self, level: usize, palette: &Palette) -> Vec<u8> {
+        self.mips[level]
+            .iter()
+            .flat_map(|&index| palette.rgba(index))
+            .collect()
+    }
+}
+
+fn read_name(cursor: &mut Cursor<&[u8]>) -> io::Result<String> {
+    let mut name = [0u8; NAME_SIZE];
+    cursor.read_exact(&mut name)?;
+    let end = name.iter().position(|&b| b == 0).unwrap_or(NAME_SIZE);
+    Ok(String::from_utf8_lossy(&name[..end]).trim().to_string())
+}
+
+/// The 256-color palette textures index into. Quake 2 takes it from
+/// `pics/colormap.pcx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 256],
+}
+
+impl Palette {
+    /// Reads the palette appended to a 256-color PCX file: a `0x0C` marker
+    /// followed by 256 RGB triples.
+    pub fn from_pcx(bytes: &[u8]) -> Result<Self, WalError> {
+        let start = bytes
+            .len()
+            .checked_sub(PCX_PALETTE_SIZE)
+            .ok_or(WalError::NoPalette)?;
+        let (marker, rgb) = bytes[start..].split_first().ok_or(WalError::NoPalette)?;
+        if *marker != 0x0C {
+            return Err(WalError::NoPalette);
+        }
+        let mut colors = [[0u8; 3]; 256];
+        for (color, rgb) in colors.iter_mut().zip(rgb.chunks_exact(3)) {
+            color.copy_from_slice(rgb);
+        }
+        Ok(Self { colors })
+    }
+
+    /// A ramp from black to white, for previews without the game's palette.
+    pub fn grayscale() -> Self {
+        let mut colors = [[0u8; 3]; 256];
+        for (i, color) in colors.iter_mut().enumerate() {
+            *color = [i as u8; 3];
+        }
+        Self { colors }
+    }
+
+    /// The color of `index`. As in the game's renderer, index 255 is
+    /// transparent.
+    pub fn rgba(&self, index: u8) -> [u8; 4] {
+        let [r, g, b] = self.colors[index as usize];
+        [r, g, b, if index == 255 { 0 } else { 255 }]
+    }
+}
@@ -0,0 +1,92 @@
+use q2_formats::{
+    bsp38::{LumpIndex, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn closed_portals_split_the_area_graph() {
+    // 1 - 2 - 3, with a second route 1 - 3 through portal 3
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_area_portal(1, 2)
+            .with_area_portal(2, 3)
+            .with_area_portal(1, 3)
+            .with_area_portal(4, 5)
+            .build(),
+    )
+    .unwrap();
+    let graph = bsp.area_graph();
+    assert_eq!(graph.num_areas(), 6);
+    assert!(graph.portals(0).is_empty());
+    let from_2: Vec<(u32, u32)> = graph
+        .portals(2)
+        .iter()
+        .map(|p| (p.portal, p.other_area))
+        .collect();
+    assert_eq!(from_2, [(1, 1), (2, 3)]);
+
+    assert!(graph.areas_connected(1, 3, &[]));
+    assert!(graph.areas_connected(1, 3, &[3]));
+    assert!(!graph.areas_connected(1, 3, &[1, 3]));
+    assert!(graph.areas_connected(2, 3, &[1, 3]));
+    assert!(!graph.areas_connected(1, 4, &[]));
+    assert!(graph.areas_connected(4, 4, &[4]));
+    assert!(!graph.areas_connected(4, 5, &[4]));
+}
+
+#[test]
+fn opening_portals_connects_areas() {
+    // 1 - 2 - 3, with a second route 1 - 3 through portal 3
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_area_portal(1, 2)
+            .with_area_portal(2, 3)
+            .with_area_portal(1, 3)
+            .with_area_portal(4, 5)
+            .build(),
+    )
+    .unwrap();
+    assert!(bsp.areas_connected(1, 3));
+    assert!(!bsp.areas_connected(1, 4));
+
+    let mut state = bsp.area_portal_state();
+    assert!(!state.areas_connected(1, 2));
+    assert!(state.areas_connected(0, 2));
+    state.set_portal_open(1, true);
+    state.set_portal_open(2, true);
+    assert!(state.areas_connected(1, 3));
+    state.set_portal_open(2, false);
+    assert!(!state.areas_connected(1, 3));
+    assert!(!state.areas_connected(2, 3));
+    state.set_portal_open(3, true);
+    assert!(state.areas_connected(2, 3));
+    assert!(state.is_portal_open(3));
+    assert!(!state.areas_connected(3, 4));
+
+    // Portals and areas the map doesn't have change nothing
+    state.set_portal_open(9, true);
+    assert!(!state.is_portal_open(9));
+    assert!(!state.areas_connected(1, 9));
+}
+
+#[test]
+fn portal_numbers_from_the_file_are_not_sizes() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_area_portal(1, 2)
+            .build(),
+    )
+    .unwrap();
+    // Both entries of the portal numbered as far out as possible
+    let mut bytes = bsp.bytes.clone();
+    let portals = bsp.lump_table()[LumpIndex::AreaPortals as usize].offset as usize;
+    for entry in [portals, portals + 8] {
+        bytes[entry..entry + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    }
+
+    let mut state = BSP38::from_bytes(bytes).unwrap().area_portal_state();
+    assert!(!state.areas_connected(1, 2));
+    state.set_portal_open(u32::MAX, true);
+    assert!(state.is_portal_open(u32::MAX));
+    assert!(state.areas_connected(1, 2));
+}
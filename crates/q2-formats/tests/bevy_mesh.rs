@@ -0,0 +1,40 @@
+#![cfg(feature = "bevy")]
+
+use bevy_render::mesh::{Mesh, VertexAttributeValues};
+use q2_formats::{
+    bsp38::{prelude::MeshOptions, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn room_converts_to_mesh() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+
+    let mesh = Mesh::from(bsp.read_faces().unwrap());
+    assert_eq!(mesh.count_vertices(), 12 * 3);
+    assert!(mesh.indices().is_none());
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_some());
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_some());
+    assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_1).is_none());
+
+    let options = MeshOptions {
+        colors: false,
+        indexed: true,
+        ..Default::default()
+    };
+    let indexed = bsp.read_faces().unwrap().into_mesh(&options);
+    // Four corners per wall: normals differ between walls, so corners aren't
+    // shared across them
+    assert_eq!(indexed.count_vertices(), 6 * 4);
+    assert_eq!(indexed.indices().map(|i| i.len()), Some(12 * 3));
+    assert!(indexed.attribute(Mesh::ATTRIBUTE_COLOR).is_none());
+
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        indexed.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("positions should be Float32x3");
+    };
+    assert!(positions
+        .iter()
+        .all(|p| p.iter().all(|&c| c == 0.0 || c == 256.0)));
+}
@@ -0,0 +1,669 @@
+//! Round-trip invariants of the BSP38 readers over synthetic maps.
+
+use proptest::prelude::*;
+use q2_formats::{
+    bsp38::{
+        prelude::{Bounds, BspError, FaceBatch, MeshBuilder, ParseOptions, SurfEdge, Validation},
+        surface::SurfaceFlags,
+        LumpIndex, BSP38,
+    },
+    test_utils::{TestMapBuilder, TestTexinfo},
+};
+
+/// A regular polygon of 3 to 8 sides on an arbitrary plane.
+fn arb_polygon() -> impl Strategy<Value = Vec<[f32; 3]>> {
+    (
+        prop::array::uniform3(-1000i32..1000),
+        prop::array::uniform3(-100i32..100),
+        1i32..200,
+        3usize..9,
+    )
+        .prop_filter("degenerate normal", |(_, n, _, _)| {
+            n.iter().any(|&c| c != 0)
+        })
+        .prop_map(|(center, normal, radius, sides)| {
+            let c = center.map(|v| v as f32);
+            let n = normal.map(|v| v as f32);
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            let n = n.map(|v| v / len);
+            // Any vector not parallel to n gives an in-plane basis
+            let a = if n[0].abs() < 0.9 {
+                [1.0, 0.0, 0.0]
+            } else {
+                [0.0, 1.0, 0.0]
+            };
+            let t = normalize(cross(n, a));
+            let b = cross(n, t);
+            (0..sides)
+                .map(|i| {
+                    let angle = i as f32 * std::f32::consts::TAU / sides as f32;
+                    let (s, co) = angle.sin_cos();
+                    let r = radius as f32;
+                    [0, 1, 2].map(|k| c[k] + r * (co * t[k] + s * b[k]))
+                })
+                .collect()
+        })
+}
+
+fn arb_texinfo() -> impl Strategy<Value = TestTexinfo> {
+    (
+        prop::array::uniform3(-2.0f32..2.0),
+        -512.0f32..512.0,
+        prop::array::uniform3(-2.0f32..2.0),
+        -512.0f32..512.0,
+        any::<u32>(),
+        any::<u32>(),
+        "[a-z0-9_]{1,8}/[a-z0-9_]{1,16}",
+        any::<u32>(),
+    )
+        .prop_map(|(u, u0, v, v0, flags, value, texture, next)| TestTexinfo {
+            u,
+            u0,
+            v,
+            v0,
+            flags,
+            value,
+            texture,
+            next,
+        })
+}
+
+fn arb_map() -> impl Strategy<Value = TestMapBuilder> {
+    prop::collection::vec(arb_texinfo(), 1..4).prop_flat_map(|texinfo| {
+        let count = texinfo.len() as u16;
+        prop::collection::vec((arb_polygon(), 0..count, -1i16..4), 1..20).prop_map(move |faces| {
+            let mut builder = TestMapBuilder::new();
+            builder.texinfo = texinfo.clone();
+            for (points, tex, cluster) in faces {
+                builder = builder.with_face(points, tex, cluster);
+            }
+            builder
+        })
+    })
+}
+
+/// Triangles of the faces of `map` that are drawn, so not sky or nodraw.
+fn drawn_triangles(map: &TestMapBuilder, bsp: &BSP38) -> usize {
+    let drawn: Vec<usize> = (0..map.faces.len())
+        .filter(|&k| surface_flags(map, k).is_drawn())
+        .collect();
+    MeshBuilder::new().build_faces(bsp, &drawn).triangle_count()
+}
+
+fn surface_flags(map: &TestMapBuilder, face: usize) -> SurfaceFlags {
+    SurfaceFlags::from_bits_retain(map.texinfo[map.faces[face].texinfo as usize].flags)
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    v.map(|c| c / len)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+proptest! {
+    #[test]
+    fn counts_round_trip(map in arb_map()) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+
+        let faces = bsp.read_face_records().unwrap();
+        prop_assert_eq!(faces.len(), map.faces.len());
+        for (face, expected) in faces.iter().zip(&map.faces) {
+            prop_assert_eq!(face.num_edges as usize, expected.points.len());
+            prop_assert_eq!(face.texinfo, expected.texinfo);
+        }
+
+        let tex_info = bsp.read_texture_info().unwrap();
+        prop_assert_eq!(tex_info.len(), map.texinfo.len());
+        for (t, expected) in tex_info.iter().zip(&map.texinfo) {
+            prop_assert_eq!(&t.texture, &expected.texture);
+            prop_assert_eq!(t.u, expected.u);
+            prop_assert_eq!(t.v, expected.v);
+            prop_assert_eq!(t.flags, expected.flags);
+            prop_assert_eq!(t.value, expected.value);
+            prop_assert_eq!(t.next, expected.next);
+        }
+    }
+
+    #[test]
+    fn bounds_cover_all_vertices(map in arb_map()) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let bounds = bsp.bounds();
+        for i in 0..3 {
+            let coords = map.faces.iter().flat_map(|f| f.points.iter().map(move |p| p[i]));
+            let min = coords.clone().fold(f32::INFINITY, f32::min);
+            let max = coords.fold(f32::NEG_INFINITY, f32::max);
+            prop_assert_eq!(bounds.min[i], min);
+            prop_assert_eq!(bounds.max[i], max);
+        }
+    }
+
+    #[test]
+    fn indices_are_valid(map in arb_map()) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let planes = bsp.read_planes().unwrap();
+        let face_edges = bsp.read_face_edges().unwrap();
+        let num_edges = bsp.read_edges().unwrap().len();
+        let num_tex_info = bsp.read_texture_info().unwrap().len();
+
+        for face in bsp.read_face_records().unwrap() {
+            prop_assert!((face.plane as usize) < planes.len());
+            prop_assert!((face.texinfo as usize) < num_tex_info);
+            let end = face.first_edge as usize + face.num_edges as usize;
+            prop_assert!(end <= face_edges.len());
+            for e in &face_edges[face.first_edge as usize..end] {
+                prop_assert!(e.edge() != 0 && e.edge() < num_edges);
+            }
+        }
+    }
+
+    #[test]
+    fn triangulation_preserves_faces(map in arb_map()) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let data = bsp.read_faces().unwrap();
+
+        let expected: usize = map.faces.iter().map(|f| f.points.len() - 2).sum();
+        prop_assert_eq!(data.triangle_count(), expected);
+        prop_assert_eq!(data.normals.len(), data.points.len());
+        prop_assert_eq!(data.uv.len() / 2, data.points.len() / 3);
+
+        // Faces are emitted in order, as a fan of N - 2 triangles each
+        let mut vertex = 0;
+        for face in &map.faces {
+            let tex = &map.texinfo[face.texinfo as usize];
+            for _ in 0..3 * (face.points.len() - 2) {
+                let p = &data.points[vertex * 3..vertex * 3 + 3];
+                prop_assert!(face.points.iter().any(|q| q == p));
+
+                let n = &data.normals[vertex * 3..vertex * 3 + 3];
+                prop_assert!((dot(n, n) - 1.0).abs() < 1e-3);
+
+                let u = tex.u0 + dot(p, &tex.u);
+                let v = tex.v0 + dot(p, &tex.v);
+                let uv = &data.uv[vertex * 2..vertex * 2 + 2];
+                prop_assert!((uv[0] - u).abs() <= 1e-3 * u.abs().max(1.0));
+                prop_assert!((uv[1] - v).abs() <= 1e-3 * v.abs().max(1.0));
+                vertex += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn batches_cover_every_triangle(map in arb_map(), threshold in 0usize..8) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let mut builder = MeshBuilder::new();
+        let mut batches = builder.build_batches(&bsp, threshold);
+        batches.extend(builder.build_translucent_batches(&bsp));
+
+        let total: usize = batches.iter().map(|b| b.data.triangle_count()).sum();
+        prop_assert_eq!(total, drawn_triangles(&map, &bsp));
+        for batch in &batches {
+            prop_assert!(batch.data.triangle_count() > 0);
+            let matches = |t: &TestTexinfo| {
+                let flags = SurfaceFlags::from_bits_retain(t.flags);
+                t.texture == batch.texture && flags.is_translucent() == batch.translucent
+            };
+            prop_assert!(map.texinfo.iter().any(matches));
+        }
+    }
+
+    #[test]
+    fn inline_models_batch_apart_from_the_world(
+        (map, split) in arb_map().prop_flat_map(|map| {
+            let len = map.faces.len();
+            (Just(map), 0..=len)
+        }),
+        threshold in 0usize..8,
+    ) {
+        let len = map.faces.len();
+        let map = map.with_inline_model(split..len);
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let models = bsp.read_models().unwrap();
+        prop_assert_eq!(models.len(), 2);
+        prop_assert_eq!(models[1].faces(), split..len);
+
+        let mut builder = MeshBuilder::new();
+        let mut world = builder.build_batches(&bsp, threshold);
+        world.extend(builder.build_translucent_batches(&bsp));
+        let inline = builder.build_model_batches(&bsp, 1);
+        prop_assert!(builder.build_model_batches(&bsp, 2).is_empty());
+
+        let drawn: Vec<usize> = (split..len)
+            .filter(|&k| surface_flags(&map, k).is_drawn())
+            .collect();
+        let inline_triangles = MeshBuilder::new().build_faces(&bsp, &drawn).triangle_count();
+        let count = |batches: &[_]| -> usize {
+            batches.iter().map(|b: &FaceBatch| b.data.triangle_count()).sum()
+        };
+        prop_assert_eq!(count(&inline), inline_triangles);
+        prop_assert_eq!(count(&world) + count(&inline), drawn_triangles(&map, &bsp));
+        for batch in &inline {
+            prop_assert_eq!(&batch.clusters, &vec![-1]);
+        }
+        let opaque_first = inline.windows(2).all(|w| w[0].translucent <= w[1].translucent);
+        prop_assert!(opaque_first);
+    }
+
+    #[test]
+    fn faces_by_texture_cover_every_triangle(map in arb_map()) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let by_texture = bsp.read_faces_by_texture().unwrap();
+
+        let total: usize = by_texture.values().map(|data| data.triangle_count()).sum();
+        prop_assert_eq!(total, drawn_triangles(&map, &bsp));
+        for (texture, data) in &by_texture {
+            prop_assert!(data.triangle_count() > 0);
+            prop_assert!(map.texinfo.iter().any(|t| &t.texture == texture));
+        }
+    }
+
+    #[test]
+    fn batches_rebuild_individually(map in arb_map(), threshold in 0usize..8) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let mut builder = MeshBuilder::new();
+        let mut batches = builder.build_batches(&bsp, threshold);
+        batches.extend(builder.build_translucent_batches(&bsp));
+        let keys = builder.face_batch_keys(&bsp);
+
+        for batch in &batches {
+            let (texture, clusters) = (&batch.texture, &batch.clusters);
+            let rebuilt = builder.build_batch(&bsp, texture, clusters, None, batch.translucent, |_| true);
+            prop_assert_eq!(&rebuilt.points, &batch.data.points);
+
+            // Dropping the batch's faces leaves nothing to draw
+            let rebuilt = builder.build_batch(&bsp, texture, clusters, None, batch.translucent, |k| {
+                &keys[k].0 != texture || !clusters.contains(&keys[k].1)
+            });
+            prop_assert_eq!(rebuilt.triangle_count(), 0);
+        }
+    }
+
+    #[test]
+    fn chunked_batches_rebuild_individually(
+        map in arb_map(),
+        threshold in 0usize..8,
+        size in 16.0f32..512.0,
+    ) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let mut builder = MeshBuilder::new().with_chunks(size);
+        let mut batches = builder.build_batches(&bsp, threshold);
+        batches.extend(builder.build_translucent_batches(&bsp));
+
+        let keys = builder.face_batch_keys(&bsp);
+
+        let total: usize = batches.iter().map(|b| b.data.triangle_count()).sum();
+        prop_assert_eq!(total, drawn_triangles(&map, &bsp));
+        for batch in &batches {
+            prop_assert!(batch.chunk.is_some());
+            let rebuilt = builder.build_batch(
+                &bsp,
+                &batch.texture,
+                &batch.clusters,
+                batch.chunk,
+                batch.translucent,
+                |_| true,
+            );
+            prop_assert_eq!(&rebuilt.points, &batch.data.points);
+
+            // The faces keyed to the batch are all of its faces
+            let rebuilt = builder.build_batch(
+                &bsp,
+                &batch.texture,
+                &batch.clusters,
+                batch.chunk,
+                batch.translucent,
+                |k| {
+                    let (texture, cluster, chunk) = &keys[k];
+                    texture != &batch.texture
+                        || !batch.clusters.contains(cluster)
+                        || *chunk != batch.chunk
+                },
+            );
+            prop_assert_eq!(rebuilt.triangle_count(), 0);
+        }
+    }
+
+    #[test]
+    fn face_subsets_match_full_build(map in arb_map(), keep in prop::collection::vec(any::<bool>(), 16)) {
+        let bsp = BSP38::from_bytes(map.build()).unwrap();
+        let all = bsp.read_faces().unwrap();
+        let faces: Vec<usize> = (0..map.faces.len()).filter(|&k| keep[k % keep.len()]).collect();
+        let subset = MeshBuilder::new().build_faces(&bsp, &faces).clone();
+
+        // The subset is the full build with the other faces' fans left out
+        let mut expected = Vec::new();
+        let mut vertex = 0;
+        for (k, face) in map.faces.iter().enumerate() {
+            let n = 3 * (face.points.len() - 2);
+            if faces.contains(&k) {
+                expected.extend_from_slice(&all.points[vertex * 3..(vertex + n) * 3]);
+            }
+            vertex += n;
+        }
+        prop_assert_eq!(subset.points, expected);
+    }
+
+    #[test]
+    fn eager_reads_match_lazy(map in arb_map()) {
+        let lazy = BSP38::from_bytes(map.build()).unwrap();
+        let eager = ParseOptions::new()
+            .validation(Validation::Strict)
+            .eager(&[
+                LumpIndex::Entities,
+                LumpIndex::Planes,
+                LumpIndex::Vertices,
+                LumpIndex::Edges,
+                LumpIndex::Visibility,
+                LumpIndex::Texinfo,
+                LumpIndex::Faces,
+                LumpIndex::FaceEdges,
+            ])
+            .parse(map.build())
+            .unwrap();
+
+        prop_assert_eq!(eager.read_vertices().unwrap(), lazy.read_vertices().unwrap());
+        prop_assert_eq!(eager.read_planes().unwrap(), lazy.read_planes().unwrap());
+        prop_assert_eq!(eager.read_edges().unwrap(), lazy.read_edges().unwrap());
+        prop_assert_eq!(eager.read_face_edges().unwrap(), lazy.read_face_edges().unwrap());
+        prop_assert_eq!(eager.read_entities().unwrap(), lazy.read_entities().unwrap());
+        prop_assert_eq!(eager.read_faces().unwrap().points, lazy.read_faces().unwrap().points);
+        prop_assert_eq!(lazy.decoded_bytes(), 0);
+        prop_assert!(eager.decoded_bytes() >= eager.read_vertices().unwrap().len() * 4);
+    }
+
+    #[test]
+    fn vis_round_trips(rows in (1usize..40).prop_flat_map(|n| {
+        prop::collection::vec(prop::collection::vec(any::<bool>(), n), n)
+    })) {
+        let bsp = BSP38::from_bytes(TestMapBuilder::new().with_vis(rows.clone()).build()).unwrap();
+        let vis = bsp.read_vis_matrix().unwrap();
+        prop_assert_eq!(vis.num_clusters(), rows.len());
+        for (from, row) in rows.iter().enumerate() {
+            for (to, &visible) in row.iter().enumerate() {
+                prop_assert_eq!(vis.is_visible(from, to), visible);
+            }
+        }
+    }
+
+    #[test]
+    fn entities_round_trip(entities in prop::collection::vec((
+        "[a-z_]{1,16}",
+        prop::collection::vec(("[a-z_]{1,8}", "[a-zA-Z0-9 _.-]{0,16}"), 0..5),
+    ), 0..8)) {
+        let mut map = TestMapBuilder::new();
+        for (classname, properties) in &entities {
+            let properties: Vec<(&str, &str)> = properties
+                .iter()
+                .filter(|(k, _)| k != "classname")
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            map = map.with_entity(classname, &properties);
+        }
+
+        let parsed = BSP38::from_bytes(map.build()).unwrap().read_entities().unwrap();
+        prop_assert_eq!(parsed.len(), map.entities.len());
+        for (entity, (classname, properties)) in parsed.iter().zip(&map.entities) {
+            prop_assert_eq!(&entity.classname, classname);
+            prop_assert_eq!(&entity.properties, properties);
+        }
+    }
+}
+
+#[test]
+fn room_has_six_faces() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+    assert_eq!(bsp.read_face_records().unwrap().len(), 6);
+    assert_eq!(bsp.read_faces().unwrap().triangle_count(), 12);
+    assert_eq!(bsp.read_vertices().unwrap().len(), 8 * 3);
+    // Every edge of a closed box is shared by two faces
+    assert_eq!(bsp.read_edges().unwrap().len(), 12 + 1);
+    assert_eq!(bsp.read_edge_points().unwrap().len(), 6 * (12 + 1));
+
+    let bounds = bsp.bounds();
+    assert_eq!(bounds.min, [0.0; 3]);
+    assert_eq!(bounds.max, [256.0; 3]);
+
+    let entities = bsp.read_entities().unwrap();
+    assert_eq!(entities.len(), 1);
+    assert_eq!(entities[0].classname, "worldspawn");
+}
+
+#[test]
+fn edges_to_missing_vertices_are_skipped() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+    // Edge 1 starting at a vertex past the end of the Vertices lump
+    let mut bytes = bsp.bytes.clone();
+    let edge = bsp.lump_table()[LumpIndex::Edges as usize].offset as usize + 4;
+    bytes[edge..edge + 2].copy_from_slice(&u16::MAX.to_le_bytes());
+    let corrupt = BSP38::from_bytes(bytes).unwrap();
+
+    assert_eq!(corrupt.read_edge_points().unwrap().len(), 6 * 12);
+    // The face starting its walk at the missing vertex is dropped
+    assert_eq!(corrupt.read_faces().unwrap().triangle_count(), 10);
+    assert_eq!(corrupt.faces_in_bounds(corrupt.bounds()).len(), 5);
+
+    let edges = corrupt.read_edges().unwrap();
+    assert_eq!(SurfEdge(1).start(&edges), Some(u16::MAX));
+    assert_eq!(SurfEdge(-1).start(&edges), Some(edges[1].v1));
+    assert_eq!(SurfEdge(99).end(&edges), None);
+}
+
+#[test]
+fn strict_validation_rejects_partial_records() {
+    let mut bytes = TestMapBuilder::room([0.0; 3], [64.0; 3]).build();
+    // Shorten the vertices lump by a byte
+    let length_at = 12 + 8 * LumpIndex::Vertices as usize;
+    let length = i32::from_le_bytes(bytes[length_at..length_at + 4].try_into().unwrap());
+    bytes[length_at..length_at + 4].copy_from_slice(&(length - 1).to_le_bytes());
+
+    let strict = ParseOptions::new().validation(Validation::Strict);
+    assert!(matches!(
+        strict.parse(bytes.clone()),
+        Err(BspError::InvalidLumpSize {
+            name: "vertices",
+            ..
+        })
+    ));
+    // Lenient parsing accepts the file, and the lump's reader rejects it
+    let lenient = ParseOptions::new().parse(bytes).unwrap();
+    assert!(matches!(
+        lenient.read_vertices(),
+        Err(BspError::InvalidLumpSize {
+            name: "vertices",
+            ..
+        })
+    ));
+    assert!(lenient.read_faces().is_err());
+    assert_eq!(lenient.read_planes().unwrap().len(), 7);
+
+    assert!(matches!(
+        ParseOptions::new().parse(b"PACK".to_vec()),
+        Err(BspError::Truncated(4))
+    ));
+}
+
+#[test]
+fn from_bytes_returns_errors_for_corrupt_files() {
+    assert!(matches!(
+        BSP38::from_bytes(b"IBSP\x26\0\0\0".to_vec()),
+        Err(BspError::Truncated(8))
+    ));
+    let mut bytes = TestMapBuilder::room([0.0; 3], [64.0; 3]).build();
+    bytes[0..4].copy_from_slice(b"PACK");
+    assert!(matches!(
+        BSP38::from_bytes(bytes),
+        Err(BspError::InvalidMagic(magic)) if &magic == b"PACK"
+    ));
+
+    // A truncated file parses, but the readers of lumps it cut short fail
+    let mut bytes = TestMapBuilder::room([0.0; 3], [64.0; 3]).build();
+    let faces_at = 8 + 8 * LumpIndex::Faces as usize;
+    let faces_offset = i32::from_le_bytes(bytes[faces_at..faces_at + 4].try_into().unwrap());
+    bytes.truncate(faces_offset as usize + 20);
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    assert!(matches!(
+        bsp.read_face_records(),
+        Err(BspError::InvalidLump { name: "faces", offset, .. }) if offset == faces_offset
+    ));
+    assert!(bsp.read_faces().is_err());
+    assert!(bsp.read_faces_by_texture().is_err());
+    // Builders and queries treat them as empty
+    assert!(bsp.read_lightmaps().faces.is_empty());
+}
+
+#[test]
+fn world_batches_leave_out_faces_before_the_world_model() {
+    let mut glass = TestTexinfo::named("e1u1/glass");
+    glass.flags = SurfaceFlags::TRANS33.bits();
+    let quad = vec![
+        [0.0, 0.0, 0.0],
+        [64.0, 0.0, 0.0],
+        [64.0, 64.0, 0.0],
+        [0.0, 64.0, 0.0],
+    ];
+    let mut bytes = TestMapBuilder::new()
+        .with_texinfo(TestTexinfo::named("e1u1/wall"))
+        .with_texinfo(glass)
+        .with_face(quad.clone(), 0, 0)
+        .with_face(quad.clone(), 1, 0)
+        .with_face(quad.clone(), 0, 0)
+        .with_face(quad, 1, 0)
+        .with_inline_model(2..4)
+        .build();
+    // Swap the models' faces, so the inline model's come first
+    let lumps = BSP38::from_bytes(bytes.clone()).unwrap().lump_table();
+    let models = lumps[LumpIndex::Models as usize].offset as usize;
+    for (model, first_face) in [(0, 2u32), (1, 0)] {
+        let at = models + 48 * model + 40;
+        bytes[at..at + 4].copy_from_slice(&first_face.to_le_bytes());
+    }
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+
+    let mut builder = MeshBuilder::new();
+    let count =
+        |batches: &[FaceBatch]| -> usize { batches.iter().map(|b| b.data.triangle_count()).sum() };
+    assert_eq!(count(&builder.build_batches(&bsp, 0)), 2);
+    assert_eq!(count(&builder.build_translucent_batches(&bsp)), 2);
+    assert_eq!(count(&builder.build_model_batches(&bsp, 1)), 4);
+}
+
+#[test]
+fn unique_textures_count_faces_and_merge_flags() {
+    let mut wall = TestTexinfo::named("e1u1/wall");
+    wall.flags = 0x4;
+    let mut sky = TestTexinfo::named("e1u1/sky");
+    sky.flags = 0x10;
+    let mut wall_lit = TestTexinfo::named("e1u1/wall");
+    wall_lit.flags = 0x1;
+
+    let quad = vec![[0.0, 0.0, 0.0], [64.0, 0.0, 0.0], [64.0, 64.0, 0.0]];
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::new()
+            .with_texinfo(wall)
+            .with_texinfo(sky)
+            .with_texinfo(wall_lit)
+            .with_texinfo(TestTexinfo::named("e1u1/unused"))
+            .with_face(quad.clone(), 0, 0)
+            .with_face(quad.clone(), 2, 0)
+            .with_face(quad, 1, 0)
+            .build(),
+    )
+    .unwrap();
+
+    let textures = bsp.unique_textures();
+    let summary: Vec<(&str, usize, u32)> = textures
+        .iter()
+        .map(|t| (t.name.as_str(), t.faces, t.flags))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("e1u1/sky", 1, 0x10),
+            ("e1u1/unused", 0, 0),
+            ("e1u1/wall", 2, 0x5)
+        ]
+    );
+}
+
+#[test]
+fn texture_animations_follow_next_chains() {
+    let frame = |texture: &str, next: u32| TestTexinfo {
+        next,
+        ..TestTexinfo::named(texture)
+    };
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::new()
+            .with_texinfo(frame("e1u1/lava1", 1))
+            .with_texinfo(frame("e1u1/lava2", 2))
+            .with_texinfo(frame("e1u1/lava3", 0))
+            .with_texinfo(frame("e1u1/light1", 4))
+            .with_texinfo(frame("e1u1/light2", 99))
+            .with_texinfo(frame("e1u1/self", 5))
+            .with_texinfo(TestTexinfo::named("e1u1/wall"))
+            .build(),
+    )
+    .unwrap();
+
+    let animations = bsp.texture_animations();
+    let frames =
+        |texture: &str| -> Vec<&str> { animations[texture].iter().map(String::as_str).collect() };
+    assert_eq!(animations.len(), 4);
+    assert_eq!(
+        frames("e1u1/lava1"),
+        ["e1u1/lava1", "e1u1/lava2", "e1u1/lava3"]
+    );
+    assert_eq!(
+        frames("e1u1/lava3"),
+        ["e1u1/lava3", "e1u1/lava1", "e1u1/lava2"]
+    );
+    // A chain may end instead of looping
+    assert_eq!(frames("e1u1/light1"), ["e1u1/light1", "e1u1/light2"]);
+    assert!(!animations.contains_key("e1u1/light2"));
+    assert!(!animations.contains_key("e1u1/self"));
+    assert!(!animations.contains_key("e1u1/wall"));
+}
+
+#[test]
+fn box_queries_find_faces_of_the_touched_rooms() {
+    // Two disjoint rooms, each its own cluster
+    let mut builder = TestMapBuilder::room([0.0; 3], [128.0; 3]);
+    let other = TestMapBuilder::room([256.0, 0.0, 0.0], [384.0, 128.0, 128.0]);
+    for face in other.faces {
+        builder = builder.with_face(face.points, 0, 1);
+    }
+    let bsp = BSP38::from_bytes(builder.build()).unwrap();
+
+    let query = |min: [f32; 3], max: [f32; 3]| {
+        let bounds = Bounds { min, max };
+        (bsp.leafs_in_bounds(bounds), bsp.faces_in_bounds(bounds))
+    };
+    let leaf_clusters = |leafs: &[usize]| -> Vec<i16> {
+        let all = bsp.read_leafs().unwrap();
+        leafs.iter().map(|&l| all[l].cluster).collect()
+    };
+
+    // A box inside the first room reaches none of its walls
+    let (leafs, faces) = query([32.0; 3], [96.0; 3]);
+    assert_eq!(leaf_clusters(&leafs), [0]);
+    assert!(faces.is_empty());
+
+    // The floor of the second room
+    let (leafs, faces) = query([300.0, 32.0, -8.0], [340.0, 96.0, 8.0]);
+    assert_eq!(leaf_clusters(&leafs), [1]);
+    assert_eq!(faces, [6]);
+
+    // Spanning the gap reaches the facing walls of both rooms
+    let (leafs, faces) = query([120.0, 32.0, 32.0], [264.0, 96.0, 96.0]);
+    assert_eq!(leaf_clusters(&leafs), [0, 1]);
+    assert_eq!(faces, [3, 8]);
+}
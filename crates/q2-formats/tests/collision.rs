@@ -0,0 +1,113 @@
+use q2_formats::{
+    bsp38::{
+        contents::{CONTENTS_DETAIL, CONTENTS_SOLID, CONTENTS_WATER, MASK_SOLID},
+        LumpIndex, BSP38,
+    },
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn brushes_cook_into_hulls_and_faces_into_a_welded_mesh() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [128.0; 3])
+            .with_brush([0.0, 0.0, -16.0], [128.0, 128.0, 0.0], CONTENTS_SOLID)
+            .with_brush([0.0, 0.0, 0.0], [128.0, 128.0, 32.0], CONTENTS_WATER)
+            .build(),
+    )
+    .unwrap();
+
+    let models = bsp.model_collision(MASK_SOLID);
+    assert_eq!(models.len(), 1);
+    let world = &models[0];
+
+    // Only the floor brush is solid; its hull is the box
+    assert_eq!(world.hulls.len(), 1);
+    let hull = &world.hulls[0];
+    assert_eq!(hull.contents, CONTENTS_SOLID);
+    assert_eq!(hull.planes.len(), 6);
+    let mut corners = hull.vertices.clone();
+    corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(corners.len(), 8);
+    assert_eq!(corners[0], [0.0, 0.0, -16.0]);
+    assert_eq!(corners[7], [128.0, 128.0, 0.0]);
+
+    // Six quads sharing the eight corners of the room
+    assert_eq!(world.mesh.vertices.len(), 8);
+    assert_eq!(world.mesh.triangles.len(), 12);
+
+    let water = bsp.model_collision(CONTENTS_WATER);
+    assert_eq!(water[0].hulls.len(), 1);
+    assert_eq!(water[0].hulls[0].contents, CONTENTS_WATER);
+}
+
+#[test]
+fn collision_proxy_keeps_the_outside_of_structural_brushes() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [256.0; 3])
+            .with_brush([0.0, 0.0, -16.0], [128.0, 128.0, 0.0], CONTENTS_SOLID)
+            .with_brush([128.0, 0.0, -16.0], [256.0, 128.0, 0.0], CONTENTS_SOLID)
+            .with_brush(
+                [32.0, 32.0, 0.0],
+                [64.0, 64.0, 8.0],
+                CONTENTS_SOLID | CONTENTS_DETAIL,
+            )
+            .with_brush([0.0, 128.0, -16.0], [256.0, 256.0, 0.0], CONTENTS_WATER)
+            .build(),
+    )
+    .unwrap();
+
+    // Two touching slabs, without the sides where they meet
+    let proxy = bsp.collision_proxy(MASK_SOLID);
+    assert_eq!(proxy.triangles.len(), 20);
+    assert_eq!(proxy.vertices.len(), 12);
+    for vertex in &proxy.vertices {
+        assert!(vertex[1] <= 128.0 && vertex[2] <= 0.0);
+    }
+
+    // Triangles wind counterclockwise seen from outside: the top faces up
+    let up = proxy.triangles.iter().any(|t| {
+        let [a, b, c] = t.map(|i| proxy.vertices[i as usize]);
+        let (ab, ac) = ([b[0] - a[0], b[1] - a[1]], [c[0] - a[0], c[1] - a[1]]);
+        a[2] == 0.0 && b[2] == 0.0 && c[2] == 0.0 && ab[0] * ac[1] - ab[1] * ac[0] > 0.0
+    });
+    assert!(up);
+}
+
+#[test]
+fn model_faces_past_the_faces_lump_are_ignored() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [128.0; 3]).build()).unwrap();
+    // The world model claiming billions of faces
+    let mut bytes = bsp.bytes.clone();
+    let num_faces = bsp.lump_table()[LumpIndex::Models as usize].offset as usize + 44;
+    bytes[num_faces..num_faces + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let corrupt = BSP38::from_bytes(bytes).unwrap();
+
+    let models = corrupt.model_collision(MASK_SOLID);
+    assert_eq!(models[0].mesh, bsp.model_collision(MASK_SOLID)[0].mesh);
+}
+
+#[test]
+fn brushes_with_too_many_sides_are_skipped() {
+    // Eleven copies of the same box, 66 sides in all
+    let builder = (0..11).fold(TestMapBuilder::room([0.0; 3], [128.0; 3]), |builder, _| {
+        builder.with_brush([0.0, 0.0, -16.0], [128.0, 128.0, 0.0], CONTENTS_SOLID)
+    });
+    let bsp = BSP38::from_bytes(builder.build()).unwrap();
+    assert_eq!(bsp.model_collision(MASK_SOLID)[0].hulls.len(), 11);
+
+    // The first brush taking all of them, which enclose the same box
+    let mut bytes = bsp.bytes.clone();
+    let num_sides = bsp.lump_table()[LumpIndex::Brushes as usize].offset as usize + 4;
+    bytes[num_sides..num_sides + 4].copy_from_slice(&66u32.to_le_bytes());
+    let corrupt = BSP38::from_bytes(bytes).unwrap();
+    assert_eq!(corrupt.model_collision(MASK_SOLID)[0].hulls.len(), 10);
+
+    // With 64, it has a hull again
+    let mut bytes = bsp.bytes.clone();
+    bytes[num_sides..num_sides + 4].copy_from_slice(&64u32.to_le_bytes());
+    let hulls = BSP38::from_bytes(bytes)
+        .unwrap()
+        .model_collision(MASK_SOLID);
+    assert_eq!(hulls[0].hulls.len(), 11);
+    assert_eq!(hulls[0].hulls[0].planes.len(), 64);
+}
@@ -0,0 +1,45 @@
+use q2_formats::{bsp38::BSP38, test_utils::TestMapBuilder};
+
+#[test]
+fn entities_lump_parses_to_key_values() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [256.0; 3])
+            .with_entity(
+                "info_player_start",
+                &[("origin", "128 64 24"), ("angle", "90")],
+            )
+            .with_entity("trigger_relay", &[("targetname", "t1"), ("delay", "2")])
+            .with_entity("func_door", &[("model", "*2")])
+            .with_entity(
+                "misc_banner",
+                &[("model", "models/objects/banner/tris.md2")],
+            )
+            .build(),
+    )
+    .unwrap();
+    let entities = bsp.read_entities().unwrap();
+    assert_eq!(entities.len(), 5);
+    assert_eq!(entities[0].classname, "worldspawn");
+
+    let start = &entities[1];
+    assert_eq!(start.get("classname"), Some("info_player_start"));
+    assert_eq!(start.origin(), Some([128.0, 64.0, 24.0]));
+    assert_eq!(start.angle(), Some(90.0));
+    assert_eq!(start.targetname(), None);
+    assert_eq!(start.model(), None);
+
+    let relay = &entities[2];
+    assert_eq!(relay.origin(), None);
+    assert_eq!(relay.targetname(), Some("t1"));
+    assert_eq!(relay.get_f32("delay"), Some(2.0));
+    assert_eq!(
+        relay.properties,
+        [
+            ("targetname".to_string(), "t1".to_string()),
+            ("delay".to_string(), "2".to_string())
+        ]
+    );
+
+    assert_eq!(entities[3].model(), Some(2));
+    assert_eq!(entities[4].model(), None);
+}
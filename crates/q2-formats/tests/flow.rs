@@ -0,0 +1,111 @@
+use q2_formats::{
+    bsp38::{contents::CONTENTS_SOLID, LumpIndex, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+/// Two rooms side by side, the second 512 units along X and 64 higher.
+fn two_rooms() -> TestMapBuilder {
+    let mut map = TestMapBuilder::room([0.0; 3], [256.0; 3]);
+    let second = TestMapBuilder::room([512.0, 0.0, 64.0], [768.0, 256.0, 320.0]);
+    for face in second.faces {
+        map = map.with_face(face.points, 0, 0);
+    }
+    map
+}
+
+#[test]
+fn flow_report_walks_from_spawns_to_weapons() {
+    let bytes = TestMapBuilder::room([0.0; 3], [512.0; 3])
+        // A wall across the room, with a gap at the far end
+        .with_brush([240.0, 0.0, -16.0], [272.0, 448.0, 512.0], CONTENTS_SOLID)
+        .with_entity("info_player_deathmatch", &[("origin", "48 48 24")])
+        .with_entity("info_player_deathmatch", &[("origin", "464 464 24")])
+        .with_entity("weapon_railgun", &[("origin", "464 48 16")])
+        .with_entity("weapon_shotgun", &[("origin", "144 48 16")])
+        .with_entity("ammo_slugs", &[("origin", "464 80 16")])
+        .with_entity("item_health", &[("origin", "48 464 16")])
+        .build();
+    let report = BSP38::from_bytes(bytes).unwrap().flow_report();
+
+    let pickups = &report.areas[&0];
+    assert_eq!((pickups.spawns, pickups.weapons), (2, 2));
+    assert_eq!((pickups.ammo, pickups.items, pickups.keys), (1, 1, 0));
+
+    let first = &report.spawns[0];
+    let (weapon, distance) = first.nearest_weapon.clone().unwrap();
+    assert_eq!((weapon.as_str(), distance), ("weapon_shotgun", 96.0));
+    // The railgun is behind the wall: around it, not through it
+    let around = first.average_weapon_distance.unwrap() * 2.0 - 96.0;
+    assert!(around > 2.0 * 416.0, "{}", around);
+
+    let second = &report.spawns[1];
+    assert_eq!(second.nearest_weapon.as_ref().unwrap().0, "weapon_railgun");
+    assert_eq!(report.connectivity.components, 1);
+    assert_eq!(report.connectivity.stranded_spawns, 0);
+}
+
+#[test]
+fn flow_report_finds_separate_regions() {
+    let bytes = two_rooms()
+        .with_entity("info_player_deathmatch", &[("origin", "48 48 24")])
+        .with_entity("info_player_deathmatch", &[("origin", "560 48 88")])
+        .with_entity("weapon_railgun", &[("origin", "208 48 16")])
+        .build();
+    let report = BSP38::from_bytes(bytes).unwrap().flow_report();
+
+    let connectivity = &report.connectivity;
+    assert_eq!(connectivity.nav_nodes, 2 * 8 * 8);
+    assert_eq!(connectivity.components, 2);
+    assert_eq!(connectivity.largest_component, 0.5);
+    assert_eq!(connectivity.stranded_spawns, 1);
+    assert_eq!(report.spawns[1].nearest_weapon, None);
+    assert_eq!(report.average_spawn_to_weapon, Some(160.0));
+}
+
+#[test]
+fn nav_grid_stays_small_for_huge_coordinates() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+    let scale = |bytes: &mut [u8], lump: LumpIndex, start: usize, count: usize| {
+        let info = bsp.lump_table()[lump as usize];
+        let data = &mut bytes[info.offset as usize..(info.offset + info.length) as usize];
+        for record in data.chunks_exact_mut(4).skip(start).take(count) {
+            let value = f32::from_le_bytes(record.try_into().unwrap()) * 1e6;
+            record.copy_from_slice(&value.to_le_bytes());
+        }
+    };
+
+    // Floors millions of cells wide, cut to the world model's box, give or
+    // take a cell
+    let mut bytes = bsp.bytes.clone();
+    scale(&mut bytes, LumpIndex::Vertices, 0, usize::MAX);
+    let nav = BSP38::from_bytes(bytes.clone()).unwrap().nav_grid(32.0);
+    assert!(nav.nodes().len() <= 9 * 9);
+
+    // And with the world model's box as large, given up on
+    scale(&mut bytes, LumpIndex::Models, 0, 6);
+    let nav = BSP38::from_bytes(bytes).unwrap().nav_grid(32.0);
+    assert!(nav.nodes().is_empty());
+
+    // Even when the floors span every cell the grid can number
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([-256.0; 3], [256.0; 3]).build()).unwrap();
+    let mut bytes = bsp.bytes.clone();
+    for _ in 0..2 {
+        scale(&mut bytes, LumpIndex::Vertices, 0, usize::MAX);
+        scale(&mut bytes, LumpIndex::Models, 0, 6);
+    }
+    let nav = BSP38::from_bytes(bytes).unwrap().nav_grid(32.0);
+    assert!(nav.nodes().is_empty());
+}
+
+#[test]
+fn nav_grid_skips_world_faces_past_the_faces_lump() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [256.0; 3]).build()).unwrap();
+    let mut bytes = bsp.bytes.clone();
+    // The world model's first_face and num_faces, billions of faces long
+    let offset = bsp.lump_table()[LumpIndex::Models as usize].offset as usize;
+    bytes[offset + 40..offset + 44].copy_from_slice(&(1u32 << 31).to_le_bytes());
+    bytes[offset + 44..offset + 48].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let nav = BSP38::from_bytes(bytes).unwrap().nav_grid(32.0);
+    assert!(nav.nodes().is_empty());
+}
@@ -0,0 +1,105 @@
+use q2_formats::{
+    bsp38::{prelude::MeshBuilder, LumpIndex, BSP38},
+    test_utils::{lightmap_color, TestMapBuilder},
+};
+
+fn room(lightmaps: bool) -> BSP38 {
+    let builder = TestMapBuilder::room([0.0; 3], [256.0; 3]);
+    let builder = match lightmaps {
+        true => builder.with_lightmaps(),
+        false => builder,
+    };
+    BSP38::from_bytes(builder.build()).unwrap()
+}
+
+#[test]
+fn lightmaps_are_packed_without_overlap() {
+    let atlas = room(true).read_lightmaps();
+    assert_eq!(atlas.pages.len(), 1);
+    let size = atlas.page_size as usize;
+    assert_eq!(atlas.pages[0].len(), size * size * 4);
+    // The white corner
+    assert_eq!(atlas.pages[0][..4], [255; 4]);
+
+    let lightmaps: Vec<_> = atlas.faces.iter().map(|face| face.unwrap()).collect();
+    // The floor spans 256 texels on both axes: 16 luxels plus one
+    assert_eq!((lightmaps[0].width, lightmaps[0].height), (17, 17));
+    assert_eq!((lightmaps[0].min_s, lightmaps[0].min_t), (0.0, 0.0));
+
+    let mut owner = vec![None; size * size];
+    for (k, lightmap) in lightmaps.iter().enumerate() {
+        let [r, g, b] = lightmap_color(k);
+        for y in lightmap.y..lightmap.y + lightmap.height {
+            for x in lightmap.x..lightmap.x + lightmap.width {
+                let i = y as usize * size + x as usize;
+                assert_eq!(owner[i], None, "face {} overlaps another face", k);
+                assert_ne!(i, 0, "face {} covers the white corner", k);
+                owner[i] = Some(k);
+                assert_eq!(atlas.pages[0][i * 4..i * 4 + 4], [r, g, b, 255]);
+            }
+        }
+    }
+}
+
+#[test]
+fn lightmap_uvs_hit_luxel_centers() {
+    let bsp = room(true);
+    let atlas = bsp.read_lightmaps();
+    let floor = atlas.faces[0].unwrap();
+    let size = atlas.page_size as f32;
+    assert_eq!(
+        floor.uv(0.0, 0.0),
+        [(floor.x as f32 + 0.5) / size, (floor.y as f32 + 0.5) / size]
+    );
+    assert_eq!(
+        floor.uv(256.0, 256.0),
+        [
+            (floor.x as f32 + 16.5) / size,
+            (floor.y as f32 + 16.5) / size
+        ]
+    );
+
+    let data = MeshBuilder::new()
+        .with_lightmaps(&atlas)
+        .build(&bsp)
+        .clone();
+    assert_eq!(data.uv1.len(), data.uv.len());
+    // The first triangles belong to the floor
+    for (uv, uv1) in data.uv.chunks(2).zip(data.uv1.chunks(2)).take(6) {
+        assert_eq!(uv1, floor.uv(uv[0], uv[1]));
+    }
+}
+
+#[test]
+fn faces_without_lighting_are_unlit() {
+    let bsp = room(false);
+    let atlas = bsp.read_lightmaps();
+    assert!(atlas.faces.iter().all(Option::is_none));
+    assert_eq!(atlas.pages.len(), 1);
+
+    let data = MeshBuilder::new()
+        .with_lightmaps(&atlas)
+        .build(&bsp)
+        .clone();
+    assert!(data
+        .uv1
+        .chunks(2)
+        .all(|uv1| uv1 == atlas.unlit_uv().as_slice()));
+    assert!(bsp.read_faces().unwrap().uv1.is_empty());
+}
+
+#[test]
+fn faces_with_empty_lightmaps_are_skipped() {
+    let bsp = room(true);
+    // Texture offsets at infinity leave the faces' extents without luxels
+    let mut bytes = bsp.bytes.clone();
+    let tex_info = bsp.lump_table()[LumpIndex::Texinfo as usize];
+    for texinfo in (0..tex_info.length as usize / 76).map(|i| tex_info.offset as usize + 76 * i) {
+        bytes[texinfo + 12..texinfo + 16].copy_from_slice(&f32::INFINITY.to_le_bytes());
+    }
+    let corrupt = BSP38::from_bytes(bytes).unwrap();
+
+    let atlas = corrupt.read_lightmaps();
+    assert_eq!(atlas.faces.len(), 6);
+    assert!(atlas.faces.iter().all(Option::is_none));
+}
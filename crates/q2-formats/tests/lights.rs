@@ -0,0 +1,60 @@
+use q2_formats::bsp38::prelude::{extract_lights, extract_sun, parse_entities};
+
+const ENTITIES: &str = r#"
+{ "classname" "worldspawn" }
+{ "classname" "light" "origin" "0 0 64" }
+{ "classname" "light" "origin" "0 0 64" "light" "200" "_color" "1 0.5 0" "style" "32" }
+{ "classname" "light" "origin" "0 0 0" "target" "spot_target" "_cone" "20" }
+{ "classname" "info_null" "targetname" "spot_target" "origin" "0 100 0" }
+{ "classname" "light_spot" "origin" "0 0 0" "angle" "-2" }
+{ "classname" "light" }
+"#;
+
+#[test]
+fn lights_follow_qrad_defaults() {
+    let lights = extract_lights(&parse_entities(ENTITIES));
+    // The last light has no origin
+    assert_eq!(lights.len(), 4);
+
+    assert_eq!(lights[0].entity, 1);
+    assert_eq!(lights[0].value, 300.0);
+    assert_eq!(lights[0].color, [1.0; 3]);
+    assert_eq!(lights[0].spot, None);
+    assert!((lights[0].lumens() - 800.0).abs() < 0.01);
+
+    assert_eq!(lights[1].value, 200.0);
+    assert_eq!(lights[1].range(), 200.0);
+    assert_eq!(lights[1].color, [1.0, 0.5, 0.0]);
+    assert_eq!(lights[1].style, 32);
+
+    let spot = lights[2].spot.unwrap();
+    assert_eq!(spot.direction, [0.0, 1.0, 0.0]);
+    assert_eq!(spot.cone, 20.0);
+
+    let spot = lights[3].spot.unwrap();
+    assert_eq!(spot.direction, [0.0, 0.0, -1.0]);
+    assert_eq!(spot.cone, 10.0);
+}
+
+#[test]
+fn sun_comes_from_worldspawn_keys() {
+    assert_eq!(extract_sun(&parse_entities(ENTITIES)), None);
+
+    let sun = extract_sun(&parse_entities(
+        r#"{ "classname" "worldspawn" "_sun_vector" "0 3 -4" "_sun_light" "150" "_sun_color" "1 0.5 0.5" }"#,
+    ))
+    .unwrap();
+    assert_eq!(sun.direction, [0.0, 0.6, -0.8]);
+    assert_eq!(sun.value, 150.0);
+    assert_eq!(sun.color, [1.0, 0.5, 0.5]);
+
+    // Yaw 90, 45 degrees down
+    let sun = extract_sun(&parse_entities(
+        r#"{ "classname" "worldspawn" "_sun_angle" "90 -45" }"#,
+    ))
+    .unwrap();
+    let expected = [0.0, 0.5f32.sqrt(), -(0.5f32.sqrt())];
+    for (got, want) in sun.direction.iter().zip(expected) {
+        assert!((got - want).abs() < 1e-6);
+    }
+}
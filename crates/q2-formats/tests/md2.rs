@@ -0,0 +1,166 @@
+use glam::Vec3;
+use q2_formats::md2::{Md2, Md2Animation, Md2Error, Md2Frame};
+
+/// An .md2 of a unit square in the XY plane, facing up, as two clockwise
+/// triangles over four vertices and five texture coordinates, so one vertex
+/// has two. The second frame lifts the square by 2 units.
+fn md2_bytes() -> Vec<u8> {
+    let (num_skins, num_vertices, num_tex_coords, num_triangles, num_frames) = (1, 4, 5, 2, 2);
+    let frame_size = 40 + 4 * num_vertices;
+    let ofs_skins = 68;
+    let ofs_tex_coords = ofs_skins + 64 * num_skins;
+    let ofs_triangles = ofs_tex_coords + 4 * num_tex_coords;
+    let ofs_frames = ofs_triangles + 12 * num_triangles;
+    let ofs_end = ofs_frames + frame_size * num_frames;
+
+    let mut bytes = b"IDP2".to_vec();
+    let header: [i32; 16] = [
+        8,
+        64,
+        32,
+        frame_size,
+        num_skins,
+        num_vertices,
+        num_tex_coords,
+        num_triangles,
+        0,
+        num_frames,
+        ofs_skins,
+        ofs_tex_coords,
+        ofs_triangles,
+        ofs_frames,
+        ofs_end,
+        ofs_end,
+    ];
+    for value in header {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut skin = [0u8; 64];
+    skin[..27].copy_from_slice(b"models/items/armor/skin.pcx");
+    bytes.extend_from_slice(&skin);
+    for st in [[0i16, 0], [32, 0], [32, 16], [0, 16], [64, 32]] {
+        for value in st {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    // Clockwise seen from above; vertex 3 takes texture coordinate 4 in the
+    // second triangle
+    for indices in [[0u16, 3, 1, 0, 3, 1], [1, 3, 2, 1, 4, 2]] {
+        for value in indices {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for (name, z) in [(b"stand01", 0.0f32), (b"stand02", 2.0)] {
+        for value in [0.5f32, 0.5, 1.0, 0.0, 0.0, z] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut frame_name = [0u8; 16];
+        frame_name[..7].copy_from_slice(name);
+        bytes.extend_from_slice(&frame_name);
+        for [x, y] in [[0u8, 0], [2, 0], [2, 2], [0, 2]] {
+            bytes.extend_from_slice(&[x, y, 0, 5]);
+        }
+    }
+    assert_eq!(bytes.len(), ofs_end as usize);
+    bytes
+}
+
+#[test]
+fn md2_reads_header_skins_and_frames() {
+    let md2 = Md2::from_bytes(&md2_bytes()).unwrap();
+    assert_eq!((md2.skin_width, md2.skin_height), (64, 32));
+    assert_eq!(md2.skins, ["models/items/armor/skin.pcx"]);
+    assert_eq!(md2.tex_coords.len(), 5);
+    assert_eq!(md2.triangles[1].tex_coords, [1, 4, 2]);
+    assert_eq!(md2.frames.len(), 2);
+    assert_eq!(md2.frames[1].name, "stand02");
+    assert_eq!(md2.frames[1].positions[2], Vec3::new(1.0, 1.0, 2.0));
+    assert_eq!(md2.frames[0].normal_indices, [5; 4]);
+    assert_eq!(md2.animations(), [("stand".to_string(), 0..2)]);
+}
+
+#[test]
+fn md2_mesh_splits_vertices_by_texture_coordinate() {
+    let md2 = Md2::from_bytes(&md2_bytes()).unwrap();
+    let layout = md2.mesh_layout();
+    assert_eq!(layout.vertices.len(), 5);
+    assert_eq!(layout.indices.len(), 6);
+
+    let positions = md2.mesh_positions(1, &layout);
+    let uvs = md2.mesh_uvs(&layout);
+    assert!(positions.iter().all(|p| p[2] == 2.0));
+    assert!(uvs.contains(&[1.0, 1.0]) && uvs.contains(&[0.5, 0.5]));
+
+    // Turned counter-clockwise, so the square faces up
+    for triangle in layout.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+        assert!((b - a).cross(c - a).z > 0.0);
+    }
+    assert!(md2
+        .mesh_normals(0, &layout)
+        .iter()
+        .all(|&n| Vec3::from(n).abs_diff_eq(Vec3::Z, 1e-6)));
+}
+
+#[test]
+fn md2_finds_named_animations() {
+    let frame = |name: &str| Md2Frame {
+        name: name.to_string(),
+        positions: vec![],
+        normal_indices: vec![],
+    };
+    let mut md2 = Md2 {
+        skin_width: 1,
+        skin_height: 1,
+        skins: vec![],
+        tex_coords: vec![],
+        triangles: vec![],
+        frames: ["pain101", "pain102", "pain201", "run1", "run2", "run10"]
+            .map(frame)
+            .to_vec(),
+    };
+    assert_eq!(md2.animation_frames(Md2Animation::Pain), Some(0..2));
+    assert_eq!(md2.animation_frames(Md2Animation::Run), Some(3..6));
+    assert_eq!(md2.animation_frames(Md2Animation::Stand), None);
+
+    // Unnamed frames fall back to where a player model has the animation
+    md2.frames = (0..200).map(|i| frame(&format!("frame{}", i))).collect();
+    assert_eq!(md2.animation_frames(Md2Animation::Death), Some(178..184));
+}
+
+#[test]
+fn md2_rejects_bad_files() {
+    let bytes = md2_bytes();
+    assert!(matches!(
+        Md2::from_bytes(b"IBSP\x26\0\0\0"),
+        Err(Md2Error::InvalidMagic(_))
+    ));
+
+    let mut version = bytes.clone();
+    version[4] = 7;
+    assert!(matches!(
+        Md2::from_bytes(&version),
+        Err(Md2Error::UnsupportedVersion(7))
+    ));
+
+    let mut truncated = bytes.clone();
+    truncated.truncate(bytes.len() - 1);
+    assert!(matches!(
+        Md2::from_bytes(&truncated),
+        Err(Md2Error::InvalidSection { name: "Frames", .. })
+    ));
+
+    // Vertex index 9 in the first triangle
+    let mut index = bytes.clone();
+    let ofs_triangles = 68 + 64 + 4 * 5;
+    index[ofs_triangles] = 9;
+    assert!(matches!(
+        Md2::from_bytes(&index),
+        Err(Md2Error::InvalidIndex {
+            triangle: 0,
+            index: 9,
+            count: 4
+        })
+    ));
+}
@@ -0,0 +1,89 @@
+use q2_formats::{
+    bsp38::{prelude::FaceMetric, LumpIndex, BSP38},
+    test_utils::{TestMapBuilder, TestTexinfo},
+};
+
+#[test]
+fn face_metrics_measure_each_face() {
+    // A room in cluster 0, and a small floor at half the texture density in
+    // cluster 1, which sees the room but not the other way around
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [128.0; 3])
+            .with_texinfo(TestTexinfo {
+                u: [0.5, 0.0, 0.0],
+                v: [0.0, 0.5, 0.0],
+                ..TestTexinfo::named("e1u1/floor1_3")
+            })
+            .with_face(
+                vec![
+                    [200.0, 0.0, 0.0],
+                    [264.0, 0.0, 0.0],
+                    [264.0, 64.0, 0.0],
+                    [200.0, 64.0, 0.0],
+                ],
+                1,
+                1,
+            )
+            .with_vis(vec![vec![true, false], vec![true, true]])
+            .build(),
+    )
+    .unwrap();
+    let metric = |metric| bsp.face_metric(metric);
+
+    let area = metric(FaceMetric::Area);
+    assert_eq!(area[..6], [16384.0; 6]);
+    assert_eq!(area[6], 4096.0);
+
+    let scale = metric(FaceMetric::TextureScale);
+    assert_eq!(scale, [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0]);
+
+    assert_eq!(
+        metric(FaceMetric::Cluster),
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]
+    );
+    assert_eq!(
+        metric(FaceMetric::Overdraw),
+        [6.0, 6.0, 6.0, 6.0, 6.0, 6.0, 7.0]
+    );
+
+    // 9x9 luxels over the floor, 4x3 over the small face
+    let density = metric(FaceMetric::LuxelDensity);
+    assert_eq!(density[0], 81.0 / 16384.0);
+    assert_eq!(density[6], 12.0 / 4096.0);
+}
+
+#[test]
+fn overdraw_counts_clusters_past_the_vis_matrix_as_seen() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [128.0; 3])
+            .with_face(
+                vec![
+                    [200.0, 0.0, 0.0],
+                    [264.0, 0.0, 0.0],
+                    [264.0, 64.0, 0.0],
+                    [200.0, 64.0, 0.0],
+                ],
+                0,
+                1,
+            )
+            .with_vis(vec![vec![true, false], vec![true, true]])
+            .build(),
+    )
+    .unwrap();
+
+    // Moves the small floor's leaf to the last cluster a leaf can name
+    let mut bytes = bsp.bytes.clone();
+    let offset = bsp.lump_table()[LumpIndex::Leafs as usize].offset as usize;
+    let leaf = bsp
+        .read_leafs()
+        .unwrap()
+        .iter()
+        .position(|leaf| leaf.cluster == 1);
+    let cluster = offset + 28 * leaf.unwrap() + 4;
+    bytes[cluster..cluster + 2].copy_from_slice(&i16::MAX.to_le_bytes());
+
+    let overdraw = BSP38::from_bytes(bytes)
+        .unwrap()
+        .face_metric(FaceMetric::Overdraw);
+    assert_eq!(overdraw, [7.0; 7]);
+}
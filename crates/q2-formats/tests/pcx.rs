@@ -0,0 +1,61 @@
+use q2_formats::pcx::{Pcx, PcxError};
+
+/// A 3x2 .pcx with 4 bytes per line: a run of three 7s and 200 as the
+/// padding byte, which needs a run of one as it has both top bits set, then
+/// literal 1, 2 and 3 and a padding byte.
+fn pcx_bytes() -> Vec<u8> {
+    let mut bytes = vec![0x0A, 5, 1, 8];
+    for value in [0u16, 0, 2, 1, 72, 72] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.resize(65, 0);
+    bytes.push(1);
+    bytes.extend_from_slice(&4u16.to_le_bytes());
+    bytes.resize(128, 0);
+    bytes.extend_from_slice(&[0xC3, 7, 0xC1, 200, 1, 2, 3, 0]);
+    bytes.push(0x0C);
+    for i in 0..=255u8 {
+        bytes.extend_from_slice(&[i, i, 255 - i]);
+    }
+    bytes
+}
+
+#[test]
+fn pcx_decodes_runs_and_palette() {
+    let pcx = Pcx::from_bytes(&pcx_bytes()).unwrap();
+    assert_eq!((pcx.width, pcx.height), (3, 2));
+    assert_eq!(pcx.pixels, [7, 7, 7, 1, 2, 3]);
+    let rgba = pcx.to_rgba();
+    assert_eq!(rgba.len(), 3 * 2 * 4);
+    assert_eq!(&rgba[12..16], &[1, 1, 254, 255]);
+}
+
+#[test]
+fn pcx_rejects_bad_files() {
+    let bytes = pcx_bytes();
+    assert!(matches!(
+        Pcx::from_bytes(b"IDP2"),
+        Err(PcxError::InvalidMagic(b'I'))
+    ));
+
+    let mut planes = bytes.clone();
+    planes[65] = 3;
+    assert!(matches!(
+        Pcx::from_bytes(&planes),
+        Err(PcxError::Unsupported { planes: 3, .. })
+    ));
+
+    let mut truncated = bytes[..128 + 5].to_vec();
+    truncated.extend_from_slice(&bytes[bytes.len() - 769..]);
+    assert!(matches!(
+        Pcx::from_bytes(&truncated),
+        Err(PcxError::Truncated(5))
+    ));
+
+    let mut no_palette = bytes.clone();
+    no_palette.truncate(bytes.len() - 769);
+    assert!(matches!(
+        Pcx::from_bytes(&no_palette),
+        Err(PcxError::NoPalette)
+    ));
+}
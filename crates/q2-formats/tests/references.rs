@@ -0,0 +1,46 @@
+use q2_formats::{
+    bsp38::{prelude::BrokenReference, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn references_of_built_maps_are_in_range() {
+    let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+        .with_brush([0.0; 3], [64.0; 3], 1)
+        .with_vis(vec![vec![true]])
+        .with_area_portal(1, 2)
+        .with_inline_model(0..2)
+        .build();
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    assert_eq!(bsp.check_references(), []);
+}
+
+#[test]
+fn references_past_the_end_are_reported() {
+    let mut bytes = TestMapBuilder::room([0.0; 3], [256.0; 3]).build();
+    let bsp = BSP38::from_bytes(bytes.clone()).unwrap();
+    let edges = bsp.read_edges().unwrap().len();
+    let lump = bsp
+        .lump_table()
+        .into_iter()
+        .find(|lump| lump.name == "face_edges")
+        .unwrap();
+    // The second face edge goes backwards along an edge that isn't there
+    let at = lump.offset as usize + 4;
+    bytes[at..at + 4].copy_from_slice(&(-(edges as i32) - 5).to_le_bytes());
+
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    let broken = bsp.check_references();
+    let expected = BrokenReference {
+        lump: "face_edges",
+        index: 1,
+        target: "edges",
+        value: edges as i64 + 5,
+        count: edges,
+    };
+    assert_eq!(
+        expected.to_string(),
+        format!("face_edges[1] refers to edges {}, of {}", edges + 5, edges)
+    );
+    assert_eq!(broken, [expected]);
+}
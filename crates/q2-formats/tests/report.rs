@@ -0,0 +1,59 @@
+use q2_formats::{
+    bsp38::{analyze, surface::SURF_SKY},
+    test_utils::{TestMapBuilder, TestTexinfo},
+};
+
+#[test]
+fn report_summarizes_the_map() {
+    let sky = TestTexinfo {
+        flags: SURF_SKY,
+        ..TestTexinfo::named("e1u1/sky1")
+    };
+    let bytes = TestMapBuilder::new()
+        .with_texinfo(TestTexinfo::named("e1u1/floor1_3"))
+        .with_texinfo(sky)
+        .with_face(vec![[0.0; 3], [64.0, 0.0, 0.0], [64.0, 64.0, 0.0]], 0, 0)
+        .with_face(
+            vec![[0.0, 0.0, 8.0], [64.0, 0.0, 8.0], [64.0, 64.0, 8.0]],
+            0,
+            1,
+        )
+        .with_face(
+            vec![[-512.0, 0.0, 0.0], [512.0, 0.0, 0.0], [512.0, 0.0, 512.0]],
+            1,
+            1,
+        )
+        .with_vis(vec![vec![true, false], vec![true, true]])
+        .with_entity("worldspawn", &[("message", "The Edge")])
+        .with_entity("info_player_deathmatch", &[])
+        .with_entity("info_player_deathmatch", &[])
+        .build();
+    let size = bytes.len();
+    let report = analyze(bytes).unwrap();
+
+    assert_eq!(report.size, size);
+    assert_eq!(report.stats.faces, 3);
+    assert_eq!(report.stats.triangles, 3);
+    assert_eq!(report.stats.clusters, 2);
+    assert_eq!(report.stats.entities, 3);
+    assert_eq!(report.title.as_deref(), Some("The Edge"));
+    assert_eq!(report.classnames["info_player_deathmatch"], 2);
+    assert_eq!(report.textures.len(), 2);
+
+    // Cluster 0 sees its own face, cluster 1 all three
+    assert_eq!(report.vis.average_visible_clusters, 1.5);
+    assert_eq!(report.vis.average_drawn_faces, 2.0);
+    assert_eq!(report.vis.worst_cluster, Some((1, 3)));
+
+    assert_eq!(report.bounds.min, [-512.0, 0.0, 0.0]);
+    assert_eq!(report.view_bounds.min, [0.0; 3]);
+    assert_eq!(report.view_bounds.max, [64.0, 64.0, 8.0]);
+}
+
+#[test]
+fn maps_without_vis_draw_everything() {
+    let report = analyze(TestMapBuilder::room([0.0; 3], [64.0; 3]).build()).unwrap();
+    assert_eq!(report.vis.average_drawn_faces, 6.0);
+    assert_eq!(report.vis.worst_cluster, None);
+    assert!(analyze(b"not a map".to_vec()).is_err());
+}
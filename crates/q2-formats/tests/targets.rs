@@ -0,0 +1,48 @@
+use q2_formats::bsp38::prelude::{parse_entities, Link, LinkKind, TargetGraph};
+
+const ENTITIES: &str = r#"
+{ "classname" "worldspawn" }
+{ "classname" "trigger_once" "target" "door1" "killtarget" "msg" }
+{ "classname" "func_door" "targetname" "door1" }
+{ "classname" "func_door" "targetname" "door1" }
+{ "classname" "target_speaker" "targetname" "msg" }
+{ "classname" "path_corner" "targetname" "p1" "target" "p2" "pathtarget" "missing" }
+{ "classname" "path_corner" "targetname" "p2" "target" "p1" }
+"#;
+
+#[test]
+fn links_resolve_to_every_named_entity() {
+    let entities = parse_entities(ENTITIES);
+    let graph = TargetGraph::new(&entities);
+
+    let from_trigger: Vec<&Link> = graph.targets_of(1).collect();
+    assert_eq!(
+        from_trigger,
+        [
+            &Link {
+                from: 1,
+                to: 2,
+                kind: LinkKind::Target
+            },
+            &Link {
+                from: 1,
+                to: 3,
+                kind: LinkKind::Target
+            },
+            &Link {
+                from: 1,
+                to: 4,
+                kind: LinkKind::KillTarget
+            },
+        ]
+    );
+    assert_eq!(graph.named("door1"), [2, 3]);
+    assert_eq!(graph.sources_of(5).map(|l| l.from).collect::<Vec<_>>(), [6]);
+    assert_eq!(graph.links().len(), 5);
+
+    let unresolved = graph.unresolved();
+    assert_eq!(unresolved.len(), 1);
+    assert_eq!(unresolved[0].from, 5);
+    assert_eq!(unresolved[0].kind, LinkKind::PathTarget);
+    assert_eq!(unresolved[0].name, "missing");
+}
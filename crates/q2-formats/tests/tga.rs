@@ -0,0 +1,52 @@
+use q2_formats::tga::{Tga, TgaError};
+
+fn header(image_type: u8, width: u16, height: u16, bits: u8, descriptor: u8) -> Vec<u8> {
+    // A two byte image id, skipped
+    let mut bytes = vec![2, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    for value in [width, height] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[bits, descriptor, b'i', b'd']);
+    bytes
+}
+
+#[test]
+fn tga_reads_raw_bottom_up_images() {
+    // BGR, bottom row first: red and green under blue and white
+    let mut bytes = header(2, 2, 2, 24, 0);
+    bytes.extend_from_slice(&[0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255]);
+    let tga = Tga::from_bytes(&bytes).unwrap();
+    assert_eq!((tga.width, tga.height), (2, 2));
+    assert_eq!(
+        tga.rgba,
+        [0, 0, 255, 255, 255, 255, 255, 255, 255, 0, 0, 255, 0, 255, 0, 255]
+    );
+
+    bytes.truncate(bytes.len() - 3);
+    assert!(matches!(
+        Tga::from_bytes(&bytes),
+        Err(TgaError::Truncated(3))
+    ));
+}
+
+#[test]
+fn tga_reads_run_length_encoded_top_down_images() {
+    // A run of three translucent reds across the rows, then one raw gray
+    let mut bytes = header(10, 2, 2, 32, 0x28);
+    bytes.extend_from_slice(&[0x82, 0, 0, 255, 128, 0x00, 9, 9, 9, 255]);
+    let tga = Tga::from_bytes(&bytes).unwrap();
+    assert_eq!(&tga.rgba[..4], &[255, 0, 0, 128]);
+    assert_eq!(&tga.rgba[8..12], &[255, 0, 0, 128]);
+    assert_eq!(&tga.rgba[12..], &[9, 9, 9, 255]);
+
+    let grayscale = [header(11, 3, 1, 8, 0x20), vec![0x82, 77]].concat();
+    assert_eq!(
+        Tga::from_bytes(&grayscale).unwrap().rgba,
+        [77, 77, 77, 255].repeat(3)
+    );
+
+    assert!(matches!(
+        Tga::from_bytes(&header(1, 2, 2, 8, 0)),
+        Err(TgaError::Unsupported { image_type: 1, .. })
+    ));
+}
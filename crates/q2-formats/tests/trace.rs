@@ -0,0 +1,145 @@
+use q2_formats::{
+    bsp38::{
+        contents::{CONTENTS_SOLID, CONTENTS_WATER, MASK_SOLID},
+        prelude::OcclusionOptions,
+        LumpIndex, BSP38,
+    },
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn traces_stop_at_the_solid_leaf_under_the_floor() {
+    // The single room leaf has the solid leaf below z = -1
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [128.0; 3]).build()).unwrap();
+    let tracer = bsp.tracer();
+
+    let down = tracer.trace_line([64.0, 64.0, 63.0], [64.0, 64.0, -65.0], MASK_SOLID);
+    assert_eq!(down, Some(0.5));
+    assert_eq!(
+        tracer.trace_line([64.0, 64.0, 63.0], [64.0, 64.0, 255.0], MASK_SOLID),
+        None
+    );
+    assert_eq!(tracer.leaf_at([64.0, 64.0, 63.0]), Some(1));
+    assert_eq!(tracer.leaf_at([64.0, 64.0, -8.0]), Some(0));
+    assert_eq!(tracer.cluster_at([64.0, 64.0, 63.0]), Some(0));
+    assert_eq!(tracer.cluster_at([64.0, 64.0, -8.0]), None);
+    assert_eq!(bsp.leaf_at_point([64.0, 64.0, 63.0]), 1);
+    assert_eq!(bsp.leaf_at_point([64.0, 64.0, -8.0]), 0);
+
+    // Starting inside the solid is a hit right away
+    assert_eq!(
+        tracer.trace_line([64.0, 64.0, -8.0], [64.0, 64.0, 8.0], MASK_SOLID),
+        Some(0.0)
+    );
+}
+
+#[test]
+fn occlusion_darkens_faces_looking_at_the_solid() {
+    let bsp = BSP38::from_bytes(TestMapBuilder::room([0.0; 3], [128.0; 3]).build()).unwrap();
+    let mut data = bsp.read_faces().unwrap();
+    data.bake_occlusion(
+        &bsp.tracer(),
+        &OcclusionOptions {
+            rays: 16,
+            distance: 256.0,
+        },
+    );
+    assert_eq!(data.colors.len(), data.points.len());
+
+    // Faces 0 and 1 are the floor and the ceiling, two triangles each
+    let occlusion = |vertex: usize| data.colors[3 * vertex];
+    assert!((0..6).all(|v| occlusion(v) == 1.0));
+    assert!((6..12).all(|v| occlusion(v) < 1.0 && occlusion(v) > 0.0));
+}
+
+#[test]
+fn box_traces_stop_short_of_brushes() {
+    // A solid floor brush, with a pool of water above one half of it
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [128.0; 3])
+            .with_brush([0.0, 0.0, -16.0], [128.0, 128.0, 0.0], CONTENTS_SOLID)
+            .with_brush([0.0, 0.0, 0.0], [64.0, 128.0, 32.0], CONTENTS_WATER)
+            .build(),
+    )
+    .unwrap();
+    let tracer = bsp.tracer();
+
+    let ray = tracer.trace_ray([96.0, 64.0, 64.0], [96.0, 64.0, -64.0], MASK_SOLID);
+    assert!((ray.fraction - (64.0 - 0.03125) / 128.0).abs() < 1e-6);
+    assert!((ray.end[2] - 0.03125).abs() < 1e-4);
+    assert_eq!(ray.plane, Some([0.0, 0.0, 1.0, 0.0]));
+    assert_eq!(ray.contents, CONTENTS_SOLID);
+    assert!(!ray.start_solid && !ray.all_solid);
+
+    // The box stops with its bottom on the floor
+    let player = tracer.trace_box(
+        [96.0, 64.0, 64.0],
+        [96.0, 64.0, -64.0],
+        [16.0, 16.0, 24.0],
+        MASK_SOLID,
+    );
+    assert!((player.end[2] - 24.03125).abs() < 1e-4);
+    assert_eq!(player.plane, Some([0.0, 0.0, 1.0, 0.0]));
+
+    // Water is not in the mask, so the ray goes through the pool
+    let pool = tracer.trace_ray([32.0, 64.0, 64.0], [32.0, 64.0, -64.0], MASK_SOLID);
+    assert_eq!(pool.contents, CONTENTS_SOLID);
+    let water = tracer.trace_ray([32.0, 64.0, 64.0], [32.0, 64.0, -64.0], CONTENTS_WATER);
+    assert_eq!(water.contents, CONTENTS_WATER);
+    assert!((water.end[2] - 32.03125).abs() < 1e-4);
+
+    // Sideways moves above the floor hit nothing
+    let clear = tracer.trace_box(
+        [16.0, 64.0, 64.0],
+        [112.0, 64.0, 64.0],
+        [8.0; 3],
+        MASK_SOLID,
+    );
+    assert_eq!(clear.fraction, 1.0);
+    assert_eq!(clear.end, [112.0, 64.0, 64.0]);
+    assert_eq!(clear.plane, None);
+
+    // Starting in the floor, and staying there
+    let stuck = tracer.trace_ray([64.0, 64.0, -8.0], [32.0, 64.0, -8.0], MASK_SOLID);
+    assert!(stuck.start_solid && stuck.all_solid);
+    assert_eq!(stuck.fraction, 0.0);
+    // Starting in the floor, and leaving it
+    let out = tracer.trace_ray([64.0, 64.0, -8.0], [64.0, 64.0, 64.0], MASK_SOLID);
+    assert!(out.start_solid && !out.all_solid);
+    assert_eq!(out.fraction, 1.0);
+    // A box overlapping the floor, without moving
+    let overlap = tracer.trace_box([64.0, 64.0, 8.0], [64.0, 64.0, 8.0], [16.0; 3], MASK_SOLID);
+    assert!(overlap.all_solid);
+}
+
+#[test]
+fn box_traces_give_up_on_trees_with_cycles() {
+    // A floor in each of 65 clusters, enough nodes that walking both sides
+    // of each would never finish
+    let builder = (1..65).fold(TestMapBuilder::room([0.0; 3], [64.0; 3]), |builder, i| {
+        let x = 64.0 * i as f32;
+        let floor = vec![
+            [x, 0.0, 0.0],
+            [x + 64.0, 0.0, 0.0],
+            [x + 64.0, 64.0, 0.0],
+            [x, 64.0, 0.0],
+        ];
+        builder.with_face(floor, 0, i)
+    });
+    let bsp = BSP38::from_bytes(builder.build()).unwrap();
+    assert_eq!(bsp.read_nodes().unwrap().len(), 64);
+
+    // Every node leads back to the first one on both sides
+    let mut bytes = bsp.bytes.clone();
+    let info = bsp.lump_table()[LumpIndex::Nodes as usize];
+    let nodes = &mut bytes[info.offset as usize..(info.offset + info.length) as usize];
+    for node in nodes.chunks_exact_mut(28) {
+        node[4..12].copy_from_slice(&[0; 8]);
+    }
+
+    // A box straddling every plane, which stops once it has walked as many
+    // nodes as the tree has
+    let tracer = BSP38::from_bytes(bytes).unwrap().tracer();
+    let trace = tracer.trace_box([0.0; 3], [4096.0, 64.0, 64.0], [8192.0; 3], MASK_SOLID);
+    assert_eq!(trace.fraction, 1.0);
+}
@@ -0,0 +1,119 @@
+use q2_formats::{
+    bsp38::{LumpIndex, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+#[test]
+fn leafs_see_each_other_through_their_clusters() {
+    // Leaf 0 is solid, leafs 1 to 3 hold clusters 0 to 2
+    let quad = |x: f32| vec![[x, 0.0, 0.0], [x + 64.0, 0.0, 0.0], [x + 64.0, 64.0, 0.0]];
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::new()
+            .with_face(quad(0.0), 0, 0)
+            .with_face(quad(128.0), 0, 1)
+            .with_face(quad(256.0), 0, 2)
+            .with_vis(vec![
+                vec![true, true, false],
+                vec![true, true, true],
+                vec![false, true, true],
+            ])
+            .build(),
+    )
+    .unwrap();
+
+    assert!(bsp.leaf_visible_from(2, 1));
+    assert!(!bsp.leaf_visible_from(3, 1));
+    assert!(bsp.leaf_visible_from(3, 2));
+    assert!(!bsp.leaf_visible_from(0, 1));
+    assert!(!bsp.leaf_visible_from(1, 0));
+    assert!(!bsp.leaf_visible_from(1, 99));
+
+    assert_eq!(bsp.visible_leafs(1).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(bsp.visible_leafs(2).collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(bsp.visible_leafs(0).count(), 0);
+
+    let vis = bsp.leaf_visibility();
+    assert_eq!(vis.num_leafs(), 4);
+    assert_eq!(vis.visible_leafs(3).collect::<Vec<_>>(), [2, 3]);
+}
+
+#[test]
+fn vis_stats_find_the_worst_cluster() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_vis(vec![
+                vec![true, true, false],
+                vec![false, true, true],
+                vec![false, true, true],
+            ])
+            .build(),
+    )
+    .unwrap();
+    let vis = bsp.read_vis_matrix().unwrap();
+    assert_eq!(vis.visible_from(1).collect::<Vec<_>>(), [1, 2]);
+
+    let stats = vis.stats();
+    assert_eq!(stats.clusters, 3);
+    assert_eq!(stats.average_visible, 2.0);
+    assert_eq!(stats.worst_cluster, Some((0, 2)));
+    assert_eq!(stats.one_sided_pairs, 1);
+
+    let symmetric = vis.symmetric();
+    assert!(symmetric.is_visible(1, 0));
+    assert!(!symmetric.is_visible(2, 0));
+    let stats = symmetric.stats();
+    assert_eq!(stats.worst_cluster, Some((1, 3)));
+    assert_eq!(stats.one_sided_pairs, 0);
+}
+
+#[test]
+fn cluster_count_is_capped_by_the_lump_size() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_vis(vec![vec![true, false], vec![false, true]])
+            .build(),
+    )
+    .unwrap();
+    let vis = bsp.lump_table()[LumpIndex::Visibility as usize];
+
+    // A count claiming billions of clusters in a lump holding two
+    let mut bytes = bsp.bytes.clone();
+    let count = vis.offset as usize;
+    bytes[count..count + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let corrupt = BSP38::from_bytes(bytes).unwrap();
+    assert_eq!(corrupt.read_vis_matrix().unwrap().num_clusters(), 2);
+
+    // Cut off in the middle of the second cluster's offsets
+    let mut bytes = bsp.bytes.clone();
+    let length = 8 + 8 * LumpIndex::Visibility as usize + 4;
+    bytes[length..length + 4].copy_from_slice(&16i32.to_le_bytes());
+    let truncated = BSP38::from_bytes(bytes).unwrap();
+    assert_eq!(truncated.read_vis_matrix().unwrap().num_clusters(), 1);
+}
+
+#[test]
+fn matrices_too_large_to_be_real_are_empty() {
+    let bsp = BSP38::from_bytes(
+        TestMapBuilder::room([0.0; 3], [64.0; 3])
+            .with_vis(vec![vec![true, false], vec![false, true]])
+            .build(),
+    )
+    .unwrap();
+    let vis = bsp.read_vis_matrix().unwrap();
+    assert_eq!(vis.row(1), Some(&[0b10][..]));
+    assert_eq!(vis.row(2), None);
+
+    // Half a megabyte of nothing but offsets, for a 512 MB matrix
+    let clusters = 1 << 16;
+    let mut lump = (clusters as u32).to_le_bytes().to_vec();
+    lump.resize(4 + 8 * clusters, 0);
+    let mut bytes = bsp.bytes.clone();
+    let (table, offset) = (8 + 8 * LumpIndex::Visibility as usize, bytes.len() as u32);
+    bytes[table..table + 4].copy_from_slice(&offset.to_le_bytes());
+    bytes[table + 4..table + 8].copy_from_slice(&(lump.len() as u32).to_le_bytes());
+    bytes.extend(lump);
+
+    let corrupt = BSP38::from_bytes(bytes).unwrap().read_vis_matrix().unwrap();
+    assert_eq!(corrupt.num_clusters(), 0);
+    assert_eq!(corrupt.memory_bytes(), 0);
+}
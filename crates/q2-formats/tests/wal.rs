@@ -0,0 +1,70 @@
+use q2_formats::wal::{Palette, Wal, WalError, MIP_LEVELS};
+
+/// A .wal file of `width` by `height` texels where each texel of each mip
+/// level holds its level.
+fn wal_bytes(width: u32, height: u32) -> Vec<u8> {
+    let mut name = [0u8; 32];
+    name[..13].copy_from_slice(b"e1u1/floor1_3");
+    let mut bytes = name.to_vec();
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    let mut offset = 100;
+    for level in 0..MIP_LEVELS {
+        bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += ((width >> level) * (height >> level)) as usize;
+    }
+    bytes.extend_from_slice(&[0u8; 32]);
+    for value in [0x4u32, 0x1, 7] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for level in 0..MIP_LEVELS {
+        let texels = ((width >> level) * (height >> level)) as usize;
+        bytes.resize(bytes.len() + texels, level as u8);
+    }
+    bytes
+}
+
+#[test]
+fn wal_reads_header_and_mips() {
+    let wal = Wal::from_bytes(&wal_bytes(16, 8)).unwrap();
+    assert_eq!(wal.name, "e1u1/floor1_3");
+    assert_eq!((wal.width, wal.height), (16, 8));
+    assert_eq!(wal.next_name, "");
+    assert_eq!((wal.flags, wal.contents, wal.value), (0x4, 0x1, 7));
+    for level in 0..MIP_LEVELS {
+        let (w, h) = wal.mip_size(level);
+        assert_eq!(wal.mips[level], vec![level as u8; (w * h) as usize]);
+    }
+
+    let rgba = wal.to_rgba(1, &Palette::grayscale());
+    assert_eq!(rgba.len(), 8 * 4 * 4);
+    assert_eq!(&rgba[..4], &[1, 1, 1, 255]);
+
+    let mut bytes = wal_bytes(16, 8);
+    bytes.truncate(bytes.len() - 1);
+    assert!(matches!(
+        Wal::from_bytes(&bytes),
+        Err(WalError::InvalidMip { level: 3, .. })
+    ));
+    assert!(matches!(
+        Wal::from_bytes(&wal_bytes(12, 8)),
+        Err(WalError::InvalidSize { .. })
+    ));
+}
+
+#[test]
+fn palette_comes_from_the_end_of_a_pcx() {
+    let mut pcx = vec![0u8; 128];
+    pcx.push(0x0C);
+    for i in 0..=255u8 {
+        pcx.extend_from_slice(&[i, 255 - i, 7]);
+    }
+    let palette = Palette::from_pcx(&pcx).unwrap();
+    assert_eq!(palette.rgba(1), [1, 254, 7, 255]);
+    assert_eq!(palette.rgba(255), [255, 0, 7, 0]);
+
+    assert!(matches!(
+        Palette::from_pcx(&pcx[1..pcx.len() - 1]),
+        Err(WalError::NoPalette)
+    ));
+}
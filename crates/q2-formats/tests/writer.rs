@@ -0,0 +1,71 @@
+use q2_formats::{
+    bsp38::{prelude::Strip, LumpIndex, BSP38},
+    test_utils::TestMapBuilder,
+};
+
+fn lump_bytes(bsp: &BSP38, lump: LumpIndex) -> &[u8] {
+    let info = bsp.lump_table()[lump as usize];
+    &bsp.bytes[info.offset as usize..(info.offset + info.length) as usize]
+}
+
+#[test]
+fn written_maps_keep_every_lump() {
+    let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+        .with_entity("info_player_start", &[("origin", "64 64 24")])
+        .with_vis(vec![vec![true]])
+        .with_area_portal(1, 2)
+        .with_lightmaps()
+        .build();
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    let written = BSP38::from_bytes(bsp.to_bytes()).unwrap();
+
+    for lump in LumpIndex::ALL {
+        assert_eq!(
+            lump_bytes(&written, lump),
+            lump_bytes(&bsp, lump),
+            "{:?}",
+            lump
+        );
+        assert_eq!(written.lump_table()[lump as usize].offset % 4, 0);
+    }
+    assert_eq!(written.to_bytes(), bsp.to_bytes());
+}
+
+#[test]
+fn stripped_lumps_are_zeroed_or_removed() {
+    let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+        .with_vis(vec![vec![true]])
+        .with_lightmaps()
+        .build();
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    let stripped = bsp.write_stripped(&[
+        (LumpIndex::Lighting, Strip::Zero),
+        (LumpIndex::Visibility, Strip::Remove),
+    ]);
+    assert!(stripped.len() < bsp.bytes.len());
+    let stripped = BSP38::from_bytes(stripped).unwrap();
+
+    let lighting = lump_bytes(&stripped, LumpIndex::Lighting);
+    assert_eq!(lighting.len(), lump_bytes(&bsp, LumpIndex::Lighting).len());
+    assert!(lighting.iter().all(|&b| b == 0));
+    assert!(!stripped.has_lump(LumpIndex::Visibility));
+    assert_eq!(stripped.read_vis_matrix().unwrap().num_clusters(), 0);
+    assert_eq!(
+        lump_bytes(&stripped, LumpIndex::Faces),
+        lump_bytes(&bsp, LumpIndex::Faces)
+    );
+}
+
+#[test]
+fn zeroed_visibility_reads_as_no_clusters() {
+    let bytes = TestMapBuilder::room([0.0; 3], [256.0; 3])
+        .with_vis(vec![vec![true]])
+        .build();
+    let bsp = BSP38::from_bytes(bytes).unwrap();
+    let stripped = bsp.write_stripped(&[(LumpIndex::Visibility, Strip::Zero)]);
+    let stripped = BSP38::from_bytes(stripped).unwrap();
+
+    assert!(stripped.has_lump(LumpIndex::Visibility));
+    assert_eq!(stripped.read_vis_matrix().unwrap().num_clusters(), 0);
+    assert_eq!(stripped.report().vis.worst_cluster, None);
+}
@@ -0,0 +1,88 @@
+//! Exports the collision shapes of a BSP as JSON, for physics engines that
+//! can't read BSP files.
+//!
+//! Usage: `bspcollision [--mask <contents>] <map.bsp>`
+//!
+//! The output holds one entry per model (model 0 is the world) with its brush
+//! hulls as planes and corners, and its faces as an indexed triangle mesh,
+//! all in map coordinates. The mask defaults to solid and window brushes.
+
+use std::process::ExitCode;
+
+use q2_formats::bsp38::{contents::MASK_SOLID, prelude::ParseOptions};
+use serde_json::json;
+
+const USAGE: &str = "Usage: bspcollision [--mask <contents>] <map.bsp>";
+
+fn main() -> ExitCode {
+    let mut mask = MASK_SOLID;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mask" => match args.next().and_then(|m| parse_mask(&m)) {
+                Some(m) => mask = m,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let options = ParseOptions::new().log_level(None);
+    let bsp = std::fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| options.parse(bytes).map_err(|err| err.to_string()));
+    let bsp = match bsp {
+        Ok(bsp) => bsp,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let models: Vec<_> = bsp
+        .model_collision(mask)
+        .iter()
+        .enumerate()
+        .map(|(i, model)| {
+            json!({
+                "model": i,
+                "hulls": model.hulls.iter().map(|hull| json!({
+                    "contents": hull.contents,
+                    "planes": hull.planes,
+                    "vertices": hull.vertices,
+                })).collect::<Vec<_>>(),
+                "mesh": {
+                    "vertices": model.mesh.vertices,
+                    "triangles": model.mesh.triangles,
+                },
+            })
+        })
+        .collect();
+    let value = json!({ "path": path, "mask": mask, "models": models });
+    println!("{}", serde_json::to_string(&value).unwrap());
+    ExitCode::SUCCESS
+}
+
+/// Parses a contents mask in decimal or `0x` hexadecimal.
+fn parse_mask(mask: &str) -> Option<i32> {
+    match mask.strip_prefix("0x") {
+        Some(hex) => i32::from_str_radix(hex, 16).ok(),
+        None => mask.parse().ok(),
+    }
+}
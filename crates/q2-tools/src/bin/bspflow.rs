@@ -0,0 +1,137 @@
+//! Prints the gameplay flow of BSP files for reviewing deathmatch maps:
+//! pickups and spawns by area, walking distances from the spawns to the
+//! weapons, and how connected the walkable space is.
+//!
+//! Usage: `bspflow [--json] <map.bsp>...`
+
+use std::process::ExitCode;
+
+use q2_formats::bsp38::prelude::{FlowReport, ParseOptions};
+use serde_json::{json, Value};
+
+const USAGE: &str = "Usage: bspflow [--json] <map.bsp>...";
+
+fn print_text(path: &str, report: &FlowReport) {
+    println!("{}", path);
+    println!(
+        "  {:<6} {:>6} {:>7} {:>5} {:>5} {:>4}",
+        "area", "spawns", "weapons", "ammo", "items", "keys"
+    );
+    for (area, pickups) in &report.areas {
+        println!(
+            "  {:<6} {:>6} {:>7} {:>5} {:>5} {:>4}",
+            area, pickups.spawns, pickups.weapons, pickups.ammo, pickups.items, pickups.keys
+        );
+    }
+
+    println!();
+    for spawn in &report.spawns {
+        let [x, y, z] = spawn.origin;
+        let nearest = match &spawn.nearest_weapon {
+            Some((weapon, distance)) => format!("{} at {:.0}", weapon, distance),
+            None => "no weapon in reach".to_string(),
+        };
+        let average = spawn.average_weapon_distance.map_or(String::new(), |d| {
+            format!(", {:.0} to weapons on average", d)
+        });
+        println!(
+            "  spawn {:<5} ({:.0} {:.0} {:.0}): {}{}",
+            spawn.entity, x, y, z, nearest, average
+        );
+    }
+    if let Some(average) = report.average_spawn_to_weapon {
+        println!("  nearest weapon on average: {:.0}", average);
+    }
+
+    let c = &report.connectivity;
+    println!();
+    println!("  nav nodes      {:>8}", c.nav_nodes);
+    println!("  regions        {:>8}", c.components);
+    println!("  largest region {:>7.0}%", c.largest_component * 100.0);
+    println!("  stranded spawns{:>8}", c.stranded_spawns);
+    println!("  areas          {:>8}", c.areas);
+    println!("  area portals   {:>8}", c.area_portals);
+}
+
+fn to_json(path: &str, report: &FlowReport) -> Value {
+    let c = &report.connectivity;
+    json!({
+        "path": path,
+        "areas": report.areas.iter().map(|(area, pickups)| json!({
+            "area": area,
+            "spawns": pickups.spawns,
+            "weapons": pickups.weapons,
+            "ammo": pickups.ammo,
+            "items": pickups.items,
+            "keys": pickups.keys,
+        })).collect::<Vec<_>>(),
+        "spawns": report.spawns.iter().map(|spawn| json!({
+            "entity": spawn.entity,
+            "origin": spawn.origin,
+            "nearest_weapon": spawn.nearest_weapon.as_ref().map(|(weapon, distance)| json!({
+                "classname": weapon,
+                "distance": distance,
+            })),
+            "average_weapon_distance": spawn.average_weapon_distance,
+        })).collect::<Vec<_>>(),
+        "average_spawn_to_weapon": report.average_spawn_to_weapon,
+        "connectivity": {
+            "nav_nodes": c.nav_nodes,
+            "components": c.components,
+            "largest_component": c.largest_component,
+            "stranded_spawns": c.stranded_spawns,
+            "areas": c.areas,
+            "area_portals": c.area_portals,
+        },
+    })
+}
+
+fn main() -> ExitCode {
+    let mut json_output = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json_output = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let options = ParseOptions::new().log_level(None);
+    let mut reports = Vec::new();
+    for path in paths {
+        let bsp = std::fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| options.parse(bytes).map_err(|err| err.to_string()));
+        match bsp {
+            Ok(bsp) => reports.push((path, bsp.flow_report())),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if json_output {
+        let value = match reports.as_slice() {
+            [(path, report)] => to_json(path, report),
+            _ => Value::Array(reports.iter().map(|(p, r)| to_json(p, r)).collect()),
+        };
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+    } else {
+        for (i, (path, report)) in reports.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print_text(path, report);
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,266 @@
+//! Prints the header, lump table, stats, textures and entities of BSP files.
+//!
+//! Usage: `bspinfo [--json] [--vis] <map.bsp>...`
+//!
+//! With `--vis`, also prints the cluster visibility matrix, made symmetric,
+//! with how many clusters each cluster sees, for finding vis hotspots.
+
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+use q2_formats::bsp38::{
+    prelude::{BspError, ParseOptions, TextureUsage, Validation, VisMatrix, VisStats},
+    BSP38,
+};
+use serde_json::{json, Value};
+
+struct Report {
+    path: String,
+    bsp: BSP38,
+    textures: Vec<TextureUsage>,
+    classnames: BTreeMap<String, usize>,
+    num_entities: usize,
+    num_triangles: usize,
+    num_clusters: usize,
+    /// The symmetric cluster visibility matrix, with `--vis`.
+    vis: Option<VisMatrix>,
+}
+
+impl Report {
+    fn new(path: String, bsp: BSP38, vis: bool) -> Result<Self, BspError> {
+        let num_triangles = bsp
+            .read_face_records()?
+            .iter()
+            .map(|face| (face.num_edges as usize).saturating_sub(2))
+            .sum();
+
+        let entities = bsp.read_entities()?;
+        let mut classnames = BTreeMap::new();
+        for entity in &entities {
+            *classnames.entry(entity.classname.clone()).or_insert(0) += 1;
+        }
+
+        let matrix = bsp.read_vis_matrix()?;
+        Ok(Self {
+            num_clusters: matrix.num_clusters(),
+            vis: vis.then(|| matrix.symmetric()),
+            textures: bsp.unique_textures(),
+            path,
+            bsp,
+            classnames,
+            num_entities: entities.len(),
+            num_triangles,
+        })
+    }
+
+    fn count(&self, lump: &str) -> usize {
+        self.bsp
+            .lump_table()
+            .iter()
+            .find(|l| l.name == lump)
+            .and_then(|l| l.count)
+            .unwrap_or(0)
+    }
+
+    fn stats(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("vertices", self.count("vertices")),
+            ("planes", self.count("planes")),
+            ("faces", self.count("faces")),
+            ("triangles", self.num_triangles),
+            ("texinfo", self.count("texinfo")),
+            ("textures", self.textures.len()),
+            ("nodes", self.count("nodes")),
+            ("leafs", self.count("leafs")),
+            ("clusters", self.num_clusters),
+            ("models", self.count("models")),
+            ("brushes", self.count("brushes")),
+            ("entities", self.num_entities),
+        ]
+    }
+
+    fn print_text(&self) {
+        let bounds = self.bsp.bounds();
+        println!("{}", self.path);
+        println!("  {}", self.bsp);
+        println!("  bounds: {:?} - {:?}", bounds.min, bounds.max);
+
+        println!();
+        println!(
+            "  {:<14} {:>10} {:>10} {:>8}",
+            "lump", "offset", "length", "count"
+        );
+        for lump in self.bsp.lump_table() {
+            let count = lump.count.map(|c| c.to_string()).unwrap_or_default();
+            println!(
+                "  {:<14} {:>10} {:>10} {:>8}",
+                lump.name, lump.offset, lump.length, count
+            );
+        }
+
+        println!();
+        for (name, value) in self.stats() {
+            println!("  {:<14} {:>10}", name, value);
+        }
+
+        println!();
+        println!("  textures ({}):", self.textures.len());
+        for texture in &self.textures {
+            println!(
+                "    {:<32} {:>6} faces  flags {:#x}",
+                texture.name, texture.faces, texture.flags
+            );
+        }
+
+        println!();
+        println!("  entities ({}):", self.num_entities);
+        for (classname, count) in &self.classnames {
+            println!("    {:<32} {:>6}", classname, count);
+        }
+
+        if let Some(vis) = &self.vis {
+            self.print_vis(vis);
+        }
+    }
+
+    /// The stats of the symmetric matrix, with the one-sided pairs of the
+    /// compiled one.
+    fn vis_stats(&self, vis: &VisMatrix) -> VisStats {
+        VisStats {
+            one_sided_pairs: self
+                .bsp
+                .read_vis_matrix()
+                .unwrap_or_default()
+                .stats()
+                .one_sided_pairs,
+            ..vis.stats()
+        }
+    }
+
+    fn print_vis(&self, vis: &VisMatrix) {
+        let stats = self.vis_stats(vis);
+        println!();
+        println!(
+            "  vis: {} clusters, {:.1} visible on average, {} one-sided pairs",
+            stats.clusters, stats.average_visible, stats.one_sided_pairs
+        );
+        if let Some((cluster, visible)) = stats.worst_cluster {
+            println!("  worst cluster: {} sees {}", cluster, visible);
+        }
+        for from in 0..vis.num_clusters() {
+            let row: String = (0..vis.num_clusters())
+                .map(|to| if vis.is_visible(from, to) { '#' } else { '.' })
+                .collect();
+            println!(
+                "    {:>5} {:>5} {}",
+                from,
+                vis.visible_from(from).count(),
+                row
+            );
+        }
+    }
+
+    fn vis_json(&self, vis: &VisMatrix) -> Value {
+        let stats = self.vis_stats(vis);
+        json!({
+            "clusters": stats.clusters,
+            "average_visible": stats.average_visible,
+            "one_sided_pairs": stats.one_sided_pairs,
+            "worst_cluster": stats.worst_cluster.map(|(cluster, visible)| json!({
+                "cluster": cluster,
+                "visible": visible,
+            })),
+            "visible": (0..vis.num_clusters())
+                .map(|from| vis.visible_from(from).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        let bounds = self.bsp.bounds();
+        let mut value = json!({
+            "path": self.path,
+            "magic": self.bsp.magic,
+            "version": self.bsp.version,
+            "size": self.bsp.bytes.len(),
+            "bounds": { "min": bounds.min, "max": bounds.max },
+            "lumps": self.bsp.lump_table().iter().map(|lump| json!({
+                "name": lump.name,
+                "offset": lump.offset,
+                "length": lump.length,
+                "count": lump.count,
+            })).collect::<Vec<_>>(),
+            "stats": self.stats().into_iter().collect::<BTreeMap<_, _>>(),
+            "textures": self.textures.iter().map(|texture| json!({
+                "name": texture.name,
+                "faces": texture.faces,
+                "flags": texture.flags,
+            })).collect::<Vec<_>>(),
+            "entities": self.classnames.iter().map(|(classname, count)| json!({
+                "classname": classname,
+                "count": count,
+            })).collect::<Vec<_>>(),
+        });
+        if let Some(vis) = &self.vis {
+            value["vis"] = self.vis_json(vis);
+        }
+        value
+    }
+}
+
+const USAGE: &str = "Usage: bspinfo [--json] [--vis] <map.bsp>...";
+
+fn main() -> ExitCode {
+    let mut json_output = false;
+    let mut vis = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json_output = true,
+            "--vis" => vis = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let options = ParseOptions::new()
+        .validation(Validation::Strict)
+        .log_level(None);
+    let mut reports = Vec::new();
+    for path in paths {
+        let report = std::fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| options.parse(bytes).map_err(|err| err.to_string()))
+            .and_then(|bsp| Report::new(path.clone(), bsp, vis).map_err(|err| err.to_string()));
+        match report {
+            Ok(report) => reports.push(report),
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if json_output {
+        let value = match reports.as_slice() {
+            [report] => report.to_json(),
+            _ => Value::Array(reports.iter().map(Report::to_json).collect()),
+        };
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+    } else {
+        for (i, report) in reports.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            report.print_text();
+        }
+    }
+    ExitCode::SUCCESS
+}
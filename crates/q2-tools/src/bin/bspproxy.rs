@@ -0,0 +1,103 @@
+//! Exports a simplified collision proxy of a BSP as a Wavefront OBJ mesh, for
+//! physics or occlusion in engines that can't read BSP files.
+//!
+//! Usage: `bspproxy [--mask <contents>] <map.bsp> [<out.obj>]`
+//!
+//! The mesh is made of the sides of the world's structural brushes with the
+//! mask contents, leaving out detail brushes and sides buried in other
+//! brushes, so it is much lighter than the drawn faces and has few holes. It
+//! is in map coordinates, Z up. The mask defaults to solid and window
+//! brushes; the mesh is written to stdout without an output path.
+
+use std::{fmt::Write, process::ExitCode};
+
+use q2_formats::bsp38::{
+    contents::MASK_SOLID,
+    prelude::{CollisionMesh, ParseOptions},
+};
+
+const USAGE: &str = "Usage: bspproxy [--mask <contents>] <map.bsp> [<out.obj>]";
+
+fn main() -> ExitCode {
+    let mut mask = MASK_SOLID;
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mask" => match args.next().and_then(|m| parse_mask(&m)) {
+                Some(m) => mask = m,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ if paths.len() < 2 => paths.push(arg),
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(path) = paths.first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let options = ParseOptions::new().log_level(None);
+    let bsp = std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| options.parse(bytes).map_err(|err| err.to_string()));
+    let bsp = match bsp {
+        Ok(bsp) => bsp,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let proxy = bsp.collision_proxy(mask);
+    let obj = to_obj(&proxy, path, mask);
+    match paths.get(1) {
+        Some(out) => {
+            if let Err(err) = std::fs::write(out, obj) {
+                eprintln!("{}: {}", out, err);
+                return ExitCode::FAILURE;
+            }
+            eprintln!(
+                "{}: {} vertices, {} triangles",
+                out,
+                proxy.vertices.len(),
+                proxy.triangles.len()
+            );
+        }
+        None => print!("{}", obj),
+    }
+    ExitCode::SUCCESS
+}
+
+/// Writes `mesh` as an OBJ file with a single object.
+fn to_obj(mesh: &CollisionMesh, path: &str, mask: i32) -> String {
+    let mut obj = String::new();
+    writeln!(obj, "# Collision proxy of {} (mask {:#x})", path, mask).unwrap();
+    writeln!(obj, "o proxy").unwrap();
+    for [x, y, z] in &mesh.vertices {
+        writeln!(obj, "v {} {} {}", x, y, z).unwrap();
+    }
+    // OBJ indices start at 1
+    for [a, b, c] in &mesh.triangles {
+        writeln!(obj, "f {} {} {}", a + 1, b + 1, c + 1).unwrap();
+    }
+    obj
+}
+
+/// Parses a contents mask in decimal or `0x` hexadecimal.
+fn parse_mask(mask: &str) -> Option<i32> {
+    match mask.strip_prefix("0x") {
+        Some(hex) => i32::from_str_radix(hex, 16).ok(),
+        None => mask.parse().ok(),
+    }
+}
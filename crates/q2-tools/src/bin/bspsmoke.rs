@@ -0,0 +1,241 @@
+//! Loads every BSP under some directories the way the viewer does, as a
+//! regression check for the parser: each map is parsed with strict
+//! validation, has its lump references checked, and is triangulated into
+//! batches with lightmaps. Prints the time each stage took and any error,
+//! and fails if any map did.
+//!
+//! Usage: `bspsmoke [--json] <dir or map.bsp>...`
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use q2_formats::bsp38::prelude::{MeshBuilder, ParseOptions, Validation};
+use serde_json::{json, Value};
+
+const USAGE: &str = "Usage: bspsmoke [--json] <dir or map.bsp>...";
+
+/// Triangles below which batches merge, as the viewer's default.
+const MERGE_TRIANGLES: usize = 64;
+
+struct Timings {
+    read: Duration,
+    parse: Duration,
+    check: Duration,
+    mesh: Duration,
+}
+
+struct Outcome {
+    path: PathBuf,
+    timings: Timings,
+    triangles: usize,
+    batches: usize,
+    error: Option<String>,
+}
+
+impl Outcome {
+    fn total(&self) -> Duration {
+        let t = &self.timings;
+        t.read + t.parse + t.check + t.mesh
+    }
+
+    fn print_text(&self) {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let t = &self.timings;
+        match &self.error {
+            None => println!(
+                "ok   {:>8.1} ms  (read {:.1}, parse {:.1}, check {:.1}, mesh {:.1})  {:>7} triangles {:>5} batches  {}",
+                ms(self.total()),
+                ms(t.read),
+                ms(t.parse),
+                ms(t.check),
+                ms(t.mesh),
+                self.triangles,
+                self.batches,
+                self.path.display()
+            ),
+            Some(error) => println!("FAIL {}: {}", self.path.display(), error),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let t = &self.timings;
+        json!({
+            "path": self.path.display().to_string(),
+            "ok": self.error.is_none(),
+            "error": self.error,
+            "ms": {
+                "total": ms(self.total()),
+                "read": ms(t.read),
+                "parse": ms(t.parse),
+                "check": ms(t.check),
+                "mesh": ms(t.mesh),
+            },
+            "triangles": self.triangles,
+            "batches": self.batches,
+        })
+    }
+}
+
+/// Loads one map, stopping at the first stage that fails.
+fn smoke_test(path: &Path) -> Outcome {
+    let mut outcome = Outcome {
+        path: path.to_path_buf(),
+        timings: Timings {
+            read: Duration::ZERO,
+            parse: Duration::ZERO,
+            check: Duration::ZERO,
+            mesh: Duration::ZERO,
+        },
+        triangles: 0,
+        batches: 0,
+        error: None,
+    };
+    let timings = &mut outcome.timings;
+
+    let start = Instant::now();
+    let bytes = std::fs::read(path);
+    timings.read = start.elapsed();
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            outcome.error = Some(err.to_string());
+            return outcome;
+        }
+    };
+
+    let start = Instant::now();
+    let bsp = ParseOptions::new()
+        .validation(Validation::Strict)
+        .log_level(None)
+        .parse(bytes);
+    timings.parse = start.elapsed();
+    let bsp = match bsp {
+        Ok(bsp) => bsp,
+        Err(err) => {
+            outcome.error = Some(err.to_string());
+            return outcome;
+        }
+    };
+
+    // Reading every lump for the check is most of its time
+    let start = Instant::now();
+    let broken = bsp.check_references();
+    timings.check = start.elapsed();
+    if let Some(first) = broken.first() {
+        outcome.error = Some(match broken.len() {
+            1 => first.to_string(),
+            n => format!("{} (and {} more broken references)", first, n - 1),
+        });
+        return outcome;
+    }
+
+    let start = Instant::now();
+    let batches = panic::catch_unwind(AssertUnwindSafe(|| {
+        let atlas = bsp.read_lightmaps();
+        let mut builder = MeshBuilder::with_capacity_for(&bsp).with_lightmaps(&atlas);
+        let mut batches = builder.build_batches(&bsp, MERGE_TRIANGLES);
+        batches.extend(builder.build_translucent_batches(&bsp));
+        batches
+    }));
+    timings.mesh = start.elapsed();
+    match batches {
+        Ok(batches) => {
+            outcome.triangles = batches.iter().map(|b| b.data.triangle_count()).sum();
+            outcome.batches = batches.len();
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or("unknown panic");
+            outcome.error = Some(format!("panicked while triangulating: {}", message));
+        }
+    }
+    outcome
+}
+
+/// The .bsp files under `path`, or `path` itself if it's a file.
+fn find_maps(path: &Path, maps: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        maps.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        let is_bsp = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("bsp"));
+        if path.is_dir() || is_bsp {
+            find_maps(&path, maps)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut json_output = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json_output = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let mut maps = Vec::new();
+    for path in &paths {
+        if let Err(err) = find_maps(path, &mut maps) {
+            eprintln!("{}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+    }
+    maps.sort();
+
+    // Panics are reported with their map instead
+    panic::set_hook(Box::new(|_| {}));
+    let mut outcomes = Vec::new();
+    for map in &maps {
+        let outcome = smoke_test(map);
+        if !json_output {
+            outcome.print_text();
+        }
+        outcomes.push(outcome);
+    }
+    let _ = panic::take_hook();
+
+    let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+    if json_output {
+        let value = json!({
+            "maps": outcomes.len(),
+            "failed": failed,
+            "results": outcomes.iter().map(Outcome::to_json).collect::<Vec<_>>(),
+        });
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+    } else {
+        let total: Duration = outcomes.iter().map(Outcome::total).sum();
+        println!(
+            "{} maps, {} failed, {:.1} ms",
+            outcomes.len(),
+            failed,
+            total.as_secs_f64() * 1000.0
+        );
+    }
+    match failed {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,102 @@
+//! Writes a copy of a BSP with some lumps zeroed or removed, such as the
+//! lighting and vis of maps previewed on the web, which the viewer can draw
+//! without.
+//!
+//! Usage: `bspstrip [--zero <lump>]... [--remove <lump>]... [--preview] <in.bsp> <out.bsp>`
+//!
+//! Lumps are named as in bspinfo's lump table, e.g. `lighting` or
+//! `visibility`. `--preview` removes the lighting and visibility lumps.
+
+use std::process::ExitCode;
+
+use q2_formats::bsp38::{
+    prelude::{ParseOptions, Strip},
+    LumpIndex,
+};
+
+const USAGE: &str =
+    "Usage: bspstrip [--zero <lump>]... [--remove <lump>]... [--preview] <in.bsp> <out.bsp>";
+
+/// Lumps `--preview` removes.
+const PREVIEW: [LumpIndex; 2] = [LumpIndex::Lighting, LumpIndex::Visibility];
+
+fn main() -> ExitCode {
+    let mut strip = Vec::new();
+    let mut paths = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let edit = match arg.as_str() {
+            "--zero" => Strip::Zero,
+            "--remove" => Strip::Remove,
+            "--preview" => {
+                strip.extend(PREVIEW.map(|lump| (lump, Strip::Remove)));
+                continue;
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                paths.push(arg);
+                continue;
+            }
+        };
+        let Some(name) = args.next() else {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        };
+        match LumpIndex::from_name(&name) {
+            Some(lump) => strip.push((lump, edit)),
+            None => {
+                let names: Vec<&str> = LumpIndex::ALL.iter().map(|lump| lump.name()).collect();
+                eprintln!(
+                    "unknown lump {:?}, expected one of {}",
+                    name,
+                    names.join(", ")
+                );
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let [input, output] = paths.as_slice() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let options = ParseOptions::new().log_level(None);
+    let bsp = std::fs::read(input)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| options.parse(bytes).map_err(|err| err.to_string()));
+    let bsp = match bsp {
+        Ok(bsp) => bsp,
+        Err(err) => {
+            eprintln!("{}: {}", input, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let bytes = bsp.write_stripped(&strip);
+    if let Err(err) = std::fs::write(output, &bytes) {
+        eprintln!("{}: {}", output, err);
+        return ExitCode::FAILURE;
+    }
+    for lump in LumpIndex::ALL {
+        let Some(&(_, edit)) = strip.iter().rev().find(|(l, _)| *l == lump) else {
+            continue;
+        };
+        let length = bsp.lump_table()[lump as usize].length;
+        let verb = match edit {
+            Strip::Zero => "zeroed",
+            Strip::Remove => "removed",
+        };
+        println!("{:<12} {:>10} bytes {}", lump.name(), length, verb);
+    }
+    println!(
+        "{} -> {}: {} -> {} bytes",
+        input,
+        output,
+        bsp.bytes.len(),
+        bytes.len()
+    );
+    ExitCode::SUCCESS
+}
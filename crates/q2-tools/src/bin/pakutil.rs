@@ -0,0 +1,149 @@
+//! Lists, extracts and creates Quake 2 .pak archives.
+//!
+//! Usage:
+//!
+//! ```text
+//! pakutil list <file.pak> [pattern...]
+//! pakutil extract <file.pak> <out_dir> [pattern...]
+//! pakutil create <file.pak> <in_dir> [pattern...]
+//! ```
+//!
+//! Patterns are globs matched against archive paths (e.g. `maps/*.bsp`,
+//! `textures/e1u1/*`); with no patterns every file is included.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Component, Path, PathBuf};
+use std::process::ExitCode;
+
+use glob::Pattern;
+use q2_formats::pak::{PakArchive, PakWriter};
+
+const USAGE: &str = "Usage:
+  pakutil list <file.pak> [pattern...]
+  pakutil extract <file.pak> <out_dir> [pattern...]
+  pakutil create <file.pak> <in_dir> [pattern...]";
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+struct Filter(Vec<Pattern>);
+
+impl Filter {
+    fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Pattern::new(p))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(Self(patterns))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.0.is_empty() || self.0.iter().any(|p| p.matches(name))
+    }
+}
+
+fn list(pak: &str, filter: &Filter) -> Result<()> {
+    let archive = PakArchive::open(pak)?;
+    let mut total = 0u64;
+    let mut count = 0;
+    for entry in archive.entries().iter().filter(|e| filter.matches(&e.name)) {
+        println!("{:>10}  {}", entry.length, entry.name);
+        total += entry.length as u64;
+        count += 1;
+    }
+    println!("{:>10}  {} files", total, count);
+    Ok(())
+}
+
+fn extract(pak: &str, out_dir: &Path, filter: &Filter) -> Result<()> {
+    let mut archive = PakArchive::open(pak)?;
+    let entries: Vec<_> = archive
+        .entries()
+        .iter()
+        .filter(|e| filter.matches(&e.name))
+        .cloned()
+        .collect();
+
+    for entry in entries {
+        // Never write outside of out_dir, whatever the archive contains
+        let relative = Path::new(&entry.name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            eprintln!("Skipping unsafe path: {}", entry.name);
+            continue;
+        }
+        let path = out_dir.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, archive.read_entry(&entry)?)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+fn create(pak: &str, in_dir: &Path, filter: &Filter) -> Result<()> {
+    let mut files = Vec::new();
+    collect_files(in_dir, &mut files)?;
+    files.sort();
+
+    let mut writer = PakWriter::new(BufWriter::new(File::create(pak)?))?;
+    for path in files {
+        let name = path
+            .strip_prefix(in_dir)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !filter.matches(&name) {
+            continue;
+        }
+        writer.add_file(&name, &std::fs::read(&path)?)?;
+        println!("{}", name);
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<bool> {
+    match args {
+        [cmd, pak, patterns @ ..] if cmd == "list" => list(pak, &Filter::new(patterns)?)?,
+        [cmd, pak, dir, patterns @ ..] if cmd == "extract" => {
+            extract(pak, Path::new(dir), &Filter::new(patterns)?)?
+        }
+        [cmd, pak, dir, patterns @ ..] if cmd == "create" => {
+            create(pak, Path::new(dir), &Filter::new(patterns)?)?
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("pakutil: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
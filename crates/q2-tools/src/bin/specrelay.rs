@@ -0,0 +1,309 @@
+//! Relays spectator sessions between web viewers: every text message a
+//! viewer sends is forwarded to the other viewers connected to the same
+//! path, so `ws://host:8765/base1-review` is one session.
+//!
+//! Usage: `specrelay [--port <port>]`
+//!
+//! The relay doesn't read the messages, which are the viewers' camera
+//! updates, so it needs no changes as they grow. It speaks just enough of
+//! WebSocket (RFC 6455) for browsers: unfragmented text frames, pings and
+//! closes.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+const USAGE: &str = "Usage: specrelay [--port <port>]";
+
+const DEFAULT_PORT: u16 = 8765;
+
+/// Appended to the client's key in the handshake's accept key.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message forwarded; camera updates are about a hundred bytes.
+const MAX_MESSAGE: u64 = 64 * 1024;
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// The viewers of each session path, by connection number.
+type Sessions = Arc<Mutex<HashMap<String, HashMap<usize, TcpStream>>>>;
+
+fn main() -> ExitCode {
+    let mut port = DEFAULT_PORT;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => match args.next().and_then(|p| p.parse().ok()) {
+                Some(p) => port = p,
+                None => {
+                    eprintln!("{}", USAGE);
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("port {}: {}", port, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    eprintln!("Relaying sessions on ws://0.0.0.0:{}/<session>", port);
+
+    let sessions = Sessions::default();
+    for (connection, stream) in listener.incoming().enumerate() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let sessions = sessions.clone();
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|addr| addr.to_string());
+            let peer = peer.unwrap_or_else(|_| "?".to_string());
+            if let Err(err) = serve(stream, connection, &sessions) {
+                eprintln!("{}: {}", peer, err);
+            }
+        });
+    }
+    ExitCode::SUCCESS
+}
+
+/// Accepts a viewer into its session and forwards its messages until it
+/// leaves.
+fn serve(stream: TcpStream, connection: usize, sessions: &Sessions) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let session = handshake(&mut reader, &stream)?;
+    eprintln!("{} joined {}", connection, session);
+    sessions
+        .lock()
+        .unwrap()
+        .entry(session.clone())
+        .or_default()
+        .insert(connection, stream.try_clone()?);
+
+    // Closing the tab drops the connection without a close frame
+    let result = match relay(&mut reader, &stream, connection, &session, sessions) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+        result => result,
+    };
+
+    let mut sessions = sessions.lock().unwrap();
+    if let Some(viewers) = sessions.get_mut(&session) {
+        viewers.remove(&connection);
+        if viewers.is_empty() {
+            sessions.remove(&session);
+        }
+    }
+    eprintln!("{} left {}", connection, session);
+    result
+}
+
+fn relay(
+    reader: &mut impl Read,
+    mut stream: &TcpStream,
+    connection: usize,
+    session: &str,
+    sessions: &Sessions,
+) -> io::Result<()> {
+    loop {
+        let (opcode, payload) = read_frame(reader)?;
+        match opcode {
+            OP_TEXT => {
+                let frame = frame(OP_TEXT, &payload);
+                let sessions = sessions.lock().unwrap();
+                let viewers = sessions.get(session).into_iter().flatten();
+                for (_, mut viewer) in viewers.filter(|(&other, _)| other != connection) {
+                    // A viewer that can't keep up drops out when it reads
+                    let _ = viewer.write_all(&frame);
+                }
+            }
+            OP_PING => stream.write_all(&frame(OP_PONG, &payload))?,
+            OP_CLOSE => {
+                let _ = stream.write_all(&frame(OP_CLOSE, &[]));
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the HTTP upgrade request, answers it, and returns its path.
+fn handshake(reader: &mut impl BufRead, mut stream: &TcpStream) -> io::Result<String> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let path = match request.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", path, _] => path.to_string(),
+        _ => return Err(invalid("not a WebSocket request")),
+    };
+    let mut key = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated request"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    let key = key.ok_or_else(|| invalid("no Sec-WebSocket-Key"))?;
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    Ok(path)
+}
+
+/// The `Sec-WebSocket-Accept` answer to a client's key.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// Reads a frame from a client, whose frames are masked, as its opcode and
+/// unmasked payload.
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+    let length = match header[1] & 0x7f {
+        126 => {
+            let mut length = [0u8; 2];
+            reader.read_exact(&mut length)?;
+            u16::from_be_bytes(length) as u64
+        }
+        127 => {
+            let mut length = [0u8; 8];
+            reader.read_exact(&mut length)?;
+            u64::from_be_bytes(length)
+        }
+        length => length as u64,
+    };
+    if length > MAX_MESSAGE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message of {} bytes", length),
+        ));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
+
+/// An unmasked, unfragmented frame, as servers send them.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+
+    for block in padded.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(h) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => text.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use crate::render::FpsText;
+
+/// Rate lightstyle patterns step at, in steps per second, as in Quake 2.
+pub const LIGHTSTYLE_HZ: f32 = 10.0;
+
+/// Applies the [Accessibility] settings: the UI scale and the HUD theme.
+/// Reduced motion and lightstyle limiting are read by the systems they
+/// affect.
+pub struct AccessibilityPlugin {
+    pub settings: Accessibility,
+}
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.settings.clone()).add_systems(
+            Update,
+            (apply_ui_scale, apply_hud_theme).run_if(resource_changed::<Accessibility>),
+        );
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Accessibility {
+    /// Holds the orbit camera still instead of circling the map.
+    pub reduced_motion: bool,
+    /// Draws the HUD in white on black instead of amber on the scene.
+    pub high_contrast: bool,
+    /// Factor of the size of all UI, text included.
+    pub ui_scale: f32,
+    /// Holds flickering and pulsing lightstyles at their average brightness,
+    /// so lights never flash, see [lightstyle_brightness].
+    pub safe_lightstyles: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self {
+            reduced_motion: false,
+            high_contrast: false,
+            ui_scale: 1.0,
+            safe_lightstyles: false,
+        }
+    }
+}
+
+impl Accessibility {
+    /// Runs a console command: `a11y_reduced_motion <0|1>`,
+    /// `a11y_high_contrast <0|1>`, `a11y_safe_lightstyles <0|1>` or
+    /// `a11y_ui_scale <scale>`.
+    ///
+    /// ```
+    /// # use q2_viewer::accessibility::Accessibility;
+    /// let mut settings = Accessibility::default();
+    /// settings.run_command("a11y_ui_scale 1.5").unwrap();
+    /// settings.run_command("a11y_reduced_motion 1").unwrap();
+    /// assert_eq!(settings.ui_scale, 1.5);
+    /// assert!(settings.reduced_motion);
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let (Some(name), Some(value), None) = (words.next(), words.next(), words.next()) else {
+            return Err(match line.split_whitespace().next() {
+                Some(name) if name.starts_with("a11y_") => format!("usage: {} <value>", name),
+                _ => format!("unknown command {:?}", line),
+            });
+        };
+        let flag = match value {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(format!("usage: {} <0|1>", name)),
+        };
+        match name {
+            "a11y_reduced_motion" => self.reduced_motion = flag?,
+            "a11y_high_contrast" => self.high_contrast = flag?,
+            "a11y_safe_lightstyles" => self.safe_lightstyles = flag?,
+            "a11y_ui_scale" => {
+                self.ui_scale = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|scale| (0.25..=4.0).contains(scale))
+                    .ok_or_else(|| "usage: a11y_ui_scale <0.25 to 4>".to_string())?
+            }
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// Brightness of a Quake lightstyle `pattern` at `time` seconds, where `a` is
+/// dark, `m` normal and `y` double brightness, stepping at [LIGHTSTYLE_HZ].
+///
+/// With `safe` set, the pattern's average brightness is held instead: the
+/// fast flicker of many styles flashes more often than the three times a
+/// second that is safe for photosensitive viewers.
+///
+/// ```
+/// # use q2_viewer::accessibility::lightstyle_brightness;
+/// assert_eq!(lightstyle_brightness("ay", 0.0, false), 0.0);
+/// assert_eq!(lightstyle_brightness("ay", 0.1, false), 2.0);
+/// assert_eq!(lightstyle_brightness("ay", 0.1, true), 1.0);
+/// ```
+pub fn lightstyle_brightness(pattern: &str, time: f32, safe: bool) -> f32 {
+    let steps: Vec<f32> = pattern
+        .bytes()
+        .map(|step| step.clamp(b'a', b'z').saturating_sub(b'a') as f32 / 12.0)
+        .collect();
+    if steps.is_empty() {
+        return 1.0;
+    }
+    if safe {
+        return steps.iter().sum::<f32>() / steps.len() as f32;
+    }
+    let step = (time * LIGHTSTYLE_HZ).max(0.0) as usize % steps.len();
+    steps[step]
+}
+
+fn apply_ui_scale(settings: Res<Accessibility>, mut scale: ResMut<UiScale>) {
+    scale.0 = settings.ui_scale;
+}
+
+fn apply_hud_theme(
+    settings: Res<Accessibility>,
+    mut hud: Query<(&mut Text, &mut BackgroundColor), With<FpsText>>,
+) {
+    let (color, background) = match settings.high_contrast {
+        true => (Color::WHITE, Color::srgba(0.0, 0.0, 0.0, 0.85)),
+        false => (Color::srgb(0.7, 0.5, 0.1), Color::NONE),
+    };
+    for (mut text, mut background_color) in hud.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+        background_color.0 = background;
+    }
+}
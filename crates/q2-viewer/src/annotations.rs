@@ -0,0 +1,468 @@
+//! Review notes placed in the world: a text, and optionally a screenshot,
+//! at the point the camera looks at, so level designers can collect
+//! feedback inside the viewer.
+//!
+//! On native, each map's notes are kept in a JSON sidecar,
+//! `notes/<map>.json`, loaded with the map and saved as they change, with
+//! their screenshots next to it. On the web, notes are exported and
+//! imported as the same JSON through [notes_json_js] and
+//! [notes_import_js].
+
+use std::{path::Path, sync::Mutex};
+
+use bevy::{
+    prelude::*, render::view::screenshot::ScreenshotManager, transform::TransformSystem,
+    window::PrimaryWindow,
+};
+use bevy_mod_raycast::prelude::{Raycast, RaycastSettings, RaycastVisibility};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    asset::MapSummary, maps::MapManager, render::ScaledView, sim::Interpolated, start::WorldBatch,
+};
+
+/// Folder the sidecars and screenshots are written to, relative to the
+/// working directory.
+const NOTES_DIR: &str = "notes";
+
+/// Distance in front of the camera a note goes when it looks at nothing.
+const NOTE_DISTANCE: f32 = 64.0;
+
+/// Height above its point a note's text is drawn at.
+const LABEL_HEIGHT: f32 = 12.0;
+
+/// Notes as JSON, from JavaScript, added on the next frame.
+static IMPORTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The notes of the current map as JSON, for [notes_json_js].
+static LATEST: Mutex<String> = Mutex::new(String::new());
+
+/// Places, shows and keeps [Annotations].
+///
+/// The console takes `note <text>` and `note_shot <text>`, which also saves
+/// a screenshot, downloaded on the web, `note_delete <n>`, `notes_clear`,
+/// `notes_show <0|1>`, and on native `notes_export <file>` and
+/// `notes_import <file>`.
+pub struct AnnotationPlugin;
+
+impl Plugin for AnnotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Annotations>()
+            .add_systems(
+                Update,
+                (
+                    switch_notes,
+                    import_notes,
+                    place_notes,
+                    save_notes,
+                    spawn_note_labels,
+                    draw_notes,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                update_note_labels.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// A note placed in a map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Where the note is, in map coordinates.
+    pub position: [f32; 3],
+    pub text: String,
+    /// File name of the screenshot taken with the note, next to the
+    /// sidecar.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screenshot: Option<String>,
+}
+
+/// The notes of a map, as saved to its sidecar and exported.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationFile {
+    /// Map name, the asset path without the `.bsp` extension.
+    pub map: String,
+    pub notes: Vec<Annotation>,
+}
+
+impl AnnotationFile {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// ```
+    /// # use q2_viewer::annotations::{Annotation, AnnotationFile};
+    /// let file = AnnotationFile {
+    ///     map: "maps/base1".to_string(),
+    ///     notes: vec![Annotation {
+    ///         position: [64.0, 0.0, 24.0],
+    ///         text: "Lift is too slow".to_string(),
+    ///         screenshot: None,
+    ///     }],
+    /// };
+    /// assert_eq!(AnnotationFile::from_json(&file.to_json()), Ok(file));
+    /// assert!(AnnotationFile::from_json("{}").is_err());
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| err.to_string())
+    }
+}
+
+/// The notes of the current map.
+#[derive(Resource, Debug, Clone)]
+pub struct Annotations {
+    pub show: bool,
+    pub file: AnnotationFile,
+    /// Notes asked for by the console, placed on the next frame, with
+    /// whether to take a screenshot.
+    pending: Vec<(String, bool)>,
+    /// Whether the notes changed since they were saved.
+    dirty: bool,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self {
+            show: true,
+            file: AnnotationFile::default(),
+            pending: Vec::new(),
+            dirty: false,
+        }
+    }
+}
+
+impl Annotations {
+    /// Adds the notes of `file` that aren't already there.
+    pub fn import(&mut self, file: AnnotationFile) {
+        for note in file.notes {
+            if !self.file.notes.contains(&note) {
+                self.file.notes.push(note);
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Runs a console command, see [AnnotationPlugin].
+    ///
+    /// ```
+    /// # use q2_viewer::annotations::Annotations;
+    /// let mut notes = Annotations::default();
+    /// notes.run_command("note Needs more light").unwrap();
+    /// assert!(notes.run_command("note_delete 1").is_err());
+    /// assert!(notes.run_command("notes_show maybe").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match (name, rest) {
+            ("note" | "note_shot", "") => return Err(format!("usage: {} <text>", name)),
+            ("note", text) => self.pending.push((text.to_string(), false)),
+            ("note_shot", text) => self.pending.push((text.to_string(), true)),
+            ("note_delete", n) => {
+                let index = n
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n >= 1 && n <= self.file.notes.len())
+                    .ok_or_else(|| {
+                        format!("usage: note_delete <1 to {}>", self.file.notes.len())
+                    })?;
+                self.file.notes.remove(index - 1);
+                self.dirty = true;
+            }
+            ("notes_clear", "") => {
+                self.file.notes.clear();
+                self.dirty = true;
+            }
+            ("notes_show", "0") => self.show = false,
+            ("notes_show", "1") => self.show = true,
+            ("notes_show", _) => return Err("usage: notes_show <0|1>".to_string()),
+            ("notes_export" | "notes_import", "") => return Err(format!("usage: {} <file>", name)),
+            ("notes_export", path) => write_file(path, &self.file.to_json())?,
+            ("notes_import", path) => {
+                let json = read_file(path)?;
+                self.import(
+                    AnnotationFile::from_json(&json).map_err(|err| format!("{}: {}", path, err))?,
+                );
+            }
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_file(path: &str, text: &str) -> Result<(), String> {
+    std::fs::write(path, text).map_err(|err| format!("{}: {}", path, err))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_file(path: &str) -> Result<String, String> {
+    Err(format!("{:?} needs a file system", path))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_file(path: &str, _text: &str) -> Result<(), String> {
+    Err(format!("{:?} needs a file system", path))
+}
+
+/// Path of the sidecar of `map`.
+#[cfg(not(target_arch = "wasm32"))]
+fn sidecar_path(map: &str) -> std::path::PathBuf {
+    Path::new(NOTES_DIR).join(format!("{}.json", map))
+}
+
+/// The notes of the current map, as JSON.
+#[wasm_bindgen(js_name = notes_json)]
+pub fn notes_json_js() -> String {
+    LATEST.lock().unwrap().clone()
+}
+
+/// Adds notes exported as JSON to the current map's on the next frame.
+#[wasm_bindgen(js_name = notes_import)]
+pub fn notes_import_js(json: &str) {
+    IMPORTS.lock().unwrap().push(json.to_string());
+}
+
+/// Swaps in the notes of each map as it becomes the current one.
+fn switch_notes(mut annotations: ResMut<Annotations>, maps: Res<MapManager>) {
+    let Some(map) = maps.current() else {
+        return;
+    };
+    if annotations.file.map == map {
+        return;
+    }
+    annotations.file = AnnotationFile {
+        map: map.to_string(),
+        notes: Vec::new(),
+    };
+    annotations.dirty = false;
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(json) = std::fs::read_to_string(sidecar_path(map)) {
+        match AnnotationFile::from_json(&json) {
+            Ok(file) => annotations.file.notes = file.notes,
+            Err(err) => warn!("{}: {}", sidecar_path(map).display(), err),
+        }
+    }
+    *LATEST.lock().unwrap() = annotations.file.to_json();
+}
+
+fn import_notes(mut annotations: ResMut<Annotations>) {
+    let imports = std::mem::take(&mut *IMPORTS.lock().unwrap());
+    for json in imports {
+        match AnnotationFile::from_json(&json) {
+            Ok(file) => annotations.import(file),
+            Err(err) => warn!("Could not import notes: {}", err),
+        }
+    }
+}
+
+/// Places the notes asked for at the world geometry in the middle of the
+/// view.
+#[allow(clippy::too_many_arguments)]
+fn place_notes(
+    mut annotations: ResMut<Annotations>,
+    mut raycast: Raycast,
+    mut screenshots: ResMut<ScreenshotManager>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Interpolated>>,
+    batches: Query<(), With<WorldBatch>>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+) {
+    if annotations.pending.is_empty() {
+        return;
+    }
+    let Some(offset) = maps
+        .root()
+        .and_then(|root| summaries.get(root).ok())
+        .map(MapSummary::world_offset)
+    else {
+        return;
+    };
+    let Some((camera, transform)) = cameras.iter().next() else {
+        return;
+    };
+    let center = camera.logical_viewport_size().unwrap_or_default() / 2.0;
+    let Some(ray) = camera.viewport_to_world(transform, center) else {
+        return;
+    };
+    let is_world = |entity| batches.contains(entity);
+    let settings = RaycastSettings::default()
+        .with_visibility(RaycastVisibility::Ignore)
+        .with_filter(&is_world);
+    let point = match raycast.cast_ray(ray, &settings).first() {
+        Some((_, hit)) => hit.position(),
+        None => ray.get_point(NOTE_DISTANCE),
+    };
+
+    for (text, screenshot) in std::mem::take(&mut annotations.pending) {
+        let screenshot = match (screenshot, windows.iter().next()) {
+            (true, Some(window)) => save_screenshot(&mut screenshots, window, &annotations.file),
+            _ => None,
+        };
+        info!("Note {}: {}", annotations.file.notes.len() + 1, text);
+        annotations.file.notes.push(Annotation {
+            position: (point - offset).to_array(),
+            text,
+            screenshot,
+        });
+        annotations.dirty = true;
+    }
+}
+
+/// Saves a screenshot next to the sidecar of the notes in `file`, or on
+/// the web downloads it, returning its file name.
+fn save_screenshot(
+    screenshots: &mut ScreenshotManager,
+    window: Entity,
+    file: &AnnotationFile,
+) -> Option<String> {
+    let dir = Path::new(NOTES_DIR).join(&file.map);
+    let stem = dir.file_name()?.to_string_lossy().into_owned();
+    let dir = dir.parent()?;
+    let taken = |name: &String| {
+        file.notes
+            .iter()
+            .any(|note| note.screenshot.as_ref() == Some(name))
+            || dir.join(name).exists()
+    };
+    let name = (file.notes.len() + 1..)
+        .map(|n| format!("{}-{}.png", stem, n))
+        .find(|name| !taken(name))?;
+    // Fails on the web, which has no folders
+    let _ = std::fs::create_dir_all(dir);
+    match screenshots.save_screenshot_to_disk(window, dir.join(&name)) {
+        Ok(()) => Some(name),
+        Err(err) => {
+            warn!("Could not take a screenshot: {}", err);
+            None
+        }
+    }
+}
+
+/// Writes the notes to the sidecar as they change, and publishes them to
+/// JavaScript.
+fn save_notes(mut annotations: ResMut<Annotations>) {
+    if !annotations.dirty || annotations.file.map.is_empty() {
+        return;
+    }
+    annotations.dirty = false;
+    let json = annotations.file.to_json();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let path = sidecar_path(&annotations.file.map);
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, &json));
+        if let Err(err) = written {
+            warn!("{}: {}", path.display(), err);
+        }
+    }
+    *LATEST.lock().unwrap() = json;
+}
+
+/// The text of a note, drawn over its point.
+#[derive(Component, Debug, Clone)]
+pub struct NoteLabel {
+    /// World position the label is drawn at.
+    pub position: Vec3,
+}
+
+/// Spawns a label for each note, anew whenever they change.
+fn spawn_note_labels(
+    mut commands: Commands,
+    annotations: Res<Annotations>,
+    labels: Query<Entity, With<NoteLabel>>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    new_maps: Query<(), Added<MapSummary>>,
+) {
+    if !annotations.is_changed() && new_maps.is_empty() {
+        return;
+    }
+    for entity in labels.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(offset) = maps
+        .root()
+        .and_then(|root| summaries.get(root).ok())
+        .map(MapSummary::world_offset)
+    else {
+        return;
+    };
+    for (i, note) in annotations.file.notes.iter().enumerate() {
+        let text = TextBundle::from_section(
+            format!("{}. {}", i + 1, note.text),
+            TextStyle {
+                font_size: 15.0,
+                color: Color::srgb(1.0, 0.85, 0.4),
+                ..default()
+            },
+        )
+        .with_text_justify(JustifyText::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        });
+        commands.spawn((
+            TextBundle {
+                visibility: Visibility::Hidden,
+                ..text
+            },
+            NoteLabel {
+                position: Vec3::from(note.position) + offset + Vec3::Z * LABEL_HEIGHT,
+            },
+        ));
+    }
+}
+
+/// Marks the point of each note.
+fn draw_notes(annotations: Res<Annotations>, labels: Query<&NoteLabel>, mut gizmos: Gizmos) {
+    if !annotations.show {
+        return;
+    }
+    let color = Color::srgb(1.0, 0.85, 0.4);
+    for label in labels.iter() {
+        let point = label.position - Vec3::Z * LABEL_HEIGHT;
+        gizmos.sphere(point, Quat::IDENTITY, 4.0, color);
+        gizmos.line(point, label.position, color);
+    }
+}
+
+/// Places each label over its note on screen.
+fn update_note_labels(
+    annotations: Res<Annotations>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Interpolated>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<ScaledView>,
+    mut labels: Query<(&NoteLabel, &Node, &mut Style, &mut Visibility)>,
+) {
+    let (Some((camera, camera_transform)), Some(window)) =
+        (cameras.iter().next(), windows.iter().next())
+    else {
+        return;
+    };
+    for (label, node, mut style, mut visibility) in labels.iter_mut() {
+        let Some(screen) = camera
+            .world_to_viewport(camera_transform, label.position)
+            .filter(|_| annotations.show)
+        else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        let screen = view.to_window(window, screen);
+        let size = node.size();
+        style.left = Val::Px(screen.x - size.x / 2.0);
+        style.top = Val::Px(screen.y - size.y);
+    }
+}
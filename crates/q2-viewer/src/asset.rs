@@ -0,0 +1,424 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    utils::Instant,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use q2_formats::bsp38::{
+    prelude::{
+        Bounds, BspError, FaceBatch, LightmapAtlas, MeshBuilder, MeshOptions, OcclusionOptions,
+        ParseOptions,
+    },
+    LumpIndex, BSP38,
+};
+
+pub use q2_formats::bsp38::prelude::CollisionMesh;
+
+/// A loaded map, with the artifacts the loader was asked to build from it.
+///
+/// Simple consumers can spawn [BSP38Asset::meshes] and use
+/// [BSP38Asset::collision] without touching the raw [BSP38].
+///
+/// The asset is reflected without the raw map data, so inspectors can browse
+/// [BSP38Asset::summary]. It can't be created through reflection, so it has
+/// no `ReflectAsset`; the summary is also put on the map's
+/// [MapInstance](crate::maps::MapInstance) entity for entity inspectors.
+#[derive(Asset, Reflect, Debug)]
+#[reflect(from_reflect = false)]
+pub struct BSP38Asset {
+    #[reflect(ignore)]
+    pub bsp: BSP38,
+    pub summary: MapSummary,
+    /// World geometry, one mesh per texture batch. Empty if
+    /// [BSP38LoaderSettings::meshes] is off.
+    pub meshes: Vec<WorldMesh>,
+    /// Geometry of the inline models `*1`, `*2`, ..., one mesh per texture,
+    /// starting with `*1`. Empty if [BSP38LoaderSettings::meshes] is off.
+    pub inline_models: Vec<Vec<WorldMesh>>,
+    /// The grid the world meshes were split along, see
+    /// [BSP38LoaderSettings::chunk_size].
+    pub chunk_size: Option<f32>,
+    /// The settings ambient occlusion was baked into the world meshes with,
+    /// `None` if [BSP38LoaderSettings::ambient_occlusion] is off.
+    #[reflect(ignore)]
+    pub occlusion: Option<OcclusionOptions>,
+    /// Lightmap atlas pages of the world meshes, whose lightmap UVs point
+    /// into them. Empty if [BSP38LoaderSettings::lightmaps] is off.
+    pub lightmaps: Vec<Handle<Image>>,
+    /// Where the lightmap of each face lies in [BSP38Asset::lightmaps]. The
+    /// luxels themselves have been moved into the images.
+    #[reflect(ignore)]
+    pub lightmap_atlas: Option<LightmapAtlas>,
+    #[reflect(ignore)]
+    pub collision: Option<CollisionMesh>,
+    /// Time spent in each stage of the loader.
+    #[reflect(ignore)]
+    pub timings: LoadTimings,
+}
+
+impl BSP38Asset {
+    /// Translation from map coordinates to the world, centering the map
+    /// horizontally on the origin.
+    pub fn world_offset(&self) -> Vec3 {
+        let bounds = self.bsp.bounds();
+        centering_offset(Vec3::from(bounds.min), Vec3::from(bounds.max))
+    }
+}
+
+fn centering_offset(min: Vec3, max: Vec3) -> Vec3 {
+    Vec3::new(-(min.x + max.x) / 2.0, -(min.y + max.y) / 2.0, 0.0)
+}
+
+impl std::fmt::Display for BSP38Asset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.bsp, f)
+    }
+}
+
+/// A world mesh holding the faces of one texture in one or more PVS clusters.
+#[derive(Debug, Clone, Reflect)]
+pub struct WorldMesh {
+    pub mesh: Handle<Mesh>,
+    pub texture: String,
+    pub clusters: Vec<i16>,
+    /// The grid cell of the faces, see [BSP38LoaderSettings::chunk_size].
+    pub chunk: Option<[i32; 3]>,
+    /// Whether the faces are see-through, drawn blended with the alpha of
+    /// their [SurfaceFlags](q2_formats::bsp38::surface::SurfaceFlags).
+    pub translucent: bool,
+}
+
+/// Metadata of a map, in map coordinates.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct MapSummary {
+    pub version: u32,
+    pub bounds_min: Vec3,
+    pub bounds_max: Vec3,
+    pub num_vertices: usize,
+    pub num_faces: usize,
+    pub num_triangles: usize,
+    pub num_leafs: usize,
+    pub textures: Vec<TextureSummary>,
+    pub entities: Vec<EntitySummary>,
+}
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct TextureSummary {
+    pub name: String,
+    pub faces: usize,
+    pub flags: u32,
+}
+
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct EntitySummary {
+    pub classname: String,
+    pub targetname: Option<String>,
+    pub origin: Option<Vec3>,
+    /// Yaw in degrees, from the `angle` key.
+    pub angle: Option<f32>,
+    /// Inline model the entity is drawn with, from the `model` key.
+    pub model: Option<usize>,
+}
+
+impl MapSummary {
+    pub fn new(bsp: &BSP38) -> Result<Self, BspError> {
+        let bounds = bsp.bounds();
+        let faces = bsp.read_face_records()?;
+        let lump_count = |lump: LumpIndex| bsp.lump_table()[lump as usize].count.unwrap_or(0);
+        Ok(Self {
+            version: bsp.version,
+            bounds_min: Vec3::from(bounds.min),
+            bounds_max: Vec3::from(bounds.max),
+            num_vertices: lump_count(LumpIndex::Vertices),
+            num_faces: faces.len(),
+            num_triangles: faces
+                .iter()
+                .map(|face| (face.num_edges as usize).saturating_sub(2))
+                .sum(),
+            num_leafs: lump_count(LumpIndex::Leafs),
+            textures: bsp
+                .unique_textures()
+                .into_iter()
+                .map(|texture| TextureSummary {
+                    name: texture.name,
+                    faces: texture.faces,
+                    flags: texture.flags,
+                })
+                .collect(),
+            entities: bsp
+                .read_entities()?
+                .into_iter()
+                .map(|entity| EntitySummary {
+                    targetname: entity.targetname().map(str::to_string),
+                    origin: entity.origin().map(Vec3::from),
+                    angle: entity.angle(),
+                    model: entity.model(),
+                    classname: entity.classname,
+                })
+                .collect(),
+        })
+    }
+
+    /// Where the player enters the map: the `info_player_start` without a
+    /// `targetname`, as in single player, else any start, deathmatch start
+    /// or coop start, in that order.
+    pub fn player_start(&self) -> Option<&EntitySummary> {
+        let starts = || {
+            self.entities
+                .iter()
+                .filter(|entity| entity.origin.is_some())
+        };
+        let of_class =
+            |classname: &'static str| starts().find(move |entity| entity.classname == classname);
+        starts()
+            .find(|entity| entity.classname == "info_player_start" && entity.targetname.is_none())
+            .or_else(|| of_class("info_player_start"))
+            .or_else(|| of_class("info_player_deathmatch"))
+            .or_else(|| of_class("info_player_coop"))
+    }
+
+    /// Translation from map coordinates to the world, the same as
+    /// [BSP38Asset::world_offset].
+    pub fn world_offset(&self) -> Vec3 {
+        centering_offset(self.bounds_min, self.bounds_max)
+    }
+
+    /// Bounds of the map once spawned, moved by [MapSummary::world_offset].
+    pub fn world_bounds(&self) -> Bounds {
+        let offset = self.world_offset();
+        Bounds {
+            min: (self.bounds_min + offset).to_array(),
+            max: (self.bounds_max + offset).to_array(),
+        }
+    }
+}
+
+/// Which artifacts [BSP38AssetLoader] builds besides the parsed map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BSP38LoaderSettings {
+    pub meshes: bool,
+    pub collision: bool,
+    /// World batches with fewer triangles than this are merged with the other
+    /// small batches of the same texture.
+    pub batch_merge_triangles: usize,
+    /// Split the world batches along a grid of cubes this many units wide,
+    /// so Bevy can frustum cull the parts of the map out of view even where
+    /// the PVS can't.
+    pub chunk_size: Option<f32>,
+    /// Bake ambient occlusion into the vertex colors of the world meshes, so
+    /// the untextured preview shows corners and crevices.
+    pub ambient_occlusion: bool,
+    /// Extract the baked lightmaps into atlas images and give the world
+    /// meshes lightmap UVs. Maps without a Lighting lump have none.
+    pub lightmaps: bool,
+}
+
+impl Default for BSP38LoaderSettings {
+    fn default() -> Self {
+        Self {
+            meshes: true,
+            collision: true,
+            batch_merge_triangles: 64,
+            chunk_size: Some(1024.0),
+            ambient_occlusion: true,
+            lightmaps: true,
+        }
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum BSP38AssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid BSP: {0}")]
+    Bsp(#[from] BspError),
+}
+
+#[derive(Default)]
+pub struct BSP38AssetLoader;
+
+impl AssetLoader for BSP38AssetLoader {
+    type Asset = BSP38Asset;
+    type Settings = BSP38LoaderSettings;
+    type Error = BSP38AssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        settings: &'a BSP38LoaderSettings,
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        match reader.read_to_end(&mut bytes).await {
+            Ok(_) => {}
+            Err(e) => return Err(BSP38AssetLoaderError::from(e)),
+        };
+        let _span = info_span!("load_bsp", path = %load_context.path().display()).entered();
+        let mut timings = LoadTimings::default();
+
+        // Decode the PVS and entities here, off the main thread
+        let options = ParseOptions::new().eager(&[LumpIndex::Visibility, LumpIndex::Entities]);
+        let bsp = timings.time("parse", || BSP38::parse_with(bytes, &options))?;
+        // Fails for maps whose face or entity lumps can't be read
+        let summary = MapSummary::new(&bsp)?;
+        let num_models = bsp.read_models()?.len();
+
+        // Maps stripped of their lighting are drawn without lightmaps
+        let lit = settings.lightmaps && bsp.has_lump(LumpIndex::Lighting);
+        let mut lightmap_atlas = lit.then(|| timings.time("lightmaps", || bsp.read_lightmaps()));
+        let mut lightmaps = Vec::new();
+        if let Some(atlas) = &mut lightmap_atlas {
+            for (i, luxels) in std::mem::take(&mut atlas.pages).into_iter().enumerate() {
+                let image = lightmap_image(atlas.page_size, luxels);
+                lightmaps.push(load_context.add_labeled_asset(format!("lightmap{}", i), image));
+            }
+        }
+
+        let occlusion = settings.ambient_occlusion.then(OcclusionOptions::default);
+        let mut meshes = Vec::new();
+        let mut inline_models = Vec::new();
+        if settings.meshes {
+            let (mut batches, mut models) = timings.time("mesh", || {
+                let mut builder = match &lightmap_atlas {
+                    Some(atlas) => MeshBuilder::new().with_lightmaps(atlas),
+                    None => MeshBuilder::new(),
+                };
+                if let Some(size) = settings.chunk_size {
+                    builder = builder.with_chunks(size);
+                }
+                let mut batches = builder.build_batches(&bsp, settings.batch_merge_triangles);
+                batches.extend(builder.build_translucent_batches(&bsp));
+                let models: Vec<Vec<FaceBatch>> = (1..num_models)
+                    .map(|model| builder.build_model_batches(&bsp, model))
+                    .collect();
+                (batches, models)
+            });
+            if let Some(options) = &occlusion {
+                timings.time("ao", || {
+                    let tracer = bsp.tracer();
+                    for batch in batches.iter_mut().chain(models.iter_mut().flatten()) {
+                        batch.data.bake_occlusion(&tracer, options);
+                    }
+                });
+            }
+            meshes = world_meshes(load_context, "batch", batches, settings);
+            for (i, batches) in models.into_iter().enumerate() {
+                let label = format!("model{}_batch", i + 1);
+                inline_models.push(world_meshes(load_context, &label, batches, settings));
+            }
+        }
+
+        let collision = settings
+            .collision
+            .then(|| {
+                timings.time("collision", || {
+                    bsp.read_faces()
+                        .map(|data| CollisionMesh::from_face_data(&data))
+                })
+            })
+            .transpose()?;
+
+        Ok(BSP38Asset {
+            summary,
+            bsp,
+            meshes,
+            inline_models,
+            chunk_size: settings.chunk_size,
+            occlusion,
+            lightmaps,
+            lightmap_atlas,
+            collision,
+            timings,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bsp"]
+    }
+}
+
+/// Adds the meshes of `batches` to the asset, labeled `<label><index>`.
+fn world_meshes(
+    load_context: &mut LoadContext,
+    label: &str,
+    batches: Vec<FaceBatch>,
+    settings: &BSP38LoaderSettings,
+) -> Vec<WorldMesh> {
+    batches
+        .into_iter()
+        .enumerate()
+        .map(|(i, batch)| {
+            // Without baked occlusion the colors are the debug palette,
+            // which would tint the flat material
+            let mesh = batch.data.into_mesh(&MeshOptions {
+                colors: settings.ambient_occlusion,
+                ..default()
+            });
+            WorldMesh {
+                mesh: load_context.add_labeled_asset(format!("{}{}", label, i), mesh),
+                texture: batch.texture,
+                clusters: batch.clusters,
+                chunk: batch.chunk,
+                translucent: batch.translucent,
+            }
+        })
+        .collect()
+}
+
+/// An atlas page of `size` by `size` RGBA luxels.
+fn lightmap_image(size: u32, luxels: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        luxels,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::linear();
+    image
+}
+
+/// Wall-clock time of each map loading stage, for the stats overlay. Also
+/// on the map's [MapInstance](crate::maps::MapInstance) entity once its
+/// world has spawned, with the stages of spawning it.
+#[derive(Component, Debug, Clone, Default)]
+pub struct LoadTimings(pub Vec<(&'static str, Duration)>);
+
+impl LoadTimings {
+    /// Runs `f` in a tracing span for `stage` and records how long it took.
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let _span = info_span!("load_stage", stage).entered();
+        let start = Instant::now();
+        let out = f();
+        self.0.push((stage, start.elapsed()));
+        out
+    }
+}
+
+impl std::fmt::Display for LoadTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (stage, time)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {:.1} ms", stage, time.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    audio::{AddAudioSource, Decodable, Source},
+    prelude::*,
+};
+
+use q2_formats::bsp38::{prelude::Tracer, surface::SURF_SKY, BSP38};
+
+use crate::{asset::BSP38Asset, maps::MapInstance};
+
+/// Longest echo delay, in seconds. Bounds the echo buffer of each sound.
+const MAX_DELAY: f32 = 0.25;
+
+/// How quickly the listener's reverb fades to the preset of a new space, per
+/// second.
+const BLEND_RATE: f32 = 4.0;
+
+/// Reverb for positional audio from the spaces of the map.
+///
+/// Each PVS cluster gets a [ReverbPreset] from the volume of its leafs and
+/// how much of it is open to the sky. As the [SpatialListener] moves between
+/// clusters, every [ReverbSound] that is playing fades to the new preset.
+/// Spawn sounds with a [ReverbAudio] component to play them through it:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use q2_viewer::audio::ReverbAudio;
+/// fn spawn_hum(mut commands: Commands, asset_server: Res<AssetServer>) {
+///     commands.spawn((
+///         ReverbAudio(asset_server.load("sound/world/amb10.ogg")),
+///         PlaybackSettings::LOOP.with_spatial(true),
+///         SpatialBundle::from_transform(Transform::from_xyz(0.0, 0.0, 64.0)),
+///     ));
+/// }
+/// ```
+pub struct ReverbZonePlugin;
+
+impl Plugin for ReverbZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<ReverbSound>()
+            .init_resource::<ListenerReverb>()
+            .add_systems(
+                Update,
+                (setup_reverb_zones, update_listener_reverb, attach_reverb),
+            );
+    }
+}
+
+/// Reverb of a space: an echo `delay` seconds late, fed back into itself
+/// scaled by `feedback` and mixed into the sound scaled by `wet`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Reflect)]
+pub struct ReverbPreset {
+    pub delay: f32,
+    pub feedback: f32,
+    pub wet: f32,
+}
+
+impl ReverbPreset {
+    /// No reverb, used outside of any cluster.
+    pub const DRY: Self = Self {
+        delay: 0.02,
+        feedback: 0.0,
+        wet: 0.0,
+    };
+
+    /// A preset for a space of `volume` cubic map units, where `openness` is
+    /// the share of its surfaces open to the sky, from 0 to 1.
+    ///
+    /// Bigger spaces echo later and longer; open ones hardly echo at all.
+    pub fn for_space(volume: f32, openness: f32) -> Self {
+        // Map units are roughly inches, and sound travels about 13500 inches
+        // a second
+        let size = volume.max(0.0).cbrt();
+        let closed = 1.0 - openness.clamp(0.0, 1.0);
+        Self {
+            delay: (2.0 * size / 13_500.0).clamp(Self::DRY.delay, MAX_DELAY),
+            feedback: (0.2 + size / 4096.0).min(0.7) * closed,
+            wet: 0.4 * closed,
+        }
+    }
+
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            delay: self.delay + (other.delay - self.delay) * t,
+            feedback: self.feedback + (other.feedback - self.feedback) * t,
+            wet: self.wet + (other.wet - self.wet) * t,
+        }
+    }
+}
+
+/// The reverb preset of every leaf of a map, on its [MapInstance] entity.
+#[derive(Component)]
+pub struct ReverbZones {
+    tracer: Tracer,
+    /// Map coordinates of the world origin, see [BSP38Asset::world_offset].
+    offset: Vec3,
+    /// Preset of each leaf, from its cluster. None for leafs outside of any
+    /// cluster.
+    leafs: Vec<Option<ReverbPreset>>,
+}
+
+impl ReverbZones {
+    pub fn new(bsp: &BSP38, offset: Vec3) -> Self {
+        let leafs = bsp.read_leafs().unwrap_or_default();
+        let leaf_faces = bsp.read_leaf_faces().unwrap_or_default();
+        let faces = bsp.read_face_records().unwrap_or_default();
+        let tex_info = bsp.read_texture_info().unwrap_or_default();
+        let is_sky = |face: usize| {
+            faces
+                .get(face)
+                .and_then(|face| tex_info.get(face.texinfo as usize))
+                .is_some_and(|tex| tex.flags & SURF_SKY != 0)
+        };
+
+        // (volume, sky faces, faces) of each cluster
+        let num_clusters = leafs.iter().map(|l| l.cluster + 1).max().unwrap_or(0) as usize;
+        let mut spaces = vec![(0.0f32, 0usize, 0usize); num_clusters];
+        for leaf in &leafs {
+            let Some(space) = usize::try_from(leaf.cluster)
+                .ok()
+                .and_then(|c| spaces.get_mut(c))
+            else {
+                continue;
+            };
+            space.0 += (0..3)
+                .map(|i| (leaf.maxs[i] as f32 - leaf.mins[i] as f32).max(0.0))
+                .product::<f32>();
+            for face in leaf.faces(&leaf_faces) {
+                space.1 += usize::from(is_sky(face));
+                space.2 += 1;
+            }
+        }
+        let presets: Vec<ReverbPreset> = spaces
+            .iter()
+            .map(|&(volume, sky, faces)| {
+                ReverbPreset::for_space(volume, sky as f32 / faces.max(1) as f32)
+            })
+            .collect();
+
+        Self {
+            tracer: bsp.tracer(),
+            offset,
+            leafs: leafs
+                .iter()
+                .map(|leaf| {
+                    usize::try_from(leaf.cluster)
+                        .ok()
+                        .and_then(|c| presets.get(c).copied())
+                })
+                .collect(),
+        }
+    }
+
+    /// The preset of the cluster at `point`, in the map's local space, or
+    /// None outside of any cluster.
+    pub fn preset_at(&self, point: Vec3) -> Option<ReverbPreset> {
+        let leaf = self.tracer.leaf_at((point - self.offset).to_array())?;
+        self.leafs.get(leaf).copied().flatten()
+    }
+}
+
+/// The reverb preset applied to every [ReverbSound], following the listener.
+#[derive(Resource, Default)]
+pub struct ListenerReverb {
+    /// The preset being heard, fading towards the preset of the listener's
+    /// cluster.
+    pub preset: ReverbPreset,
+    shared: Arc<SharedReverb>,
+}
+
+/// A preset shared with the audio thread, one atomic per parameter.
+#[derive(Default)]
+struct SharedReverb([AtomicU32; 3]);
+
+impl SharedReverb {
+    fn get(&self) -> ReverbPreset {
+        let [delay, feedback, wet] = self
+            .0
+            .each_ref()
+            .map(|v| f32::from_bits(v.load(Ordering::Relaxed)));
+        ReverbPreset {
+            delay,
+            feedback,
+            wet,
+        }
+    }
+
+    fn set(&self, preset: ReverbPreset) {
+        for (v, value) in self
+            .0
+            .iter()
+            .zip([preset.delay, preset.feedback, preset.wet])
+        {
+            v.store(value.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// A sound to play through the listener's reverb once it has loaded.
+#[derive(Component)]
+pub struct ReverbAudio(pub Handle<AudioSource>);
+
+/// A loaded sound played through the listener's reverb.
+#[derive(Asset, TypePath)]
+pub struct ReverbSound {
+    pub source: AudioSource,
+    params: Arc<SharedReverb>,
+}
+
+impl Decodable for ReverbSound {
+    type DecoderItem = f32;
+    type Decoder = ReverbDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        let input = self.source.decoder();
+        let channels = input.channels();
+        let sample_rate = input.sample_rate();
+        let len = (MAX_DELAY * sample_rate as f32) as usize * channels as usize + channels as usize;
+        ReverbDecoder {
+            input,
+            params: self.params.clone(),
+            channels,
+            sample_rate,
+            echo: vec![0.0; len],
+            pos: 0,
+            // Let the echo ring out for a while after the sound ends
+            tail: 4 * len,
+        }
+    }
+}
+
+/// Mixes a feedback echo into a decoded sound, with the parameters read from
+/// the shared preset for every sample.
+pub struct ReverbDecoder {
+    input: <AudioSource as Decodable>::Decoder,
+    params: Arc<SharedReverb>,
+    channels: u16,
+    sample_rate: u32,
+    /// Ring buffer of interleaved echo samples.
+    echo: Vec<f32>,
+    pos: usize,
+    tail: usize,
+}
+
+impl Iterator for ReverbDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let dry = match self.input.next() {
+            Some(sample) => f32::from(sample) / 32768.0,
+            None if self.tail > 0 => {
+                self.tail -= 1;
+                0.0
+            }
+            None => return None,
+        };
+        let preset = self.params.get();
+        let channels = self.channels as usize;
+        let len = self.echo.len();
+        // Whole frames, so each channel echoes itself
+        let delay = ((preset.delay * self.sample_rate as f32) as usize * channels)
+            .clamp(channels, len - channels);
+        let delayed = self.echo[(self.pos + len - delay) % len];
+        self.echo[self.pos] = dry + delayed * preset.feedback;
+        self.pos = (self.pos + 1) % len;
+        Some(dry + delayed * preset.wet)
+    }
+}
+
+impl Source for ReverbDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Builds the reverb zones of each map instance once its world is spawned.
+fn setup_reverb_zones(
+    mut commands: Commands,
+    instances: Query<(Entity, &MapInstance), Changed<MapInstance>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    for (root, instance) in instances.iter() {
+        if !instance.spawned {
+            continue;
+        }
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        let zones = ReverbZones::new(&asset.bsp, asset.world_offset());
+        commands.entity(root).insert(zones);
+    }
+}
+
+/// Fades the shared preset towards the preset of the listener's cluster.
+fn update_listener_reverb(
+    mut reverb: ResMut<ListenerReverb>,
+    listeners: Query<&GlobalTransform, With<SpatialListener>>,
+    maps: Query<(&ReverbZones, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    let Some(listener) = listeners.iter().next() else {
+        return;
+    };
+    let target = maps
+        .iter()
+        .find_map(|(zones, transform)| {
+            let local = transform
+                .affine()
+                .inverse()
+                .transform_point3(listener.translation());
+            zones.preset_at(local)
+        })
+        .unwrap_or(ReverbPreset::DRY);
+
+    let t = (BLEND_RATE * time.delta_seconds()).min(1.0);
+    reverb.preset = reverb.preset.lerp(target, t);
+    reverb.shared.set(reverb.preset);
+}
+
+/// Starts playing [ReverbAudio] sounds once they have loaded.
+fn attach_reverb(
+    mut commands: Commands,
+    mut reverb_sounds: ResMut<Assets<ReverbSound>>,
+    reverb: Res<ListenerReverb>,
+    sounds: Query<(Entity, &ReverbAudio), Without<Handle<ReverbSound>>>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    for (entity, audio) in sounds.iter() {
+        let Some(source) = sources.get(&audio.0) else {
+            continue;
+        };
+        let sound = reverb_sounds.add(ReverbSound {
+            source: source.clone(),
+            params: reverb.shared.clone(),
+        });
+        commands.entity(entity).insert(sound);
+    }
+}
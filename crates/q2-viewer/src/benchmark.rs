@@ -0,0 +1,378 @@
+//! Benchmark runs: the camera follows the same scripted path through a map
+//! for a set time with culling on, and the frame times and load stages are
+//! reported as JSON, so renderer changes can be compared run to run.
+//!
+//! Started with the `benchmark` and `map` start options, from JavaScript:
+//!
+//! ```js
+//! const options = new mod.StartOptions();
+//! options.map = 'q2dm1';
+//! options.benchmark = 30;
+//! mod.start_with('app-canvas', options);
+//! ```
+//!
+//! The report is logged, and on native also printed to stdout and written to
+//! `benchmarks/<map>.json` before the app exits.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    asset::{LoadTimings, MapSummary},
+    framing::{frame_map, OVERVIEW_PITCH},
+    maps::MapManager,
+    render::PvsCulling,
+    sim::{interpolate, Interpolated},
+};
+
+/// Height of the player's eyes above a spawn point, as in the game.
+const VIEW_HEIGHT: f32 = 22.0;
+
+/// How far ahead along the path the camera looks, so it turns smoothly at
+/// the spawns.
+const LOOK_AHEAD: f32 = 128.0;
+
+/// Runs a [Benchmark] once the first map has spawned.
+pub struct BenchmarkPlugin {
+    /// Seconds to measure for.
+    pub seconds: f32,
+}
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Benchmark::new(self.seconds))
+            .add_systems(Update, (start_benchmark, advance_benchmark).chain())
+            .add_systems(PostUpdate, drive_camera.before(interpolate));
+    }
+}
+
+/// A benchmark in progress. It removes itself once its report is out.
+#[derive(Resource)]
+pub struct Benchmark {
+    /// Seconds to measure for, after the warmup.
+    pub seconds: f32,
+    /// Frames drawn along the start of the path before measuring, so
+    /// pipelines and textures have time to load.
+    pub warmup_frames: usize,
+    path: Option<BenchmarkPath>,
+    map: String,
+    load: LoadTimings,
+    frame: usize,
+    /// Real seconds measured so far.
+    elapsed: f32,
+    /// Real seconds each measured frame took.
+    frame_times: Vec<f32>,
+}
+
+impl Benchmark {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            seconds,
+            warmup_frames: 60,
+            path: None,
+            map: String::new(),
+            load: LoadTimings::default(),
+            frame: 0,
+            elapsed: 0.0,
+            frame_times: Vec::new(),
+        }
+    }
+
+    /// How far along the path the camera is, from 0 to 1.
+    fn progress(&self) -> f32 {
+        match self.seconds > 0.0 {
+            true => (self.elapsed / self.seconds).min(1.0),
+            false => 1.0,
+        }
+    }
+
+    fn report(&self) -> BenchmarkReport {
+        BenchmarkReport {
+            map: self.map.clone(),
+            seconds: self.elapsed,
+            frames: FrameStats::new(&self.frame_times),
+            load: self
+                .load
+                .0
+                .iter()
+                .map(|&(stage, time)| LoadStage {
+                    stage,
+                    ms: time.as_secs_f64() * 1000.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The camera's way through a map: a loop at eye height through its spawn
+/// points, or an orbit around the whole map if it has fewer than two.
+///
+/// Spawns are joined by straight lines, which can cross walls, but the path
+/// is the same on every run, which is what comparisons need.
+#[derive(Debug, Clone)]
+pub enum BenchmarkPath {
+    Spawns {
+        points: Vec<Vec3>,
+        /// Distance along the loop to each point, ending with its length.
+        distances: Vec<f32>,
+    },
+    Orbit(Vec<Transform>),
+}
+
+impl BenchmarkPath {
+    /// The path through `summary`'s map once spawned, framing the orbit for
+    /// `projection`.
+    pub fn new(summary: &MapSummary, projection: &PerspectiveProjection) -> Self {
+        let offset = summary.world_offset();
+        let spawns = |classname: &str| -> Vec<Vec3> {
+            summary
+                .entities
+                .iter()
+                .filter(|entity| entity.classname == classname)
+                .filter_map(|entity| entity.origin)
+                .map(|origin| origin + offset + Vec3::Z * VIEW_HEIGHT)
+                .collect()
+        };
+        let mut points = spawns("info_player_deathmatch");
+        if points.len() < 2 {
+            points.extend(spawns("info_player_start"));
+        }
+        if points.len() < 2 {
+            const STEPS: usize = 32;
+            let bounds = summary.world_bounds();
+            let keys = (0..=STEPS)
+                .map(|i| {
+                    let yaw = i as f32 / STEPS as f32 * TAU;
+                    frame_map(&bounds, OVERVIEW_PITCH, yaw, projection)
+                })
+                .collect();
+            return BenchmarkPath::Orbit(keys);
+        }
+        points.push(points[0]);
+        let mut distances = vec![0.0];
+        for pair in points.windows(2) {
+            distances.push(distances[distances.len() - 1] + pair[0].distance(pair[1]));
+        }
+        BenchmarkPath::Spawns { points, distances }
+    }
+
+    /// The camera transform `progress` of the way along the path, from 0 to
+    /// 1.
+    pub fn sample(&self, progress: f32) -> Transform {
+        match self {
+            BenchmarkPath::Spawns { points, distances } => {
+                let length = distances[distances.len() - 1];
+                let at = |distance: f32| -> Vec3 {
+                    let distance = distance.rem_euclid(length.max(f32::EPSILON));
+                    let next = distances
+                        .iter()
+                        .position(|&d| d > distance)
+                        .unwrap_or(distances.len() - 1)
+                        .max(1);
+                    let (a, b) = (distances[next - 1], distances[next]);
+                    let t = match b > a {
+                        true => (distance - a) / (b - a),
+                        false => 0.0,
+                    };
+                    points[next - 1].lerp(points[next], t)
+                };
+                let distance = progress * length;
+                let eye = at(distance);
+                let target = at(distance + LOOK_AHEAD);
+                let forward = Vec3::new(target.x - eye.x, target.y - eye.y, 0.0);
+                match forward.length_squared() > 0.0 {
+                    true => Transform::from_translation(eye).looking_to(forward, Vec3::Z),
+                    false => Transform::from_translation(eye),
+                }
+            }
+            BenchmarkPath::Orbit(keys) => {
+                let f = progress.clamp(0.0, 1.0) * (keys.len() - 1) as f32;
+                let i = (f as usize).min(keys.len() - 2);
+                let (a, b) = (keys[i], keys[i + 1]);
+                let t = f - i as f32;
+                Transform {
+                    translation: a.translation.lerp(b.translation, t),
+                    rotation: a.rotation.slerp(b.rotation, t),
+                    scale: Vec3::ONE,
+                }
+            }
+        }
+    }
+}
+
+/// Frame time statistics, in milliseconds, of a benchmark run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FrameStats {
+    pub count: usize,
+    /// Frames per second over the whole run.
+    pub fps: f64,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl FrameStats {
+    /// Statistics of the frames taking `frame_times` seconds each, with
+    /// nearest-rank percentiles:
+    ///
+    /// ```
+    /// # use q2_viewer::benchmark::FrameStats;
+    /// let times: Vec<f32> = (1..=100).map(|ms| ms as f32 / 1000.0).collect();
+    /// let stats = FrameStats::new(&times);
+    /// assert_eq!(stats.count, 100);
+    /// assert_eq!(stats.p50_ms.round(), 50.0);
+    /// assert_eq!(stats.p99_ms.round(), 99.0);
+    /// assert_eq!(stats.max_ms.round(), 100.0);
+    /// ```
+    pub fn new(frame_times: &[f32]) -> Self {
+        if frame_times.is_empty() {
+            return Self::default();
+        }
+        let mut ms: Vec<f64> = frame_times.iter().map(|&t| t as f64 * 1000.0).collect();
+        ms.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = (p * ms.len() as f64).ceil() as usize;
+            ms[rank.clamp(1, ms.len()) - 1]
+        };
+        let total: f64 = ms.iter().sum();
+        Self {
+            count: ms.len(),
+            fps: match total > 0.0 {
+                true => ms.len() as f64 * 1000.0 / total,
+                false => 0.0,
+            },
+            mean_ms: total / ms.len() as f64,
+            min_ms: ms[0],
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: ms[ms.len() - 1],
+        }
+    }
+}
+
+/// Time of one map loading stage, see [LoadTimings].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadStage {
+    pub stage: &'static str,
+    pub ms: f64,
+}
+
+/// What a benchmark run measured.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    pub map: String,
+    /// Real seconds measured, after the warmup.
+    pub seconds: f32,
+    pub frames: FrameStats,
+    /// Loading stages of the map, in the order they ran.
+    pub load: Vec<LoadStage>,
+}
+
+/// Sets the path once the map has spawned, and turns culling on in case it
+/// was turned off.
+fn start_benchmark(
+    benchmark: Option<ResMut<Benchmark>>,
+    maps: Res<MapManager>,
+    mut culling: ResMut<PvsCulling>,
+    roots: Query<(&MapSummary, &LoadTimings)>,
+    cameras: Query<&Projection, With<Interpolated>>,
+) {
+    let Some(mut benchmark) = benchmark else {
+        return;
+    };
+    if benchmark.path.is_some() {
+        return;
+    }
+    let Some((summary, load)) = maps.root().and_then(|root| roots.get(root).ok()) else {
+        return;
+    };
+    let projection = match cameras.iter().next() {
+        Some(Projection::Perspective(perspective)) => perspective.clone(),
+        _ => PerspectiveProjection::default(),
+    };
+    benchmark.path = Some(BenchmarkPath::new(summary, &projection));
+    benchmark.map = maps.current().unwrap_or_default().to_string();
+    benchmark.load = load.clone();
+    culling.lock = false;
+    culling.locked_view = None;
+    culling.novis = false;
+    culling.noareas = false;
+    info!(
+        "Benchmarking {} for {} s after {} warmup frames",
+        benchmark.map, benchmark.seconds, benchmark.warmup_frames
+    );
+}
+
+/// Counts the frames and measures them after the warmup, then reports.
+fn advance_benchmark(
+    mut commands: Commands,
+    benchmark: Option<ResMut<Benchmark>>,
+    time: Res<Time<Real>>,
+    #[cfg(not(target_arch = "wasm32"))] mut exit: EventWriter<AppExit>,
+) {
+    let Some(mut benchmark) = benchmark else {
+        return;
+    };
+    if benchmark.path.is_none() {
+        return;
+    }
+    benchmark.frame += 1;
+    if benchmark.frame <= benchmark.warmup_frames {
+        return;
+    }
+    let delta = time.delta_seconds();
+    benchmark.elapsed += delta;
+    benchmark.frame_times.push(delta);
+    if benchmark.elapsed < benchmark.seconds {
+        return;
+    }
+
+    let report = benchmark.report();
+    let json = serde_json::to_string_pretty(&report).unwrap_or_default();
+    info!(
+        "Benchmark of {}: {} frames, {:.1} fps",
+        report.map, report.frames.count, report.frames.fps
+    );
+    #[cfg(target_arch = "wasm32")]
+    info!("{}", json);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        println!("{}", json);
+        let path = std::path::Path::new("benchmarks").join(format!("{}.json", report.map));
+        // Map names can have folders
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, &json));
+        match written {
+            Ok(()) => info!("Benchmark written to {}", path.display()),
+            Err(err) => error!("Could not write {}: {}", path.display(), err),
+        }
+        exit.send(AppExit::Success);
+    }
+    commands.remove_resource::<Benchmark>();
+}
+
+/// Moves the main camera along the path. Runs after the fixed steps, so it
+/// wins over whatever animates the camera there.
+fn drive_camera(
+    benchmark: Option<Res<Benchmark>>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+) {
+    let Some(benchmark) = benchmark else {
+        return;
+    };
+    let Some(path) = &benchmark.path else {
+        return;
+    };
+    let transform = path.sample(benchmark.progress());
+    for mut interpolated in cameras.iter_mut() {
+        *interpolated = Interpolated::new(transform);
+    }
+}
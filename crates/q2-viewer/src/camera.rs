@@ -0,0 +1,205 @@
+use bevy::{
+    input::mouse::MouseMotion,
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+
+use crate::{
+    accessibility::Accessibility,
+    asset::MapSummary,
+    framing::{frame_map, OVERVIEW_PITCH},
+    maps::MapManager,
+    sim::Interpolated,
+};
+
+/// Limit of the fly camera's pitch, short of straight up or down where the
+/// yaw is lost.
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+/// Moves the main camera, either orbiting the current map, flying freely
+/// through it or walking it as the player. F switches between orbiting and
+/// flying, P between orbiting and walking.
+///
+/// The fly camera captures the mouse to look around, moves with WASD, rises
+/// and sinks with E and Q, and goes faster while Shift is held. Escape
+/// releases the mouse and goes back to orbiting.
+pub struct CameraControllerPlugin;
+
+impl Plugin for CameraControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraMode>()
+            .init_resource::<FlyCamera>()
+            .add_systems(Update, (switch_camera_mode, accumulate_mouse_look))
+            .add_systems(
+                FixedUpdate,
+                (
+                    orbit_camera.run_if(resource_equals(CameraMode::Orbit)),
+                    fly_camera.run_if(resource_equals(CameraMode::Fly)),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// Circles the current map, framing all of it.
+    #[default]
+    Orbit,
+    Fly,
+    /// Walks the map as the player, see [PlayerPlugin](crate::player::PlayerPlugin).
+    Walk,
+}
+
+/// Settings of the fly camera.
+#[derive(Resource, Debug, Clone)]
+pub struct FlyCamera {
+    /// Speed in map units per second.
+    pub speed: f32,
+    /// Factor of the speed while Shift is held.
+    pub sprint: f32,
+    /// Radians turned per pixel of mouse motion.
+    pub sensitivity: f32,
+    /// Mouse motion since the last simulation step, in pixels.
+    look: Vec2,
+}
+
+impl FlyCamera {
+    /// Mouse motion since the last call, in radians.
+    pub(crate) fn take_look(&mut self) -> Vec2 {
+        std::mem::take(&mut self.look) * self.sensitivity
+    }
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        Self {
+            speed: 400.0,
+            sprint: 3.0,
+            sensitivity: 0.002,
+            look: Vec2::ZERO,
+        }
+    }
+}
+
+/// Run condition for systems whose keys the fly camera takes over.
+pub fn not_flying(mode: Option<Res<CameraMode>>) -> bool {
+    mode.is_none_or(|mode| *mode != CameraMode::Fly)
+}
+
+fn switch_camera_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut mode: ResMut<CameraMode>,
+    mut fly: ResMut<FlyCamera>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let toggle = |other: CameraMode| match *mode == other {
+        true => CameraMode::Orbit,
+        false => other,
+    };
+    let next = if keys.just_pressed(KeyCode::KeyF) {
+        toggle(CameraMode::Fly)
+    } else if keys.just_pressed(KeyCode::KeyP) {
+        toggle(CameraMode::Walk)
+    } else if keys.just_pressed(KeyCode::Escape) {
+        CameraMode::Orbit
+    } else {
+        return;
+    };
+    *mode = next;
+    fly.look = Vec2::ZERO;
+
+    let captured = next != CameraMode::Orbit;
+    for mut window in windows.iter_mut() {
+        window.cursor.grab_mode = match captured {
+            true => CursorGrabMode::Locked,
+            false => CursorGrabMode::None,
+        };
+        window.cursor.visible = !captured;
+    }
+}
+
+fn accumulate_mouse_look(
+    mode: Res<CameraMode>,
+    mut fly: ResMut<FlyCamera>,
+    mut motion: EventReader<MouseMotion>,
+) {
+    let delta: Vec2 = motion.read().map(|event| event.delta).sum();
+    if *mode != CameraMode::Orbit {
+        fly.look += delta;
+    }
+}
+
+// Runs on the fixed timestep, where `Time` is the simulation clock
+fn orbit_camera(
+    mut query: Query<(&mut Interpolated, &Projection), With<Camera>>, //
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    accessibility: Option<Res<Accessibility>>,
+    time: Res<Time>,
+) {
+    // Speed of rotation, held still for reduced motion
+    let speed = match accessibility.is_some_and(|settings| settings.reduced_motion) {
+        true => 0.0,
+        false => 0.25,
+    };
+
+    // Orbit the current map once it has loaded, framing all of it
+    let Some(summary) = maps.root().and_then(|root| summaries.get(root).ok()) else {
+        return;
+    };
+    let bounds = summary.world_bounds();
+    for (mut interpolated, projection) in query.iter_mut() {
+        let Projection::Perspective(perspective) = projection else {
+            continue;
+        };
+        let angle = time.elapsed_seconds() * speed;
+        interpolated.current = frame_map(&bounds, OVERVIEW_PITCH, angle, perspective);
+    }
+}
+
+fn fly_camera(
+    mut query: Query<&mut Interpolated, With<Camera>>,
+    mut fly: ResMut<FlyCamera>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+) {
+    let look = fly.take_look();
+    let axis = |positive: KeyCode, negative: KeyCode| {
+        (keys.pressed(positive) as i32 - keys.pressed(negative) as i32) as f32
+    };
+
+    let input = Vec3::new(
+        axis(KeyCode::KeyD, KeyCode::KeyA),
+        axis(KeyCode::KeyW, KeyCode::KeyS),
+        axis(KeyCode::KeyE, KeyCode::KeyQ),
+    );
+    let sprint = match keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        true => fly.sprint,
+        false => 1.0,
+    };
+    let step = fly.speed * sprint * time.delta_seconds();
+
+    for mut interpolated in query.iter_mut() {
+        let transform = &mut interpolated.current;
+        let forward = mouse_look(transform, look);
+        let right = forward.cross(Vec3::Z).normalize();
+
+        let motion = right * input.x + forward * input.y + Vec3::Z * input.z;
+        transform.translation += motion.normalize_or_zero() * step;
+    }
+}
+
+/// Turns `transform` by `look` radians of yaw and pitch, and returns its new
+/// forward direction. Yaws around the map's Z up, so the horizon stays level.
+pub(crate) fn mouse_look(transform: &mut Transform, look: Vec2) -> Vec3 {
+    let forward = transform.forward();
+    let yaw = forward.y.atan2(forward.x) - look.x;
+    let pitch = (forward.z.asin() - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+    let forward = Vec3::new(
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+    );
+    transform.look_to(forward, Vec3::Z);
+    forward
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+
+use q2_formats::bsp38::prelude::Bounds;
+
+/// How far above the horizon the overview camera looks down from, in
+/// radians.
+pub const OVERVIEW_PITCH: f32 = 0.45;
+
+/// Smallest radius framed, so empty or tiny maps still get a sensible view.
+const MIN_RADIUS: f32 = 64.0;
+
+/// A camera transform that fits all of `bounds` in view, looking at their
+/// center from `pitch` radians above the horizon and `yaw` radians around
+/// +Z from +X.
+///
+/// The camera backs off until the bounding sphere of the box fits in both the
+/// vertical and horizontal field of view of `projection`:
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use q2_formats::bsp38::prelude::Bounds;
+/// # use q2_viewer::framing::frame_map;
+/// let bounds = Bounds { min: [-100.0; 3], max: [100.0; 3] };
+/// let camera = frame_map(&bounds, 0.0, 0.0, &PerspectiveProjection::default());
+/// assert!(camera.translation.x > 100.0);
+/// assert!(camera.forward().dot(Vec3::NEG_X) > 0.999);
+/// ```
+pub fn frame_map(
+    bounds: &Bounds,
+    pitch: f32,
+    yaw: f32,
+    projection: &PerspectiveProjection,
+) -> Transform {
+    let (min, max) = (Vec3::from(bounds.min), Vec3::from(bounds.max));
+    let (center, radius) = match min.cmple(max).all() {
+        true => ((min + max) / 2.0, (max - min).length() / 2.0),
+        // Empty bounds
+        false => (Vec3::ZERO, 0.0),
+    };
+
+    let half_fov = projection.fov / 2.0;
+    let half_horizontal_fov = (half_fov.tan() * projection.aspect_ratio).atan();
+    let distance = radius.max(MIN_RADIUS) / half_fov.min(half_horizontal_fov).sin();
+
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let direction = Vec3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, sin_pitch);
+    Transform::from_translation(center + direction * distance).looking_at(center, Vec3::Z)
+}
@@ -0,0 +1,162 @@
+use bevy::prelude::*;
+
+use crate::{asset::MapSummary, maps::MapManager, sim::Interpolated};
+
+/// Cells drawn from the camera to each edge of the grid.
+const HALF_CELLS: i32 = 32;
+
+/// Every this many lines of the grid is drawn brighter.
+const MAJOR_EVERY: i32 = 8;
+
+/// Length of the origin axes, in map units.
+const AXIS_LENGTH: f32 = 128.0;
+
+/// A world grid in the map's coordinates, with the map's origin axes, to
+/// check entity origins and brush alignment against. The grid lies on the
+/// XY plane at a map height, follows the camera, and draws every 8th line
+/// brighter. Toggled with the G key.
+///
+/// The console takes `r_grid <0|1>`, `r_grid_spacing <units>` and
+/// `r_grid_height <z>`.
+pub struct GridPlugin;
+
+impl Plugin for GridPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GridOverlay>()
+            .add_systems(Update, (toggle_grid, draw_grid).chain());
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct GridOverlay {
+    pub show: bool,
+    /// Size of a cell, in map units; brushes are usually aligned to 8 or
+    /// 16.
+    pub spacing: f32,
+    /// Map height of the grid's plane.
+    pub height: f32,
+}
+
+impl Default for GridOverlay {
+    fn default() -> Self {
+        Self {
+            show: false,
+            spacing: 64.0,
+            height: 0.0,
+        }
+    }
+}
+
+impl GridOverlay {
+    /// Runs a console command: `r_grid <0|1>`, `r_grid_spacing <units>` or
+    /// `r_grid_height <z>`.
+    ///
+    /// ```
+    /// # use q2_viewer::grid::GridOverlay;
+    /// let mut grid = GridOverlay::default();
+    /// grid.run_command("r_grid_spacing 16").unwrap();
+    /// assert_eq!(grid.spacing, 16.0);
+    /// assert!(grid.run_command("r_grid_spacing 0").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["r_grid", "0"] => self.show = false,
+            ["r_grid", "1"] => self.show = true,
+            ["r_grid", ..] => return Err("usage: r_grid <0|1>".to_string()),
+            ["r_grid_spacing", value] => {
+                self.spacing = value
+                    .parse()
+                    .ok()
+                    .filter(|spacing| (1.0..=4096.0).contains(spacing))
+                    .ok_or_else(|| "usage: r_grid_spacing <1 to 4096>".to_string())?;
+            }
+            ["r_grid_spacing", ..] => return Err("usage: r_grid_spacing <1 to 4096>".to_string()),
+            ["r_grid_height", value] => {
+                self.height = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|height| height.is_finite())
+                    .ok_or_else(|| "usage: r_grid_height <z>".to_string())?;
+            }
+            ["r_grid_height", ..] => return Err("usage: r_grid_height <z>".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+
+    /// The grid lines along X and along Y nearest to `center`, in map
+    /// coordinates, as their ends and whether each is a major line.
+    ///
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use q2_viewer::grid::GridOverlay;
+    /// let grid = GridOverlay::default();
+    /// let lines = grid.lines(Vec2::new(100.0, 0.0));
+    /// assert_eq!(lines.len(), 2 * 65);
+    /// // Through the origin, a major line
+    /// let x_axis = (Vec3::new(-1920.0, 0.0, 0.0), Vec3::new(2176.0, 0.0, 0.0), true);
+    /// assert!(lines.contains(&x_axis));
+    /// ```
+    pub fn lines(&self, center: Vec2) -> Vec<(Vec3, Vec3, bool)> {
+        let cell = (center / self.spacing).round().as_ivec2();
+        let (start, end) = (
+            cell - IVec2::splat(HALF_CELLS),
+            cell + IVec2::splat(HALF_CELLS),
+        );
+        let at = |x: i32, y: i32| Vec3::new(x as f32, y as f32, 0.0) * self.spacing;
+        let height = Vec3::Z * self.height;
+        let along_x = (start.y..=end.y).map(|y| (at(start.x, y), at(end.x, y), y));
+        let along_y = (start.x..=end.x).map(|x| (at(x, start.y), at(x, end.y), x));
+        along_x
+            .chain(along_y)
+            .map(|(a, b, i)| (a + height, b + height, i % MAJOR_EVERY == 0))
+            .collect()
+    }
+}
+
+fn toggle_grid(keys: Res<ButtonInput<KeyCode>>, mut grid: ResMut<GridOverlay>) {
+    if keys.just_pressed(KeyCode::KeyG) {
+        grid.show = !grid.show;
+    }
+}
+
+/// Draws the grid under the camera and the axes at the current map's
+/// origin.
+fn draw_grid(
+    grid: Res<GridOverlay>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    cameras: Query<&GlobalTransform, (With<Camera3d>, With<Interpolated>)>,
+    mut gizmos: Gizmos,
+) {
+    if !grid.show {
+        return;
+    }
+    let Some(offset) = maps
+        .root()
+        .and_then(|root| summaries.get(root).ok())
+        .map(MapSummary::world_offset)
+    else {
+        return;
+    };
+    let camera = cameras
+        .iter()
+        .next()
+        .map_or(Vec3::ZERO, |transform| transform.translation() - offset);
+
+    let minor = Color::srgba(0.6, 0.6, 0.6, 0.25);
+    let major = Color::srgba(0.8, 0.8, 0.8, 0.6);
+    for (a, b, is_major) in grid.lines(camera.truncate()) {
+        let color = if is_major { major } else { minor };
+        gizmos.line(a + offset, b + offset, color);
+    }
+
+    let axes = [
+        (Vec3::X, Color::srgb(1.0, 0.2, 0.2)),
+        (Vec3::Y, Color::srgb(0.2, 1.0, 0.2)),
+        (Vec3::Z, Color::srgb(0.3, 0.4, 1.0)),
+    ];
+    for (axis, color) in axes {
+        gizmos.arrow(offset, offset + axis * AXIS_LENGTH, color);
+    }
+}
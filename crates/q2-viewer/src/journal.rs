@@ -0,0 +1,409 @@
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    accessibility::Accessibility,
+    annotations::Annotations,
+    asset::MapSummary,
+    grid::GridOverlay,
+    maps::{MapInstance, MapManager},
+    quality::Quality,
+    rebuild::FaceFilter,
+    render::{HeatmapPalette, PvsCulling, SunOverride, SunShadows},
+    session::SpectatorSession,
+};
+
+/// First line of a journal file.
+const HEADER: &str = "q2-viewer journal 1";
+
+/// Command lines sent from JavaScript, run on the next frame.
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The latest [Journal] as text, for [journal_text_js].
+static LATEST: Mutex<String> = Mutex::new(String::new());
+
+/// Runs [ConsoleCommand]s and records what happens in the viewer into the
+/// [Journal]: maps loaded, entities triggered and commands run. A journal can
+/// be saved and played back with [JournalReplay], to reproduce a bug or as a
+/// macro.
+///
+/// Besides the settings commands of each module, the console takes
+/// `journal_clear`, and on native `journal_save <file>` and
+/// `journal_play <file>`. From JavaScript:
+///
+/// ```js
+/// mod.run_command("r_profile low");
+/// console.log(mod.journal_text());
+/// ```
+pub struct JournalPlugin;
+
+impl Plugin for JournalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Journal>()
+            .add_event::<ConsoleCommand>()
+            .add_event::<EntityTriggered>()
+            .add_systems(
+                Update,
+                (
+                    (send_pending, replay_journal, run_console_commands).chain(),
+                    journal_maps,
+                    journal_triggers,
+                ),
+            )
+            .add_systems(Last, publish_journal);
+    }
+}
+
+/// A console command line to run, such as `r_profile low`.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ConsoleCommand(pub String);
+
+/// Sent when a map entity fires its targets.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct EntityTriggered {
+    pub classname: String,
+    pub targetname: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalEvent {
+    /// A map finished loading, by asset path without the `.bsp` extension.
+    MapLoaded(String),
+    EntityTriggered {
+        classname: String,
+        targetname: Option<String>,
+    },
+    /// A console command that ran without error.
+    Command(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    /// Seconds since the viewer started.
+    pub time: f64,
+    pub event: JournalEvent,
+}
+
+/// What happened in the viewer, in order. As text, one event per line:
+///
+/// ```
+/// # use q2_viewer::journal::{Journal, JournalEntry, JournalEvent};
+/// let mut journal = Journal::default();
+/// journal.push(1.5, JournalEvent::MapLoaded("maps/base1".to_string()));
+/// journal.push(2.0, JournalEvent::Command("r_profile low".to_string()));
+/// journal.push(
+///     4.25,
+///     JournalEvent::EntityTriggered {
+///         classname: "func_button".to_string(),
+///         targetname: Some("door1".to_string()),
+///     },
+/// );
+/// assert_eq!(Journal::parse(&journal.to_text()), Ok(journal.clone()));
+///
+/// // Loads that no command asked for are replayed as one
+/// let script = journal.replay_script();
+/// assert_eq!(script[0], (0.0, "changelevel maps/base1".to_string()));
+/// assert_eq!(script[1], (0.5, "r_profile low".to_string()));
+/// ```
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Journal {
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    pub fn push(&mut self, time: f64, event: JournalEvent) {
+        self.entries.push(JournalEntry { time, event });
+    }
+
+    /// The journal as text: a header, then a line per entry.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![HEADER.to_string()];
+        for JournalEntry { time, event } in &self.entries {
+            lines.push(match event {
+                JournalEvent::MapLoaded(map) => format!("{} map {}", time, map),
+                JournalEvent::EntityTriggered {
+                    classname,
+                    targetname: Some(targetname),
+                } => format!("{} trigger {} {}", time, classname, targetname),
+                JournalEvent::EntityTriggered {
+                    classname,
+                    targetname: None,
+                } => format!("{} trigger {}", time, classname),
+                JournalEvent::Command(line) => format!("{} command {}", time, line),
+            });
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(format!("not a journal, expected {:?}", HEADER));
+        }
+        let mut journal = Journal::default();
+        for (i, line) in lines {
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            let mut parts = line.splitn(3, ' ');
+            let (Some(time), Some(kind)) = (parts.next(), parts.next()) else {
+                return Err(error("expected a time and an event"));
+            };
+            let time = time.parse().map_err(|_| error("bad time"))?;
+            let rest = parts.next().unwrap_or("");
+            let event = match kind {
+                "map" => JournalEvent::MapLoaded(rest.to_string()),
+                "trigger" => {
+                    let mut words = rest.split_whitespace();
+                    JournalEvent::EntityTriggered {
+                        classname: words
+                            .next()
+                            .ok_or_else(|| error("missing classname"))?
+                            .to_string(),
+                        targetname: words.next().map(str::to_string),
+                    }
+                }
+                "command" => JournalEvent::Command(rest.to_string()),
+                _ => return Err(error(&format!("unknown event {:?}", kind))),
+            };
+            journal.push(time, event);
+        }
+        Ok(journal)
+    }
+
+    /// The commands that replay the journal, with their time from the first
+    /// entry. Map loads become `changelevel` commands, unless a command
+    /// since the previous load asked for the map; triggers are left out, as
+    /// the replayed session fires them itself.
+    pub fn replay_script(&self) -> Vec<(f64, String)> {
+        let start = self.entries.first().map_or(0.0, |entry| entry.time);
+        let mut script = Vec::new();
+        let mut requested = false;
+        for JournalEntry { time, event } in &self.entries {
+            let line = match event {
+                JournalEvent::MapLoaded(map) => {
+                    let asked = std::mem::take(&mut requested);
+                    match asked {
+                        true => continue,
+                        false => format!("changelevel {}", map),
+                    }
+                }
+                JournalEvent::Command(line) => {
+                    let command = line.split_whitespace().next();
+                    requested |= matches!(command, Some("changelevel" | "nextmap"));
+                    line.clone()
+                }
+                JournalEvent::EntityTriggered { .. } => continue,
+            };
+            script.push((time - start, line));
+        }
+        script
+    }
+
+    /// Runs a console command: `journal_clear`.
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["journal_clear"] => self.entries.clear(),
+            ["journal_clear", ..] => return Err("usage: journal_clear".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// Plays the commands of a journal back at the pace they were recorded,
+/// removing itself at the end. Insert it as a resource to start.
+#[derive(Resource, Debug, Clone)]
+pub struct JournalReplay {
+    script: Vec<(f64, String)>,
+    /// Time the replay started at, once it has.
+    start: Option<f64>,
+    next: usize,
+}
+
+impl JournalReplay {
+    pub fn new(journal: &Journal) -> Self {
+        Self {
+            script: journal.replay_script(),
+            start: None,
+            next: 0,
+        }
+    }
+}
+
+/// Runs a command line from JavaScript on the next frame, as if typed in the
+/// console.
+#[wasm_bindgen(js_name = run_command)]
+pub fn run_command_js(line: &str) {
+    PENDING.lock().unwrap().push(line.to_string());
+}
+
+/// The journal of the running viewer, as text.
+#[wasm_bindgen(js_name = journal_text)]
+pub fn journal_text_js() -> String {
+    LATEST.lock().unwrap().clone()
+}
+
+fn send_pending(mut commands: EventWriter<ConsoleCommand>) {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    commands.send_batch(pending.into_iter().map(ConsoleCommand));
+}
+
+fn replay_journal(
+    mut commands: Commands,
+    mut console: EventWriter<ConsoleCommand>,
+    replay: Option<ResMut<JournalReplay>>,
+    time: Res<Time>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    let start = *replay.start.get_or_insert(now);
+    while let Some((at, line)) = replay.script.get(replay.next) {
+        if *at > now - start {
+            return;
+        }
+        console.send(ConsoleCommand(line.clone()));
+        replay.next += 1;
+    }
+    info!("Replayed {} journal commands", replay.script.len());
+    commands.remove_resource::<JournalReplay>();
+}
+
+/// Hands each command to the modules until one knows it, and journals the
+/// ones that ran.
+#[allow(clippy::too_many_arguments)]
+fn run_console_commands(
+    mut commands: Commands,
+    mut console: EventReader<ConsoleCommand>,
+    mut journal: ResMut<Journal>,
+    mut maps: ResMut<MapManager>,
+    mut quality: Option<ResMut<Quality>>,
+    mut accessibility: Option<ResMut<Accessibility>>,
+    mut palette: Option<ResMut<HeatmapPalette>>,
+    mut culling: Option<ResMut<PvsCulling>>,
+    mut filter: Option<ResMut<FaceFilter>>,
+    mut sun: Option<ResMut<SunShadows>>,
+    mut session: Option<ResMut<SpectatorSession>>,
+    mut notes: Option<ResMut<Annotations>>,
+    mut grid: Option<ResMut<GridOverlay>>,
+    mut sun_override: Option<ResMut<SunOverride>>,
+    time: Res<Time>,
+) {
+    for ConsoleCommand(line) in console.read() {
+        let line = line.trim();
+        let result = match line.split_whitespace().next() {
+            None => continue,
+            Some("journal_save" | "journal_play") => file_command(&mut commands, &journal, line),
+            Some(_) => {
+                let handlers: [&mut dyn FnMut(&str) -> Result<(), String>; 12] = [
+                    &mut |line| journal.run_command(line),
+                    &mut |line| maps.run_command(line),
+                    &mut |line| run_on(&mut quality, line, Quality::run_command),
+                    &mut |line| run_on(&mut accessibility, line, Accessibility::run_command),
+                    &mut |line| run_on(&mut palette, line, HeatmapPalette::run_command),
+                    &mut |line| run_on(&mut culling, line, PvsCulling::run_command),
+                    &mut |line| run_on(&mut filter, line, FaceFilter::run_command),
+                    &mut |line| run_on(&mut sun, line, SunShadows::run_command),
+                    &mut |line| run_on(&mut session, line, SpectatorSession::run_command),
+                    &mut |line| run_on(&mut notes, line, Annotations::run_command),
+                    &mut |line| run_on(&mut grid, line, GridOverlay::run_command),
+                    &mut |line| run_on(&mut sun_override, line, SunOverride::run_command),
+                ];
+                let mut result = Err(format!("unknown command {:?}", line));
+                for handler in handlers {
+                    result = handler(line);
+                    if !result
+                        .as_ref()
+                        .is_err_and(|err| err.starts_with("unknown command"))
+                    {
+                        break;
+                    }
+                }
+                result
+            }
+        };
+        match result {
+            Ok(()) => {
+                info!("] {}", line);
+                let now = time.elapsed_seconds_f64();
+                journal.push(now, JournalEvent::Command(line.to_string()));
+            }
+            Err(err) => warn!("] {}: {}", line, err),
+        }
+    }
+}
+
+/// Runs `line` on a resource that may be missing, such as one whose plugin
+/// isn't added.
+fn run_on<T: Resource>(
+    resource: &mut Option<ResMut<T>>,
+    line: &str,
+    run: fn(&mut T, &str) -> Result<(), String>,
+) -> Result<(), String> {
+    match resource {
+        Some(resource) => run(resource, line),
+        None => Err(format!("unknown command {:?}", line)),
+    }
+}
+
+/// Runs `journal_save <file>` or `journal_play <file>`.
+#[cfg(not(target_arch = "wasm32"))]
+fn file_command(commands: &mut Commands, journal: &Journal, line: &str) -> Result<(), String> {
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["journal_save", path] => {
+            std::fs::write(path, journal.to_text()).map_err(|err| format!("{}: {}", path, err))
+        }
+        ["journal_play", path] => {
+            let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            let journal = Journal::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+            commands.insert_resource(JournalReplay::new(&journal));
+            Ok(())
+        }
+        [name, ..] => Err(format!("usage: {} <file>", name)),
+        [] => Err(format!("unknown command {:?}", line)),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn file_command(_commands: &mut Commands, _journal: &Journal, line: &str) -> Result<(), String> {
+    Err(format!("{:?} needs a file system", line))
+}
+
+fn journal_maps(
+    mut journal: ResMut<Journal>,
+    maps: Query<&MapInstance, Added<MapSummary>>,
+    time: Res<Time>,
+) {
+    for instance in maps.iter() {
+        let now = time.elapsed_seconds_f64();
+        journal.push(now, JournalEvent::MapLoaded(instance.name.clone()));
+    }
+}
+
+fn journal_triggers(
+    mut journal: ResMut<Journal>,
+    mut triggers: EventReader<EntityTriggered>,
+    time: Res<Time>,
+) {
+    for trigger in triggers.read() {
+        journal.push(
+            time.elapsed_seconds_f64(),
+            JournalEvent::EntityTriggered {
+                classname: trigger.classname.clone(),
+                targetname: trigger.targetname.clone(),
+            },
+        );
+    }
+}
+
+fn publish_journal(journal: Res<Journal>) {
+    if journal.is_changed() {
+        *LATEST.lock().unwrap() = journal.to_text();
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::{prelude::*, transform::TransformSystem, window::PrimaryWindow};
+
+use crate::{
+    asset::MapSummary, camera::not_flying, maps::MapScoped, render::ScaledView, sim::Interpolated,
+};
+
+/// Height above an entity's origin its label is drawn at.
+const LABEL_HEIGHT: f32 = 24.0;
+
+/// Debug labels with the classname and targetname of every map entity that
+/// has an origin, drawn over the entity facing the screen.
+///
+/// Labels fade out with distance so entity-dense maps stay readable: the
+/// targetname goes first, then the whole label. Toggled with the E key,
+/// except while the fly camera uses it to rise.
+pub struct EntityLabelPlugin;
+
+impl Plugin for EntityLabelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EntityLabels>()
+            .add_systems(Update, (spawn_labels, toggle_labels.run_if(not_flying)))
+            .add_systems(
+                PostUpdate,
+                update_labels.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Which entity labels are shown, and how far away.
+#[derive(Resource, Debug, Clone)]
+pub struct EntityLabels {
+    pub show: bool,
+    /// Classname prefixes to label, such as `weapon_` or `info_player_`.
+    /// Empty labels every class.
+    pub classes: Vec<String>,
+    /// Distance at which labels start to fade out.
+    pub fade_start: f32,
+    /// Distance past which labels are hidden.
+    pub fade_end: f32,
+}
+
+impl Default for EntityLabels {
+    fn default() -> Self {
+        Self {
+            show: false,
+            classes: Vec::new(),
+            fade_start: 768.0,
+            fade_end: 1536.0,
+        }
+    }
+}
+
+impl EntityLabels {
+    pub fn shows(&self, classname: &str) -> bool {
+        self.classes.is_empty()
+            || self
+                .classes
+                .iter()
+                .any(|prefix| classname.starts_with(prefix.as_str()))
+    }
+
+    /// Opacity of a label `distance` away from the camera. The targetname
+    /// line fades over the first half of the range.
+    fn opacity(&self, distance: f32, detail: bool) -> f32 {
+        let (start, end) = match detail {
+            true => (self.fade_start / 2.0, self.fade_start),
+            false => (self.fade_start, self.fade_end),
+        };
+        1.0 - ((distance - start) / (end - start).max(1.0)).clamp(0.0, 1.0)
+    }
+}
+
+/// A label over a map entity.
+#[derive(Component, Debug, Clone)]
+pub struct EntityLabel {
+    pub classname: String,
+    /// World position the label is drawn at.
+    pub position: Vec3,
+}
+
+fn toggle_labels(keys: Res<ButtonInput<KeyCode>>, mut labels: ResMut<EntityLabels>) {
+    if keys.just_pressed(KeyCode::KeyE) {
+        labels.show = !labels.show;
+    }
+}
+
+/// Spawns a hidden label for each entity of a map once its world is spawned.
+fn spawn_labels(mut commands: Commands, maps: Query<&MapSummary, Added<MapSummary>>) {
+    for summary in maps.iter() {
+        let offset = summary.world_offset();
+        for entity in &summary.entities {
+            let Some(origin) = entity.origin else {
+                continue;
+            };
+            let style = |font_size| TextStyle {
+                font_size,
+                color: Color::WHITE,
+                ..default()
+            };
+            let mut sections = vec![TextSection::new(entity.classname.clone(), style(16.0))];
+            if let Some(targetname) = &entity.targetname {
+                sections.push(TextSection::new(format!("\n{}", targetname), style(13.0)));
+            }
+            let text = TextBundle::from_sections(sections)
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                });
+            commands.spawn((
+                TextBundle {
+                    visibility: Visibility::Hidden,
+                    ..text
+                },
+                EntityLabel {
+                    classname: entity.classname.clone(),
+                    position: origin + offset + Vec3::Z * LABEL_HEIGHT,
+                },
+                MapScoped,
+            ));
+        }
+    }
+}
+
+/// Places each label over its entity on screen and fades it by distance.
+fn update_labels(
+    settings: Res<EntityLabels>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Interpolated>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<ScaledView>,
+    mut labels: Query<(&EntityLabel, &Node, &mut Style, &mut Text, &mut Visibility)>,
+) {
+    let (Some((camera, camera_transform)), Some(window)) =
+        (cameras.iter().next(), windows.iter().next())
+    else {
+        return;
+    };
+    for (label, node, mut style, mut text, mut visibility) in labels.iter_mut() {
+        let distance = camera_transform.translation().distance(label.position);
+        let visible =
+            settings.show && distance < settings.fade_end && settings.shows(&label.classname);
+        let Some(screen) = camera
+            .world_to_viewport(camera_transform, label.position)
+            .filter(|_| visible)
+        else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        let screen = view.to_window(window, screen);
+
+        // Centered over the entity, using last frame's layout of the text
+        let size = node.size();
+        style.left = Val::Px(screen.x - size.x / 2.0);
+        style.top = Val::Px(screen.y - size.y);
+        for (i, section) in text.sections.iter_mut().enumerate() {
+            let opacity = settings.opacity(distance, i > 0);
+            section.style.color.set_alpha(opacity);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+pub mod accessibility;
+pub mod annotations;
+pub mod asset;
+pub mod audio;
+pub mod benchmark;
+pub mod camera;
+pub mod framing;
+pub mod grid;
+pub mod journal;
+pub mod labels;
+pub mod locale;
+pub mod maps;
+pub mod md2;
+pub mod measure;
+pub mod memory;
+pub mod pak;
+pub mod pcx;
+#[cfg(feature = "rapier")]
+pub mod physics;
+pub mod player;
+pub mod quality;
+pub mod rebuild;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod record;
+pub mod render;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod replay;
+pub mod seed;
+pub mod session;
+pub mod sim;
+pub mod spawn;
+mod start;
+pub mod targets;
+pub mod tga;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tour;
+pub mod wal;
+mod window;
+pub mod work;
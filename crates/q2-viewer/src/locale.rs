@@ -0,0 +1,221 @@
+use std::borrow::Cow;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    utils::HashMap,
+};
+use thiserror::Error;
+
+/// The English pack, built in so every key has a text to fall back to.
+const ENGLISH: &str = include_str!("locale/en.lang");
+
+/// Translates on-screen text through [LanguagePack]s.
+///
+/// Systems showing text build a [Message] from a key and its values, and
+/// the [Locale] turns it into text in the current language when it's drawn.
+/// Packs are `.lang` files of `key = text` lines, with `{0}`, `{1}` and so on
+/// where the values go, and `#` comments:
+///
+/// ```text
+/// # German
+/// stat.textures = Texturen
+/// textures.summary = {0} verschieden, {1} unbenutzt
+/// ```
+///
+/// Keys missing from a pack fall back to English, so packs can be partial.
+pub struct LocalePlugin {
+    /// Language loaded from `lang/<language>.lang`, or None for English.
+    pub language: Option<String>,
+}
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        let mut locale = Locale::default();
+        if let Some(language) = &self.language {
+            locale.language = language.clone();
+        }
+        app.init_asset::<LanguagePack>()
+            .init_asset_loader::<LanguagePackLoader>()
+            .insert_resource(locale)
+            .add_systems(Startup, load_language)
+            .add_systems(Update, apply_language);
+    }
+}
+
+/// Strings of one language, by key.
+#[derive(Asset, TypePath, Debug, Clone, Default)]
+pub struct LanguagePack {
+    pub strings: HashMap<String, String>,
+}
+
+impl LanguagePack {
+    /// Parses the `key = text` lines of a `.lang` file. Text may contain
+    /// `\n` for line breaks.
+    pub fn parse(text: &str) -> Result<Self, LanguagePackError> {
+        let mut strings = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(LanguagePackError::Syntax(i + 1))?;
+            strings.insert(key.trim().to_string(), value.trim().replace("\\n", "\n"));
+        }
+        Ok(Self { strings })
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LanguagePackError {
+    #[error("could not read language pack: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("language pack is not UTF-8: {0}")]
+    Utf8(#[from] std::string::FromUtf8Error),
+    #[error("line {0}: expected key = text")]
+    Syntax(usize),
+}
+
+#[derive(Default)]
+pub struct LanguagePackLoader;
+
+impl AssetLoader for LanguagePackLoader {
+    type Asset = LanguagePack;
+    type Settings = ();
+    type Error = LanguagePackError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<LanguagePack, LanguagePackError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        LanguagePack::parse(&String::from_utf8(bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lang"]
+    }
+}
+
+/// A piece of on-screen text: a key of the language packs and the values
+/// filled into its text, or text shown as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Localized {
+        key: Cow<'static, str>,
+        args: Vec<String>,
+    },
+    /// Text that isn't translated, such as numbers or names from a map.
+    Verbatim(String),
+}
+
+impl Message {
+    pub fn new(key: &'static str) -> Self {
+        Self::Localized {
+            key: Cow::Borrowed(key),
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds the value of the next `{n}` placeholder.
+    pub fn arg(mut self, value: impl ToString) -> Self {
+        if let Self::Localized { args, .. } = &mut self {
+            args.push(value.to_string());
+        }
+        self
+    }
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Verbatim(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Verbatim(text.to_string())
+    }
+}
+
+/// The language text is shown in.
+#[derive(Resource)]
+pub struct Locale {
+    /// Name of the language, such as `en` or `de`.
+    pub language: String,
+    strings: HashMap<String, String>,
+    english: HashMap<String, String>,
+    pack: Option<Handle<LanguagePack>>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
+            strings: HashMap::new(),
+            english: LanguagePack::parse(ENGLISH)
+                .expect("the English pack parses")
+                .strings,
+            pack: None,
+        }
+    }
+}
+
+impl Locale {
+    /// Text of `key` in the current language, falling back to English and
+    /// then to the key itself.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.english.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// Uses `pack` for the current language.
+    pub fn set_pack(&mut self, pack: &LanguagePack) {
+        self.strings = pack.strings.clone();
+    }
+
+    pub fn text(&self, message: &Message) -> String {
+        match message {
+            Message::Verbatim(text) => text.clone(),
+            Message::Localized { key, args } => {
+                let mut text = self.get(key).to_string();
+                for (i, arg) in args.iter().enumerate() {
+                    text = text.replace(&format!("{{{}}}", i), arg);
+                }
+                text
+            }
+        }
+    }
+}
+
+fn load_language(mut locale: ResMut<Locale>, asset_server: Res<AssetServer>) {
+    if locale.language != "en" {
+        let path = format!("lang/{}.lang", locale.language);
+        locale.pack = Some(asset_server.load(path));
+    }
+}
+
+fn apply_language(
+    mut locale: ResMut<Locale>,
+    packs: Res<Assets<LanguagePack>>,
+    mut events: EventReader<AssetEvent<LanguagePack>>,
+) {
+    for event in events.read() {
+        let Some(handle) = &locale.pack else {
+            continue;
+        };
+        if event.is_loaded_with_dependencies(handle.id()) || event.is_modified(handle.id()) {
+            if let Some(pack) = packs.get(handle.id()) {
+                let pack = pack.clone();
+                locale.set_pack(&pack);
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use bevy::prelude::*;
+
+use crate::{
+    asset::BSP38Asset,
+    render::{InstancedAssets, WatchedAssets},
+    sim::Interpolated,
+    start::camera_start,
+    targets::MapTargets,
+};
+
+/// A map shown in the world. Its meshes are spawned as children of this
+/// entity once the asset loads, so the entity's transform places the whole
+/// map and several maps can be shown at once:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use q2_viewer::maps::MapBundle;
+/// fn gallery(mut commands: Commands, asset_server: Res<AssetServer>) {
+///     for (i, map) in ["q2dm1", "q2dm2", "q2dm3"].into_iter().enumerate() {
+///         let transform = Transform::from_xyz(i as f32 * 4096.0, 0.0, 0.0);
+///         commands.spawn(MapBundle::new(&asset_server, map, transform));
+///     }
+/// }
+/// ```
+#[derive(Component)]
+pub struct MapInstance {
+    /// Map name, the asset path without the `.bsp` extension.
+    pub name: String,
+    pub handle: Handle<BSP38Asset>,
+    /// Whether the map's world has been spawned under this entity.
+    pub(crate) spawned: bool,
+}
+
+#[derive(Bundle)]
+pub struct MapBundle {
+    pub instance: MapInstance,
+    pub spatial: SpatialBundle,
+}
+
+impl MapBundle {
+    pub fn new(asset_server: &AssetServer, map: &str, transform: Transform) -> Self {
+        Self {
+            instance: MapInstance {
+                name: map.to_string(),
+                handle: asset_server.load(format!("{}.bsp", map)),
+                spawned: false,
+            },
+            spatial: SpatialBundle::from_transform(transform),
+        }
+    }
+}
+
+/// Marks an entity that belongs to the current map without being a child of
+/// its [MapInstance]. Everything marked is despawned, with its children, when
+/// the map changes.
+///
+/// Spawn hooks registered with the
+/// [ClassnameRegistry](crate::spawn::ClassnameRegistry) should add it to
+/// whatever they spawn.
+#[derive(Component, Default)]
+pub struct MapScoped;
+
+/// One model of a map, spawned as a child of its [MapInstance] at the same
+/// offset as the world meshes. Model 0 is the world; the others are the
+/// inline models of doors, platforms and other movers, which move with this
+/// entity's transform.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct BrushModel {
+    /// Index into the map's Models lump.
+    pub index: usize,
+}
+
+/// The map entity drawn with an inline [BrushModel], such as a `func_door`
+/// or `func_plat`, on the model's entity.
+#[derive(Component, Debug, Clone)]
+pub struct BrushEntity {
+    pub classname: String,
+    pub targetname: Option<String>,
+}
+
+/// The main map and the rotation of maps to cycle through.
+///
+/// Map names are asset paths without the `.bsp` extension. Changes requested
+/// with [MapManager::changelevel] or [MapManager::next] happen at the start of
+/// the next frame.
+#[derive(Resource)]
+pub struct MapManager {
+    pub rotation: Vec<String>,
+    current: Option<String>,
+    pending: Option<String>,
+    root: Option<Entity>,
+}
+
+impl Default for MapManager {
+    fn default() -> Self {
+        Self {
+            rotation: vec!["q2dm1".to_string()],
+            current: None,
+            pending: None,
+            root: None,
+        }
+    }
+}
+
+impl MapManager {
+    /// Name of the map being shown, or None before the first map is loaded.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// The [MapInstance] entity of the current map.
+    pub fn root(&self) -> Option<Entity> {
+        self.root
+    }
+
+    /// Switches to `map`, which doesn't have to be in the rotation.
+    pub fn changelevel(&mut self, map: &str) {
+        self.pending = Some(map.to_string());
+    }
+
+    /// Switches to the map after the current one in the rotation, wrapping
+    /// around at the end.
+    pub fn next(&mut self) {
+        let position = self
+            .current
+            .as_ref()
+            .and_then(|current| self.rotation.iter().position(|map| map == current));
+        let next = match position {
+            Some(i) => (i + 1) % self.rotation.len(),
+            None => 0,
+        };
+        if let Some(map) = self.rotation.get(next) {
+            self.pending = Some(map.clone());
+        }
+    }
+
+    /// Runs a console command: `changelevel <map>` or `nextmap`.
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("changelevel"), Some(map), None) => self.changelevel(map),
+            (Some("changelevel"), ..) => return Err("usage: changelevel <map>".to_string()),
+            (Some("nextmap"), None, None) => self.next(),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// Loads the first map of the rotation and switches maps on request. N
+/// advances the rotation.
+pub struct MapManagerPlugin;
+
+impl Plugin for MapManagerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapManager>()
+            .add_systems(Startup, |mut maps: ResMut<MapManager>| maps.next())
+            .add_systems(PreUpdate, (change_level, watch_new_maps).chain())
+            .add_systems(Update, next_map_key);
+    }
+}
+
+fn watch_new_maps(
+    mut watched: ResMut<WatchedAssets>,
+    instances: Query<&MapInstance, Added<MapInstance>>,
+) {
+    for instance in instances.iter() {
+        watched.watch(instance.handle.clone());
+    }
+}
+
+fn next_map_key(keys: Res<ButtonInput<KeyCode>>, mut maps: ResMut<MapManager>) {
+    if keys.just_pressed(KeyCode::KeyN) {
+        maps.next();
+    }
+}
+
+/// Tears down everything belonging to the current map and starts loading the
+/// pending one.
+fn change_level(
+    mut commands: Commands,
+    mut maps: ResMut<MapManager>,
+    mut instanced: ResMut<InstancedAssets>,
+    mut targets: ResMut<MapTargets>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+    scoped: Query<Entity, With<MapScoped>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(map) = maps.pending.take() else {
+        return;
+    };
+    info!(
+        "Changing level from {} to {}",
+        maps.current().unwrap_or("(none)"),
+        map
+    );
+
+    for entity in maps.root.into_iter().chain(scoped.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    instanced.clear();
+    *targets = MapTargets {
+        show: targets.show,
+        ..default()
+    };
+
+    // Despawning the old root drops the previous map's asset and its meshes
+    let root = commands.spawn(MapBundle::new(&asset_server, &map, Transform::IDENTITY));
+    maps.root = Some(root.id());
+    maps.current = Some(map);
+
+    for mut camera in cameras.iter_mut() {
+        *camera = Interpolated::new(camera_start());
+    }
+}
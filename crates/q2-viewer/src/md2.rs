@@ -0,0 +1,303 @@
+use std::ops::Range;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology},
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+    },
+};
+use thiserror::Error;
+
+use q2_formats::md2::{Md2, Md2Animation, Md2Error};
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Md2AssetLoaderError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid MD2: {0}")]
+    Md2(#[from] Md2Error),
+    #[error("The model has no frames")]
+    NoFrames,
+}
+
+/// A keyframe of an [Md2Asset], with a position and normal per vertex of
+/// its mesh.
+#[derive(Debug, Clone)]
+pub struct Md2MeshFrame {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+/// An item or monster model, see [Md2AssetLoader].
+#[derive(Asset, TypePath, Debug)]
+pub struct Md2Asset {
+    pub md2: Md2,
+    /// The model in its first frame, with skin UVs from 0 to 1. Models
+    /// animate by swapping in the positions and normals of other frames, so
+    /// the mesh stays readable on the CPU.
+    pub mesh: Handle<Mesh>,
+    /// Every frame, in the order of the file.
+    pub frames: Vec<Md2MeshFrame>,
+    /// Asset paths of the skin images, from the model.
+    pub skins: Vec<String>,
+}
+
+impl Md2Asset {
+    /// Index of the frame named `name`.
+    pub fn frame(&self, name: &str) -> Option<usize> {
+        self.frames.iter().position(|frame| frame.name == name)
+    }
+}
+
+/// Loads .md2 models as an [Md2Asset], with the mesh as the `mesh` labeled
+/// asset and every frame unpacked into the mesh's vertex order up front, so
+/// animating a model only copies or blends vertex data.
+#[derive(Default)]
+pub struct Md2AssetLoader;
+
+impl AssetLoader for Md2AssetLoader {
+    type Asset = Md2Asset;
+    type Settings = ();
+    type Error = Md2AssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Md2Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let md2 = Md2::from_bytes(&bytes)?;
+        if md2.frames.is_empty() {
+            return Err(Md2AssetLoaderError::NoFrames);
+        }
+
+        let layout = md2.mesh_layout();
+        let frames: Vec<Md2MeshFrame> = (0..md2.frames.len())
+            .map(|i| Md2MeshFrame {
+                name: md2.frames[i].name.clone(),
+                positions: md2.mesh_positions(i, &layout),
+                normals: md2.mesh_normals(i, &layout),
+            })
+            .collect();
+
+        let mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, frames[0].positions.clone())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, frames[0].normals.clone())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, md2.mesh_uvs(&layout))
+        .with_inserted_indices(Indices::U32(layout.indices));
+        let mesh = load_context.add_labeled_asset("mesh".to_string(), mesh);
+
+        Ok(Md2Asset {
+            skins: md2.skins.clone(),
+            md2,
+            mesh,
+            frames,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["md2"]
+    }
+}
+
+/// Animates entities with an [Md2Animator] and a `Handle<Md2Asset>`. Each
+/// is given its own copy of the model's mesh once the model loads, so
+/// instances animate independently, with bounds covering every frame.
+pub struct Md2AnimationPlugin;
+
+impl Plugin for Md2AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (attach_md2_meshes, animate_md2).chain());
+    }
+}
+
+/// Plays a range of frames of an [Md2Asset], blending from one keyframe to
+/// the next:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use q2_formats::md2::Md2Animation;
+/// # use q2_viewer::md2::Md2Animator;
+/// fn spawn_soldier(mut commands: Commands, assets: Res<AssetServer>) {
+///     commands.spawn((
+///         Md2Animator::new(Md2Animation::Run),
+///         assets.load::<q2_viewer::md2::Md2Asset>("models/monsters/soldier/tris.md2"),
+///         Handle::<StandardMaterial>::default(),
+///         SpatialBundle::default(),
+///     ));
+/// }
+/// ```
+#[derive(Component, Debug, Clone)]
+pub struct Md2Animator {
+    /// Keyframes per second; the game steps models at 10.
+    pub fps: f32,
+    pub animation: Option<Md2Animation>,
+    /// The frames played, when not those of `animation`.
+    pub frames: Range<usize>,
+    /// Whether to start over after the last frame, or hold it.
+    pub looping: bool,
+    /// Seconds since the animation started.
+    pub time: f32,
+    shown: Option<(usize, usize, f32)>,
+}
+
+impl Default for Md2Animator {
+    fn default() -> Self {
+        Self::new(Md2Animation::Stand)
+    }
+}
+
+impl Md2Animator {
+    pub fn new(animation: Md2Animation) -> Self {
+        Self {
+            fps: 10.0,
+            animation: Some(animation),
+            frames: animation.frames(),
+            looping: animation.looping(),
+            time: 0.0,
+            shown: None,
+        }
+    }
+
+    /// Plays `frames`, for animations outside of [Md2Animation], such as
+    /// those of [Md2::animations].
+    pub fn with_frames(frames: Range<usize>, looping: bool) -> Self {
+        Self {
+            animation: None,
+            frames,
+            looping,
+            ..default()
+        }
+    }
+
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    /// Starts `animation` over.
+    pub fn play(&mut self, animation: Md2Animation) {
+        *self = Self {
+            fps: self.fps,
+            ..Self::new(animation)
+        };
+    }
+
+    /// Whether an animation that doesn't loop is holding its last frame.
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time * self.fps >= self.frames.len().saturating_sub(1) as f32
+    }
+
+    /// The two keyframes of `frames` to show and how far to blend from the
+    /// first to the second, with `frames` kept within the `count` frames of
+    /// the model.
+    ///
+    /// ```
+    /// # use q2_viewer::md2::Md2Animator;
+    /// let mut animator = Md2Animator::with_frames(40..46, true);
+    /// animator.time = 0.55;
+    /// let (from, to, blend) = animator.keyframes(40..46, 198);
+    /// assert_eq!((from, to), (45, 40));
+    /// assert!((blend - 0.5).abs() < 1e-4);
+    /// ```
+    pub fn keyframes(&self, frames: Range<usize>, count: usize) -> (usize, usize, f32) {
+        let start = frames.start.min(count.saturating_sub(1));
+        let len = frames.end.min(count).saturating_sub(start).max(1);
+        let position = (self.time * self.fps).max(0.0);
+        let (from, to, blend) = if self.looping {
+            let position = position % len as f32;
+            let from = position as usize % len;
+            (from, (from + 1) % len, position.fract())
+        } else {
+            let position = position.min((len - 1) as f32);
+            let from = position as usize;
+            (from, (from + 1).min(len - 1), position.fract())
+        };
+        (start + from, start + to, blend)
+    }
+}
+
+/// Gives each animated entity a copy of its model's mesh to animate.
+#[allow(clippy::type_complexity)]
+fn attach_md2_meshes(
+    mut commands: Commands,
+    models: Query<(Entity, &Handle<Md2Asset>), (With<Md2Animator>, Without<Handle<Mesh>>)>,
+    assets: Res<Assets<Md2Asset>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, handle) in models.iter() {
+        let Some(asset) = assets.get(handle) else {
+            continue;
+        };
+        let Some(mesh) = meshes.get(&asset.mesh).cloned() else {
+            continue;
+        };
+        let positions = asset
+            .frames
+            .iter()
+            .flat_map(|frame| frame.positions.iter().copied().map(Vec3::from));
+        let (min, max) = positions.fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        );
+        commands
+            .entity(entity)
+            .insert((meshes.add(mesh), Aabb::from_min_max(min, max)));
+    }
+}
+
+/// Advances the animators and blends the keyframes they show into their
+/// entity's mesh.
+fn animate_md2(
+    time: Res<Time>,
+    mut models: Query<(&mut Md2Animator, &Handle<Md2Asset>, &Handle<Mesh>)>,
+    assets: Res<Assets<Md2Asset>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (mut animator, handle, mesh) in models.iter_mut() {
+        let Some(asset) = assets.get(handle) else {
+            continue;
+        };
+        animator.time += time.delta_seconds();
+        let frames = animator
+            .animation
+            .and_then(|animation| asset.md2.animation_frames(animation))
+            .unwrap_or_else(|| animator.frames.clone());
+        let shown = animator.keyframes(frames, asset.frames.len());
+        if animator.shown == Some(shown) {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(mesh) else {
+            continue;
+        };
+        animator.shown = Some(shown);
+
+        let (from, to, blend) = shown;
+        let (from, to) = (&asset.frames[from], &asset.frames[to]);
+        let lerp = |a: &[[f32; 3]], b: &[[f32; 3]]| -> Vec<[f32; 3]> {
+            a.iter()
+                .zip(b)
+                .map(|(&a, &b)| Vec3::from(a).lerp(Vec3::from(b), blend).to_array())
+                .collect()
+        };
+        let normals = lerp(&from.normals, &to.normals)
+            .into_iter()
+            .map(|n| Vec3::from(n).normalize_or_zero().to_array())
+            .collect::<Vec<_>>();
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            lerp(&from.positions, &to.positions),
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    }
+}
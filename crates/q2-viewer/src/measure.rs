@@ -0,0 +1,181 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_mod_raycast::prelude::{Raycast, RaycastSettings, RaycastVisibility};
+
+use crate::{
+    locale::{Locale, Message},
+    render::{OverlayStats, ScaledView},
+    sim::Interpolated,
+    start::WorldBatch,
+};
+
+/// Meters per map unit: Quake units are treated as inches.
+pub const METERS_PER_UNIT: f32 = 0.0254;
+
+/// A tape measure for mappers: with measure mode on (M key), two clicks on
+/// world geometry report the distance between the points in units and
+/// meters, along with the X, Y and Z deltas, and draw them in the world.
+/// A third click starts a new measurement.
+pub struct MeasurePlugin;
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Measurement>().add_systems(
+            Update,
+            (
+                toggle_measure,
+                pick_points,
+                report_measurement,
+                draw_measurement,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// The points picked in measure mode, in world space.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Measurement {
+    pub active: bool,
+    /// Up to two picked points; the first click of a new measurement
+    /// replaces both.
+    pub points: Vec<Vec3>,
+}
+
+impl Measurement {
+    /// Adds a picked point, starting over once both ends are set.
+    pub fn pick(&mut self, point: Vec3) {
+        if self.points.len() >= 2 {
+            self.points.clear();
+        }
+        self.points.push(point);
+    }
+
+    /// The vector from the first point to the second, once both are picked.
+    pub fn delta(&self) -> Option<Vec3> {
+        match self.points[..] {
+            [a, b] => Some(b - a),
+            _ => None,
+        }
+    }
+
+    /// The measurement as shown in the overlay.
+    ///
+    /// ```
+    /// # use bevy::math::Vec3;
+    /// # use q2_viewer::{locale::Locale, measure::Measurement};
+    /// let mut measure = Measurement::default();
+    /// measure.pick(Vec3::ZERO);
+    /// measure.pick(Vec3::new(0.0, 0.0, 64.0));
+    /// assert_eq!(
+    ///     Locale::default().text(&measure.report().unwrap()),
+    ///     "64.0 units (1.63 m)  dx 0.0  dy 0.0  dz 64.0"
+    /// );
+    /// ```
+    pub fn report(&self) -> Option<Message> {
+        let delta = self.delta()?;
+        let length = delta.length();
+        Some(
+            Message::new("measure.report")
+                .arg(format!("{:.1}", length))
+                .arg(format!("{:.2}", length * METERS_PER_UNIT))
+                .arg(format!("{:.1}", delta.x.abs()))
+                .arg(format!("{:.1}", delta.y.abs()))
+                .arg(format!("{:.1}", delta.z.abs())),
+        )
+    }
+}
+
+fn toggle_measure(keys: Res<ButtonInput<KeyCode>>, mut measure: ResMut<Measurement>) {
+    if keys.just_pressed(KeyCode::KeyM) {
+        measure.active = !measure.active;
+        measure.points.clear();
+    }
+}
+
+/// Picks the world geometry under the cursor on left click, dropping the
+/// points whenever a new map is spawned.
+#[allow(clippy::too_many_arguments)]
+fn pick_points(
+    mut measure: ResMut<Measurement>,
+    mut raycast: Raycast,
+    mouse: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    view: Res<ScaledView>,
+    cameras: Query<(&Camera, &GlobalTransform), With<Interpolated>>,
+    batches: Query<(), With<WorldBatch>>,
+    new_batches: Query<(), Added<WorldBatch>>,
+) {
+    if !new_batches.is_empty() {
+        measure.points.clear();
+    }
+    if !measure.active || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(window) = windows.iter().next() else {
+        return;
+    };
+    let Some(cursor) = window.cursor_position() else {
+        return;
+    };
+    let cursor = view.to_viewport(window, cursor);
+    let Some(ray) = cameras
+        .iter()
+        .next()
+        .and_then(|(camera, transform)| camera.viewport_to_world(transform, cursor))
+    else {
+        return;
+    };
+
+    // World batches may be hidden by culling or the heatmap, but the
+    // geometry is still there
+    let is_world = |entity| batches.contains(entity);
+    let settings = RaycastSettings::default()
+        .with_visibility(RaycastVisibility::Ignore)
+        .with_filter(&is_world);
+    if let Some((_, hit)) = raycast.cast_ray(ray, &settings).first() {
+        measure.pick(hit.position());
+    }
+}
+
+fn report_measurement(
+    measure: Res<Measurement>,
+    locale: Res<Locale>,
+    mut stats: ResMut<OverlayStats>,
+) {
+    if !measure.is_changed() {
+        return;
+    }
+    match measure.report() {
+        Some(report) => {
+            info!("Measured {}", locale.text(&report));
+            stats.set("measure", report);
+        }
+        None if measure.active => stats.set("measure", Message::new("measure.prompt")),
+        None => stats.remove("measure"),
+    }
+}
+
+/// Draws the picked points, the line between them, and its X, Y and Z legs.
+fn draw_measurement(measure: Res<Measurement>, mut gizmos: Gizmos) {
+    if !measure.active {
+        return;
+    }
+    for &point in &measure.points {
+        gizmos.sphere(point, Quat::IDENTITY, 4.0, Color::WHITE);
+    }
+    let (Some(delta), Some(&start)) = (measure.delta(), measure.points.first()) else {
+        return;
+    };
+    gizmos.line(start, start + delta, Color::WHITE);
+    let legs = [
+        (Vec3::X, Color::srgb(1.0, 0.2, 0.2)),
+        (Vec3::Y, Color::srgb(0.2, 1.0, 0.2)),
+        (Vec3::Z, Color::srgb(0.3, 0.4, 1.0)),
+    ];
+    let mut corner = start;
+    for (axis, color) in legs {
+        let next = corner + axis * delta;
+        gizmos.line(corner, next, color);
+        corner = next;
+    }
+}
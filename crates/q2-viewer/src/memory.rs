@@ -0,0 +1,150 @@
+use std::{sync::Mutex, time::Duration};
+
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, Mesh},
+    time::common_conditions::on_timer,
+};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    asset::BSP38Asset,
+    locale::Message,
+    pak::MountedArchives,
+    render::{OverlayStats, TextureStreaming},
+};
+
+/// How often the [MemoryStats] are counted again.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The latest [MemoryStats], for [memory_stats_js].
+static LATEST: Mutex<MemoryStats> = Mutex::new(MemoryStats::EMPTY);
+
+/// Counts the memory held by loaded maps and archives into [MemoryStats]
+/// once a second, and shows the total in the overlay.
+pub struct MemoryStatsPlugin;
+
+impl Plugin for MemoryStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MemoryStats>().add_systems(
+            Update,
+            update_memory_stats.run_if(on_timer(UPDATE_INTERVAL)),
+        );
+    }
+}
+
+/// Bytes of CPU memory held by what the viewer has loaded, to see why a big
+/// map struggles on a small device. GPU copies of meshes and images aren't
+/// counted, nor are meshes already dropped from the main world after upload.
+///
+/// From JavaScript:
+///
+/// ```js
+/// const stats = mod.memory_stats();
+/// console.log(`${stats.total() / 1048576} MiB`);
+/// ```
+#[wasm_bindgen]
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Raw BSP files.
+    pub bsp: usize,
+    /// Lumps decoded from them up front, see
+    /// [BSP38::decoded_bytes](q2_formats::bsp38::BSP38::decoded_bytes).
+    pub decoded: usize,
+    /// World and inline model meshes, and collision meshes.
+    pub meshes: usize,
+    /// Lightmap atlas pages.
+    pub lightmaps: usize,
+    /// Streamed textures.
+    pub textures: usize,
+    /// Opened .pak archives, which are kept whole.
+    pub archives: usize,
+}
+
+#[wasm_bindgen]
+impl MemoryStats {
+    pub fn total(&self) -> usize {
+        self.bsp + self.decoded + self.meshes + self.lightmaps + self.textures + self.archives
+    }
+}
+
+impl MemoryStats {
+    const EMPTY: MemoryStats = MemoryStats {
+        bsp: 0,
+        decoded: 0,
+        meshes: 0,
+        lightmaps: 0,
+        textures: 0,
+        archives: 0,
+    };
+}
+
+/// The latest [MemoryStats] of the running viewer.
+#[wasm_bindgen(js_name = memory_stats)]
+pub fn memory_stats_js() -> MemoryStats {
+    *LATEST.lock().unwrap()
+}
+
+/// Bytes of the vertex and index buffers of `mesh`.
+fn mesh_bytes(mesh: &Mesh) -> usize {
+    let indices = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.len() * 2,
+        Some(Indices::U32(indices)) => indices.len() * 4,
+        None => 0,
+    };
+    mesh.count_vertices() * mesh.get_vertex_size() as usize + indices
+}
+
+fn update_memory_stats(
+    mut memory: ResMut<MemoryStats>,
+    mut stats: ResMut<OverlayStats>,
+    maps: Res<Assets<BSP38Asset>>,
+    meshes: Res<Assets<Mesh>>,
+    images: Res<Assets<Image>>,
+    streaming: Option<Res<TextureStreaming>>,
+    archives: Option<Res<MountedArchives>>,
+) {
+    let mut next = MemoryStats {
+        textures: streaming.map_or(0, |streaming| streaming.resident_bytes()),
+        archives: archives.map_or(0, |archives| archives.bytes()),
+        ..default()
+    };
+    for (_, map) in maps.iter() {
+        next.bsp += map.bsp.bytes.capacity();
+        next.decoded += map.bsp.decoded_bytes();
+        next.meshes += map
+            .meshes
+            .iter()
+            .chain(map.inline_models.iter().flatten())
+            .filter_map(|batch| meshes.get(&batch.mesh))
+            .map(mesh_bytes)
+            .sum::<usize>();
+        if let Some(collision) = &map.collision {
+            next.meshes += std::mem::size_of_val(collision.vertices.as_slice())
+                + std::mem::size_of_val(collision.triangles.as_slice());
+        }
+        next.lightmaps += map
+            .lightmaps
+            .iter()
+            .filter_map(|page| images.get(page))
+            .map(|image| image.data.len())
+            .sum::<usize>();
+    }
+
+    *LATEST.lock().unwrap() = next;
+    if *memory != next {
+        *memory = next;
+    }
+    let mib = |bytes: usize| format!("{:.1}", bytes as f64 / (1 << 20) as f64);
+    stats.set(
+        "memory",
+        Message::new("memory.summary")
+            .arg(mib(next.total()))
+            .arg(mib(next.bsp))
+            .arg(mib(next.decoded))
+            .arg(mib(next.meshes))
+            .arg(mib(next.lightmaps))
+            .arg(mib(next.textures))
+            .arg(mib(next.archives)),
+    );
+}
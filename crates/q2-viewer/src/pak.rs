@@ -0,0 +1,203 @@
+use std::{
+    io,
+    path::{self, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    asset::{
+        io::{
+            AssetReader, AssetReaderError, AssetSource, AssetSourceBuilder, AssetSourceId,
+            ErasedAssetReader, PathStream, Reader, VecReader,
+        },
+        AsyncReadExt,
+    },
+    prelude::*,
+    utils::HashMap,
+};
+
+use q2_formats::pak::{PakArchive, PakError};
+
+/// Folder the default asset source reads from unless configured otherwise,
+/// relative to the working directory or, on the web, to the page.
+pub const ASSET_ROOT: &str = "assets";
+
+type Archive = PakArchive<io::Cursor<Vec<u8>>>;
+
+/// Reads files out of .pak archives in the default asset source, so Quake 2
+/// content loads straight from the game's archives:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use q2_viewer::{asset::BSP38Asset, pak::PakAssetPlugin};
+/// # fn load(asset_server: Res<AssetServer>) {
+/// let map: Handle<BSP38Asset> = asset_server.load("pak0.pak/maps/q2dm1.bsp");
+/// # }
+/// ```
+///
+/// Paths are split at the first component ending in `.pak`; everything else
+/// goes to the platform's default reader. Each archive is fetched whole on
+/// first use and kept in memory, which also works over HTTP on the web.
+///
+/// Replaces the default asset source, so it must be added before
+/// `DefaultPlugins`.
+pub struct PakAssetPlugin {
+    /// Folder the assets are read from, or on the web the base URL they are
+    /// fetched from, e.g. `https://cdn.example.com/q2/`.
+    pub root: String,
+}
+
+impl Default for PakAssetPlugin {
+    fn default() -> Self {
+        Self {
+            root: ASSET_ROOT.to_string(),
+        }
+    }
+}
+
+impl Plugin for PakAssetPlugin {
+    fn build(&self, app: &mut App) {
+        info!("Reading assets from {}", self.root);
+        let mut default_reader = AssetSource::get_default_reader(self.root.clone());
+        let mounted = MountedArchives::default();
+        app.insert_resource(mounted.clone()).register_asset_source(
+            AssetSourceId::Default,
+            AssetSourceBuilder::platform_default(&self.root, None).with_reader(move || {
+                Box::new(PakAssetReader::new(default_reader()).with_mounted(mounted.clone()))
+            }),
+        );
+    }
+}
+
+/// Bytes held by the archives a [PakAssetReader] has opened, shared with
+/// the reader so the app can show them.
+#[derive(Resource, Clone, Default)]
+pub struct MountedArchives(Arc<AtomicUsize>);
+
+impl MountedArchives {
+    pub fn bytes(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// An [AssetReader] serving files inside .pak archives from those read by
+/// `inner`.
+pub struct PakAssetReader {
+    inner: Box<dyn ErasedAssetReader>,
+    archives: Mutex<HashMap<PathBuf, Arc<Mutex<Archive>>>>,
+    mounted: MountedArchives,
+}
+
+impl PakAssetReader {
+    pub fn new(inner: Box<dyn ErasedAssetReader>) -> Self {
+        Self {
+            inner,
+            archives: Mutex::default(),
+            mounted: MountedArchives::default(),
+        }
+    }
+
+    /// Counts the bytes of the archives opened in `mounted`.
+    pub fn with_mounted(mut self, mounted: MountedArchives) -> Self {
+        self.mounted = mounted;
+        self
+    }
+
+    /// The archive and the `/` separated path inside of it, if `path` points
+    /// into an archive.
+    fn split(path: &Path) -> Option<(PathBuf, String)> {
+        let mut archive = PathBuf::new();
+        let mut components = path.components();
+        for component in components.by_ref() {
+            archive.push(component);
+            let path::Component::Normal(name) = component else {
+                continue;
+            };
+            let is_pak = Path::new(name)
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pak"));
+            if is_pak {
+                let inner: Vec<_> = components
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect();
+                return (!inner.is_empty()).then(|| (archive, inner.join("/")));
+            }
+        }
+        None
+    }
+
+    async fn archive(&self, path: &Path) -> Result<Arc<Mutex<Archive>>, AssetReaderError> {
+        let cached = self.archives.lock().unwrap().get(path).cloned();
+        if let Some(archive) = cached {
+            return Ok(archive);
+        }
+        let mut bytes = Vec::new();
+        self.inner.read(path).await?.read_to_end(&mut bytes).await?;
+        let archive = PakArchive::from_bytes(bytes).map_err(io::Error::other)?;
+        info!(
+            "Opened {} with {} files",
+            path.display(),
+            archive.entries().len()
+        );
+        let bytes = archive.memory_bytes();
+        let archive = Arc::new(Mutex::new(archive));
+        let opened = self
+            .archives
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), archive.clone());
+        // Two loads may open the same archive at once; count it once
+        if opened.is_none() {
+            self.mounted.0.fetch_add(bytes, Ordering::Relaxed);
+        }
+        Ok(archive)
+    }
+}
+
+impl AssetReader for PakAssetReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        let Some((archive_path, name)) = Self::split(path) else {
+            return self.inner.read(path).await;
+        };
+        let archive = self.archive(&archive_path).await?;
+        let bytes = archive
+            .lock()
+            .unwrap()
+            .read_file(&name)
+            .map_err(|err| match err {
+                PakError::NotFound(_) => AssetReaderError::NotFound(path.to_path_buf()),
+                err => io::Error::other(err).into(),
+            })?;
+        Ok(Box::new(VecReader::new(bytes)))
+    }
+
+    /// Archives hold no .meta files, so their files load with the default
+    /// settings.
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<Box<Reader<'a>>, AssetReaderError> {
+        match Self::split(path) {
+            Some(_) => Err(AssetReaderError::NotFound(path.to_path_buf())),
+            None => self.inner.read_meta(path).await,
+        }
+    }
+
+    /// Directories inside archives aren't listed.
+    async fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<Box<PathStream>, AssetReaderError> {
+        match Self::split(path) {
+            Some(_) => Err(AssetReaderError::NotFound(path.to_path_buf())),
+            None => self.inner.read_directory(path).await,
+        }
+    }
+
+    async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+        match Self::split(path) {
+            Some(_) => Ok(false),
+            None => self.inner.is_directory(path).await,
+        }
+    }
+}
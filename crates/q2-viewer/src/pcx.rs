@@ -0,0 +1,65 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use thiserror::Error;
+
+use q2_formats::pcx::{Pcx, PcxError};
+
+use crate::tga::{read_override, tga_image};
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum PcxAssetLoaderError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid PCX: {0}")]
+    Pcx(#[from] PcxError),
+}
+
+/// Loads .pcx images, such as `pics/` and model skins, as RGBA [Image]s
+/// colored through their own palette, with index 255 transparent. As in the
+/// engine, a .tga of the same name next to the .pcx replaces it.
+#[derive(Default)]
+pub struct PcxAssetLoader;
+
+impl AssetLoader for PcxAssetLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = PcxAssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Image, Self::Error> {
+        let path = load_context.path().to_path_buf();
+        if let Some(tga) = read_override(&path, load_context).await {
+            return Ok(tga_image(&tga, 1));
+        }
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let pcx = Pcx::from_bytes(&bytes)?;
+        let size = Extent3d {
+            width: pcx.width,
+            height: pcx.height,
+            depth_or_array_layers: 1,
+        };
+        Ok(Image::new(
+            size,
+            TextureDimension::D2,
+            pcx.to_rgba(),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        ))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pcx"]
+    }
+}
@@ -0,0 +1,109 @@
+//! Static and kinematic [bevy_rapier3d] colliders for maps, behind the
+//! `rapier` feature.
+//!
+//! ```no_run
+//! # use bevy::prelude::*;
+//! # use bevy_rapier3d::prelude::*;
+//! # use q2_viewer::physics::RapierMapPlugin;
+//! App::new()
+//!     .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+//!     .add_plugins(RapierMapPlugin::default());
+//! ```
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use q2_formats::bsp38::{
+    contents::MASK_SOLID,
+    prelude::{ConvexHull, ModelCollision},
+};
+
+use crate::{
+    asset::BSP38Asset,
+    maps::{BrushModel, MapInstance},
+};
+
+/// Adds a collider to every [BrushModel] of a map as it is spawned.
+///
+/// The world model gets a fixed body and inline models a kinematic one, so
+/// doors and platforms push dynamic bodies around as their transform is
+/// animated. Colliders are the convex hulls of the brushes with any of the
+/// `mask` contents, or the model's triangles when it has no such brushes.
+pub struct RapierMapPlugin {
+    pub mask: i32,
+}
+
+impl Default for RapierMapPlugin {
+    fn default() -> Self {
+        Self { mask: MASK_SOLID }
+    }
+}
+
+/// Brush contents that get colliders, from [RapierMapPlugin::mask].
+#[derive(Resource)]
+struct ColliderMask(i32);
+
+impl Plugin for RapierMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ColliderMask(self.mask))
+            .add_systems(Update, add_model_colliders);
+    }
+}
+
+fn add_model_colliders(
+    mut commands: Commands,
+    mut cooked: Local<HashMap<AssetId<BSP38Asset>, Vec<ModelCollision>>>,
+    mask: Res<ColliderMask>,
+    models: Query<(Entity, &BrushModel, &Parent), Added<BrushModel>>,
+    instances: Query<&MapInstance>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    for (entity, model, parent) in models.iter() {
+        let Ok(instance) = instances.get(parent.get()) else {
+            continue;
+        };
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        // Every model of a map is spawned in the same frame, so the map is
+        // cooked once per load
+        let collision = cooked
+            .entry(instance.handle.id())
+            .or_insert_with(|| asset.bsp.model_collision(mask.0));
+        let Some(collision) = collision.get(model.index) else {
+            continue;
+        };
+        let Some(collider) = model_collider(collision) else {
+            continue;
+        };
+        let body = match model.index {
+            0 => RigidBody::Fixed,
+            _ => RigidBody::KinematicPositionBased,
+        };
+        commands.entity(entity).insert((body, collider));
+    }
+    cooked.clear();
+}
+
+fn model_collider(collision: &ModelCollision) -> Option<Collider> {
+    if collision.hulls.is_empty() {
+        let mesh = &collision.mesh;
+        if mesh.triangles.is_empty() {
+            return None;
+        }
+        let vertices = mesh.vertices.iter().copied().map(Vec3::from).collect();
+        return Some(Collider::trimesh(vertices, mesh.triangles.clone()));
+    }
+    let shapes = collision
+        .hulls
+        .iter()
+        .filter_map(hull_collider)
+        .map(|collider| (Vec3::ZERO, Quat::IDENTITY, collider))
+        .collect();
+    Some(Collider::compound(shapes))
+}
+
+fn hull_collider(hull: &ConvexHull) -> Option<Collider> {
+    let points: Vec<Vec3> = hull.vertices.iter().copied().map(Vec3::from).collect();
+    Collider::convex_hull(&points)
+}
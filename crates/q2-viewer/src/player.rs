@@ -0,0 +1,457 @@
+use bevy::prelude::*;
+use q2_formats::bsp38::{contents::MASK_PLAYERSOLID, prelude::Tracer};
+
+use crate::{
+    asset::MapSummary,
+    camera::{mouse_look, CameraMode, FlyCamera},
+    locale::Message,
+    maps::MapManager,
+    render::OverlayStats,
+    sim::Interpolated,
+    start::MapTracer,
+};
+
+/// Bounds of the player's box around its origin, standing.
+const PLAYER_MINS: Vec3 = Vec3::new(-16.0, -16.0, -24.0);
+const PLAYER_MAXS: Vec3 = Vec3::new(16.0, 16.0, 32.0);
+/// Top of the player's box while crouching.
+const DUCKED_MAX_Z: f32 = 4.0;
+/// Height spawned players are raised by above their start, as in Quake 2,
+/// so they don't start stuck in the floor.
+const SPAWN_HEIGHT: f32 = 9.0;
+/// Height of the eye above the origin, standing and crouching.
+const VIEW_HEIGHT: f32 = 22.0;
+const DUCKED_VIEW_HEIGHT: f32 = -2.0;
+
+/// Highest step walked up without jumping.
+const STEP_SIZE: f32 = 18.0;
+/// Steepest slope stood on, as the Z of its normal.
+const MIN_STEP_NORMAL: f32 = 0.7;
+/// Velocity components smaller than this after clipping are dropped.
+const STOP_EPSILON: f32 = 0.1;
+/// Planes the player can slide along at once before stopping.
+const MAX_CLIP_PLANES: usize = 5;
+
+/// Walks the current map as the player while the camera is in
+/// [CameraMode::Walk], with the movement of Quake 2's `pmove`.
+///
+/// The player's box slides along the brushes of the map, steps up stairs,
+/// falls and jumps. WASD moves, Space jumps and Ctrl crouches; the mouse
+/// looks around as with the fly camera. The player starts at the map's
+/// [player start](MapSummary::player_start) when walking starts, and again
+/// whenever the map changes; on maps without one, where the camera is.
+///
+/// When a map loads, the camera is also put at its player start, facing the
+/// start's angle, so the fly camera begins where the game would.
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Player>()
+            .add_systems(
+                Update,
+                (
+                    respawn_player.run_if(resource_changed::<CameraMode>),
+                    place_camera_at_start,
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                move_player.run_if(resource_equals(CameraMode::Walk)),
+            );
+    }
+}
+
+/// Movement tuning, in map units and seconds. The defaults are Quake 2's.
+#[derive(Debug, Clone)]
+pub struct Movement {
+    pub gravity: f32,
+    /// Top speed running, and crouching.
+    pub max_speed: f32,
+    pub duck_speed: f32,
+    /// Speed asked for at full input, before clamping to the top speed.
+    pub input_speed: f32,
+    pub accelerate: f32,
+    /// Acceleration in the air, as a factor of the asked speed per second.
+    pub air_accelerate: f32,
+    pub friction: f32,
+    /// Below this speed, friction stops the player as if moving this fast.
+    pub stop_speed: f32,
+    pub jump_speed: f32,
+}
+
+impl Default for Movement {
+    fn default() -> Self {
+        Self {
+            gravity: 800.0,
+            max_speed: 300.0,
+            duck_speed: 100.0,
+            input_speed: 400.0,
+            accelerate: 10.0,
+            air_accelerate: 1.0,
+            friction: 6.0,
+            stop_speed: 100.0,
+            jump_speed: 270.0,
+        }
+    }
+}
+
+/// What the player is asked to do for one step.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlayerInput {
+    /// Direction the player is looking.
+    pub view: Vec3,
+    /// Forward and right movement, from -1 to 1.
+    pub forward: f32,
+    pub right: f32,
+    pub jump: bool,
+    pub crouch: bool,
+}
+
+/// The player's state, in the map's own coordinates.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Player {
+    pub origin: Vec3,
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub ducked: bool,
+    pub movement: Movement,
+    /// Jump stays held until released, so holding it doesn't bunny hop.
+    jump_held: bool,
+    /// The map the player was placed in.
+    root: Option<Entity>,
+}
+
+impl Player {
+    fn maxs(&self) -> Vec3 {
+        match self.ducked {
+            true => PLAYER_MAXS.with_z(DUCKED_MAX_Z),
+            false => PLAYER_MAXS,
+        }
+    }
+
+    /// Height of the eye above the origin.
+    pub fn view_height(&self) -> f32 {
+        match self.ducked {
+            true => DUCKED_VIEW_HEIGHT,
+            false => VIEW_HEIGHT,
+        }
+    }
+
+    fn trace(&self, tracer: &Tracer, start: Vec3, end: Vec3) -> Trace {
+        let trace = tracer.trace(
+            start.to_array(),
+            end.to_array(),
+            PLAYER_MINS.to_array(),
+            self.maxs().to_array(),
+            MASK_PLAYERSOLID,
+        );
+        Trace {
+            end: Vec3::from(trace.end),
+            fraction: trace.fraction,
+            normal: trace.plane.map(|[x, y, z, _]| Vec3::new(x, y, z)),
+            all_solid: trace.all_solid,
+        }
+    }
+
+    /// Moves the player by one step of `dt` seconds.
+    pub fn step(&mut self, tracer: &Tracer, input: &PlayerInput, dt: f32) {
+        self.check_duck(tracer, input.crouch);
+        self.categorize_position(tracer);
+        self.check_jump(input.jump);
+        self.apply_friction(dt);
+
+        // Only the horizontal part of the view steers
+        let forward = input.view.with_z(0.0).normalize_or_zero();
+        let right = forward.cross(Vec3::Z);
+        let wish = (forward * input.forward + right * input.right) * self.movement.input_speed;
+        let max_speed = match self.ducked {
+            true => self.movement.duck_speed,
+            false => self.movement.max_speed,
+        };
+        let wish_speed = wish.length().min(max_speed);
+        let wish_dir = wish.normalize_or_zero();
+
+        if self.on_ground {
+            self.velocity.z = 0.0;
+            self.accelerate(wish_dir, wish_speed, self.movement.accelerate, dt);
+            if self.velocity.x != 0.0 || self.velocity.y != 0.0 {
+                self.step_slide_move(tracer, dt);
+            }
+        } else {
+            self.accelerate(wish_dir, wish_speed, self.movement.air_accelerate, dt);
+            self.velocity.z -= self.movement.gravity * dt;
+            self.step_slide_move(tracer, dt);
+        }
+        self.categorize_position(tracer);
+    }
+
+    fn check_duck(&mut self, tracer: &Tracer, crouch: bool) {
+        if crouch {
+            self.ducked = true;
+        } else if self.ducked {
+            // Only stand up where there is room to
+            let standing = Self {
+                ducked: false,
+                ..self.clone()
+            };
+            if !standing.trace(tracer, self.origin, self.origin).all_solid {
+                self.ducked = false;
+            }
+        }
+    }
+
+    /// Whether the player stands on the ground: something walkable just
+    /// under its feet, while not flying up.
+    fn categorize_position(&mut self, tracer: &Tracer) {
+        if self.velocity.z > 180.0 {
+            self.on_ground = false;
+            return;
+        }
+        let below = self.origin - Vec3::Z * 0.25;
+        let trace = self.trace(tracer, self.origin, below);
+        self.on_ground = trace
+            .normal
+            .is_some_and(|normal| normal.z >= MIN_STEP_NORMAL);
+    }
+
+    fn check_jump(&mut self, jump: bool) {
+        if !jump {
+            self.jump_held = false;
+            return;
+        }
+        if self.jump_held || !self.on_ground {
+            return;
+        }
+        self.jump_held = true;
+        self.on_ground = false;
+        self.velocity.z =
+            (self.velocity.z + self.movement.jump_speed).max(self.movement.jump_speed);
+    }
+
+    fn apply_friction(&mut self, dt: f32) {
+        let speed = self.velocity.length();
+        if speed < 1.0 {
+            self.velocity.x = 0.0;
+            self.velocity.y = 0.0;
+            return;
+        }
+        let drop = match self.on_ground {
+            true => speed.max(self.movement.stop_speed) * self.movement.friction * dt,
+            false => 0.0,
+        };
+        self.velocity *= (speed - drop).max(0.0) / speed;
+    }
+
+    fn accelerate(&mut self, wish_dir: Vec3, wish_speed: f32, accelerate: f32, dt: f32) {
+        let add_speed = wish_speed - self.velocity.dot(wish_dir);
+        if add_speed <= 0.0 {
+            return;
+        }
+        let accel_speed = (accelerate * dt * wish_speed).min(add_speed);
+        self.velocity += wish_dir * accel_speed;
+    }
+
+    /// Slides along what is in the way, then tries the same move from a step
+    /// higher and keeps whichever got further.
+    fn step_slide_move(&mut self, tracer: &Tracer, dt: f32) {
+        let (start_origin, start_velocity) = (self.origin, self.velocity);
+        self.slide_move(tracer, dt);
+        let (down_origin, down_velocity) = (self.origin, self.velocity);
+
+        let up = self.trace(tracer, start_origin, start_origin + Vec3::Z * STEP_SIZE);
+        if up.all_solid {
+            // No room to step up
+            return;
+        }
+        self.origin = up.end;
+        self.velocity = start_velocity;
+        self.slide_move(tracer, dt);
+
+        // Back down onto the step
+        let down = self.trace(tracer, self.origin, self.origin - Vec3::Z * STEP_SIZE);
+        if !down.all_solid {
+            self.origin = down.end;
+        }
+
+        let moved = |origin: Vec3| (origin - start_origin).truncate().length_squared();
+        let on_step = down
+            .normal
+            .is_some_and(|normal| normal.z >= MIN_STEP_NORMAL);
+        if moved(down_origin) > moved(self.origin) || !on_step {
+            self.origin = down_origin;
+            self.velocity = down_velocity;
+        } else {
+            // Stepping keeps the fall or climb of the plain slide
+            self.velocity.z = down_velocity.z;
+        }
+    }
+
+    /// Moves for `dt` seconds, clipping the velocity against every plane hit
+    /// on the way.
+    fn slide_move(&mut self, tracer: &Tracer, dt: f32) {
+        let primal_velocity = self.velocity;
+        let mut planes: Vec<Vec3> = Vec::new();
+        let mut time_left = dt;
+        for _ in 0..4 {
+            let trace = self.trace(tracer, self.origin, self.origin + self.velocity * time_left);
+            if trace.all_solid {
+                // Stuck in a brush
+                self.velocity.z = 0.0;
+                return;
+            }
+            if trace.fraction > 0.0 {
+                self.origin = trace.end;
+                planes.clear();
+            }
+            let Some(normal) = trace.normal.filter(|_| trace.fraction < 1.0) else {
+                break;
+            };
+            time_left -= time_left * trace.fraction;
+            if planes.len() >= MAX_CLIP_PLANES {
+                self.velocity = Vec3::ZERO;
+                break;
+            }
+            planes.push(normal);
+
+            // Find a velocity along every plane hit, or along the crease of
+            // two of them
+            let fits = planes.iter().enumerate().find_map(|(i, &plane)| {
+                let velocity = clip_velocity(self.velocity, plane, 1.01);
+                let along_all = planes
+                    .iter()
+                    .enumerate()
+                    .all(|(j, &other)| j == i || velocity.dot(other) >= 0.0);
+                along_all.then_some(velocity)
+            });
+            self.velocity = match (fits, planes.as_slice()) {
+                (Some(velocity), _) => velocity,
+                (None, &[a, b]) => {
+                    let crease = a.cross(b);
+                    crease * crease.dot(self.velocity)
+                }
+                (None, _) => Vec3::ZERO,
+            };
+            // Don't turn back and jitter in corners
+            if self.velocity.dot(primal_velocity) <= 0.0 {
+                self.velocity = Vec3::ZERO;
+                break;
+            }
+        }
+    }
+}
+
+/// The parts of a box trace the player needs, in [Vec3]s.
+struct Trace {
+    end: Vec3,
+    fraction: f32,
+    normal: Option<Vec3>,
+    all_solid: bool,
+}
+
+/// `velocity` without its part into the plane with `normal`, bounced off
+/// slightly by `overbounce`.
+fn clip_velocity(velocity: Vec3, normal: Vec3, overbounce: f32) -> Vec3 {
+    let clipped = velocity - normal * velocity.dot(normal) * overbounce;
+    Vec3::select(
+        clipped.abs().cmplt(Vec3::splat(STOP_EPSILON)),
+        Vec3::ZERO,
+        clipped,
+    )
+}
+
+/// The eye of a player at the map's player start, facing its angle, in
+/// world coordinates.
+fn start_view(summary: &MapSummary, root_transform: &GlobalTransform) -> Option<Transform> {
+    let start = summary.player_start()?;
+    let origin = start.origin? + Vec3::Z * (SPAWN_HEIGHT + VIEW_HEIGHT);
+    let yaw = start.angle.unwrap_or(0.0).to_radians();
+    let eye = root_transform.transform_point(origin + summary.world_offset());
+    let forward = root_transform
+        .affine()
+        .transform_vector3(Vec3::new(yaw.cos(), yaw.sin(), 0.0));
+    Some(Transform::from_translation(eye).looking_to(forward, Vec3::Z))
+}
+
+/// Puts the camera at the player start of each map as it loads.
+fn place_camera_at_start(
+    maps: Res<MapManager>,
+    roots: Query<(Entity, &MapSummary, &GlobalTransform), Added<MapSummary>>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+) {
+    for (root, summary, root_transform) in roots.iter() {
+        if maps.root() != Some(root) {
+            continue;
+        }
+        let Some(view) = start_view(summary, root_transform) else {
+            continue;
+        };
+        for mut camera in cameras.iter_mut() {
+            *camera = Interpolated::new(view);
+        }
+    }
+}
+
+/// Places the player again when walking starts.
+fn respawn_player(mut player: ResMut<Player>, mut stats: ResMut<OverlayStats>) {
+    player.root = None;
+    stats.remove("player");
+}
+
+// Runs on the fixed timestep, where `Time` is the simulation clock
+#[allow(clippy::too_many_arguments)]
+fn move_player(
+    mut player: ResMut<Player>,
+    mut fly: ResMut<FlyCamera>,
+    mut stats: ResMut<OverlayStats>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    maps: Res<MapManager>,
+    roots: Query<(&MapTracer, &MapSummary, &GlobalTransform)>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+) {
+    let Some((tracer, summary, root_transform)) = maps.root().and_then(|root| roots.get(root).ok())
+    else {
+        return;
+    };
+    let Some(mut camera) = cameras.iter_mut().next() else {
+        return;
+    };
+    let offset = summary.world_offset();
+    let to_local = root_transform.affine().inverse();
+
+    if player.root != maps.root() {
+        if let Some(view) = start_view(summary, root_transform) {
+            *camera = Interpolated::new(view);
+        }
+        let eye = to_local.transform_point3(camera.current.translation) - offset;
+        *player = Player {
+            origin: eye - Vec3::Z * VIEW_HEIGHT,
+            root: maps.root(),
+            movement: player.movement.clone(),
+            ..default()
+        };
+    }
+
+    let axis = |positive: KeyCode, negative: KeyCode| {
+        (keys.pressed(positive) as i32 - keys.pressed(negative) as i32) as f32
+    };
+    let input = PlayerInput {
+        view: mouse_look(&mut camera.current, fly.take_look()),
+        forward: axis(KeyCode::KeyW, KeyCode::KeyS),
+        right: axis(KeyCode::KeyD, KeyCode::KeyA),
+        jump: keys.pressed(KeyCode::Space),
+        crouch: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+    };
+    player.step(&tracer.0, &input, time.delta_seconds());
+
+    let eye = player.origin + Vec3::Z * player.view_height();
+    camera.current.translation = root_transform.transform_point(eye + offset);
+    let key = match (player.on_ground, player.ducked) {
+        (_, true) => "player.crouching",
+        (false, false) => "player.falling",
+        (true, false) => "player.walking",
+    };
+    let speed = player.velocity.truncate().length();
+    stats.set("player", Message::new(key).arg(format!("{:.0}", speed)));
+}
@@ -0,0 +1,354 @@
+use bevy::{
+    pbr::Lightmap,
+    prelude::*,
+    render::renderer::{RenderAdapterInfo, RenderDevice},
+};
+
+use crate::{
+    asset::BSP38Asset,
+    maps::{BrushModel, MapInstance},
+    render::TextureStreaming,
+    sim::Interpolated,
+    start::WorldBatch,
+};
+
+/// Device memory, in GiB, at or below which the low profile is picked.
+const LOW_MEMORY_GIB: f32 = 2.0;
+/// Largest texture size below which the low profile is picked.
+const LOW_TEXTURE_SIZE: u32 = 4096;
+/// Sample counts [Quality::msaa] can take.
+const MSAA_SAMPLES: [u32; 4] = [1, 2, 4, 8];
+/// Values [Quality::resolution_scale] can take.
+const SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.25..=2.0;
+
+/// Picks a [QualityProfile] for the device at startup and applies the
+/// [Quality] settings, so weak phones get a lighter viewer instead of
+/// crawling. Each setting can then be changed on its own with a console
+/// command, see [Quality::run_command].
+pub struct QualityPlugin {
+    /// Profile to use instead of detecting one.
+    pub profile: Option<QualityProfile>,
+    /// Memory of the device in GiB, as the browser's `navigator.deviceMemory`
+    /// reports it. Unknown on native.
+    pub device_memory: Option<f32>,
+    /// [Quality::msaa] to use instead of the profile's.
+    pub msaa: Option<u32>,
+    /// [Quality::resolution_scale] to use instead of the profile's.
+    pub resolution_scale: Option<f32>,
+}
+
+impl Plugin for QualityPlugin {
+    fn build(&self, app: &mut App) {
+        let device = DeviceProfile {
+            profile: self.profile,
+            device_memory: self.device_memory,
+            msaa: self.msaa,
+            resolution_scale: self.resolution_scale,
+        };
+        let quality = device.quality(self.profile.unwrap_or(QualityProfile::High));
+        app.insert_resource(device)
+            .insert_resource(quality)
+            .add_systems(Startup, detect_quality)
+            .add_systems(Update, apply_quality);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityProfile {
+    High,
+    /// No lightmaps, textures two mip levels down, vertex lighting, a
+    /// nearer far plane, no anti-aliasing, a lower resolution and no lights
+    /// from light entities.
+    Low,
+}
+
+impl QualityProfile {
+    /// The profile for a device with `max_texture_size` and, if known,
+    /// `device_memory` GiB of memory.
+    ///
+    /// ```
+    /// # use q2_viewer::quality::QualityProfile;
+    /// assert_eq!(QualityProfile::detect(8192, Some(8.0)), QualityProfile::High);
+    /// assert_eq!(QualityProfile::detect(8192, Some(2.0)), QualityProfile::Low);
+    /// assert_eq!(QualityProfile::detect(2048, None), QualityProfile::Low);
+    /// ```
+    pub fn detect(max_texture_size: u32, device_memory: Option<f32>) -> Self {
+        let low_memory = device_memory.is_some_and(|gib| gib <= LOW_MEMORY_GIB);
+        match low_memory || max_texture_size < LOW_TEXTURE_SIZE {
+            true => QualityProfile::Low,
+            false => QualityProfile::High,
+        }
+    }
+}
+
+/// What [QualityPlugin] knows about the device.
+#[derive(Resource)]
+struct DeviceProfile {
+    profile: Option<QualityProfile>,
+    device_memory: Option<f32>,
+    msaa: Option<u32>,
+    resolution_scale: Option<f32>,
+}
+
+impl DeviceProfile {
+    /// The settings of `profile`, with the ones given at startup.
+    fn quality(&self, profile: QualityProfile) -> Quality {
+        let mut quality = Quality::for_profile(profile);
+        if let Some(samples) = self.msaa.filter(|samples| MSAA_SAMPLES.contains(samples)) {
+            quality.msaa = samples;
+        }
+        if let Some(scale) = self
+            .resolution_scale
+            .filter(|scale| SCALE_RANGE.contains(scale))
+        {
+            quality.resolution_scale = scale;
+        }
+        quality
+    }
+}
+
+/// Rendering settings traded against speed and memory.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Quality {
+    /// Draw the baked lightmaps of the world.
+    pub lightmaps: bool,
+    /// Mip levels dropped from every texture loaded from now on, like Quake
+    /// 2's `gl_picmip`.
+    pub picmip: u32,
+    /// Light the world from its vertex colors (the baked ambient occlusion)
+    /// alone, skipping per-pixel lighting.
+    pub vertex_lighting: bool,
+    /// Distance of the camera's far plane, in map units.
+    pub far_plane: f32,
+    /// Samples per pixel of multi-sample anti-aliasing, 1 for none. WebGL2
+    /// only has 1 and 4.
+    pub msaa: u32,
+    /// Size the world renders at, as a factor of the window's size: below 1
+    /// for speed on weak GPUs and large screens, above 1 to supersample. See
+    /// [ScaledView](crate::render::ScaledView).
+    pub resolution_scale: f32,
+    /// Most lights spawned from the light entities of a map, the brightest
+    /// first, see [MapLightPlugin](crate::render::MapLightPlugin). 0 for
+    /// none.
+    pub dynamic_lights: usize,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Self::for_profile(QualityProfile::High)
+    }
+}
+
+impl Quality {
+    pub fn for_profile(profile: QualityProfile) -> Self {
+        match profile {
+            QualityProfile::High => Self {
+                lightmaps: true,
+                picmip: 0,
+                vertex_lighting: false,
+                far_plane: 10_000.0,
+                msaa: 4,
+                resolution_scale: 1.0,
+                dynamic_lights: 32,
+            },
+            QualityProfile::Low => Self {
+                lightmaps: false,
+                picmip: 2,
+                vertex_lighting: true,
+                far_plane: 4_000.0,
+                msaa: 1,
+                resolution_scale: 0.75,
+                dynamic_lights: 0,
+            },
+        }
+    }
+
+    /// The [Msaa] setting for [Quality::msaa].
+    pub fn msaa_setting(&self) -> Msaa {
+        match self.msaa {
+            0 | 1 => Msaa::Off,
+            2 => Msaa::Sample2,
+            3 | 4 => Msaa::Sample4,
+            _ => Msaa::Sample8,
+        }
+    }
+
+    /// Runs a console command: `r_profile <high|low>`, `r_lightmaps <0|1>`,
+    /// `gl_picmip <levels>`, `r_vertexlight <0|1>`, `r_farplane <units>`,
+    /// `r_msaa <1|2|4|8>`, `r_scale <factor>`, with the factor from 0.25
+    /// to 2, or `r_dynamiclights <count>`.
+    ///
+    /// ```
+    /// # use q2_viewer::quality::Quality;
+    /// let mut quality = Quality::default();
+    /// quality.run_command("r_profile low").unwrap();
+    /// quality.run_command("r_lightmaps 1").unwrap();
+    /// assert!(quality.lightmaps && quality.vertex_lighting);
+    /// assert!(quality.run_command("gl_picmip -1").is_err());
+    /// quality.run_command("r_scale 0.5").unwrap();
+    /// assert_eq!(quality.resolution_scale, 0.5);
+    /// assert!(quality.run_command("r_msaa 3").is_err());
+    /// quality.run_command("r_dynamiclights 8").unwrap();
+    /// assert_eq!(quality.dynamic_lights, 8);
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let flag = |value: &str, usage: &str| match value {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(usage.to_string()),
+        };
+        match words[..] {
+            ["r_profile", "high"] => *self = Self::for_profile(QualityProfile::High),
+            ["r_profile", "low"] => *self = Self::for_profile(QualityProfile::Low),
+            ["r_profile", ..] => return Err("usage: r_profile <high|low>".to_string()),
+            ["r_lightmaps", value] => {
+                self.lightmaps = flag(value, "usage: r_lightmaps <0|1>")?;
+            }
+            ["r_vertexlight", value] => {
+                self.vertex_lighting = flag(value, "usage: r_vertexlight <0|1>")?;
+            }
+            ["gl_picmip", value] => {
+                self.picmip = value
+                    .parse()
+                    .map_err(|_| "usage: gl_picmip <levels>".to_string())?;
+            }
+            ["r_farplane", value] => {
+                self.far_plane = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|far| *far > 0.0)
+                    .ok_or_else(|| "usage: r_farplane <units>".to_string())?;
+            }
+            ["r_msaa", value] => {
+                self.msaa = value
+                    .parse()
+                    .ok()
+                    .filter(|samples| MSAA_SAMPLES.contains(samples))
+                    .ok_or_else(|| "usage: r_msaa <1|2|4|8>".to_string())?;
+            }
+            ["r_scale", value] => {
+                self.resolution_scale = value
+                    .parse()
+                    .ok()
+                    .filter(|scale| SCALE_RANGE.contains(scale))
+                    .ok_or_else(|| "usage: r_scale <factor>, from 0.25 to 2".to_string())?;
+            }
+            ["r_dynamiclights", value] => {
+                self.dynamic_lights = value
+                    .parse()
+                    .map_err(|_| "usage: r_dynamiclights <count>".to_string())?;
+            }
+            [name, ..]
+                if [
+                    "r_lightmaps",
+                    "r_vertexlight",
+                    "gl_picmip",
+                    "r_farplane",
+                    "r_msaa",
+                    "r_scale",
+                    "r_dynamiclights",
+                ]
+                .contains(&name) =>
+            {
+                return Err(format!("usage: {} <value>", name));
+            }
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+fn detect_quality(
+    device: Res<DeviceProfile>,
+    render_device: Option<Res<RenderDevice>>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    mut quality: ResMut<Quality>,
+) {
+    if device.profile.is_some() {
+        return;
+    }
+    let max_texture_size = render_device.map_or(u32::MAX, |render_device| {
+        render_device.limits().max_texture_dimension_2d
+    });
+    let profile = QualityProfile::detect(max_texture_size, device.device_memory);
+    info!(
+        "Picked the {:?} quality profile for {} (max texture size {}, {} GiB of memory)",
+        profile,
+        adapter.map_or("an unknown adapter".to_string(), |adapter| adapter
+            .name
+            .clone()),
+        max_texture_size,
+        device
+            .device_memory
+            .map_or("unknown".to_string(), |gib| gib.to_string())
+    );
+    *quality = device.quality(profile);
+}
+
+/// Applies the [Quality] when it changes, and to world batches as they
+/// spawn.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn apply_quality(
+    mut commands: Commands,
+    quality: Res<Quality>,
+    mut streaming: ResMut<TextureStreaming>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut msaa: ResMut<Msaa>,
+    mut cameras: Query<&mut Projection, With<Interpolated>>,
+    batches: Query<(
+        Entity,
+        Ref<WorldBatch>,
+        &Handle<StandardMaterial>,
+        &Parent,
+        Has<Lightmap>,
+    )>,
+    models: Query<&Parent, With<BrushModel>>,
+    instances: Query<&MapInstance>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    if quality.is_changed() {
+        streaming.min_mip_bias = quality.picmip;
+        msaa.set_if_neq(quality.msaa_setting());
+        for mut projection in cameras.iter_mut() {
+            if let Projection::Perspective(perspective) = &mut *projection {
+                perspective.far = quality.far_plane;
+            }
+        }
+    }
+
+    for (entity, batch, material, parent, has_lightmap) in batches.iter() {
+        if !quality.is_changed() && !batch.is_added() {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            if material.unlit != quality.vertex_lighting {
+                material.unlit = quality.vertex_lighting;
+            }
+        }
+        match (quality.lightmaps, has_lightmap) {
+            (false, true) => {
+                commands.entity(entity).remove::<Lightmap>();
+            }
+            (true, false) => {
+                // Batches draw with one page, as when they were spawned
+                let map = models.get(parent.get()).unwrap_or(parent);
+                let page = instances
+                    .get(map.get())
+                    .ok()
+                    .and_then(|instance| assets.get(&instance.handle))
+                    .and_then(|asset| match asset.lightmaps.as_slice() {
+                        [page] => Some(page.clone()),
+                        _ => None,
+                    });
+                if let Some(image) = page {
+                    commands.entity(entity).insert(Lightmap {
+                        image,
+                        uv_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bevy::{prelude::*, utils::Instant};
+
+use q2_formats::bsp38::prelude::{Bounds, MeshBuilder, MeshOptions};
+
+use crate::{
+    asset::BSP38Asset, locale::Message, maps::MapInstance, render::OverlayStats, start::WorldBatch,
+};
+
+/// Which world faces are drawn. Changing the filter rebuilds only the world
+/// batches holding faces it shows or hides differently.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaceFilter {
+    /// Faces whose texture has any of these surface flags are left out, see
+    /// [q2_formats::bsp38::surface].
+    pub hidden_flags: u32,
+}
+
+impl FaceFilter {
+    /// Runs a console command: `r_hideflags <mask>`, with the mask in decimal
+    /// or as `0x` hex.
+    ///
+    /// ```
+    /// # use q2_viewer::rebuild::FaceFilter;
+    /// let mut filter = FaceFilter::default();
+    /// filter.run_command("r_hideflags 0x84").unwrap();
+    /// assert_eq!(filter.hidden_flags, 0x84);
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        let mask = match (words.next(), words.next(), words.next()) {
+            (Some("r_hideflags"), Some(mask), None) => match mask.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => mask.parse(),
+            },
+            (Some("r_hideflags"), ..) => return Err("usage: r_hideflags <mask>".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        };
+        self.hidden_flags = mask.map_err(|err| format!("invalid mask: {}", err))?;
+        Ok(())
+    }
+}
+
+/// Asks for the world batches of a map holding any of `faces` to be rebuilt,
+/// e.g. after a decal was added on them or a door spawned over them.
+#[derive(Event, Debug, Clone)]
+pub struct RebuildFaces {
+    /// The [MapInstance] entity of the map.
+    pub map: Entity,
+    /// Indices into the map's Faces lump.
+    pub faces: Vec<usize>,
+}
+
+impl RebuildFaces {
+    /// Rebuilds the faces of the leafs touching `bounds`, in map coordinates.
+    pub fn in_bounds(map: Entity, asset: &BSP38Asset, bounds: Bounds) -> Self {
+        Self {
+            map,
+            faces: asset.bsp.faces_in_bounds(bounds),
+        }
+    }
+}
+
+/// Rebuilds world meshes in place when some of their faces change, instead of
+/// regenerating and re-uploading the whole world: only the (texture, cluster)
+/// batches holding a changed face get new vertex data.
+pub struct RebuildPlugin;
+
+impl Plugin for RebuildPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FaceFilter>()
+            .add_event::<RebuildFaces>()
+            .add_systems(PostUpdate, rebuild_world_batches);
+    }
+}
+
+/// Collects the changed faces of each map and rebuilds the batches holding
+/// them.
+#[allow(clippy::too_many_arguments)]
+fn rebuild_world_batches(
+    mut events: EventReader<RebuildFaces>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut stats: ResMut<OverlayStats>,
+    mut previous: Local<FaceFilter>,
+    filter: Res<FaceFilter>,
+    instances: Query<(Entity, &MapInstance)>,
+    batches: Query<(&WorldBatch, &Handle<Mesh>, &Parent)>,
+    new_batches: Query<&Parent, Added<WorldBatch>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    let mut changed: HashMap<Entity, BTreeSet<usize>> = HashMap::new();
+    for event in events.read() {
+        changed
+            .entry(event.map)
+            .or_default()
+            .extend(event.faces.iter().copied());
+    }
+
+    // Flags whose faces show or hide differently than when the batches were
+    // built. Freshly loaded batches were built without any filter.
+    let toggled = previous.hidden_flags ^ filter.hidden_flags;
+    *previous = filter.clone();
+    let new_roots: BTreeSet<Entity> = new_batches.iter().map(|parent| parent.get()).collect();
+    for (root, instance) in instances.iter() {
+        let flags = match new_roots.contains(&root) {
+            true => filter.hidden_flags,
+            false => toggled,
+        };
+        if flags == 0 {
+            continue;
+        }
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        let face_flags = face_flags(asset);
+        changed
+            .entry(root)
+            .or_default()
+            .extend((0..face_flags.len()).filter(|&k| face_flags[k] & flags != 0));
+    }
+    if changed.values().all(BTreeSet::is_empty) {
+        return;
+    }
+
+    let start = Instant::now();
+    let (mut rebuilt, mut total) = (0, 0);
+    for (root, faces) in changed {
+        let Ok((_, instance)) = instances.get(root) else {
+            continue;
+        };
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        // Keep the lightmap UVs the loader laid out
+        let mut builder = match &asset.lightmap_atlas {
+            Some(atlas) => MeshBuilder::new().with_lightmaps(atlas),
+            None => MeshBuilder::new(),
+        };
+        if let Some(size) = asset.chunk_size {
+            builder = builder.with_chunks(size);
+        }
+        let keys = builder.face_batch_keys(&asset.bsp);
+        let dirty: BTreeSet<(&str, i16, Option<[i32; 3]>)> = faces
+            .iter()
+            .filter_map(|&k| keys.get(k))
+            .map(|(texture, cluster, chunk)| (texture.as_str(), *cluster, *chunk))
+            .collect();
+        let face_flags = face_flags(asset);
+        let keep = |k: usize| face_flags[k] & filter.hidden_flags == 0;
+
+        let mut tracer = None;
+        for (batch, handle, parent) in batches.iter() {
+            if parent.get() != root {
+                continue;
+            }
+            total += 1;
+            let is_dirty = batch
+                .clusters
+                .iter()
+                .any(|&cluster| dirty.contains(&(batch.texture.as_str(), cluster, batch.chunk)));
+            if !is_dirty {
+                continue;
+            }
+
+            let mut data = builder
+                .build_batch(
+                    &asset.bsp,
+                    &batch.texture,
+                    &batch.clusters,
+                    batch.chunk,
+                    batch.translucent,
+                    keep,
+                )
+                .clone();
+            // Keep the look the loader gave the batch: colors are only there
+            // when ambient occlusion was baked
+            if let Some(options) = &asset.occlusion {
+                let tracer = tracer.get_or_insert_with(|| asset.bsp.tracer());
+                data.bake_occlusion(tracer, options);
+            }
+            meshes.insert(
+                handle,
+                data.into_mesh(&MeshOptions {
+                    colors: asset.occlusion.is_some(),
+                    ..default()
+                }),
+            );
+            rebuilt += 1;
+        }
+    }
+    stats.set(
+        "rebuild",
+        Message::new("rebuild.progress")
+            .arg(rebuilt)
+            .arg(total)
+            .arg(format!("{:.1}", start.elapsed().as_secs_f64() * 1000.0)),
+    );
+}
+
+/// Surface flags of the texture of each face.
+fn face_flags(asset: &BSP38Asset) -> Vec<u32> {
+    let tex_info = asset.bsp.read_texture_info().unwrap_or_default();
+    asset
+        .bsp
+        .read_face_records()
+        .unwrap_or_default()
+        .iter()
+        .map(|face| tex_info.get(face.texinfo as usize).map_or(0, |t| t.flags))
+        .collect()
+}
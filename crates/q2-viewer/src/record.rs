@@ -0,0 +1,668 @@
+//! Flythrough videos of maps, rendered offscreen along a [CameraPath].
+//!
+//! Only available on native, since it reads frames back from the GPU and
+//! writes them to disk or to an `ffmpeg` process.
+//!
+//! ```no_run
+//! # use bevy::prelude::*;
+//! # use q2_viewer::record::{CameraPath, RecordOutput, RecordSettings, Recording};
+//! fn record_flythrough(mut commands: Commands) {
+//!     let path = CameraPath::orbit(Vec3::new(0.0, 0.0, 500.0), 2000.0, 800.0, 20.0);
+//!     let settings = RecordSettings {
+//!         output: RecordOutput::Ffmpeg("q2dm1.mp4".into()),
+//!         ..default()
+//!     };
+//!     match Recording::start(path, settings) {
+//!         Ok(recording) => commands.insert_resource(recording),
+//!         Err(err) => error!("Could not start recording: {}", err),
+//!     }
+//! }
+//! ```
+
+use std::{
+    f32::consts::TAU,
+    io::{self, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        graph::CameraDriverLabel,
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_graph::{self, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageDataLayout,
+            Maintain, MapMode, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::GpuImage,
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    time::TimeUpdateStrategy,
+};
+
+use q2_formats::bsp38::prelude::Bounds;
+
+use crate::{
+    asset::MapSummary,
+    framing::{frame_map, OVERVIEW_PITCH},
+    maps::MapManager,
+    sim::interpolate,
+    sim::Interpolated,
+};
+
+/// Bytes per pixel of the captured frames, which are RGBA8.
+const PIXEL_SIZE: usize = 4;
+
+/// Frames to wait for the last captured frames to be written before giving
+/// up on them.
+const DRAIN_FRAMES: usize = 120;
+
+/// Records the main camera along a [CameraPath] while a [Recording] resource
+/// exists, then removes it.
+///
+/// While recording, every frame advances time by exactly one video frame, so
+/// the simulation and the path play at the video's rate however long frames
+/// take to render and save. The main camera renders into an offscreen image
+/// of the recording's size instead of the window. F9 records an orbit around
+/// the current map into `recordings/<map>/`.
+pub struct RecorderPlugin;
+
+impl Plugin for RecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (record_key, advance_recording, finish_recording))
+            .add_systems(PostUpdate, drive_camera.before(interpolate));
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_systems(ExtractSchedule, extract_capture)
+            .add_systems(
+                Render,
+                read_capture
+                    .after(RenderSet::Render)
+                    .before(RenderSet::Cleanup),
+            );
+        let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        graph.add_node(CaptureLabel, CaptureNode);
+        graph.add_node_edge(CameraDriverLabel, CaptureLabel);
+    }
+}
+
+/// A camera transform at a point in time along a [CameraPath].
+#[derive(Debug, Clone, Copy)]
+pub struct CameraKey {
+    /// Seconds from the start of the path.
+    pub time: f32,
+    pub transform: Transform,
+}
+
+/// Keyframed camera motion. Positions follow a Catmull-Rom spline through the
+/// keys and rotations are slerped between them:
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use q2_viewer::record::{CameraKey, CameraPath};
+/// let path = CameraPath::new(vec![
+///     CameraKey { time: 0.0, transform: Transform::from_xyz(0.0, 0.0, 0.0) },
+///     CameraKey { time: 2.0, transform: Transform::from_xyz(100.0, 0.0, 0.0) },
+/// ]);
+/// assert_eq!(path.duration(), 2.0);
+/// assert_eq!(path.sample(1.0).translation, Vec3::new(50.0, 0.0, 0.0));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+}
+
+impl CameraPath {
+    /// A path through `keys`, which are sorted by time.
+    pub fn new(mut keys: Vec<CameraKey>) -> Self {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keys }
+    }
+
+    /// One turn around `center` at `radius` and `height` above it, looking at
+    /// the center, taking `duration` seconds.
+    pub fn orbit(center: Vec3, radius: f32, height: f32, duration: f32) -> Self {
+        const STEPS: usize = 32;
+        let keys = (0..=STEPS)
+            .map(|i| {
+                let f = i as f32 / STEPS as f32;
+                let (sin, cos) = (f * TAU).sin_cos();
+                let eye = center + Vec3::new(radius * cos, radius * sin, height);
+                CameraKey {
+                    time: f * duration,
+                    transform: Transform::from_translation(eye).looking_at(center, Vec3::Z),
+                }
+            })
+            .collect();
+        Self::new(keys)
+    }
+
+    /// One turn around the whole of `bounds`, each key framed by
+    /// [frame_map] at the [OVERVIEW_PITCH], taking `duration` seconds.
+    pub fn overview(bounds: &Bounds, projection: &PerspectiveProjection, duration: f32) -> Self {
+        const STEPS: usize = 32;
+        let keys = (0..=STEPS)
+            .map(|i| {
+                let f = i as f32 / STEPS as f32;
+                CameraKey {
+                    time: f * duration,
+                    transform: frame_map(bounds, OVERVIEW_PITCH, f * TAU, projection),
+                }
+            })
+            .collect();
+        Self::new(keys)
+    }
+
+    pub fn keys(&self) -> &[CameraKey] {
+        &self.keys
+    }
+
+    /// Time of the last key, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// The camera transform `time` seconds along the path, held at the first
+    /// and last keys outside of it.
+    pub fn sample(&self, time: f32) -> Transform {
+        let Some(next) = self.keys.iter().position(|key| key.time > time) else {
+            return self
+                .keys
+                .last()
+                .map_or(Transform::IDENTITY, |key| key.transform);
+        };
+        if next == 0 {
+            return self.keys[0].transform;
+        }
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (time - a.time) / (b.time - a.time);
+        let before = self.keys[next.saturating_sub(2)].transform.translation;
+        let after = self.keys[(next + 1).min(self.keys.len() - 1)]
+            .transform
+            .translation;
+        Transform {
+            translation: catmull_rom(
+                before,
+                a.transform.translation,
+                b.transform.translation,
+                after,
+                t,
+            ),
+            rotation: a.transform.rotation.slerp(b.transform.rotation, t),
+            scale: a.transform.scale.lerp(b.transform.scale, t),
+        }
+    }
+}
+
+/// The point `t` of the way from `p1` to `p2` on a uniform Catmull-Rom spline.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Where recorded frames go.
+#[derive(Debug, Clone)]
+pub enum RecordOutput {
+    /// PNG files named `frame_00000.png` and up, in this directory.
+    ImageSequence(PathBuf),
+    /// A video file encoded by an `ffmpeg` process on the `PATH`.
+    Ffmpeg(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordSettings {
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second of the video, and the rate time advances at while
+    /// recording.
+    pub fps: u32,
+    /// Frames rendered at the start of the path before capturing, so
+    /// pipelines and textures have time to load.
+    pub warmup_frames: usize,
+    pub output: RecordOutput,
+}
+
+impl Default for RecordSettings {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 60,
+            warmup_frames: 30,
+            output: RecordOutput::ImageSequence("recording".into()),
+        }
+    }
+}
+
+/// A recording in progress. Insert it as a resource to start recording;
+/// it removes itself once every frame has been written.
+#[derive(Resource)]
+pub struct Recording {
+    path: CameraPath,
+    settings: RecordSettings,
+    /// The offscreen image the main camera renders into.
+    image: Option<Handle<Image>>,
+    /// Index of the frame being rendered, counting warmup frames.
+    frame: usize,
+    frames: mpsc::Sender<Frame>,
+    written: Arc<AtomicUsize>,
+}
+
+impl Recording {
+    /// Starts the writer for `settings.output`. Fails if the output directory
+    /// can't be created or `ffmpeg` can't be started.
+    pub fn start(path: CameraPath, settings: RecordSettings) -> io::Result<Self> {
+        let (frames, receiver) = mpsc::channel();
+        let written = Arc::new(AtomicUsize::new(0));
+        let writer = FrameWriter::new(&settings)?;
+        // The writer finishes once the recording and its frame senders are
+        // dropped
+        let counter = written.clone();
+        std::thread::spawn(move || writer.run(receiver, &counter));
+        Ok(Self {
+            path,
+            settings,
+            image: None,
+            frame: 0,
+            frames,
+            written,
+        })
+    }
+
+    /// Number of frames in the video.
+    pub fn num_frames(&self) -> usize {
+        (self.path.duration() * self.settings.fps as f32).round() as usize
+    }
+
+    /// Index of the video frame being rendered, None during warmup and once
+    /// every frame has been rendered.
+    fn capture_index(&self) -> Option<usize> {
+        self.frame
+            .checked_sub(self.settings.warmup_frames)
+            .filter(|&index| index < self.num_frames())
+    }
+}
+
+/// One captured frame, tightly packed RGBA8 rows.
+struct Frame {
+    index: usize,
+    pixels: Vec<u8>,
+}
+
+enum FrameWriter {
+    Images {
+        dir: PathBuf,
+        width: u32,
+        height: u32,
+    },
+    Ffmpeg(Child),
+}
+
+impl FrameWriter {
+    fn new(settings: &RecordSettings) -> io::Result<Self> {
+        Ok(match &settings.output {
+            RecordOutput::ImageSequence(dir) => {
+                std::fs::create_dir_all(dir)?;
+                Self::Images {
+                    dir: dir.clone(),
+                    width: settings.width,
+                    height: settings.height,
+                }
+            }
+            RecordOutput::Ffmpeg(file) => {
+                let child = Command::new("ffmpeg")
+                    .args(["-y", "-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+                    .arg(format!("{}x{}", settings.width, settings.height))
+                    .arg("-r")
+                    .arg(settings.fps.to_string())
+                    .args(["-i", "-", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                    .arg(file)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()?;
+                Self::Ffmpeg(child)
+            }
+        })
+    }
+
+    /// Writes frames until every sender is dropped.
+    fn run(mut self, frames: mpsc::Receiver<Frame>, written: &AtomicUsize) {
+        for frame in frames {
+            if let Err(err) = self.write(frame) {
+                error!("Could not write recorded frame: {}", err);
+            }
+            written.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Self::Ffmpeg(mut child) = self {
+            // Closing stdin ends the video
+            drop(child.stdin.take());
+            match child.wait() {
+                Ok(status) if !status.success() => error!("ffmpeg exited with {}", status),
+                Err(err) => error!("Could not wait for ffmpeg: {}", err),
+                Ok(_) => {}
+            }
+        }
+    }
+
+    fn write(&mut self, frame: Frame) -> Result<(), String> {
+        match self {
+            Self::Images { dir, width, height } => {
+                let image = Image::new(
+                    Extent3d {
+                        width: *width,
+                        height: *height,
+                        depth_or_array_layers: 1,
+                    },
+                    TextureDimension::D2,
+                    frame.pixels,
+                    TextureFormat::Rgba8UnormSrgb,
+                    RenderAssetUsages::MAIN_WORLD,
+                );
+                let path = dir.join(format!("frame_{:05}.png", frame.index));
+                image
+                    .try_into_dynamic()
+                    .map_err(|err| err.to_string())?
+                    .save(path)
+                    .map_err(|err| err.to_string())
+            }
+            Self::Ffmpeg(child) => child
+                .stdin
+                .as_mut()
+                .ok_or_else(|| "ffmpeg stdin is closed".to_string())?
+                .write_all(&frame.pixels)
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Records an orbit around the current map on F9.
+fn record_key(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    recording: Option<Res<Recording>>,
+) {
+    if !keys.just_pressed(KeyCode::F9) || recording.is_some() {
+        return;
+    }
+    let Some(summary) = maps.root().and_then(|root| summaries.get(root).ok()) else {
+        warn!("No map loaded to record");
+        return;
+    };
+    let name = maps.current().unwrap_or("map");
+    let settings = RecordSettings {
+        output: RecordOutput::ImageSequence(PathBuf::from("recordings").join(name)),
+        ..default()
+    };
+    let projection = PerspectiveProjection {
+        aspect_ratio: settings.width as f32 / settings.height as f32,
+        ..default()
+    };
+    let path = CameraPath::overview(&summary.world_bounds(), &projection, 20.0);
+    match Recording::start(path, settings) {
+        Ok(recording) => {
+            info!("Recording {} frames of {}", recording.num_frames(), name);
+            commands.insert_resource(recording);
+        }
+        Err(err) => error!("Could not start recording: {}", err),
+    }
+}
+
+/// Points the main camera at an offscreen image and locks the frame time
+/// when a recording starts, then counts the frames.
+fn advance_recording(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    recording: Option<ResMut<Recording>>,
+    mut cameras: Query<&mut Camera, With<Interpolated>>,
+) {
+    let Some(mut recording) = recording else {
+        return;
+    };
+    if recording.image.is_some() {
+        recording.frame += 1;
+        return;
+    }
+    let Some(mut camera) = cameras.iter_mut().next() else {
+        return;
+    };
+    let size = Extent3d {
+        width: recording.settings.width,
+        height: recording.settings.height,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("recording"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+    camera.target = RenderTarget::Image(image.clone());
+    recording.image = Some(image);
+    commands.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / recording.settings.fps as f64,
+    )));
+}
+
+/// Moves the main camera along the path. Runs after the fixed steps, so it
+/// wins over whatever animates the camera there.
+fn drive_camera(
+    recording: Option<Res<Recording>>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+) {
+    let Some(recording) = recording else {
+        return;
+    };
+    if recording.image.is_none() {
+        return;
+    }
+    let index = recording
+        .frame
+        .saturating_sub(recording.settings.warmup_frames);
+    let transform = recording
+        .path
+        .sample(index as f32 / recording.settings.fps as f32);
+    for mut interpolated in cameras.iter_mut() {
+        *interpolated = Interpolated::new(transform);
+    }
+}
+
+/// Gives the window its camera back once every frame has been written.
+fn finish_recording(
+    mut commands: Commands,
+    recording: Option<Res<Recording>>,
+    mut cameras: Query<&mut Camera, With<Interpolated>>,
+) {
+    let Some(recording) = recording else {
+        return;
+    };
+    let total = recording.num_frames();
+    let last = recording.settings.warmup_frames + total;
+    let written = recording.written.load(Ordering::Relaxed);
+    if written < total && recording.frame < last + DRAIN_FRAMES {
+        return;
+    }
+    if written < total {
+        warn!("Recording lost {} of {} frames", total - written, total);
+    }
+    for mut camera in cameras.iter_mut() {
+        camera.target = RenderTarget::default();
+    }
+    commands.insert_resource(TimeUpdateStrategy::Automatic);
+    commands.remove_resource::<Recording>();
+    info!("Recorded {} frames", written);
+}
+
+/// The frame to capture this frame, in the render world.
+#[derive(Resource)]
+struct ExtractedCapture {
+    image: AssetId<Image>,
+    index: usize,
+    frames: mpsc::Sender<Frame>,
+}
+
+/// The buffer captured frames are copied into, with rows padded to
+/// [wgpu's](bevy::render::render_resource) copy alignment.
+#[derive(Resource)]
+struct CaptureBuffer {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_row: usize,
+}
+
+fn extract_capture(
+    mut commands: Commands,
+    recording: Extract<Option<Res<Recording>>>,
+    buffer: Option<Res<CaptureBuffer>>,
+    render_device: Res<RenderDevice>,
+) {
+    let Some(recording) = recording.as_ref() else {
+        commands.remove_resource::<ExtractedCapture>();
+        commands.remove_resource::<CaptureBuffer>();
+        return;
+    };
+    let (Some(image), Some(index)) = (&recording.image, recording.capture_index()) else {
+        commands.remove_resource::<ExtractedCapture>();
+        return;
+    };
+    commands.insert_resource(ExtractedCapture {
+        image: image.id(),
+        index,
+        frames: recording.frames.clone(),
+    });
+
+    let (width, height) = (recording.settings.width, recording.settings.height);
+    if buffer.is_some_and(|b| b.width == width && b.height == height) {
+        return;
+    }
+    let padded_row = RenderDevice::align_copy_bytes_per_row(width as usize * PIXEL_SIZE);
+    commands.insert_resource(CaptureBuffer {
+        buffer: render_device.create_buffer(&BufferDescriptor {
+            label: Some("capture_buffer"),
+            size: (padded_row * height as usize) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }),
+        width,
+        height,
+        padded_row,
+    });
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct CaptureLabel;
+
+/// Copies the recording image into the [CaptureBuffer] once the cameras have
+/// rendered.
+struct CaptureNode;
+
+impl render_graph::Node for CaptureNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let (Some(capture), Some(buffer)) = (
+            world.get_resource::<ExtractedCapture>(),
+            world.get_resource::<CaptureBuffer>(),
+        ) else {
+            return Ok(());
+        };
+        let Some(image) = world
+            .resource::<RenderAssets<GpuImage>>()
+            .get(capture.image)
+        else {
+            return Ok(());
+        };
+        render_context.command_encoder().copy_texture_to_buffer(
+            image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer.buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(buffer.padded_row as u32),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: buffer.width,
+                height: buffer.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Reads the frame copied by [CaptureNode] back and sends it to the writer.
+fn read_capture(
+    capture: Option<Res<ExtractedCapture>>,
+    buffer: Option<Res<CaptureBuffer>>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+) {
+    let (Some(capture), Some(buffer)) = (capture, buffer) else {
+        return;
+    };
+    if gpu_images.get(capture.image).is_none() {
+        // Nothing was copied this frame
+        return;
+    }
+    let slice = buffer.buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    render_device.poll(Maintain::wait()).panic_on_timeout();
+    match receiver.recv() {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => {
+            error!("Could not read captured frame: {}", err);
+            return;
+        }
+        Err(_) => return,
+    }
+    let row = buffer.width as usize * PIXEL_SIZE;
+    let pixels = slice
+        .get_mapped_range()
+        .chunks_exact(buffer.padded_row)
+        .flat_map(|padded| &padded[..row])
+        .copied()
+        .collect();
+    buffer.buffer.unmap();
+    let _ = capture.frames.send(Frame {
+        index: capture.index,
+        pixels,
+    });
+}
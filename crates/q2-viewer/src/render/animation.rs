@@ -0,0 +1,157 @@
+use bevy::{
+    math::Affine2,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use q2_formats::bsp38::surface::SURF_FLOWING;
+
+use super::{
+    sun::{own_material, OwnMaterial},
+    TextureStreaming,
+};
+use crate::{
+    asset::BSP38Asset,
+    maps::{MapInstance, MapManager},
+    start::WorldBatch,
+};
+
+/// Cycles the textures of animated surfaces, such as flowing lava and
+/// blinking lights, through the frames of their texinfo `next` chains, see
+/// [BSP38::texture_animations](q2_formats::bsp38::BSP38::texture_animations).
+/// Frames show once [TextureStreaming] has loaded them. Also scrolls the
+/// textures of `SURF_FLOWING` surfaces, such as conveyors and lava falls.
+pub struct TextureAnimationPlugin;
+
+impl Plugin for TextureAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextureAnimations>().add_systems(
+            Update,
+            (load_animations, animate_textures, scroll_flowing).chain(),
+        );
+    }
+}
+
+/// The animated textures of the current map.
+#[derive(Resource)]
+pub struct TextureAnimations {
+    /// Frames shown per second.
+    pub fps: f32,
+    /// Texture widths flowing surfaces scroll per second, towards -u. The
+    /// game scrolls 64 widths every 40 seconds.
+    pub flow_speed: f32,
+    frames: HashMap<String, Vec<String>>,
+    root: Option<Entity>,
+}
+
+impl Default for TextureAnimations {
+    fn default() -> Self {
+        Self {
+            fps: 10.0,
+            flow_speed: 1.6,
+            frames: HashMap::new(),
+            root: None,
+        }
+    }
+}
+
+impl TextureAnimations {
+    /// The frames of `texture`, starting with itself, or none if it isn't
+    /// animated.
+    pub fn frames(&self, texture: &str) -> &[String] {
+        self.frames
+            .get(texture)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// Reads the animation chains of each map as it becomes the current one.
+fn load_animations(
+    mut animations: ResMut<TextureAnimations>,
+    maps: Res<MapManager>,
+    instances: Query<&MapInstance>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    if animations.root == maps.root() {
+        return;
+    }
+    let Some(asset) = maps
+        .root()
+        .and_then(|root| instances.get(root).ok())
+        .and_then(|instance| assets.get(&instance.handle))
+    else {
+        return;
+    };
+    animations.frames = asset.bsp.texture_animations().into_iter().collect();
+    animations.root = maps.root();
+    debug!("{} animated textures", animations.frames.len());
+}
+
+/// Puts the current frame of each animated texture on its materials.
+fn animate_textures(
+    animations: Res<TextureAnimations>,
+    streaming: Res<TextureStreaming>,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    batches: Query<(&WorldBatch, OwnMaterial)>,
+) {
+    if animations.frames.is_empty() {
+        return;
+    }
+    let frame = (time.elapsed_seconds_f64() * animations.fps as f64) as usize;
+
+    let mut updated = HashSet::new();
+    for (batch, material) in batches.iter() {
+        let frames = animations.frames(&batch.texture);
+        let Some(material) = own_material(material).filter(|_| !frames.is_empty()) else {
+            continue;
+        };
+        if !updated.insert(material.id()) {
+            continue;
+        }
+        let Some((image, size)) = streaming.resident(&frames[frame % frames.len()]) else {
+            continue;
+        };
+        // Checks every frame, but only touches materials showing the wrong
+        // one
+        let shown = materials
+            .get(material)
+            .and_then(|material| material.base_color_texture.as_ref());
+        if shown == Some(image) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = Color::WHITE.with_alpha(material.base_color.alpha());
+            material.base_color_texture = Some(image.clone());
+            material.uv_transform = Affine2::from_scale(size.recip());
+        }
+    }
+}
+
+/// Offsets the textures of flowing surfaces along u. Only the translation of
+/// the UV transform changes, so the scale set by streaming and animation is
+/// kept.
+fn scroll_flowing(
+    animations: Res<TextureAnimations>,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    batches: Query<(&WorldBatch, OwnMaterial)>,
+) {
+    // Wrapped to one width, as textures repeat, to keep the precision
+    let scroll = -(time.elapsed_seconds_f64() * animations.flow_speed as f64).fract() as f32;
+
+    let mut updated = HashSet::new();
+    for (batch, material) in batches.iter() {
+        let Some(material) = own_material(material).filter(|_| batch.flags & SURF_FLOWING != 0)
+        else {
+            continue;
+        };
+        if !updated.insert(material.id()) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(material) {
+            material.uv_transform.translation = Vec2::new(scroll, 0.0);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// Animated caustics added over the floors under a liquid: a domain-warped
+// interference pattern whose bright lines drift like focused light.
+
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings::globals
+
+@group(2) @binding(0) var<uniform> color: vec4<f32>;
+
+fn caustic(p: vec2<f32>, t: f32) -> f32 {
+    var q = p;
+    var light = 0.0;
+    for (var i = 0; i < 3; i++) {
+        let s = f32(i + 1);
+        q += vec2<f32>(sin(q.y * 0.9 + t * 0.7 * s), cos(q.x * 1.1 - t * 0.5 * s)) / s;
+        // Bright where the two wave fronts cancel out
+        let d = abs(sin(q.x) + sin(q.y));
+        light += pow(1.0 - min(d, 1.0), 4.0);
+    }
+    return light / 3.0;
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let light = caustic(mesh.world_position.xy / 48.0, globals.time);
+    return vec4<f32>(color.rgb * light, 1.0);
+}
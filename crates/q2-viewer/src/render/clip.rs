@@ -0,0 +1,255 @@
+use bevy::{
+    asset::embedded_asset,
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    utils::HashMap,
+};
+
+use super::OverlayStats;
+use crate::{asset::MapSummary, locale::Message, maps::MapManager, start::WorldBatch};
+
+/// How fast the clip plane moves along its normal, in map units per second.
+const MOVE_SPEED: f32 = 256.0;
+
+/// How fast the clip plane turns, in radians per second.
+const TURN_SPEED: f32 = 1.0;
+
+/// A standard material that discards everything in front of the clip plane.
+pub type ClipMaterial = ExtendedMaterial<StandardMaterial, ClipExtension>;
+
+/// A plane cutting away world geometry on the side its normal points to, in
+/// world space.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use q2_viewer::render::ClipPlane;
+/// let clip = ClipPlane {
+///     active: true,
+///     point: Vec3::new(0.0, 0.0, 64.0),
+///     normal: Vec3::Z,
+/// };
+/// assert!(clip.is_clipped(Vec3::new(10.0, 20.0, 100.0)));
+/// assert!(!clip.is_clipped(Vec3::new(10.0, 20.0, 0.0)));
+/// ```
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ClipPlane {
+    pub active: bool,
+    pub point: Vec3,
+    /// Unit normal; geometry on this side of the plane is cut away.
+    pub normal: Vec3,
+}
+
+impl Default for ClipPlane {
+    fn default() -> Self {
+        Self {
+            active: false,
+            point: Vec3::ZERO,
+            normal: Vec3::Z,
+        }
+    }
+}
+
+impl ClipPlane {
+    /// The plane as the normal and offset the shader tests fragments with.
+    pub fn equation(&self) -> Vec4 {
+        self.normal.extend(-self.normal.dot(self.point))
+    }
+
+    pub fn is_clipped(&self, point: Vec3) -> bool {
+        self.active && self.normal.dot(point - self.point) > 0.0
+    }
+}
+
+/// Cutaway views: with the clip plane on (C key), world geometry above the
+/// plane is discarded in the shader, showing the interior of the map. Page
+/// Up and Page Down move the plane along its normal and the arrow keys tilt
+/// it; a gizmo shows where it is.
+pub struct ClipPlanePlugin;
+
+impl Plugin for ClipPlanePlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "clip.wgsl");
+        app.add_plugins(MaterialPlugin::<ClipMaterial>::default())
+            .init_resource::<ClipPlane>()
+            .add_systems(
+                Update,
+                (
+                    toggle_clip_key,
+                    move_clip_plane,
+                    swap_clip_materials,
+                    update_clip_materials,
+                    draw_clip_plane,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct ClipExtension {
+    /// See [ClipPlane::equation].
+    #[uniform(100)]
+    pub plane: Vec4,
+}
+
+impl MaterialExtension for ClipExtension {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/clip.wgsl".into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/clip.wgsl".into()
+    }
+}
+
+/// The material of a world batch while the clip plane has replaced it.
+#[derive(Component)]
+struct Unclipped(Handle<StandardMaterial>);
+
+/// Turns the clip plane on or off, starting it level through the middle of
+/// the current map.
+fn toggle_clip_key(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut clip: ResMut<ClipPlane>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+) {
+    if !keys.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+    clip.active = !clip.active;
+    if clip.active {
+        let bounds = maps
+            .root()
+            .and_then(|root| summaries.get(root).ok())
+            .map(MapSummary::world_bounds);
+        clip.point = match bounds {
+            Some(bounds) => (Vec3::from(bounds.min) + Vec3::from(bounds.max)) / 2.0,
+            None => Vec3::ZERO,
+        };
+        clip.normal = Vec3::Z;
+    }
+}
+
+fn move_clip_plane(keys: Res<ButtonInput<KeyCode>>, time: Res<Time>, mut clip: ResMut<ClipPlane>) {
+    if !clip.active {
+        return;
+    }
+    let axis = |positive, negative| {
+        keys.pressed(positive) as i32 as f32 - keys.pressed(negative) as i32 as f32
+    };
+    let dt = time.delta_seconds();
+    let push = axis(KeyCode::PageUp, KeyCode::PageDown);
+    let yaw = axis(KeyCode::ArrowLeft, KeyCode::ArrowRight);
+    let pitch = axis(KeyCode::ArrowUp, KeyCode::ArrowDown);
+    if push == 0.0 && yaw == 0.0 && pitch == 0.0 {
+        return;
+    }
+
+    // Pitch around the horizontal axis in the plane, or X while it is level
+    let normal = clip.normal;
+    let side = normal.cross(Vec3::Z).try_normalize().unwrap_or(Vec3::X);
+    let rotation = Quat::from_rotation_z(yaw * TURN_SPEED * dt)
+        * Quat::from_axis_angle(side, pitch * TURN_SPEED * dt);
+    clip.normal = (rotation * normal).normalize();
+    clip.point += clip.normal * push * MOVE_SPEED * dt;
+}
+
+/// Gives world batches a [ClipMaterial] copy of their material while the
+/// clip plane is on, and their own material back once it is off.
+fn swap_clip_materials(
+    mut commands: Commands,
+    mut clip_materials: ResMut<Assets<ClipMaterial>>,
+    mut copies: Local<HashMap<AssetId<StandardMaterial>, Handle<ClipMaterial>>>,
+    clip: Res<ClipPlane>,
+    materials: Res<Assets<StandardMaterial>>,
+    unclipped: Query<(Entity, &Handle<StandardMaterial>), With<WorldBatch>>,
+    clipped: Query<(Entity, &Unclipped), With<WorldBatch>>,
+) {
+    if !clip.active {
+        for (entity, Unclipped(material)) in clipped.iter() {
+            commands
+                .entity(entity)
+                .remove::<(Handle<ClipMaterial>, Unclipped)>()
+                .insert(material.clone());
+        }
+        copies.clear();
+        return;
+    }
+    for (entity, material) in unclipped.iter() {
+        let Some(base) = materials.get(material) else {
+            continue;
+        };
+        let copy = copies
+            .entry(material.id())
+            .or_insert_with(|| {
+                clip_materials.add(ClipMaterial {
+                    base: base.clone(),
+                    extension: ClipExtension {
+                        plane: clip.equation(),
+                    },
+                })
+            })
+            .clone();
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert((copy, Unclipped(material.clone())));
+    }
+}
+
+fn update_clip_materials(
+    clip: Res<ClipPlane>,
+    mut clip_materials: ResMut<Assets<ClipMaterial>>,
+    mut stats: ResMut<OverlayStats>,
+) {
+    if !clip.is_changed() {
+        return;
+    }
+    if !clip.active {
+        stats.remove("clip");
+        return;
+    }
+    for (_, material) in clip_materials.iter_mut() {
+        material.extension.plane = clip.equation();
+    }
+    let (point, normal) = (clip.point, clip.normal);
+    stats.set(
+        "clip",
+        Message::new("clip.plane")
+            .arg(format!("{:.0}", point.x))
+            .arg(format!("{:.0}", point.y))
+            .arg(format!("{:.0}", point.z))
+            .arg(format!("{:.2}", normal.x))
+            .arg(format!("{:.2}", normal.y))
+            .arg(format!("{:.2}", normal.z)),
+    );
+}
+
+/// Draws the clip plane as a square around its point, with an arrow towards
+/// the side that is cut away.
+fn draw_clip_plane(
+    clip: Res<ClipPlane>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    mut gizmos: Gizmos,
+) {
+    if !clip.active {
+        return;
+    }
+    let size = maps
+        .root()
+        .and_then(|root| summaries.get(root).ok())
+        .map(|summary| {
+            let bounds = summary.world_bounds();
+            (Vec3::from(bounds.max) - Vec3::from(bounds.min)).max_element()
+        })
+        .filter(|size| *size > 0.0)
+        .unwrap_or(512.0);
+    let color = Color::srgb(1.0, 0.6, 0.1);
+    let rotation = Quat::from_rotation_arc(Vec3::Z, clip.normal);
+    gizmos.rect(clip.point, rotation, Vec2::splat(size), color);
+    gizmos.rect(clip.point, rotation, Vec2::splat(size / 2.0), color);
+    gizmos.arrow(clip.point, clip.point + clip.normal * size / 8.0, color);
+}
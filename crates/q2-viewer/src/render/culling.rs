@@ -0,0 +1,301 @@
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{
+    prelude::*,
+    render::{
+        primitives::{Aabb, Frustum},
+        view::VisibilitySystems,
+    },
+    transform::TransformSystem,
+};
+
+use super::{HeatmapMode, OverlayStats};
+use crate::{
+    asset::MapSummary,
+    locale::Message,
+    sim::Interpolated,
+    start::{MapAreas, MapTracer, Pvs, WorldBatch},
+};
+
+/// Hides the world batches outside of the PVS of the camera's cluster. The
+/// cluster is found once per map each frame, by walking the map's BSP tree
+/// down to the camera's leaf, and the overlay shows it with the number of
+/// batches drawn. `r_novis 1` turns the PVS off to compare, as in Quake 2.
+///
+/// Clusters in areas the camera's area isn't connected to through open area
+/// portals are hidden too, as behind closed doors in the game. With no doors
+/// moving here, portals are opened and closed with `areaportal <portal>
+/// <0|1>`, and `map_noareas 1` ignores them.
+///
+/// For vis development, culling can be locked at the camera's current
+/// position and view with V or the `gl_lockpvs 1` command, as in Quake 2.
+/// While locked, the camera flies around freely and everything culled from
+/// the locked view (by the PVS or the locked frustum) is drawn in translucent
+/// red instead of being hidden.
+pub struct PvsCullingPlugin;
+
+impl Plugin for PvsCullingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PvsCulling>()
+            .add_systems(Update, lock_pvs_key)
+            .add_systems(
+                PostUpdate,
+                (update_locked_view, update_area_portals, cull_world_batches)
+                    .chain()
+                    .after(TransformSystem::TransformPropagate)
+                    .after(VisibilitySystems::CalculateBounds)
+                    .before(VisibilitySystems::VisibilityPropagate),
+            );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PvsCulling {
+    /// Freeze culling at the camera's view. Takes effect at the end of the
+    /// frame.
+    pub lock: bool,
+    /// The view culling is frozen at, once locked.
+    pub locked_view: Option<LockedView>,
+    /// Draw every cluster, ignoring the PVS.
+    pub novis: bool,
+    /// Area portals opened (true) or closed from the console, applied to
+    /// every map. The others stay closed.
+    pub portals: BTreeMap<u32, bool>,
+    /// Ignore the area portals, as if every one was open.
+    pub noareas: bool,
+}
+
+/// A camera position and frustum culling is frozen at.
+#[derive(Debug, Clone)]
+pub struct LockedView {
+    pub position: Vec3,
+    pub frustum: Frustum,
+}
+
+impl PvsCulling {
+    /// Runs a console command: `gl_lockpvs <0|1>`, `r_novis <0|1>`,
+    /// `areaportal <portal> <0|1>` or `map_noareas <0|1>`.
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+        if let Some("areaportal") = words.clone().next() {
+            let words: Vec<&str> = words.collect();
+            let (portal, open) = match words[1..] {
+                [portal, "0"] => (portal.parse::<u32>(), false),
+                [portal, "1"] => (portal.parse::<u32>(), true),
+                _ => return Err("usage: areaportal <portal> <0|1>".to_string()),
+            };
+            let portal = portal.map_err(|_| "usage: areaportal <portal> <0|1>".to_string())?;
+            self.portals.insert(portal, open);
+            return Ok(());
+        }
+        match (words.next(), words.next(), words.next()) {
+            (Some("gl_lockpvs"), Some("0"), None) => self.lock = false,
+            (Some("gl_lockpvs"), Some("1"), None) => self.lock = true,
+            (Some("gl_lockpvs"), ..) => return Err("usage: gl_lockpvs <0|1>".to_string()),
+            (Some("r_novis"), Some("0"), None) => self.novis = false,
+            (Some("r_novis"), Some("1"), None) => self.novis = true,
+            (Some("r_novis"), ..) => return Err("usage: r_novis <0|1>".to_string()),
+            (Some("map_noareas"), Some("0"), None) => self.noareas = false,
+            (Some("map_noareas"), Some("1"), None) => self.noareas = true,
+            (Some("map_noareas"), ..) => return Err("usage: map_noareas <0|1>".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// A translucent copy of a world batch, shown while culling is locked and
+/// the batch is culled.
+#[derive(Component)]
+struct CulledGhost(Entity);
+
+fn lock_pvs_key(keys: Res<ButtonInput<KeyCode>>, mut culling: ResMut<PvsCulling>) {
+    if keys.just_pressed(KeyCode::KeyV) {
+        culling.lock = !culling.lock;
+    }
+}
+
+/// Captures the camera's view when culling is locked, spawning a ghost for
+/// every world batch, and drops them once unlocked.
+#[allow(clippy::too_many_arguments)]
+fn update_locked_view(
+    mut commands: Commands,
+    mut culling: ResMut<PvsCulling>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut material: Local<Option<Handle<StandardMaterial>>>,
+    cameras: Query<(&GlobalTransform, &Frustum), With<Interpolated>>,
+    batches: Query<(Entity, &Handle<Mesh>, &Transform, &Parent), With<WorldBatch>>,
+    new_batches: Query<(), Added<WorldBatch>>,
+    ghosts: Query<Entity, With<CulledGhost>>,
+) {
+    if !culling.lock {
+        if culling.locked_view.take().is_some() {
+            for ghost in ghosts.iter() {
+                commands.entity(ghost).despawn_recursive();
+            }
+        }
+        return;
+    }
+    // A new map respawns the batches, so their ghosts are spawned again
+    let respawned = !new_batches.is_empty();
+    if culling.locked_view.is_some() && !respawned {
+        return;
+    }
+    let Some((transform, frustum)) = cameras.iter().next() else {
+        return;
+    };
+    if culling.locked_view.is_none() {
+        info!("Locked PVS at {}", transform.translation());
+        culling.locked_view = Some(LockedView {
+            position: transform.translation(),
+            frustum: *frustum,
+        });
+    }
+
+    let material = material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color: Color::srgba(1.0, 0.1, 0.1, 0.25),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+        .clone();
+    for ghost in ghosts.iter() {
+        commands.entity(ghost).despawn_recursive();
+    }
+    for (batch, mesh, transform, parent) in batches.iter() {
+        let ghost = commands
+            .spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: *transform,
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                CulledGhost(batch),
+            ))
+            .id();
+        commands.entity(parent.get()).add_child(ghost);
+    }
+}
+
+/// Applies the area portals set from the console to each map, including
+/// maps loaded since.
+fn update_area_portals(culling: Res<PvsCulling>, mut maps: Query<&mut MapAreas>) {
+    for mut areas in maps.iter_mut() {
+        if !culling.is_changed() && !areas.is_added() {
+            continue;
+        }
+        for (&portal, &open) in &culling.portals {
+            areas.portals.set_portal_open(portal, open);
+        }
+    }
+}
+
+/// Shows the world batches in the PVS of the camera's cluster, or of the
+/// locked view, and the ghosts of the others while locked.
+#[allow(clippy::type_complexity)]
+fn cull_world_batches(
+    culling: Res<PvsCulling>,
+    heatmap: Res<HeatmapMode>,
+    mut stats: ResMut<OverlayStats>,
+    cameras: Query<&GlobalTransform, With<Interpolated>>,
+    maps: Query<(
+        Entity,
+        &Pvs,
+        &MapTracer,
+        &MapAreas,
+        &MapSummary,
+        &GlobalTransform,
+    )>,
+    mut batches: Query<
+        (
+            &WorldBatch,
+            &Parent,
+            &GlobalTransform,
+            Option<&Aabb>,
+            &mut Visibility,
+        ),
+        Without<CulledGhost>,
+    >,
+    mut ghosts: Query<(&CulledGhost, &mut Visibility)>,
+) {
+    // The heatmap hides the batches itself
+    if heatmap.0.is_some() {
+        stats.remove("cull");
+        return;
+    }
+    let view = match (&culling.locked_view, cameras.iter().next()) {
+        (Some(locked), _) => locked.position,
+        (None, Some(camera)) => camera.translation(),
+        (None, None) => return,
+    };
+
+    // The camera's cluster and area in each map. Outside of the world,
+    // everything is shown.
+    let views: HashMap<Entity, (Option<usize>, usize)> = maps
+        .iter()
+        .map(|(entity, _, tracer, _, summary, root)| {
+            let local = root.affine().inverse().transform_point3(view) - summary.world_offset();
+            let local = local.to_array();
+            let area = tracer.0.area_at(local).unwrap_or(0);
+            (entity, (tracer.0.cluster_at(local), area))
+        })
+        .collect();
+
+    let (mut shown, mut total) = (0, 0);
+    for (batch, parent, transform, aabb, mut visibility) in batches.iter_mut() {
+        let Ok((_, pvs, _, areas, ..)) = maps.get(parent.get()) else {
+            continue;
+        };
+        let (view_cluster, view_area) = views[&parent.get()];
+        let visible = |cluster: usize| {
+            let potentially = view_cluster.map_or(true, |from| {
+                culling.novis || pvs.0.is_visible(from, cluster)
+            });
+            let area = areas.cluster_areas.get(cluster).copied().unwrap_or(0);
+            potentially && (culling.noareas || areas.portals.areas_connected(view_area, area))
+        };
+        let in_pvs = batch
+            .clusters
+            .iter()
+            .any(|&c| usize::try_from(c).map_or(true, visible));
+        let in_frustum = match (&culling.locked_view, aabb) {
+            (Some(locked), Some(aabb)) => {
+                locked
+                    .frustum
+                    .intersects_obb(aabb, &transform.affine(), true, true)
+            }
+            _ => true,
+        };
+        visibility.set_if_neq(match in_pvs && in_frustum {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        });
+        total += 1;
+        shown += usize::from(in_pvs && in_frustum);
+    }
+    // The first map's cluster, as the viewer mostly shows one
+    let cluster = views.values().find_map(|&(cluster, _)| cluster);
+    stats.set(
+        "cull",
+        Message::new("cull.summary")
+            .arg(cluster.map_or("-".to_string(), |cluster| cluster.to_string()))
+            .arg(shown)
+            .arg(total)
+            .arg(if culling.novis { " (novis)" } else { "" }),
+    );
+
+    for (ghost, mut visibility) in ghosts.iter_mut() {
+        let culled = batches
+            .get(ghost.0)
+            .is_ok_and(|(.., batch_visibility)| *batch_visibility == Visibility::Hidden);
+        visibility.set_if_neq(match culled {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        });
+    }
+}
@@ -0,0 +1,104 @@
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+
+use crate::locale::Locale;
+
+/// Assets whose failure to load is reported in an on-screen panel rather
+/// than only in the log.
+#[derive(Resource, Default)]
+pub struct WatchedAssets {
+    handles: Vec<UntypedHandle>,
+}
+
+impl WatchedAssets {
+    pub fn watch(&mut self, handle: impl Into<UntypedHandle>) {
+        self.handles.push(handle.into());
+    }
+}
+
+/// The panel listing failed loads, spawned on the first failure.
+#[derive(Component)]
+pub struct ErrorPanel;
+
+pub(super) fn report_failed_loads(
+    mut commands: Commands,
+    mut watched: ResMut<WatchedAssets>,
+    asset_server: Res<AssetServer>,
+    locale: Res<Locale>,
+    mut panels: Query<(Entity, &mut Text), With<ErrorPanel>>,
+) {
+    let mut failures = Vec::new();
+    watched
+        .handles
+        .retain(|handle| match asset_server.get_load_state(handle.id()) {
+            Some(LoadState::Failed(error)) => {
+                let path = handle
+                    .path()
+                    .map(|p| p.to_string())
+                    .unwrap_or_else(|| "<unnamed asset>".to_string());
+                failures.push((path, error.to_string()));
+                false
+            }
+            Some(LoadState::Loaded) => false,
+            _ => true,
+        });
+    if failures.is_empty() {
+        return;
+    }
+
+    let sections = failures.into_iter().flat_map(|(path, message)| {
+        error!("Failed to load {}: {}", path, message);
+        [
+            TextSection::new(
+                format!("{}\n", path),
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                format!("{}\n", message),
+                TextStyle {
+                    font_size: 14.0,
+                    color: Color::srgb(1.0, 0.8, 0.8),
+                    ..default()
+                },
+            ),
+        ]
+    });
+
+    if let Ok((_, mut text)) = panels.get_single_mut() {
+        text.sections.extend(sections);
+        return;
+    }
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                left: Val::Px(8.0),
+                right: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(12.0)),
+                ..default()
+            },
+            background_color: Color::srgba(0.5, 0.05, 0.05, 0.9).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections(
+                    std::iter::once(TextSection::new(
+                        format!("{}\n", locale.get("error.failed_to_load")),
+                        TextStyle {
+                            font_size: 22.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ))
+                    .chain(sections),
+                ),
+                ErrorPanel,
+            ));
+        });
+}
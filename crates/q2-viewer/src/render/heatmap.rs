@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+
+use q2_formats::bsp38::{
+    prelude::{DebugPalette, FaceMetric, MeshOptions},
+    FaceData, BSP38,
+};
+
+use super::OverlayStats;
+use crate::{asset::BSP38Asset, locale::Message, maps::MapInstance, start::WorldBatch};
+
+/// Color of faces with no value, such as faces outside of any cluster.
+const NO_VALUE: [f32; 3] = [0.25, 0.25, 0.25];
+
+/// The analysis view shown instead of the world meshes, or None for the
+/// normal view. H cycles through [FaceMetric::ALL].
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeatmapMode(pub Option<FaceMetric>);
+
+/// Colors the heatmaps are drawn in. Set with `r_palette <name>`, one of the
+/// names of [DebugPalette::ALL], to pick a palette that stays readable with
+/// color blindness.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeatmapPalette(pub DebugPalette);
+
+impl HeatmapPalette {
+    /// Runs a console command: `r_palette <name>`.
+    ///
+    /// ```
+    /// # use q2_formats::bsp38::prelude::DebugPalette;
+    /// # use q2_viewer::render::HeatmapPalette;
+    /// let mut palette = HeatmapPalette::default();
+    /// palette.run_command("r_palette tol_bright").unwrap();
+    /// assert_eq!(palette.0, DebugPalette::TolBright);
+    /// assert!(palette.run_command("r_palette sepia").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let names = DebugPalette::ALL.map(|palette| palette.name()).join("|");
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["r_palette", name] => {
+                self.0 = name
+                    .parse()
+                    .map_err(|_| format!("usage: r_palette <{}>", names))?;
+                Ok(())
+            }
+            ["r_palette", ..] => Err(format!("usage: r_palette <{}>", names)),
+            _ => Err(format!("unknown command {:?}", line)),
+        }
+    }
+}
+
+/// Analysis render modes coloring every world face by a [FaceMetric], to find
+/// problem geometry: cold faces are low, hot faces high. Continuous metrics
+/// are scaled between their 5th and 95th percentile so a few outliers don't
+/// wash out the rest; clusters get one flat color each. The colors come from
+/// the [HeatmapPalette].
+pub struct HeatmapPlugin;
+
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HeatmapMode>()
+            .init_resource::<HeatmapPalette>()
+            .add_systems(Update, (cycle_heatmap_key, update_heatmap));
+    }
+}
+
+/// A heatmap mesh, a child of its [MapInstance].
+#[derive(Component)]
+struct Heatmap;
+
+fn cycle_heatmap_key(keys: Res<ButtonInput<KeyCode>>, mut mode: ResMut<HeatmapMode>) {
+    if !keys.just_pressed(KeyCode::KeyH) {
+        return;
+    }
+    let next = match mode.0 {
+        None => FaceMetric::ALL.first(),
+        Some(metric) => FaceMetric::ALL.iter().skip_while(|&&m| m != metric).nth(1),
+    };
+    mode.0 = next.copied();
+}
+
+/// Rebuilds the heatmaps when the mode changes or a map is spawned, and hides
+/// the world meshes behind them.
+#[allow(clippy::too_many_arguments)]
+fn update_heatmap(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut stats: ResMut<OverlayStats>,
+    mut batches: Query<(Ref<WorldBatch>, &mut Visibility)>,
+    mode: Res<HeatmapMode>,
+    palette: Res<HeatmapPalette>,
+    instances: Query<(Entity, Ref<MapInstance>)>,
+    heatmaps: Query<Entity, With<Heatmap>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    let visibility = match mode.0 {
+        Some(_) => Visibility::Hidden,
+        None => Visibility::Inherited,
+    };
+    for (batch, mut batch_visibility) in batches.iter_mut() {
+        if mode.is_changed() || batch.is_added() {
+            batch_visibility.set_if_neq(visibility);
+        }
+    }
+
+    let spawned = instances
+        .iter()
+        .any(|(_, instance)| instance.is_changed() && instance.spawned);
+    if !mode.is_changed() && !palette.is_changed() && !spawned {
+        return;
+    }
+    for entity in heatmaps.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(metric) = mode.0 else {
+        stats.remove("heatmap");
+        return;
+    };
+
+    let mut material = None;
+    for (root, instance) in instances.iter() {
+        if !instance.spawned {
+            continue;
+        }
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        let (data, range) = heatmap_faces(&asset.bsp, metric, palette.0);
+        if let Some((low, high)) = range {
+            stats.set(
+                "heatmap",
+                Message::new("heatmap.range")
+                    .arg(metric.name())
+                    .arg(format!("{:.4}", low))
+                    .arg(format!("{:.4}", high)),
+            );
+        } else {
+            stats.set("heatmap", metric.name());
+        }
+        let material = material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .clone();
+        let heatmap = commands
+            .spawn((
+                PbrBundle {
+                    mesh: meshes.add(data.into_mesh(&MeshOptions::default())),
+                    material,
+                    transform: Transform::from_translation(asset.world_offset()),
+                    ..default()
+                },
+                Heatmap,
+            ))
+            .id();
+        commands.entity(root).add_child(heatmap);
+    }
+}
+
+/// Every face of `bsp` colored by `metric` in `palette`, with the range of
+/// values the color ramp spans, if it is continuous.
+fn heatmap_faces(
+    bsp: &BSP38,
+    metric: FaceMetric,
+    palette: DebugPalette,
+) -> (FaceData, Option<(f32, f32)>) {
+    let values = bsp.face_metric(metric);
+    let range = match metric {
+        FaceMetric::Cluster => None,
+        _ => {
+            let mut sorted = values.clone();
+            sorted.sort_by(f32::total_cmp);
+            let percentile = |p: usize| sorted.get(sorted.len() * p / 100).copied();
+            percentile(5).zip(percentile(95))
+        }
+    };
+    let color = |value: f32| match (metric, range) {
+        (FaceMetric::Cluster, _) if value < 0.0 => NO_VALUE,
+        (FaceMetric::Cluster, _) => category_color(palette, value as usize),
+        (_, Some((low, high))) => palette.ramp((value - low) / (high - low).max(f32::EPSILON)),
+        (_, None) => NO_VALUE,
+    };
+
+    // Faces are built in order, each as a fan of num_edges - 2 triangles
+    let mut data = bsp.read_faces().unwrap_or_default();
+    let mut colors = data.colors.chunks_exact_mut(9);
+    for (face, &value) in bsp
+        .read_face_records()
+        .unwrap_or_default()
+        .iter()
+        .zip(&values)
+    {
+        let color = color(value);
+        for triangle in colors
+            .by_ref()
+            .take((face.num_edges as usize).saturating_sub(2))
+        {
+            for vertex in triangle.chunks_exact_mut(3) {
+                vertex.copy_from_slice(&color);
+            }
+        }
+    }
+    (data, range)
+}
+
+/// A distinct color for category `i`. The classic palette steps around the
+/// hue wheel by the golden angle so neighbouring indices differ; the others
+/// cycle through their few distinguishable colors.
+fn category_color(palette: DebugPalette, i: usize) -> [f32; 3] {
+    if palette != DebugPalette::Classic {
+        return palette.color(i);
+    }
+    let hue = (i as f32 * 137.508) % 360.0;
+    let color = LinearRgba::from(Color::hsl(hue, 0.7, 0.55));
+    [color.red, color.green, color.blue]
+}
@@ -0,0 +1,323 @@
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        primitives::Aabb,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::{RenderLayers, VisibilitySystems},
+    },
+    transform::TransformSystem,
+    utils::HashMap,
+};
+
+use super::OverlayStats;
+use crate::{
+    locale::Message,
+    maps::{MapManager, MapScoped},
+    sim::Interpolated,
+    start::WorldBatch,
+};
+
+/// Render layer of the first section. Each section renders alone into its
+/// impostor, so each gets a layer of its own.
+const FIRST_SECTION_LAYER: usize = 16;
+
+/// Draws far-away sections of large maps as flat impostors, toggled with I.
+///
+/// The world batches are grouped into sections on a horizontal grid. A
+/// section further from the camera than [Impostors::distance] is rendered once
+/// into a texture from the camera's position and shown as a single quad
+/// facing it, instead of drawing its batches every frame. The impostor is
+/// only captured again once the camera has moved [Impostors::refresh_distance]
+/// away, so in between the section shows no parallax: a trade of accuracy for
+/// far fewer triangles per frame on low-end hardware.
+pub struct ImpostorPlugin;
+
+impl Plugin for ImpostorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Impostors>()
+            .add_systems(Update, toggle_impostors_key)
+            .add_systems(
+                PostUpdate,
+                (build_sections, update_impostors)
+                    .chain()
+                    .after(TransformSystem::TransformPropagate)
+                    .after(VisibilitySystems::CalculateBounds)
+                    .before(VisibilitySystems::VisibilityPropagate),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct Impostors {
+    pub enabled: bool,
+    /// Sections whose bounds are further than this from the camera, in map
+    /// units, are drawn as impostors.
+    pub distance: f32,
+    /// How far the camera moves before an impostor is captured again.
+    pub refresh_distance: f32,
+    /// Edge of the square grid cells batches are grouped into, in map units.
+    pub section_size: f32,
+    /// Edge of the impostor textures, in pixels.
+    pub resolution: u32,
+    sections: Vec<Section>,
+    /// The map the sections were built for.
+    root: Option<Entity>,
+}
+
+impl Default for Impostors {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            distance: 3000.0,
+            refresh_distance: 256.0,
+            section_size: 1024.0,
+            resolution: 256,
+            sections: Vec::new(),
+            root: None,
+        }
+    }
+}
+
+/// World batches drawn together as one impostor.
+struct Section {
+    /// Center and radius of the bounding sphere of the batches, in world
+    /// space.
+    center: Vec3,
+    radius: f32,
+    batches: Vec<Entity>,
+    layer: RenderLayers,
+    camera: Entity,
+    quad: Entity,
+    /// Where the impostor was last captured from, while it is shown.
+    captured_from: Option<Vec3>,
+}
+
+fn toggle_impostors_key(keys: Res<ButtonInput<KeyCode>>, mut impostors: ResMut<Impostors>) {
+    if keys.just_pressed(KeyCode::KeyI) {
+        impostors.enabled = !impostors.enabled;
+    }
+}
+
+/// Groups the batches of the current map into sections, once their bounds
+/// are known, and spawns the camera and quad of each.
+#[allow(clippy::too_many_arguments)]
+fn build_sections(
+    mut commands: Commands,
+    mut impostors: ResMut<Impostors>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut quad_mesh: Local<Option<Handle<Mesh>>>,
+    maps: Res<MapManager>,
+    batches: Query<(Entity, &Parent, &GlobalTransform, Option<&Aabb>), With<WorldBatch>>,
+) {
+    // The cameras and quads of the previous map went with it
+    if impostors.root != maps.root() {
+        impostors.sections.clear();
+        impostors.root = maps.root();
+    }
+    let Some(root) = maps.root() else {
+        return;
+    };
+    if !impostors.enabled || !impostors.sections.is_empty() {
+        return;
+    }
+
+    let mut cells: HashMap<(i32, i32), Vec<(Entity, Vec3, Vec3)>> = HashMap::new();
+    for (entity, parent, transform, aabb) in batches.iter() {
+        if parent.get() != root {
+            continue;
+        }
+        let Some(aabb) = aabb else {
+            // Bounds are computed the frame after spawning
+            return;
+        };
+        let center = transform.transform_point(aabb.center.into());
+        let half = transform
+            .affine()
+            .transform_vector3(aabb.half_extents.into());
+        let cell = (center.truncate() / impostors.section_size)
+            .floor()
+            .as_ivec2();
+        cells.entry((cell.x, cell.y)).or_default().push((
+            entity,
+            center - half.abs(),
+            center + half.abs(),
+        ));
+    }
+
+    let quad_mesh = quad_mesh
+        .get_or_insert_with(|| meshes.add(Rectangle::new(1.0, 1.0)))
+        .clone();
+    let size = Extent3d {
+        width: impostors.resolution,
+        height: impostors.resolution,
+        ..default()
+    };
+    for (i, cell) in cells.into_values().enumerate() {
+        let min = cell.iter().fold(Vec3::MAX, |min, (_, lo, _)| min.min(*lo));
+        let max = cell.iter().fold(Vec3::MIN, |max, (_, _, hi)| max.max(*hi));
+
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: Some("impostor"),
+                size,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        image.resize(size);
+        let image = images.add(image);
+
+        let layer = RenderLayers::layer(FIRST_SECTION_LAYER + i);
+        let camera = commands
+            .spawn((
+                Camera3dBundle {
+                    camera: Camera {
+                        order: -2,
+                        target: RenderTarget::Image(image.clone()),
+                        is_active: false,
+                        clear_color: ClearColorConfig::Custom(Color::NONE),
+                        ..default()
+                    },
+                    // The main camera tonemaps the impostor
+                    tonemapping: Tonemapping::None,
+                    ..default()
+                },
+                layer.clone(),
+                MapScoped,
+            ))
+            .id();
+        let quad = commands
+            .spawn((
+                PbrBundle {
+                    mesh: quad_mesh.clone(),
+                    material: materials.add(StandardMaterial {
+                        base_color_texture: Some(image),
+                        alpha_mode: AlphaMode::Mask(0.5),
+                        unlit: true,
+                        ..default()
+                    }),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                NotShadowCaster,
+                MapScoped,
+            ))
+            .id();
+        impostors.sections.push(Section {
+            center: (min + max) / 2.0,
+            radius: (max - min).length() / 2.0,
+            batches: cell.into_iter().map(|(entity, ..)| entity).collect(),
+            layer,
+            camera,
+            quad,
+            captured_from: None,
+        });
+    }
+}
+
+/// Swaps sections between their batches and their impostor by distance,
+/// recapturing impostors the camera has moved away from.
+#[allow(clippy::type_complexity)]
+fn update_impostors(
+    mut commands: Commands,
+    mut impostors: ResMut<Impostors>,
+    mut stats: ResMut<OverlayStats>,
+    main: Query<&GlobalTransform, With<Interpolated>>,
+    mut cameras: Query<
+        (&mut Camera, &mut Transform, &mut Projection),
+        (Without<Interpolated>, Without<Handle<StandardMaterial>>),
+    >,
+    mut quads: Query<(&mut Transform, &mut Visibility), With<Handle<StandardMaterial>>>,
+) {
+    let Some(eye) = main.iter().next().map(GlobalTransform::translation) else {
+        return;
+    };
+    let impostors = &mut *impostors;
+    let (distance, refresh_distance) = (impostors.distance, impostors.refresh_distance);
+    let mut shown = 0;
+    for section in impostors.sections.iter_mut() {
+        let Ok((mut camera, mut camera_transform, mut projection)) =
+            cameras.get_mut(section.camera)
+        else {
+            continue;
+        };
+        // Captures render for one frame only
+        camera.is_active = false;
+
+        let to_section = section.center - eye;
+        let far = impostors.enabled && to_section.length() - section.radius > distance;
+        if !far {
+            if section.captured_from.take().is_some() {
+                for &batch in &section.batches {
+                    commands.entity(batch).remove::<RenderLayers>();
+                }
+                if let Ok((_, mut visibility)) = quads.get_mut(section.quad) {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+            continue;
+        }
+        shown += 1;
+        let stale = section
+            .captured_from
+            .map_or(true, |from| from.distance(eye) > refresh_distance);
+        if !stale {
+            continue;
+        }
+
+        // Frame the bounding sphere from the eye, and put the quad through
+        // its center where it covers the same view
+        let d = to_section.length();
+        let half_fov = (section.radius / d).min(0.99).asin();
+        let view = Transform::from_translation(eye).looking_at(section.center, Vec3::Z);
+        *camera_transform = view;
+        *projection = Projection::Perspective(PerspectiveProjection {
+            fov: 2.0 * half_fov,
+            aspect_ratio: 1.0,
+            near: (d - section.radius).max(1.0),
+            far: d + section.radius,
+        });
+        camera.is_active = true;
+        if let Ok((mut transform, mut visibility)) = quads.get_mut(section.quad) {
+            let edge = 2.0 * d * half_fov.tan();
+            *transform = Transform {
+                translation: section.center,
+                rotation: view.rotation,
+                scale: Vec3::new(edge, edge, 1.0),
+            };
+            *visibility = Visibility::Visible;
+        }
+        if section.captured_from.is_none() {
+            // Only the section's camera draws the batches now
+            for &batch in &section.batches {
+                commands.entity(batch).insert(section.layer.clone());
+            }
+        }
+        section.captured_from = Some(eye);
+    }
+
+    if impostors.enabled {
+        stats.set(
+            "impostors",
+            Message::new("impostors.shown")
+                .arg(shown)
+                .arg(impostors.sections.len()),
+        );
+    } else {
+        stats.remove("impostors");
+    }
+}
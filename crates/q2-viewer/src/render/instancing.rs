@@ -0,0 +1,51 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Shared mesh and material handles for objects that appear many times.
+///
+/// Bevy batches entities sharing the same mesh and material into a single
+/// instanced draw, with the per-entity transforms as instance data. Spawning
+/// repeated objects (inline models, props, debug markers) through this
+/// registry guarantees they share handles instead of each creating its own
+/// copies.
+#[derive(Resource, Default)]
+pub struct InstancedAssets {
+    entries: HashMap<String, (Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+impl InstancedAssets {
+    /// Returns the handles registered under `key`, creating them on first use.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: &str,
+        create: impl FnOnce() -> (Handle<Mesh>, Handle<StandardMaterial>),
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        if let Some(entry) = self.entries.get(key) {
+            return entry.clone();
+        }
+        let entry = create();
+        self.entries.insert(key.to_string(), entry.clone());
+        entry
+    }
+
+    /// Builds a [PbrBundle] for one instance of `key` at `transform`.
+    pub fn bundle(
+        &mut self,
+        key: &str,
+        transform: Transform,
+        create: impl FnOnce() -> (Handle<Mesh>, Handle<StandardMaterial>),
+    ) -> PbrBundle {
+        let (mesh, material) = self.get_or_insert_with(key, create);
+        PbrBundle {
+            mesh,
+            material,
+            transform,
+            ..default()
+        }
+    }
+
+    /// Drops every registered entry, e.g. when a map is unloaded.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use q2_formats::bsp38::prelude::{MapLight, METERS_PER_UNIT};
+
+use crate::{asset::BSP38Asset, maps::MapInstance, quality::Quality};
+
+/// Spawns a Bevy light for each `light` and `light_spot` entity of a map,
+/// with the brightness, falloff range and `_color` the light compiler baked
+/// into the lightmaps, so models and maps without lightmaps are lit the same
+/// way. Spotlights keep their cone.
+///
+/// Only the brightest [Quality::dynamic_lights] of a map are spawned, and
+/// they are respawned when that changes.
+pub struct MapLightPlugin;
+
+impl Plugin for MapLightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, spawn_map_lights);
+    }
+}
+
+/// On the root of a map whose lights have been spawned, how many of them
+/// were asked for.
+#[derive(Component)]
+struct SpawnedLights(usize);
+
+/// A light spawned from a light entity of the map it is a child of.
+#[derive(Component)]
+pub struct MapLightSource {
+    /// Index of the light's entity in the map's entity list.
+    pub entity: usize,
+}
+
+/// The lights of a map to spawn, the brightest `max` of them.
+fn brightest(mut lights: Vec<MapLight>, max: usize) -> Vec<MapLight> {
+    lights.sort_by(|a, b| b.value.total_cmp(&a.value));
+    lights.truncate(max);
+    lights
+}
+
+fn spawn_map_lights(
+    mut commands: Commands,
+    quality: Option<Res<Quality>>,
+    instances: Query<(
+        Entity,
+        &MapInstance,
+        Option<&SpawnedLights>,
+        Option<&Children>,
+    )>,
+    sources: Query<(), With<MapLightSource>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    let max = quality.map_or(0, |quality| quality.dynamic_lights);
+    for (root, instance, spawned, children) in instances.iter() {
+        if !instance.spawned || spawned.is_some_and(|spawned| spawned.0 == max) {
+            continue;
+        }
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        for &child in children.map(|children| &children[..]).unwrap_or_default() {
+            if !sources.contains(child) {
+                continue;
+            }
+            commands.entity(child).despawn_recursive();
+        }
+        commands.entity(root).insert(SpawnedLights(max));
+
+        let offset = asset.world_offset();
+        // Lumens are for meters; the map is in units of about an inch
+        let scale = (1.0 / METERS_PER_UNIT).powi(2);
+        for light in brightest(asset.bsp.read_lights(), max) {
+            let [r, g, b] = light.color;
+            let color = Color::srgb(r, g, b);
+            let translation = Vec3::from(light.origin) + offset;
+            let source = MapLightSource {
+                entity: light.entity,
+            };
+            let child = match light.spot {
+                Some(spot) => commands.spawn((
+                    SpotLightBundle {
+                        spot_light: SpotLight {
+                            color,
+                            intensity: light.lumens() * scale,
+                            range: light.range(),
+                            outer_angle: spot.cone.to_radians(),
+                            inner_angle: spot.cone.to_radians() * 0.8,
+                            ..default()
+                        },
+                        transform: Transform::from_translation(translation).with_rotation(
+                            Quat::from_rotation_arc(Vec3::NEG_Z, Vec3::from(spot.direction)),
+                        ),
+                        ..default()
+                    },
+                    source,
+                )),
+                None => commands.spawn((
+                    PointLightBundle {
+                        point_light: PointLight {
+                            color,
+                            intensity: light.lumens() * scale,
+                            range: light.range(),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(translation),
+                        ..default()
+                    },
+                    source,
+                )),
+            };
+            let child = child.id();
+            commands.entity(root).add_child(child);
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    asset::embedded_asset,
+    math::Vec3A,
+    pbr::{MaterialPipeline, MaterialPipelineKey, PbrProjectionPlugin},
+    prelude::*,
+    render::{
+        camera::{CameraProjection, CameraProjectionPlugin, RenderTarget},
+        mesh::MeshVertexBufferLayoutRef,
+        mesh::VertexAttributeValues,
+        render_resource::{
+            AsBindGroup, Extent3d, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages,
+        },
+    },
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+
+use q2_formats::bsp38::surface::SURF_WARP;
+
+use super::WaterSettings;
+use crate::{asset::BSP38Asset, maps::MapScoped, sim::interpolate, start::WorldBatch};
+
+/// Mirrors beyond this many are drawn with their normal material, since each
+/// one renders the whole scene again.
+const MAX_MIRRORS: usize = 4;
+
+/// Pulls the clip plane this far in front of the surface, so the camera
+/// doesn't see the mirror itself.
+const CLIP_OFFSET: f32 = 0.5;
+
+/// Reflective SURF_WARP surfaces and experimental portals.
+///
+/// Unless [WaterSettings::ripples] is on, each planar SURF_WARP batch becomes
+/// a mirror: an extra camera renders the scene reflected in its plane into a
+/// texture, which the surface shows in screen space. An oblique projection
+/// clips everything behind the mirror out of the reflection.
+///
+/// Portals are made from a `misc_portal_surface` entity whose `target` is a
+/// `misc_portal_camera`, as in Quake 3: a square at the surface shows the view
+/// from the camera.
+pub struct MirrorPlugin;
+
+impl Plugin for MirrorPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "mirror.wgsl");
+        app.add_plugins((
+            MaterialPlugin::<MirrorMaterial>::default(),
+            CameraProjectionPlugin::<ObliqueProjection>::default(),
+            PbrProjectionPlugin::<ObliqueProjection>::default(),
+        ))
+        .add_systems(Update, (setup_mirrors, setup_portals))
+        .add_systems(
+            PostUpdate,
+            update_view_cameras
+                .after(interpolate)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Shows the render target of a mirror or portal camera in screen space.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+#[bind_group_data(MirrorMaterialKey)]
+pub struct MirrorMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub texture: Handle<Image>,
+    #[uniform(2)]
+    pub tint: LinearRgba,
+    /// Mirror cameras see the world flipped horizontally.
+    pub flip_x: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MirrorMaterialKey {
+    flip_x: bool,
+}
+
+impl From<&MirrorMaterial> for MirrorMaterialKey {
+    fn from(material: &MirrorMaterial) -> Self {
+        Self {
+            flip_x: material.flip_x,
+        }
+    }
+}
+
+impl Material for MirrorMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/mirror.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let (true, Some(fragment)) = (key.bind_group_data.flip_x, &mut descriptor.fragment) {
+            fragment.shader_defs.push("FLIP_X".into());
+        }
+        Ok(())
+    }
+}
+
+/// A surface showing the view of `camera`.
+#[derive(Component, Debug, Clone, Copy)]
+pub enum ViewSurface {
+    /// Reflects the scene in the plane through `point` with normal `normal`,
+    /// both in the surface's local space.
+    Mirror {
+        normal: Vec3,
+        point: Vec3,
+        camera: Entity,
+    },
+    /// Shows the scene as seen through `entrance` placed at `exit`, in world
+    /// space.
+    Portal {
+        entrance: Transform,
+        exit: Transform,
+        camera: Entity,
+    },
+}
+
+/// Perspective projection with the near plane replaced by an arbitrary clip
+/// plane, so a mirror camera only sees what is in front of the mirror.
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component, Default)]
+pub struct ObliqueProjection {
+    pub perspective: PerspectiveProjection,
+    /// Plane in view space as (normal, distance): points with
+    /// `normal.dot(p) + distance >= 0` are kept. Zero disables clipping.
+    pub clip_plane: Vec4,
+}
+
+impl CameraProjection for ObliqueProjection {
+    fn get_clip_from_view(&self) -> Mat4 {
+        let clip_from_view = self.perspective.get_clip_from_view();
+        let plane = self.clip_plane;
+        if plane == Vec4::ZERO {
+            return clip_from_view;
+        }
+
+        // Bevy uses reverse-Z with an infinite far plane: 0 <= z <= w in clip
+        // space, z = w at the near plane. Replacing z with w - a * plane turns
+        // the near plane into the clip plane. The scale a keeps z >= 0 for
+        // everything in the frustum, so the far plane stays at infinity.
+        let tan = (self.perspective.fov / 2.0).tan();
+        let half = Vec2::new(tan * self.perspective.aspect_ratio, tan);
+        let max_slope = [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)]
+            .into_iter()
+            .map(|(x, y)| {
+                plane
+                    .truncate()
+                    .dot(Vec3::new(x * half.x, y * half.y, -1.0))
+            })
+            .fold(f32::NEG_INFINITY, f32::max);
+        if max_slope <= 0.0 {
+            // The whole frustum is behind the plane
+            return clip_from_view;
+        }
+        let w = clip_from_view.row(3);
+        Mat4::from_cols(
+            clip_from_view.row(0),
+            clip_from_view.row(1),
+            w - plane / max_slope,
+            w,
+        )
+        .transpose()
+    }
+
+    fn update(&mut self, width: f32, height: f32) {
+        self.perspective.update(width, height);
+    }
+
+    fn far(&self) -> f32 {
+        self.perspective.far
+    }
+
+    fn get_frustum_corners(&self, z_near: f32, z_far: f32) -> [Vec3A; 8] {
+        self.perspective.get_frustum_corners(z_near, z_far)
+    }
+}
+
+/// Turns planar SURF_WARP world batches into mirrors.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn setup_mirrors(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut mirror_materials: ResMut<Assets<MirrorMaterial>>,
+    meshes: Res<Assets<Mesh>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    batches: Query<(Entity, &WorldBatch, &Handle<Mesh>), Added<WorldBatch>>,
+    surfaces: Query<&ViewSurface>,
+    water: Res<WaterSettings>,
+) {
+    if water.ripples {
+        return;
+    }
+    let mut count = surfaces
+        .iter()
+        .filter(|s| matches!(s, ViewSurface::Mirror { .. }))
+        .count();
+    for (entity, batch, mesh) in batches.iter() {
+        if batch.flags & SURF_WARP == 0 {
+            continue;
+        }
+        let Some((normal, point)) = meshes.get(mesh).and_then(mesh_plane) else {
+            continue;
+        };
+        if count == MAX_MIRRORS {
+            warn!(
+                "More than {} mirrors, not reflecting {}",
+                MAX_MIRRORS, batch.texture
+            );
+            continue;
+        }
+        count += 1;
+
+        let (image, camera) = spawn_view_camera(&mut commands, &mut images, &windows);
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert((
+                mirror_materials.add(MirrorMaterial {
+                    texture: image,
+                    tint: LinearRgba::rgb(0.7, 0.8, 0.9),
+                    flip_x: true,
+                }),
+                ViewSurface::Mirror {
+                    normal,
+                    point,
+                    camera,
+                },
+            ));
+    }
+}
+
+/// Spawns portals from `misc_portal_surface` entities of newly loaded maps.
+fn setup_portals(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<BSP38Asset>>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut mirror_materials: ResMut<Assets<MirrorMaterial>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        let Some(asset) = assets.get(*id) else {
+            continue;
+        };
+        let entities = asset.bsp.read_entities().unwrap_or_default();
+        let offset = asset.world_offset();
+        let placement = |entity: &q2_formats::bsp38::prelude::Entity| {
+            let origin = Vec3::from(entity.origin()?) + offset;
+            let yaw = entity.angle().unwrap_or(0.0).to_radians();
+            // Quake entities face along +X when their angle is 0
+            let forward = Vec3::new(yaw.cos(), yaw.sin(), 0.0);
+            Some(Transform::from_translation(origin).looking_to(forward, Vec3::Z))
+        };
+
+        for surface in entities
+            .iter()
+            .filter(|e| e.classname == "misc_portal_surface")
+        {
+            let target = surface.get("target");
+            let exit = entities
+                .iter()
+                .find(|e| e.classname == "misc_portal_camera" && e.targetname() == target);
+            let (Some(entrance), Some(exit)) = (placement(surface), exit.and_then(placement))
+            else {
+                warn!("misc_portal_surface without a misc_portal_camera target");
+                continue;
+            };
+
+            let (image, camera) = spawn_view_camera(&mut commands, &mut images, &windows);
+            commands.spawn((
+                MaterialMeshBundle {
+                    mesh: meshes.add(Rectangle::new(128.0, 128.0)),
+                    material: mirror_materials.add(MirrorMaterial {
+                        texture: image,
+                        tint: LinearRgba::WHITE,
+                        flip_x: false,
+                    }),
+                    // The rectangle faces +Z, the portal faces forward (-Z)
+                    transform: entrance * Transform::from_rotation(Quat::from_rotation_y(PI)),
+                    ..default()
+                },
+                ViewSurface::Portal {
+                    entrance,
+                    exit,
+                    camera,
+                },
+                MapScoped,
+            ));
+        }
+    }
+}
+
+/// Spawns a camera rendering before the main camera into a new texture the
+/// size of the window.
+fn spawn_view_camera(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    windows: &Query<&Window, With<PrimaryWindow>>,
+) -> (Handle<Image>, Entity) {
+    let (width, height) = windows
+        .get_single()
+        .map(|w| (w.physical_width(), w.physical_height()))
+        .unwrap_or((1280, 720));
+    let size = Extent3d {
+        width: width.max(1),
+        height: height.max(1),
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("view_surface"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    let image = images.add(image);
+
+    let camera = commands
+        .spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    order: -1,
+                    target: RenderTarget::Image(image.clone()),
+                    ..default()
+                },
+                ..default()
+            },
+            MapScoped,
+        ))
+        .remove::<Projection>()
+        .insert(ObliqueProjection::default())
+        .id();
+    (image, camera)
+}
+
+type MainCamera = (With<Camera3d>, Without<ObliqueProjection>);
+
+/// Moves every mirror and portal camera to match the main camera.
+fn update_view_cameras(
+    main: Query<(&Transform, &Projection), MainCamera>,
+    surfaces: Query<(&ViewSurface, &GlobalTransform)>,
+    mut cameras: Query<(&mut Transform, &mut ObliqueProjection), Without<ViewSurface>>,
+) {
+    let Some((view, Projection::Perspective(perspective))) = main.iter().next() else {
+        return;
+    };
+    for (surface, surface_transform) in surfaces.iter() {
+        let (camera, transform, plane_normal, plane_point) = match *surface {
+            ViewSurface::Mirror {
+                normal,
+                point,
+                camera,
+            } => {
+                let normal = surface_transform
+                    .affine()
+                    .transform_vector3(normal)
+                    .normalize();
+                let point = surface_transform.transform_point(point);
+                let reflect = |v: Vec3| v - 2.0 * v.dot(normal) * normal;
+                let eye = point + reflect(view.translation - point);
+                let transform = Transform::from_translation(eye)
+                    .looking_to(reflect(*view.forward()), reflect(*view.up()));
+                (camera, transform, normal, point)
+            }
+            ViewSurface::Portal {
+                entrance,
+                exit,
+                camera,
+            } => {
+                // Looking into the entrance is looking out of the exit
+                let exit_back = exit * Transform::from_rotation(Quat::from_rotation_y(PI));
+                let relative = entrance.compute_affine().inverse() * view.compute_affine();
+                let transform =
+                    Transform::from_matrix((exit_back.compute_affine() * relative).into());
+                // Only what is in front of the exit is seen through the portal
+                (camera, transform, *exit.forward(), exit.translation)
+            }
+        };
+        let Ok((mut camera_transform, mut projection)) = cameras.get_mut(camera) else {
+            continue;
+        };
+        *camera_transform = transform;
+
+        // The visible side of the plane, moved into the camera's view space
+        let plane_point = plane_point + plane_normal * CLIP_OFFSET;
+        let world_plane = plane_normal.extend(-plane_normal.dot(plane_point));
+        let world_from_view = transform.compute_matrix();
+        projection.perspective = perspective.clone();
+        projection.clip_plane = world_from_view.transpose() * world_plane;
+    }
+}
+
+/// The plane of a mesh whose normals all point the same way, in mesh space.
+fn mesh_plane(mesh: &Mesh) -> Option<(Vec3, Vec3)> {
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+        return None;
+    };
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let normal = Vec3::from(*normals.first()?);
+    let point = Vec3::from(*positions.first()?);
+    normals
+        .iter()
+        .all(|n| Vec3::from(*n).dot(normal) > 0.999)
+        .then_some((normal, point))
+}
@@ -0,0 +1,19 @@
+// Shows a mirror or portal camera's render target, sampled at the fragment's
+// screen position so the reflected view lines up with the main view.
+
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings::view
+
+@group(2) @binding(0) var view_texture: texture_2d<f32>;
+@group(2) @binding(1) var view_sampler: sampler;
+@group(2) @binding(2) var<uniform> tint: vec4<f32>;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    var uv = (mesh.position.xy - view.viewport.xy) / view.viewport.zw;
+#ifdef FLIP_X
+    // Mirror cameras see the world flipped horizontally
+    uv.x = 1.0 - uv.x;
+#endif
+    return textureSample(view_texture, view_sampler, uv) * tint;
+}
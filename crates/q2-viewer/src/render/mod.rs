@@ -0,0 +1,151 @@
+mod animation;
+mod clip;
+mod culling;
+mod error_panel;
+mod heatmap;
+mod impostors;
+mod instancing;
+mod lights;
+mod mirror;
+mod progressive;
+mod resolution;
+mod sky;
+mod streaming;
+mod sun;
+mod sun_override;
+mod water;
+
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use std::collections::BTreeMap;
+
+use crate::locale::{Locale, Message};
+
+pub use animation::TextureAnimations;
+pub use clip::{ClipExtension, ClipMaterial, ClipPlane};
+pub use culling::{LockedView, PvsCulling};
+pub use error_panel::WatchedAssets;
+pub use heatmap::{HeatmapMode, HeatmapPalette};
+pub use impostors::Impostors;
+pub use instancing::InstancedAssets;
+pub use lights::{MapLightPlugin, MapLightSource};
+pub use mirror::{MirrorMaterial, ObliqueProjection, ViewSurface};
+pub use progressive::ProgressiveUploads;
+pub use resolution::ScaledView;
+pub use sky::SkyBox;
+pub use streaming::{texture_path, TextureStreaming};
+pub use sun::{SunShadowExtension, SunShadowMaterial, SunShadows};
+pub use sun_override::SunOverride;
+pub use water::{CausticMaterial, WarpMaterial, WaterMaterial, WaterSettings};
+
+pub struct RenderPlugin;
+
+impl Plugin for RenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            FrameTimeDiagnosticsPlugin,
+            animation::TextureAnimationPlugin,
+            clip::ClipPlanePlugin,
+            culling::PvsCullingPlugin,
+            heatmap::HeatmapPlugin,
+            impostors::ImpostorPlugin,
+            lights::MapLightPlugin,
+            mirror::MirrorPlugin,
+            resolution::ResolutionScalePlugin,
+            sky::SkyPlugin,
+            streaming::TextureStreamingPlugin,
+            sun::SunShadowPlugin,
+            sun_override::SunOverridePlugin,
+            water::WaterPlugin,
+        ))
+        .init_resource::<InstancedAssets>()
+        .init_resource::<Locale>()
+        .init_resource::<OverlayStats>()
+        .init_resource::<ProgressiveUploads>()
+        .init_resource::<WatchedAssets>()
+        .add_systems(Startup, setup_fps)
+        .add_systems(
+            PostUpdate,
+            (
+                fps_update,
+                progressive::upload_pending,
+                error_panel::report_failed_loads,
+            ),
+        );
+    }
+}
+
+/// Set up fps counter in the bottom right of the screen
+fn setup_fps(mut commands: Commands) {
+    use bevy::color::Color;
+
+    commands.spawn((
+        TextBundle::from_sections([TextSection::from_style(TextStyle {
+            font_size: 20.0,
+            color: Color::srgb(0.7, 0.5, 0.1),
+            ..default()
+        })])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+            ..Default::default()
+        }),
+        FpsText,
+    ));
+}
+
+#[derive(Component)]
+pub struct FpsText;
+
+/// Extra named lines shown under the fps counter, e.g. memory held by a
+/// decoded lump. Lines are sorted by name.
+#[derive(Resource, Default)]
+pub struct OverlayStats {
+    lines: BTreeMap<String, Message>,
+}
+
+impl OverlayStats {
+    /// Shows `value` on the line `name`, labelled with the `stat.<name>` text
+    /// of the [Locale].
+    pub fn set(&mut self, name: &str, value: impl Into<Message>) {
+        self.lines.insert(name.to_string(), value.into());
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.lines.remove(name);
+    }
+}
+
+/// Update the fps each frame
+fn fps_update(
+    frame_count: Res<bevy::core::FrameCount>,
+    diagnostics: Res<DiagnosticsStore>,
+    stats: Res<OverlayStats>,
+    locale: Res<Locale>,
+    mut query: Query<&mut Text, With<FpsText>>,
+) {
+    let fps: f64 = match diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
+        Some(fps) => match fps.smoothed() {
+            Some(value) => value,
+            _ => 0.0,
+        },
+        None => 0.0,
+    };
+
+    for mut text in &mut query {
+        let fps = (fps * 5.0).round() / 5.0;
+        let mut value = format!("{} / {:.0}", frame_count.0, fps);
+        for (name, line) in &stats.lines {
+            // Lines without a label of their own show their name
+            let key = format!("stat.{}", name);
+            let label = match locale.get(&key) {
+                label if label == key => name.as_str(),
+                label => label,
+            };
+            value.push_str(&format!("\n{}: {}", label, locale.text(line)));
+        }
+        text.sections[0].value = value;
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+/// Largest edge, in pixels, of the placeholder shown until an image uploads.
+const PLACEHOLDER_SIZE: u32 = 16;
+
+/// Queue of large images uploaded progressively over several frames.
+///
+/// Each queued image is immediately given a handle pointing at a small
+/// downsampled placeholder, so materials can reference it right away. The full
+/// resolution data is swapped in later, at most `bytes_per_frame` per frame
+/// (but always at least one image per frame), so a set of large textures such
+/// as lightmap atlas pages doesn't cause one long GPU upload stall.
+#[derive(Resource)]
+pub struct ProgressiveUploads {
+    pub bytes_per_frame: usize,
+    pending: VecDeque<(Handle<Image>, Image)>,
+}
+
+impl Default for ProgressiveUploads {
+    fn default() -> Self {
+        Self {
+            bytes_per_frame: 4 * 1024 * 1024,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl ProgressiveUploads {
+    /// Adds a placeholder for `image` to `images` and queues the full image.
+    pub fn add(&mut self, images: &mut Assets<Image>, image: Image) -> Handle<Image> {
+        let handle = images.add(placeholder(&image));
+        self.pending.push_back((handle.clone(), image));
+        handle
+    }
+
+    /// Number of images still showing their placeholder.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+pub(super) fn upload_pending(
+    mut uploads: ResMut<ProgressiveUploads>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut budget = uploads.bytes_per_frame;
+    let mut uploaded = 0;
+    while let Some((_, image)) = uploads.pending.front() {
+        let size = image.data.len();
+        if uploaded > 0 && size > budget {
+            break;
+        }
+        let (handle, image) = uploads.pending.pop_front().unwrap();
+        images.insert(&handle, image);
+        budget = budget.saturating_sub(size);
+        uploaded += 1;
+    }
+}
+
+/// Box-filters an 8-bit RGBA image down to at most PLACEHOLDER_SIZE pixels
+/// per edge. Other formats get a flat mid-grey texel.
+fn placeholder(image: &Image) -> Image {
+    let format = image.texture_descriptor.format;
+    let usage = image.asset_usage;
+    let is_rgba8 = matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    );
+    if !is_rgba8 || image.texture_descriptor.dimension != TextureDimension::D2 {
+        return Image::new_fill(
+            Extent3d::default(),
+            TextureDimension::D2,
+            &[128, 128, 128, 255],
+            TextureFormat::Rgba8UnormSrgb,
+            usage,
+        );
+    }
+
+    let (width, height) = (image.width(), image.height());
+    let step = width.max(height).div_ceil(PLACEHOLDER_SIZE).max(1);
+    let (pw, ph) = (width.div_ceil(step), height.div_ceil(step));
+
+    let mut data = Vec::with_capacity((pw * ph * 4) as usize);
+    for py in 0..ph {
+        for px in 0..pw {
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for y in (py * step)..((py + 1) * step).min(height) {
+                for x in (px * step)..((px + 1) * step).min(width) {
+                    let i = ((y * width + x) * 4) as usize;
+                    for (s, &b) in sum.iter_mut().zip(&image.data[i..i + 4]) {
+                        *s += b as u32;
+                    }
+                    count += 1;
+                }
+            }
+            data.extend(sum.iter().map(|s| (s / count.max(1)) as u8));
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: pw,
+            height: ph,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        format,
+        usage,
+    )
+}
@@ -0,0 +1,182 @@
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        texture::ImageSampler,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{quality::Quality, sim::Interpolated};
+
+/// Renders the world at [Quality::resolution_scale] times the window's size,
+/// into an image a second camera stretches over the window. That camera also
+/// draws the UI, so text stays sharp at any scale. At a scale of 1 the main
+/// camera draws to the window itself.
+pub struct ResolutionScalePlugin;
+
+impl Plugin for ResolutionScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScaledView>()
+            .add_systems(Update, scale_view);
+    }
+}
+
+/// The image the world renders into while scaled. Positions in the main
+/// camera's viewport are then in pixels of the image rather than the window,
+/// see [ScaledView::to_viewport].
+#[derive(Resource, Default)]
+pub struct ScaledView {
+    image: Option<Handle<Image>>,
+    /// Size of the image, in pixels.
+    size: UVec2,
+    /// The camera showing the image, and the sprite of the image.
+    present: Option<(Entity, Entity)>,
+}
+
+impl ScaledView {
+    /// Whether the world renders into an image.
+    pub fn is_scaled(&self) -> bool {
+        self.image.is_some()
+    }
+
+    /// Factor from positions in `window` to those in the main camera's
+    /// viewport.
+    fn factor(&self, window: &Window) -> Vec2 {
+        match self.image {
+            Some(_) => self.size.as_vec2() / window.size().max(Vec2::ONE),
+            None => Vec2::ONE,
+        }
+    }
+
+    /// `position` in `window`, such as the cursor, in the main camera's
+    /// viewport, for [Camera::viewport_to_world].
+    pub fn to_viewport(&self, window: &Window, position: Vec2) -> Vec2 {
+        position * self.factor(window)
+    }
+
+    /// `position` in the main camera's viewport, as from
+    /// [Camera::world_to_viewport], in `window`.
+    pub fn to_window(&self, window: &Window, position: Vec2) -> Vec2 {
+        position / self.factor(window)
+    }
+}
+
+/// Keeps the image sized to the window and the scale, or gives the main
+/// camera the window back at a scale of 1.
+fn scale_view(
+    mut commands: Commands,
+    mut view: ResMut<ScaledView>,
+    quality: Res<Quality>,
+    mut images: ResMut<Assets<Image>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Camera, With<Interpolated>>,
+    mut sprites: Query<&mut Sprite>,
+) {
+    let (Ok(window), Some(mut camera)) = (windows.get_single(), cameras.iter_mut().next()) else {
+        return;
+    };
+    // Leave the camera alone while something else, such as a recording,
+    // renders it into its own image
+    if let RenderTarget::Image(image) = &camera.target {
+        if Some(image) != view.image.as_ref() {
+            return;
+        }
+    }
+
+    let physical = UVec2::new(window.physical_width(), window.physical_height());
+    let unscaled = (quality.resolution_scale - 1.0).abs() < 0.01;
+    if unscaled || physical.min_element() == 0 {
+        if let Some((present, sprite)) = view.present.take() {
+            commands.entity(present).despawn();
+            commands.entity(sprite).despawn();
+        }
+        if view.image.take().is_some() {
+            camera.target = RenderTarget::default();
+        }
+        return;
+    }
+
+    let size = (physical.as_vec2() * quality.resolution_scale)
+        .round()
+        .as_uvec2()
+        .max(UVec2::ONE);
+    let extent = Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    };
+    let image = match view.image.clone() {
+        Some(image) => {
+            if view.size != size {
+                if let Some(image) = images.get_mut(&image) {
+                    image.resize(extent);
+                }
+            }
+            image
+        }
+        None => {
+            let mut image = Image {
+                texture_descriptor: TextureDescriptor {
+                    label: Some("scaled view"),
+                    size: extent,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    usage: TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_DST
+                        | TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                },
+                sampler: ImageSampler::linear(),
+                ..default()
+            };
+            image.resize(extent);
+            images.add(image)
+        }
+    };
+    view.size = size;
+    // Also after a recording gave the camera the window back
+    if !matches!(&camera.target, RenderTarget::Image(target) if *target == image) {
+        camera.target = RenderTarget::Image(image.clone());
+    }
+    view.image = Some(image.clone());
+
+    // The 2D camera's units are the window's logical pixels
+    let cover = Some(window.size());
+    match view.present {
+        Some((_, sprite)) => {
+            if let Ok(mut sprite) = sprites.get_mut(sprite) {
+                if sprite.custom_size != cover {
+                    sprite.custom_size = cover;
+                }
+            }
+        }
+        None => {
+            let present = commands
+                .spawn(Camera2dBundle {
+                    camera: Camera {
+                        order: 1,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .id();
+            let sprite = commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        custom_size: cover,
+                        ..default()
+                    },
+                    texture: image,
+                    ..default()
+                })
+                .id();
+            view.present = Some((present, sprite));
+        }
+    }
+}
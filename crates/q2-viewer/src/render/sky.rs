@@ -0,0 +1,244 @@
+use bevy::{
+    asset::LoadState,
+    core_pipeline::Skybox,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension,
+        },
+    },
+};
+
+use super::streaming::game_dir;
+use crate::{
+    asset::BSP38Asset,
+    maps::{MapInstance, MapManager},
+    sim::Interpolated,
+};
+
+/// Sky of maps whose worldspawn has no `sky` key, as in the game.
+const DEFAULT_SKY: &str = "unit1_";
+
+/// Brightness of the sky images, in cd/m², roughly matching the sun light.
+const SKY_BRIGHTNESS: f32 = 1000.0;
+
+/// The six images of a sky, in the order of the layers of a Bevy cubemap:
+/// the suffix of the image, then the map axes its pixels run along, right
+/// and down, in the image and in the cubemap layer.
+///
+/// Quake 2 maps are Z up; the cubemap is sampled with Z negated, as Bevy
+/// cubemaps are left-handed, so the +Z layer holds the sky's floor. The
+/// image axes follow the game's `st_to_vec` table in `gl_warp.c`.
+const SKY_FACES: [(&str, [IVec3; 2], [IVec3; 2]); 6] = [
+    ("rt", [IVec3::NEG_Y, IVec3::NEG_Z], [IVec3::Z, IVec3::NEG_Y]),
+    ("lf", [IVec3::Y, IVec3::NEG_Z], [IVec3::NEG_Z, IVec3::NEG_Y]),
+    ("bk", [IVec3::X, IVec3::NEG_Z], [IVec3::X, IVec3::NEG_Z]),
+    ("ft", [IVec3::NEG_X, IVec3::NEG_Z], [IVec3::X, IVec3::Z]),
+    ("dn", [IVec3::NEG_Y, IVec3::NEG_X], [IVec3::X, IVec3::NEG_Y]),
+    ("up", [IVec3::NEG_Y, IVec3::X], [IVec3::NEG_X, IVec3::NEG_Y]),
+];
+
+/// Draws the sky box named by the `sky` key of the current map's worldspawn
+/// behind the world, from the six `env/<sky><side>.tga` images next to the
+/// map's `maps/` folder, or the `.pcx` ones if those are missing. Sky faces
+/// themselves aren't drawn, so the sky shows through them.
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SkyBox>()
+            .add_systems(Update, (load_sky, build_sky).chain());
+    }
+}
+
+/// The sky of the current map.
+#[derive(Resource, Default)]
+pub struct SkyBox {
+    /// Name of the sky, the images' prefix.
+    pub name: Option<String>,
+    /// The cubemap, once all six images have loaded.
+    pub image: Option<Handle<Image>>,
+    state: SkyState,
+    root: Option<Entity>,
+}
+
+#[derive(Default)]
+enum SkyState {
+    #[default]
+    Idle,
+    Loading {
+        faces: Vec<Handle<Image>>,
+        extension: &'static str,
+    },
+}
+
+fn face_paths(map: &str, sky: &str, extension: &str) -> Vec<String> {
+    SKY_FACES
+        .iter()
+        .map(|(suffix, _, _)| format!("{}env/{}{}.{}", game_dir(map), sky, suffix, extension))
+        .collect()
+}
+
+/// Starts loading the sky of each map as it spawns.
+fn load_sky(
+    mut commands: Commands,
+    mut sky: ResMut<SkyBox>,
+    cameras: Query<Entity, With<Skybox>>,
+    maps: Res<MapManager>,
+    instances: Query<Ref<MapInstance>>,
+    assets: Res<Assets<BSP38Asset>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some(instance) = maps.root().and_then(|root| instances.get(root).ok()) else {
+        return;
+    };
+    if sky.root == maps.root() || !instance.spawned {
+        return;
+    }
+    let Some(asset) = assets.get(&instance.handle) else {
+        return;
+    };
+    let entities = asset.bsp.read_entities().unwrap_or_default();
+    let name = entities
+        .iter()
+        .find(|entity| entity.classname == "worldspawn")
+        .and_then(|worldspawn| worldspawn.get("sky"))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_SKY)
+        .to_string();
+    // Show no sky until the new one is ready
+    for camera in cameras.iter() {
+        commands.entity(camera).remove::<Skybox>();
+    }
+    let faces = face_paths(&instance.name, &name, "tga")
+        .into_iter()
+        .map(|path| asset_server.load(path))
+        .collect();
+    *sky = SkyBox {
+        name: Some(name),
+        image: None,
+        state: SkyState::Loading {
+            faces,
+            extension: "tga",
+        },
+        root: maps.root(),
+    };
+}
+
+/// Assembles the cubemap once the images have loaded, and puts it on the
+/// camera.
+fn build_sky(
+    mut commands: Commands,
+    mut sky: ResMut<SkyBox>,
+    mut images: ResMut<Assets<Image>>,
+    maps: Res<MapManager>,
+    instances: Query<&MapInstance>,
+    cameras: Query<Entity, With<Interpolated>>,
+    asset_server: Res<AssetServer>,
+) {
+    let SkyState::Loading { faces, extension } = &sky.state else {
+        return;
+    };
+    let (faces, extension) = (faces.clone(), *extension);
+    let failed = faces.iter().any(|face| {
+        matches!(
+            asset_server.get_load_state(face.id()),
+            Some(LoadState::Failed(_))
+        )
+    });
+    if failed {
+        let name = sky.name.clone().unwrap_or_default();
+        let map = maps.root().and_then(|root| instances.get(root).ok());
+        sky.state = match (extension, map) {
+            ("tga", Some(map)) => SkyState::Loading {
+                faces: face_paths(&map.name, &name, "pcx")
+                    .into_iter()
+                    .map(|path| asset_server.load(path))
+                    .collect(),
+                extension: "pcx",
+            },
+            _ => {
+                warn!("Could not load the images of sky {:?}", name);
+                SkyState::Idle
+            }
+        };
+        return;
+    }
+    let loaded: Option<Vec<Image>> = faces
+        .iter()
+        .map(|face| {
+            images
+                .get(face)
+                .and_then(|image| image.convert(TextureFormat::Rgba8UnormSrgb))
+        })
+        .collect();
+    let Some(loaded) = loaded else {
+        return;
+    };
+
+    sky.state = SkyState::Idle;
+    let Some(cubemap) = cubemap(&loaded) else {
+        warn!(
+            "The images of sky {:?} aren't all square and of the same size",
+            sky.name.as_deref().unwrap_or_default()
+        );
+        return;
+    };
+    let image = images.add(cubemap);
+    sky.image = Some(image.clone());
+    for camera in cameras.iter() {
+        commands.entity(camera).insert(Skybox {
+            image: image.clone(),
+            brightness: SKY_BRIGHTNESS,
+        });
+    }
+}
+
+/// The sky images, in the order of [SKY_FACES], turned into the layers of a
+/// cubemap.
+fn cubemap(faces: &[Image]) -> Option<Image> {
+    let size = faces.first()?.width();
+    if faces
+        .iter()
+        .any(|face| face.width() != size || face.height() != size)
+    {
+        return None;
+    }
+    let n = size as i32;
+    // Pixel centers as odd offsets from the middle of the face, so flipping
+    // and turning pixels is exact
+    let offset = |index: i32| 2 * index + 1 - n;
+    let index = |offset: i32| (offset + n - 1) / 2;
+
+    let mut data = Vec::with_capacity(faces.len() * (n * n * 4) as usize);
+    for (face, (_, [image_right, image_down], [layer_right, layer_down])) in
+        faces.iter().zip(SKY_FACES)
+    {
+        for y in 0..n {
+            for x in 0..n {
+                let point = layer_right * offset(x) + layer_down * offset(y);
+                let (u, v) = (index(point.dot(image_right)), index(point.dot(image_down)));
+                let i = ((v * n + u) * 4) as usize;
+                data.extend_from_slice(&face.data[i..i + 4]);
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    Some(image)
+}
@@ -0,0 +1,265 @@
+use bevy::{asset::LoadState, math::Affine2, prelude::*, utils::HashMap};
+
+use super::{
+    sun::{own_material, OwnMaterial},
+    OverlayStats, TextureAnimations,
+};
+use crate::{
+    locale::Message,
+    maps::{BrushModel, MapInstance, MapManager},
+    start::WorldBatch,
+    wal::TextureOverrides,
+};
+
+/// Bytes per texel of the RGBA images textures load as.
+const TEXEL_BYTES: usize = 4;
+/// Color of the batches whose texture is missing.
+const MISSING_COLOR: Color = Color::srgb(0.8, 0.3, 0.85);
+
+/// Keeps the textures world batches spawn with under a memory budget: as each
+/// one loads, it drops its largest mip levels until it fits, or it is down to
+/// its smallest one, and its batches' UVs are scaled to its size. Batches
+/// whose texture fails to load get a flat placeholder color instead. The
+/// frames of animated textures load along with their first one.
+pub struct TextureStreamingPlugin;
+
+impl Plugin for TextureStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextureStreaming>()
+            .add_systems(Update, (track_textures, finish_textures).chain());
+    }
+}
+
+#[derive(Resource)]
+pub struct TextureStreaming {
+    /// Memory all streamed textures may use together, in bytes.
+    pub budget: usize,
+    /// Mip levels every texture drops, budget or not, see
+    /// [Quality::picmip](crate::quality::Quality::picmip).
+    pub min_mip_bias: u32,
+    textures: HashMap<String, StreamedTexture>,
+    /// Bytes used by the resident textures.
+    resident: usize,
+    /// The map the textures belong to.
+    root: Option<Entity>,
+}
+
+impl Default for TextureStreaming {
+    fn default() -> Self {
+        Self {
+            // WebGL gets much less room before uploads start stalling
+            budget: match cfg!(target_arch = "wasm32") {
+                true => 64 << 20,
+                false => 256 << 20,
+            },
+            min_mip_bias: 0,
+            textures: HashMap::new(),
+            resident: 0,
+            root: None,
+        }
+    }
+}
+
+impl TextureStreaming {
+    /// Bytes used by the textures loaded so far.
+    pub fn resident_bytes(&self) -> usize {
+        self.resident
+    }
+
+    /// The image of `texture` and its full size in texels, once loaded.
+    pub fn resident(&self, texture: &str) -> Option<(&Handle<Image>, Vec2)> {
+        match self.textures.get(texture)? {
+            StreamedTexture::Resident { image, size, .. } => Some((image, *size)),
+            _ => None,
+        }
+    }
+}
+
+enum StreamedTexture {
+    Loading(Handle<Image>),
+    /// Loaded, and fit to the budget.
+    Resident {
+        image: Handle<Image>,
+        /// Size of the full texture, before dropping mip levels.
+        size: Vec2,
+        /// Number of mip levels dropped to fit the budget.
+        mip_bias: u32,
+    },
+    Failed,
+}
+
+/// The folder holding the `maps/` folder `map` is in, with a trailing `/`,
+/// or the asset root.
+pub(super) fn game_dir(map: &str) -> &str {
+    match map.rfind("maps/") {
+        Some(i) => &map[..i],
+        None => "",
+    }
+}
+
+/// The asset path of `texture` for `map`: textures sit in `textures/` next
+/// to the `maps/` folder holding the map, or in the asset root.
+pub fn texture_path(map: &str, texture: &str) -> String {
+    format!("{}textures/{}.wal", game_dir(map), texture)
+}
+
+/// Tracks the textures of the current map's batches, and starts loading
+/// the other frames of the animated ones.
+fn track_textures(
+    mut streaming: ResMut<TextureStreaming>,
+    maps: Res<MapManager>,
+    instances: Query<&MapInstance>,
+    batches: Query<(&WorldBatch, &Parent)>,
+    models: Query<&Parent, With<BrushModel>>,
+    animations: Res<TextureAnimations>,
+    asset_server: Res<AssetServer>,
+) {
+    if streaming.root != maps.root() {
+        // The previous map's textures unload with its materials
+        streaming.textures.clear();
+        streaming.resident = 0;
+        streaming.root = maps.root();
+    }
+    let Some(instance) = maps.root().and_then(|root| instances.get(root).ok()) else {
+        return;
+    };
+
+    for (batch, parent) in batches.iter() {
+        // Batches of inline models sit under their model
+        let map = models.get(parent.get()).unwrap_or(parent);
+        if Some(map.get()) != maps.root() {
+            continue;
+        }
+        // The batches' own textures are already loading, this gets their
+        // handles
+        for texture in [&batch.texture]
+            .into_iter()
+            .chain(animations.frames(&batch.texture))
+        {
+            if !streaming.textures.contains_key(texture) {
+                let image = asset_server.load(texture_path(&instance.name, texture));
+                streaming
+                    .textures
+                    .insert(texture.clone(), StreamedTexture::Loading(image));
+            }
+        }
+    }
+}
+
+/// Fits loaded textures into the budget and scales the UVs of their batches
+/// to them, or gives the batches of those that failed to load the
+/// [MISSING_COLOR].
+fn finish_textures(
+    mut streaming: ResMut<TextureStreaming>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut stats: ResMut<OverlayStats>,
+    batches: Query<(&WorldBatch, OwnMaterial)>,
+    asset_server: Res<AssetServer>,
+    overrides: Option<Res<TextureOverrides>>,
+) {
+    let streaming = &mut *streaming;
+    let mut finished = Vec::new();
+    let mut failed = Vec::new();
+    for (name, texture) in streaming.textures.iter_mut() {
+        let StreamedTexture::Loading(handle) = texture else {
+            continue;
+        };
+        match asset_server.get_load_state(handle.id()) {
+            Some(LoadState::Loaded) => {}
+            Some(LoadState::Failed(_)) => {
+                failed.push(name.clone());
+                *texture = StreamedTexture::Failed;
+                continue;
+            }
+            _ => continue,
+        }
+        let Some(image) = images.get_mut(&*handle) else {
+            continue;
+        };
+        let mip_bias = fit_budget(
+            image,
+            streaming.budget.saturating_sub(streaming.resident),
+            streaming.min_mip_bias,
+        );
+        streaming.resident += image.data.len();
+        // A .tga replacing the .wal keeps the UVs of the .wal
+        let wal_size = overrides.as_ref().and_then(|overrides| {
+            let path = handle.path()?;
+            overrides.wal_size(path.path())
+        });
+        let scale = wal_size.map_or_else(
+            || {
+                Vec2::new(
+                    (image.width() << mip_bias) as f32,
+                    (image.height() << mip_bias) as f32,
+                )
+            },
+            |size| size.as_vec2(),
+        );
+        finished.push((name.clone(), scale));
+        *texture = StreamedTexture::Resident {
+            image: handle.clone(),
+            size: scale,
+            mip_bias,
+        };
+    }
+
+    for (name, size) in finished {
+        // Face UVs are in texels of the full size texture
+        for (_, material) in batches.iter().filter(|(b, _)| b.texture == name) {
+            if let Some(material) = own_material(material).and_then(|m| materials.get_mut(m)) {
+                material.uv_transform = Affine2::from_scale(size.recip());
+            }
+        }
+    }
+    for name in failed {
+        for (_, material) in batches.iter().filter(|(b, _)| b.texture == name) {
+            if let Some(material) = own_material(material).and_then(|m| materials.get_mut(m)) {
+                // Translucent batches keep their alpha
+                material.base_color = MISSING_COLOR.with_alpha(material.base_color.alpha());
+                material.base_color_texture = None;
+            }
+        }
+    }
+
+    let (mut resident, mut biased) = (0, 0);
+    for texture in streaming.textures.values() {
+        if let StreamedTexture::Resident { mip_bias, .. } = texture {
+            resident += 1;
+            biased += (*mip_bias > 0) as usize;
+        }
+    }
+    if !streaming.textures.is_empty() {
+        stats.set(
+            "streaming",
+            Message::new("streaming.summary")
+                .arg(resident)
+                .arg(biased)
+                .arg(format!(
+                    "{:.1}",
+                    streaming.resident as f32 / (1 << 20) as f32
+                ))
+                .arg(format!("{:.0}", streaming.budget as f32 / (1 << 20) as f32)),
+        );
+    }
+}
+
+/// Drops the largest mip levels of `image`, at least `min_bias` of them, until
+/// it fits in `budget` bytes or only one level is left, returning how many
+/// were dropped.
+fn fit_budget(image: &mut Image, budget: usize, min_bias: u32) -> u32 {
+    let mut bias = 0;
+    while (bias < min_bias || image.data.len() > budget)
+        && image.texture_descriptor.mip_level_count > 1
+    {
+        let size = &mut image.texture_descriptor.size;
+        let top = size.width as usize * size.height as usize * TEXEL_BYTES;
+        image.data.drain(..top);
+        size.width = (size.width / 2).max(1);
+        size.height = (size.height / 2).max(1);
+        image.texture_descriptor.mip_level_count -= 1;
+        bias += 1;
+    }
+    bias
+}
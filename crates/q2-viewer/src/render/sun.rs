@@ -0,0 +1,282 @@
+use bevy::{
+    asset::embedded_asset,
+    pbr::{CascadeShadowConfigBuilder, ExtendedMaterial, Lightmap, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+    utils::HashMap,
+};
+
+use crate::{
+    asset::BSP38Asset,
+    maps::{MapInstance, MapManager},
+    start::WorldBatch,
+};
+
+/// Direction of the sun for maps that don't say where theirs is.
+const DEFAULT_SUN: Vec3 = Vec3::new(-1.0, -1.0, -1.0);
+
+/// Distance, in map units, up to which dynamic objects cast shadows.
+const SHADOW_DISTANCE: f32 = 4000.0;
+
+/// A standard material whose baked lightmap light is darkened in the sun's
+/// shadow.
+pub type SunShadowMaterial = ExtendedMaterial<StandardMaterial, SunShadowExtension>;
+
+/// Aims the directional light along the sun the map was lit with, see
+/// [extract_sun](q2_formats::bsp38::prelude::extract_sun), and optionally
+/// has it cast real-time shadows: lightmapped world batches then draw with
+/// a [SunShadowMaterial], so dynamic objects such as models and the player
+/// shadow the baked sunlight consistently with the world's own shadows.
+///
+/// Off by default, as a shadow map of the whole map costs a pass per
+/// cascade. World batches drawing with sun shadows aren't cut by the clip
+/// plane.
+pub struct SunShadowPlugin;
+
+impl Plugin for SunShadowPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "sun.wgsl");
+        app.add_plugins(MaterialPlugin::<SunShadowMaterial>::default())
+            .init_resource::<SunShadows>()
+            .add_systems(
+                Update,
+                (
+                    aim_sun,
+                    apply_sun_shadows,
+                    swap_sun_materials,
+                    sync_sun_materials,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SunShadows {
+    pub enabled: bool,
+    /// Fraction of the baked light the sun's shadow takes away.
+    pub strength: f32,
+    /// Direction the sunlight of the current map travels along.
+    pub direction: Vec3,
+    /// Color of the current map's sun.
+    pub color: Color,
+    root: Option<Entity>,
+}
+
+impl Default for SunShadows {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.6,
+            direction: DEFAULT_SUN.normalize(),
+            color: Color::WHITE,
+            root: None,
+        }
+    }
+}
+
+impl SunShadows {
+    /// Runs a console command: `r_sunshadows <0|1>` or
+    /// `r_sunshadow_strength <fraction>`.
+    ///
+    /// ```
+    /// # use q2_viewer::render::SunShadows;
+    /// let mut sun = SunShadows::default();
+    /// sun.run_command("r_sunshadows 1").unwrap();
+    /// assert!(sun.enabled);
+    /// assert!(sun.run_command("r_sunshadow_strength 2").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["r_sunshadows", "0"] => self.enabled = false,
+            ["r_sunshadows", "1"] => self.enabled = true,
+            ["r_sunshadows", ..] => return Err("usage: r_sunshadows <0|1>".to_string()),
+            ["r_sunshadow_strength", value] => {
+                self.strength = value
+                    .parse()
+                    .ok()
+                    .filter(|strength| (0.0..=1.0).contains(strength))
+                    .ok_or_else(|| "usage: r_sunshadow_strength <0 to 1>".to_string())?;
+            }
+            ["r_sunshadow_strength", ..] => {
+                return Err("usage: r_sunshadow_strength <0 to 1>".to_string())
+            }
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct SunShadowExtension {
+    /// See [SunShadows::strength].
+    #[uniform(100)]
+    pub strength: f32,
+}
+
+impl MaterialExtension for SunShadowExtension {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/sun.wgsl".into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/sun.wgsl".into()
+    }
+}
+
+/// The material of a world batch while a [SunShadowMaterial] has replaced
+/// it.
+#[derive(Component)]
+pub(super) struct Unshadowed(Handle<StandardMaterial>);
+
+/// The standard material of a world batch, even while it draws with a
+/// [SunShadowMaterial], for systems changing textures, see
+/// [own_material].
+pub(super) type OwnMaterial = AnyOf<(&'static Handle<StandardMaterial>, &'static Unshadowed)>;
+
+pub(super) fn own_material<'a>(
+    (material, unshadowed): (Option<&'a Handle<StandardMaterial>>, Option<&'a Unshadowed>),
+) -> Option<&'a Handle<StandardMaterial>> {
+    material.or(unshadowed.map(|Unshadowed(material)| material))
+}
+
+/// Points the directional light along the sun of each map as it becomes the
+/// current one.
+fn aim_sun(
+    mut sun: ResMut<SunShadows>,
+    maps: Res<MapManager>,
+    instances: Query<&MapInstance>,
+    assets: Res<Assets<BSP38Asset>>,
+    mut lights: Query<(&mut DirectionalLight, &mut Transform)>,
+) {
+    if sun.root == maps.root() {
+        return;
+    }
+    let Some(asset) = maps
+        .root()
+        .and_then(|root| instances.get(root).ok())
+        .and_then(|instance| assets.get(&instance.handle))
+    else {
+        return;
+    };
+    sun.root = maps.root();
+    let map_sun = asset.bsp.read_sun();
+    sun.direction = map_sun
+        .map(|map_sun| Vec3::from(map_sun.direction))
+        .unwrap_or(DEFAULT_SUN.normalize());
+    sun.color = map_sun.map_or(Color::WHITE, |map_sun| {
+        let [r, g, b] = map_sun.color;
+        Color::srgb(r, g, b)
+    });
+    for (mut light, mut transform) in lights.iter_mut() {
+        light.color = sun.color;
+        transform.rotation = Quat::from_rotation_arc(Vec3::NEG_Z, sun.direction);
+    }
+}
+
+/// Turns the sun's shadow map on or off.
+fn apply_sun_shadows(
+    mut commands: Commands,
+    sun: Res<SunShadows>,
+    mut lights: Query<(Entity, &mut DirectionalLight)>,
+) {
+    if !sun.is_changed() {
+        return;
+    }
+    for (entity, mut light) in lights.iter_mut() {
+        if light.shadows_enabled == sun.enabled {
+            continue;
+        }
+        light.shadows_enabled = sun.enabled;
+        if sun.enabled {
+            commands.entity(entity).insert(
+                CascadeShadowConfigBuilder {
+                    first_cascade_far_bound: SHADOW_DISTANCE / 8.0,
+                    maximum_distance: SHADOW_DISTANCE,
+                    ..default()
+                }
+                .build(),
+            );
+        }
+    }
+}
+
+/// Gives lightmapped world batches a [SunShadowMaterial] copy of their
+/// material while sun shadows are on, and their own material back once
+/// they are off.
+#[allow(clippy::type_complexity)]
+fn swap_sun_materials(
+    mut commands: Commands,
+    mut sun_materials: ResMut<Assets<SunShadowMaterial>>,
+    mut copies: Local<HashMap<AssetId<StandardMaterial>, Handle<SunShadowMaterial>>>,
+    sun: Res<SunShadows>,
+    materials: Res<Assets<StandardMaterial>>,
+    unshadowed: Query<(Entity, &Handle<StandardMaterial>), (With<WorldBatch>, With<Lightmap>)>,
+    shadowed: Query<(Entity, &Unshadowed), With<WorldBatch>>,
+) {
+    if !sun.enabled {
+        for (entity, Unshadowed(material)) in shadowed.iter() {
+            commands
+                .entity(entity)
+                .remove::<(Handle<SunShadowMaterial>, Unshadowed)>()
+                .insert(material.clone());
+        }
+        copies.clear();
+        return;
+    }
+    if sun.is_changed() {
+        for (_, material) in sun_materials.iter_mut() {
+            material.extension.strength = sun.strength;
+        }
+    }
+    for (entity, material) in unshadowed.iter() {
+        let Some(base) = materials.get(material) else {
+            continue;
+        };
+        let copy = copies
+            .entry(material.id())
+            .or_insert_with(|| {
+                sun_materials.add(SunShadowMaterial {
+                    base: base.clone(),
+                    extension: SunShadowExtension {
+                        strength: sun.strength,
+                    },
+                })
+            })
+            .clone();
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert((copy, Unshadowed(material.clone())));
+    }
+}
+
+/// Keeps the copies up to date with their material, as textures stream in
+/// and animate through [OwnMaterial].
+fn sync_sun_materials(
+    mut events: EventReader<AssetEvent<StandardMaterial>>,
+    mut sun_materials: ResMut<Assets<SunShadowMaterial>>,
+    materials: Res<Assets<StandardMaterial>>,
+    shadowed: Query<(&Unshadowed, &Handle<SunShadowMaterial>)>,
+) {
+    let modified: Vec<AssetId<StandardMaterial>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    if modified.is_empty() {
+        return;
+    }
+    for (Unshadowed(material), copy) in shadowed.iter() {
+        if !modified.contains(&material.id()) {
+            continue;
+        }
+        let (Some(base), Some(copy)) = (materials.get(material), sun_materials.get_mut(copy))
+        else {
+            continue;
+        };
+        copy.base = base.clone();
+    }
+}
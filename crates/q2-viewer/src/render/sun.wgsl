@@ -0,0 +1,66 @@
+// The standard PBR material with the baked lightmap light darkened where the
+// sun's shadow map is in shadow, so dynamic objects shadow the world's baked
+// sunlight.
+
+#import bevy_pbr::{
+    mesh_view_bindings as view_bindings,
+    mesh_view_types,
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+    shadows,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+// Fraction of the baked light taken away in the sun's shadow
+@group(2) @binding(100) var<uniform> strength: f32;
+
+// 1 where the sun, the first directional light, reaches the fragment, down
+// to 1 - strength in its shadow
+fn sun_visibility(world_position: vec4<f32>, world_normal: vec3<f32>) -> f32 {
+    if view_bindings::lights.n_directional_lights == 0u {
+        return 1.0;
+    }
+    let flags = view_bindings::lights.directional_lights[0].flags;
+    if (flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) == 0u {
+        return 1.0;
+    }
+    let view_z = dot(vec4<f32>(
+        view_bindings::view.view_from_world[0].z,
+        view_bindings::view.view_from_world[1].z,
+        view_bindings::view.view_from_world[2].z,
+        view_bindings::view.view_from_world[3].z
+    ), world_position);
+    let shadow = shadows::fetch_directional_shadow(0u, world_position, world_normal, view_z);
+    return mix(1.0 - strength, 1.0, shadow);
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+#ifdef LIGHTMAP
+    pbr_input.lightmap_light *= sun_visibility(in.world_position, pbr_input.world_normal);
+#endif
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+    return out;
+}
@@ -0,0 +1,353 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use crate::{
+    locale::{Locale, Message},
+    quality::Quality,
+};
+
+use super::SunShadows;
+
+/// Illuminance of the directional light, as set up at startup.
+const SUN_ILLUMINANCE: f32 = 100_000.0;
+
+/// Brightness of the ambient light at the top of its slider.
+const MAX_AMBIENT: f32 = 2000.0;
+
+/// Lights maps without their baked lighting, with a sun and a sky ambient
+/// that can be adjusted, to judge how readable the geometry is on its own.
+/// Toggled with the O key; while on, a panel of sliders sets the sun's
+/// azimuth and elevation and the ambient brightness.
+///
+/// The console takes `r_sunoverride <0|1>`, `r_sun_angle <azimuth>
+/// <elevation>` in degrees, `r_sun_color <r> <g> <b>` from 0 to 1 and
+/// `r_sun_ambient <0 to 1>`.
+pub struct SunOverridePlugin;
+
+impl Plugin for SunOverridePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SunOverride>().add_systems(
+            Update,
+            (
+                toggle_override,
+                drag_sliders,
+                apply_override,
+                show_panel,
+                update_sliders,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SunOverride {
+    pub enabled: bool,
+    /// Compass direction the sun shines from, in degrees counter-clockwise
+    /// from the map's +X.
+    pub azimuth: f32,
+    /// Height of the sun above the horizon, in degrees.
+    pub elevation: f32,
+    pub color: [f32; 3],
+    /// Brightness of the sky's ambient light, from 0 to 1.
+    pub ambient: f32,
+    /// The lightmap setting and ambient light to restore once off.
+    saved: Option<(bool, AmbientLight)>,
+}
+
+impl Default for SunOverride {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            azimuth: 45.0,
+            elevation: 50.0,
+            color: [1.0, 0.96, 0.88],
+            ambient: 0.15,
+            saved: None,
+        }
+    }
+}
+
+impl SunOverride {
+    /// Direction the sunlight travels along.
+    ///
+    /// ```
+    /// # use q2_viewer::render::SunOverride;
+    /// let sun = SunOverride {
+    ///     azimuth: 0.0,
+    ///     elevation: 90.0,
+    ///     ..Default::default()
+    /// };
+    /// assert!(sun.direction().z < -0.999);
+    /// ```
+    pub fn direction(&self) -> Vec3 {
+        let (azimuth, elevation) = (self.azimuth.to_radians(), self.elevation.to_radians());
+        -Vec3::new(
+            elevation.cos() * azimuth.cos(),
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+        )
+    }
+
+    /// Runs a console command, see [SunOverridePlugin].
+    ///
+    /// ```
+    /// # use q2_viewer::render::SunOverride;
+    /// let mut sun = SunOverride::default();
+    /// sun.run_command("r_sun_angle 90 30").unwrap();
+    /// assert_eq!((sun.azimuth, sun.elevation), (90.0, 30.0));
+    /// assert!(sun.run_command("r_sun_angle 90 120").is_err());
+    /// assert!(sun.run_command("r_sun_color 1 2 1").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        let numbers = |values: &[&str], range: std::ops::RangeInclusive<f32>| {
+            values
+                .iter()
+                .map(|value| value.parse::<f32>().ok().filter(|v| range.contains(v)))
+                .collect::<Option<Vec<f32>>>()
+        };
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["r_sunoverride", "0"] => self.enabled = false,
+            ["r_sunoverride", "1"] => self.enabled = true,
+            ["r_sunoverride", ..] => return Err("usage: r_sunoverride <0|1>".to_string()),
+            ["r_sun_angle", azimuth, elevation] => {
+                let usage = "usage: r_sun_angle <azimuth> <elevation from -90 to 90>";
+                let azimuth = numbers(&[azimuth], f32::MIN..=f32::MAX).ok_or(usage)?[0];
+                let elevation = numbers(&[elevation], -90.0..=90.0).ok_or(usage)?[0];
+                self.azimuth = azimuth.rem_euclid(360.0);
+                self.elevation = elevation;
+            }
+            ["r_sun_angle", ..] => {
+                return Err("usage: r_sun_angle <azimuth> <elevation from -90 to 90>".to_string())
+            }
+            ["r_sun_color", r, g, b] => {
+                let color = numbers(&[r, g, b], 0.0..=1.0)
+                    .ok_or_else(|| "usage: r_sun_color <r> <g> <b>, from 0 to 1".to_string())?;
+                self.color = [color[0], color[1], color[2]];
+            }
+            ["r_sun_color", ..] => {
+                return Err("usage: r_sun_color <r> <g> <b>, from 0 to 1".to_string())
+            }
+            ["r_sun_ambient", value] => {
+                self.ambient = numbers(&[value], 0.0..=1.0)
+                    .ok_or_else(|| "usage: r_sun_ambient <0 to 1>".to_string())?[0];
+            }
+            ["r_sun_ambient", ..] => return Err("usage: r_sun_ambient <0 to 1>".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// A setting of the [SunOverride] on the panel.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum Slider {
+    Azimuth,
+    Elevation,
+    Ambient,
+}
+
+impl Slider {
+    /// The setting, from 0 to 1 along the slider.
+    fn get(self, sun: &SunOverride) -> f32 {
+        match self {
+            Slider::Azimuth => sun.azimuth / 360.0,
+            Slider::Elevation => (sun.elevation + 90.0) / 180.0,
+            Slider::Ambient => sun.ambient,
+        }
+    }
+
+    fn set(self, sun: &mut SunOverride, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            Slider::Azimuth => sun.azimuth = value * 360.0,
+            Slider::Elevation => sun.elevation = value * 180.0 - 90.0,
+            Slider::Ambient => sun.ambient = value,
+        }
+    }
+
+    fn label(self, sun: &SunOverride) -> Message {
+        match self {
+            Slider::Azimuth => Message::new("sun.azimuth").arg(format!("{:.0}", sun.azimuth)),
+            Slider::Elevation => Message::new("sun.elevation").arg(format!("{:.0}", sun.elevation)),
+            Slider::Ambient => {
+                Message::new("sun.ambient").arg(format!("{:.0}", sun.ambient * 100.0))
+            }
+        }
+    }
+}
+
+/// The panel of sliders, shown while the override is on.
+#[derive(Component)]
+struct SunPanel;
+
+/// The filled part of a [Slider].
+#[derive(Component)]
+struct SliderFill(Slider);
+
+/// The label over a [Slider].
+#[derive(Component)]
+struct SliderLabel(Slider);
+
+fn toggle_override(keys: Res<ButtonInput<KeyCode>>, mut sun: ResMut<SunOverride>) {
+    if keys.just_pressed(KeyCode::KeyO) {
+        sun.enabled = !sun.enabled;
+    }
+}
+
+fn drag_sliders(
+    mut sun: ResMut<SunOverride>,
+    sliders: Query<(&Slider, &Interaction, &RelativeCursorPosition)>,
+) {
+    for (&slider, interaction, cursor) in sliders.iter() {
+        let Some(position) = cursor
+            .normalized
+            .filter(|_| *interaction == Interaction::Pressed)
+        else {
+            continue;
+        };
+        if (slider.get(&sun) - position.x).abs() > f32::EPSILON {
+            slider.set(&mut sun, position.x);
+        }
+    }
+}
+
+/// Turns the baked lighting off and lights the map with the sun and sky
+/// while the override is on, and puts back the lightmaps, the ambient light
+/// and the map's own sun once it is off.
+fn apply_override(
+    mut sun: ResMut<SunOverride>,
+    mut quality: Option<ResMut<Quality>>,
+    mut ambient: ResMut<AmbientLight>,
+    map_sun: Res<SunShadows>,
+    mut lights: Query<(&mut DirectionalLight, &mut Transform)>,
+) {
+    // Also after a new map has aimed the sun at its own
+    if !sun.is_changed() && !map_sun.is_changed() {
+        return;
+    }
+    if !sun.enabled {
+        let Some((lightmaps, saved_ambient)) = sun.saved.take() else {
+            return;
+        };
+        if let Some(quality) = quality.as_mut() {
+            quality.lightmaps = lightmaps;
+        }
+        *ambient = saved_ambient;
+        for (mut light, mut transform) in lights.iter_mut() {
+            light.color = map_sun.color;
+            light.illuminance = SUN_ILLUMINANCE;
+            transform.rotation = Quat::from_rotation_arc(Vec3::NEG_Z, map_sun.direction);
+        }
+        return;
+    }
+
+    if sun.saved.is_none() {
+        let lightmaps = quality.as_ref().map_or(true, |quality| quality.lightmaps);
+        sun.saved = Some((lightmaps, ambient.clone()));
+        if let Some(quality) = quality.as_mut() {
+            quality.lightmaps = false;
+        }
+    }
+    let [r, g, b] = sun.color;
+    ambient.color = Color::srgb(0.6, 0.7, 1.0);
+    ambient.brightness = sun.ambient * MAX_AMBIENT;
+    let direction = sun.direction();
+    for (mut light, mut transform) in lights.iter_mut() {
+        light.color = Color::srgb(r, g, b);
+        // Dimmer as the sun sets, and dark below the horizon
+        light.illuminance = SUN_ILLUMINANCE * sun.elevation.to_radians().sin().max(0.0);
+        transform.rotation = Quat::from_rotation_arc(Vec3::NEG_Z, direction);
+    }
+}
+
+/// Spawns the panel of sliders when the override goes on, and despawns it
+/// when it goes off.
+fn show_panel(
+    mut commands: Commands,
+    sun: Res<SunOverride>,
+    panels: Query<Entity, With<SunPanel>>,
+) {
+    if !sun.is_changed() || sun.enabled != panels.is_empty() {
+        return;
+    }
+    if !sun.enabled {
+        for panel in panels.iter() {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+    let panel = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            width: Val::Px(220.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(4.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            ..default()
+        },
+        background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..default()
+    };
+    commands.spawn((panel, SunPanel)).with_children(|panel| {
+        for slider in [Slider::Azimuth, Slider::Elevation, Slider::Ambient] {
+            panel.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                SliderLabel(slider),
+            ));
+            let track = NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(12.0),
+                    ..default()
+                },
+                background_color: Color::srgb(0.25, 0.25, 0.25).into(),
+                ..default()
+            };
+            panel
+                .spawn((
+                    track,
+                    slider,
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                ))
+                .with_children(|track| {
+                    let fill = NodeBundle {
+                        style: Style {
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        background_color: Color::srgb(1.0, 0.8, 0.3).into(),
+                        ..default()
+                    };
+                    track.spawn((fill, SliderFill(slider)));
+                });
+        }
+    });
+}
+
+fn update_sliders(
+    sun: Res<SunOverride>,
+    locale: Res<Locale>,
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    mut labels: Query<(&SliderLabel, &mut Text)>,
+    added: Query<(), Added<SunPanel>>,
+) {
+    if !sun.is_changed() && added.is_empty() {
+        return;
+    }
+    for (SliderFill(slider), mut style) in fills.iter_mut() {
+        style.width = Val::Percent(slider.get(&sun) * 100.0);
+    }
+    for (SliderLabel(slider), mut text) in labels.iter_mut() {
+        text.sections[0].value = locale.text(&slider.label(&sun));
+    }
+}
@@ -0,0 +1,26 @@
+// The turbulent warp the game draws SURF_WARP surfaces with: each texture
+// coordinate wobbles with a sine of the other over time, and flowing
+// surfaces scroll. The game warps the vertices of surfaces cut into 64 unit
+// pieces; this warps every fragment.
+
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings::globals
+
+@group(2) @binding(0) var base_texture: texture_2d<f32>;
+@group(2) @binding(1) var base_sampler: sampler;
+@group(2) @binding(2) var<uniform> color: vec4<f32>;
+// Size of the texture, in texels
+@group(2) @binding(3) var<uniform> size: vec2<f32>;
+// Texels per second the texture scrolls towards -s
+@group(2) @binding(4) var<uniform> scroll: f32;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let t = globals.time;
+    // Face UVs are in texels, as the s and t of EmitWaterPolys in gl_warp.c,
+    // whose sine table spans 8 texels
+    var st = mesh.uv + 8.0 * sin(mesh.uv.yx / 8.0 + t);
+    // Wrapped to one width, as the texture repeats
+    st.x -= fract(t * scroll / size.x) * size.x;
+    return textureSample(base_texture, base_sampler, st / size) * color;
+}
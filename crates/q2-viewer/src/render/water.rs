@@ -0,0 +1,440 @@
+use std::collections::BTreeSet;
+
+use bevy::{
+    asset::embedded_asset,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use q2_formats::bsp38::{
+    prelude::{Bounds, MeshBuilder, MeshOptions},
+    surface::{SURF_FLOWING, SURF_WARP},
+    BSP38,
+};
+
+use super::{mirror::setup_mirrors, TextureStreaming};
+use crate::{
+    asset::BSP38Asset,
+    maps::{MapInstance, MapManager},
+    start::WorldBatch,
+};
+
+/// Spacing, in map units, of the downward traces from a liquid surface.
+const TRACE_STEP: f32 = 16.0;
+
+/// Lifts the caustics off the floor they are projected on, so they don't
+/// z-fight with it.
+const CAUSTIC_OFFSET: f32 = 0.25;
+
+/// Texels per second flowing liquids scroll, 64 every two seconds as in the
+/// game.
+const WARP_SCROLL: f32 = 32.0;
+
+/// Which look liquid surfaces get. Read when a map is spawned; L toggles
+/// between the classic and the rippled look and reloads the current map.
+#[derive(Resource, Debug, Clone)]
+pub struct WaterSettings {
+    /// Shade SURF_WARP surfaces with animated ripple normals instead of
+    /// turning them into mirrors.
+    pub ripples: bool,
+    /// Project animated caustics onto the floors under liquid surfaces.
+    pub caustics: bool,
+    /// How far below a liquid surface floors still receive caustics.
+    pub caustic_depth: f32,
+    /// Draw the SURF_WARP surfaces that aren't mirrors with the game's
+    /// turbulent warp of their texture, see [WarpMaterial].
+    pub warp: bool,
+}
+
+impl Default for WaterSettings {
+    fn default() -> Self {
+        Self {
+            ripples: false,
+            caustics: false,
+            caustic_depth: 256.0,
+            warp: true,
+        }
+    }
+}
+
+/// Liquids: the classic warped surfaces, or modernized ripple-shaded ones and
+/// the caustics they cast.
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "water.wgsl");
+        embedded_asset!(app, "caustics.wgsl");
+        embedded_asset!(app, "warp.wgsl");
+        app.add_plugins((
+            MaterialPlugin::<WaterMaterial>::default(),
+            MaterialPlugin::<CausticMaterial>::default(),
+            MaterialPlugin::<WarpMaterial>::default(),
+        ))
+        .init_resource::<WaterSettings>()
+        .add_systems(
+            Update,
+            (
+                setup_water,
+                setup_caustics,
+                toggle_water_key,
+                // Mirrors take their batches first
+                (setup_warp.after(setup_mirrors), warp_textures).chain(),
+            ),
+        );
+    }
+}
+
+/// A liquid surface lit through a normal perturbed by travelling ripples.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct WaterMaterial {
+    /// Color of the liquid; alpha is its opacity looking straight down.
+    #[uniform(0)]
+    pub color: LinearRgba,
+}
+
+impl Material for WaterMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/water.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Blend
+    }
+}
+
+/// A liquid surface's texture, warped as in the game, unlit.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct WarpMaterial {
+    /// The texture, once streamed in; until then the surface is flat
+    /// [WarpMaterial::color].
+    #[texture(0)]
+    #[sampler(1)]
+    pub texture: Option<Handle<Image>>,
+    /// Multiplies the texture; alpha is the opacity of the surface.
+    #[uniform(2)]
+    pub color: LinearRgba,
+    /// Size of the full texture, in texels.
+    #[uniform(3)]
+    pub size: Vec2,
+    /// Texels per second the texture scrolls, for SURF_FLOWING surfaces.
+    #[uniform(4)]
+    pub scroll: f32,
+    pub alpha_mode: AlphaMode,
+}
+
+impl Material for WarpMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/warp.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+}
+
+/// Animated caustic light added on top of the floors under a liquid.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct CausticMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+}
+
+impl Material for CausticMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "embedded://q2_viewer/render/caustics.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Add
+    }
+}
+
+/// Swaps the material of new SURF_WARP world batches for a [WaterMaterial].
+fn setup_water(
+    mut commands: Commands,
+    mut water_materials: ResMut<Assets<WaterMaterial>>,
+    mut material: Local<Option<Handle<WaterMaterial>>>,
+    settings: Res<WaterSettings>,
+    batches: Query<(Entity, &WorldBatch), Added<WorldBatch>>,
+) {
+    if !settings.ripples {
+        return;
+    }
+    for (entity, batch) in batches.iter() {
+        if batch.flags & SURF_WARP == 0 {
+            continue;
+        }
+        let material = material
+            .get_or_insert_with(|| {
+                water_materials.add(WaterMaterial {
+                    color: LinearRgba::new(0.1, 0.25, 0.35, 0.75),
+                })
+            })
+            .clone();
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert(material);
+    }
+}
+
+/// Swaps the material of new SURF_WARP world batches that kept theirs for a
+/// [WarpMaterial] of the same color and opacity.
+fn setup_warp(
+    mut commands: Commands,
+    materials: Res<Assets<StandardMaterial>>,
+    mut warp_materials: ResMut<Assets<WarpMaterial>>,
+    settings: Res<WaterSettings>,
+    batches: Query<(Entity, &WorldBatch, &Handle<StandardMaterial>), Added<WorldBatch>>,
+) {
+    if !settings.warp || settings.ripples {
+        return;
+    }
+    for (entity, batch, material) in batches.iter() {
+        if batch.flags & SURF_WARP == 0 {
+            continue;
+        }
+        let Some(base) = materials.get(material) else {
+            continue;
+        };
+        let scroll = match batch.flags & SURF_FLOWING {
+            0 => 0.0,
+            _ => WARP_SCROLL,
+        };
+        commands
+            .entity(entity)
+            .remove::<Handle<StandardMaterial>>()
+            .insert(warp_materials.add(WarpMaterial {
+                texture: None,
+                color: base.base_color.into(),
+                size: Vec2::ONE,
+                scroll,
+                alpha_mode: base.alpha_mode,
+            }));
+    }
+}
+
+/// Puts streamed textures on the warp materials still waiting for theirs.
+fn warp_textures(
+    streaming: Res<TextureStreaming>,
+    mut warp_materials: ResMut<Assets<WarpMaterial>>,
+    batches: Query<(&WorldBatch, &Handle<WarpMaterial>)>,
+) {
+    for (batch, material) in batches.iter() {
+        let waiting = warp_materials
+            .get(material)
+            .is_some_and(|material| material.texture.is_none());
+        if !waiting {
+            continue;
+        }
+        let Some((image, size)) = streaming.resident(&batch.texture) else {
+            continue;
+        };
+        if let Some(material) = warp_materials.get_mut(material) {
+            // Translucent liquids keep their alpha
+            material.color = LinearRgba::WHITE.with_alpha(material.color.alpha);
+            material.texture = Some(image.clone());
+            material.size = size;
+        }
+    }
+}
+
+/// Spawns the caustics of each map instance once its world is spawned.
+fn setup_caustics(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut caustic_materials: ResMut<Assets<CausticMaterial>>,
+    settings: Res<WaterSettings>,
+    instances: Query<(Entity, &MapInstance), Changed<MapInstance>>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    if !settings.caustics {
+        return;
+    }
+    for (root, instance) in instances.iter() {
+        if !instance.spawned {
+            continue;
+        }
+        let Some(asset) = assets.get(&instance.handle) else {
+            continue;
+        };
+        let receivers = caustic_receivers(&asset.bsp, settings.caustic_depth);
+        if receivers.is_empty() {
+            continue;
+        }
+        info!("{}: caustics on {} faces", instance.name, receivers.len());
+
+        let mut data = MeshBuilder::new()
+            .build_faces(&asset.bsp, &receivers)
+            .clone();
+        for (point, normal) in data
+            .points
+            .chunks_exact_mut(3)
+            .zip(data.normals.chunks_exact(3))
+        {
+            for (p, n) in point.iter_mut().zip(normal) {
+                *p += n * CAUSTIC_OFFSET;
+            }
+        }
+        let caustics = commands
+            .spawn(MaterialMeshBundle {
+                mesh: meshes.add(data.into_mesh(&MeshOptions {
+                    colors: false,
+                    ..default()
+                })),
+                material: caustic_materials.add(CausticMaterial {
+                    color: LinearRgba::rgb(0.35, 0.45, 0.5),
+                }),
+                transform: Transform::from_translation(asset.world_offset()),
+                ..default()
+            })
+            .id();
+        commands.entity(root).add_child(caustics);
+    }
+}
+
+fn toggle_water_key(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<WaterSettings>,
+    mut maps: ResMut<MapManager>,
+) {
+    if !keys.just_pressed(KeyCode::KeyL) {
+        return;
+    }
+    let enable = !settings.ripples;
+    settings.ripples = enable;
+    settings.caustics = enable;
+    if let Some(map) = maps.current().map(str::to_string) {
+        maps.changelevel(&map);
+    }
+}
+
+/// Faces hit by tracing straight down from the upward-facing SURF_WARP
+/// surfaces of `bsp`, at most `depth` units below the surface, sorted.
+///
+/// Traces start every [TRACE_STEP] units across each liquid triangle and stop
+/// at the first upward-facing face below it. Only faces listed by the leafs
+/// are candidates, see [BSP38::faces_in_bounds].
+fn caustic_receivers(bsp: &BSP38, depth: f32) -> Vec<usize> {
+    let _span = info_span!("caustic_receivers").entered();
+    let records = bsp.read_face_records().unwrap_or_default();
+    let tex_info = bsp.read_texture_info().unwrap_or_default();
+    let is_liquid = |face: usize| {
+        tex_info
+            .get(records[face].texinfo as usize)
+            .is_some_and(|tex| tex.flags & SURF_WARP != 0)
+    };
+    let liquid: Vec<usize> = (0..records.len()).filter(|&k| is_liquid(k)).collect();
+
+    let mut builder = MeshBuilder::new();
+    let surface = builder.build_faces(bsp, &liquid).clone();
+    let mut receivers = BTreeSet::new();
+    for (points, normals) in surface
+        .points
+        .chunks_exact(9)
+        .zip(surface.normals.chunks_exact(9))
+    {
+        if normals[2] < 0.7 {
+            continue;
+        }
+        let liquid_triangle = triangle(points);
+        let min = liquid_triangle[0]
+            .min(liquid_triangle[1])
+            .min(liquid_triangle[2]);
+        let max = liquid_triangle[0]
+            .max(liquid_triangle[1])
+            .max(liquid_triangle[2]);
+        let candidates: Vec<usize> = bsp
+            .faces_in_bounds(Bounds {
+                min: [min.x, min.y, min.z - depth],
+                max: max.to_array(),
+            })
+            .into_iter()
+            .filter(|&k| !is_liquid(k))
+            .collect();
+
+        // Upward-facing triangles of the candidates, with their face. Each
+        // face is a fan of num_edges - 2 triangles.
+        let floors_data = builder.build_faces(bsp, &candidates);
+        let mut triangles = floors_data
+            .points
+            .chunks_exact(9)
+            .zip(floors_data.normals.chunks_exact(9));
+        let mut floors = Vec::new();
+        for &face in &candidates {
+            for _ in 0..records[face].num_edges.saturating_sub(2) {
+                let Some((points, normals)) = triangles.next() else {
+                    break;
+                };
+                if normals[2] > 0.0 {
+                    floors.push((triangle(points), face));
+                }
+            }
+        }
+
+        for start in trace_starts(&liquid_triangle) {
+            if let Some(face) = trace_down(start, depth, &floors) {
+                receivers.insert(face);
+            }
+        }
+    }
+    receivers.into_iter().collect()
+}
+
+fn triangle(points: &[f32]) -> [Vec3; 3] {
+    [
+        Vec3::from_slice(&points[0..3]),
+        Vec3::from_slice(&points[3..6]),
+        Vec3::from_slice(&points[6..9]),
+    ]
+}
+
+/// Points on a grid of [TRACE_STEP] inside `triangle`, plus its centroid so
+/// small triangles get at least one trace.
+fn trace_starts(triangle: &[Vec3; 3]) -> Vec<Vec3> {
+    let min = triangle[0].min(triangle[1]).min(triangle[2]);
+    let max = triangle[0].max(triangle[1]).max(triangle[2]);
+    let mut starts = vec![(triangle[0] + triangle[1] + triangle[2]) / 3.0];
+    let mut x = min.x + TRACE_STEP / 2.0;
+    while x < max.x {
+        let mut y = min.y + TRACE_STEP / 2.0;
+        while y < max.y {
+            if let Some(z) = height_at(triangle, Vec2::new(x, y)) {
+                starts.push(Vec3::new(x, y, z));
+            }
+            y += TRACE_STEP;
+        }
+        x += TRACE_STEP;
+    }
+    starts
+}
+
+/// The face of the highest floor triangle under `start`, at most `depth`
+/// below it.
+fn trace_down(start: Vec3, depth: f32, floors: &[([Vec3; 3], usize)]) -> Option<usize> {
+    floors
+        .iter()
+        .filter_map(|(triangle, face)| {
+            let z = height_at(triangle, start.truncate())?;
+            (z < start.z && z >= start.z - depth).then_some((z, *face))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, face)| face)
+}
+
+/// Height of `triangle` above `p`, or None if `p` is outside of the
+/// triangle seen from above.
+fn height_at(triangle: &[Vec3; 3], p: Vec2) -> Option<f32> {
+    let [a, b, c] = triangle.map(|v| v.truncate());
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() < 1e-6 {
+        // Vertical, so no ray straight down hits it
+        return None;
+    }
+    let u = (c - b).perp_dot(p - b) / area;
+    let v = (a - c).perp_dot(p - c) / area;
+    let w = 1.0 - u - v;
+    (u >= 0.0 && v >= 0.0 && w >= 0.0)
+        .then(|| u * triangle[0].z + v * triangle[1].z + w * triangle[2].z)
+}
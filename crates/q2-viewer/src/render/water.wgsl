@@ -0,0 +1,45 @@
+// Liquid surface shaded through ripple normals: a few travelling waves
+// perturb the surface normal, which drives a diffuse, specular and fresnel
+// term for a fixed sun.
+
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings::{globals, view}
+
+@group(2) @binding(0) var<uniform> color: vec4<f32>;
+
+// Direction towards the sun of the viewer's directional light
+const SUN: vec3<f32> = vec3<f32>(0.57735, 0.57735, 0.57735);
+
+// Gradient of the ripple height at p (map units): a sum of sine waves, each
+// given as (direction x, direction y, wavenumber, speed), with the amplitude
+// proportional to the wavelength.
+fn ripple_gradient(p: vec2<f32>, t: f32) -> vec2<f32> {
+    var waves = array<vec4<f32>, 4>(
+        vec4<f32>(0.8, 0.6, 0.05, 1.1),
+        vec4<f32>(-0.6, 0.8, 0.08, 1.7),
+        vec4<f32>(0.3, -0.95, 0.13, 2.3),
+        vec4<f32>(-0.9, -0.45, 0.21, 2.9),
+    );
+    var gradient = vec2<f32>(0.0);
+    for (var i = 0; i < 4; i++) {
+        let wave = waves[i];
+        let phase = dot(wave.xy, p) * wave.z + t * wave.w;
+        // d/dp of (0.06 / k) * sin(k * d.p + w * t)
+        gradient += 0.06 * wave.xy * cos(phase);
+    }
+    return gradient;
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let gradient = ripple_gradient(mesh.world_position.xy, globals.time);
+    let normal = normalize(normalize(mesh.world_normal) - vec3<f32>(gradient, 0.0));
+    let to_eye = normalize(view.world_position - mesh.world_position.xyz);
+
+    let diffuse = max(dot(normal, SUN), 0.0);
+    let specular = pow(max(dot(reflect(-SUN, normal), to_eye), 0.0), 64.0);
+    let fresnel = pow(1.0 - max(dot(normal, to_eye), 0.0), 5.0);
+
+    let rgb = color.rgb * (0.35 + 0.65 * diffuse) + vec3<f32>(specular + 0.3 * fresnel);
+    return vec4<f32>(rgb, mix(color.a, 1.0, fresnel));
+}
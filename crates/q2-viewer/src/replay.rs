@@ -0,0 +1,589 @@
+//! Recording of keyboard and mouse input, replayed frame by frame for
+//! scripted interaction tests of the viewer.
+//!
+//! Only available on native, since logs are read from and written to disk.
+//!
+//! A log holds, for every frame, its duration, the keys and mouse buttons
+//! pressed and released, the mouse motion and where the camera ended up.
+//! Replaying feeds the same input at the same frame times, so with the
+//! fixed simulation timestep and the [DebugRng] seed of the log the viewer
+//! goes through the same states. Each frame the camera is checked against
+//! the log, and the first frame it strays from it is reported.
+//!
+//! ```no_run
+//! # use bevy::prelude::*;
+//! # use q2_viewer::replay::{InputLog, InputReplay};
+//! fn replay_test(mut commands: Commands) {
+//!     match InputLog::load("tests/fly_q2dm1.log") {
+//!         Ok(log) => commands.insert_resource(InputReplay::new(log)),
+//!         Err(err) => error!("Could not load input log: {}", err),
+//!     }
+//! }
+//! ```
+
+use std::{path::Path, time::Duration};
+
+use bevy::{
+    input::{mouse::MouseMotion, InputSystem},
+    prelude::*,
+    reflect::{DynamicEnum, DynamicVariant, Enum, VariantType},
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+
+use crate::{
+    asset::MapSummary, camera::CameraMode, locale::Message, maps::MapManager, render::OverlayStats,
+    seed::DebugRng, sim::Interpolated,
+};
+
+/// Where F10 saves input logs and Shift+F10 replays them from.
+const LOG_PATH: &str = "recordings/input.log";
+
+/// First line of a log, with the version of the format.
+const HEADER: &str = "q2-input 1";
+
+/// How far the replayed camera may be from the logged one, in map units and
+/// quaternion components, to allow for the rounding of the log.
+const CAMERA_TOLERANCE: f32 = 1e-3;
+
+/// Records input while an [InputRecorder] exists and replays it while an
+/// [InputReplay] does.
+///
+/// F10 starts and stops recording into `recordings/input.log`, and Shift+F10
+/// replays that log. Both reload the map first, so they start from the same
+/// state.
+pub struct InputReplayPlugin;
+
+impl Plugin for InputReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(First, replay_frame_time.before(TimeSystem))
+            .add_systems(PreUpdate, replay_input.after(InputSystem))
+            .add_systems(Update, replay_key)
+            .add_systems(Last, (record_frame, check_frame, report_input_stats));
+    }
+}
+
+/// One input event of a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Press(KeyCode),
+    Release(KeyCode),
+    MousePress(MouseButton),
+    MouseRelease(MouseButton),
+    /// Mouse motion over the frame, in pixels.
+    MouseMotion(Vec2),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFrame {
+    /// Time since the previous frame.
+    pub delta: Duration,
+    pub events: Vec<InputEvent>,
+    /// Simulated transform of the main camera at the end of the frame.
+    pub camera: Transform,
+}
+
+/// A recording of input, see the [module](self) documentation.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use q2_viewer::{camera::CameraMode, replay::{InputEvent, InputFrame, InputLog}};
+/// # use std::time::Duration;
+/// let log = InputLog {
+///     seed: 1234,
+///     map: Some("q2dm1".to_string()),
+///     mode: CameraMode::Fly,
+///     frames: vec![InputFrame {
+///         delta: Duration::from_millis(16),
+///         events: vec![InputEvent::Press(KeyCode::KeyW)],
+///         camera: Transform::from_xyz(1.0, 2.0, 3.0),
+///     }],
+/// };
+/// assert_eq!(InputLog::parse(&log.to_text()), Ok(log));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputLog {
+    /// Seed of the [DebugRng] while recording.
+    pub seed: u32,
+    /// The map input was recorded on.
+    pub map: Option<String>,
+    /// Camera mode when recording started.
+    pub mode: CameraMode,
+    pub frames: Vec<InputFrame>,
+}
+
+impl InputLog {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text =
+            std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+
+    /// The log as text: a header, then a line per frame followed by a line
+    /// per event and the camera.
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![HEADER.to_string(), format!("seed {}", self.seed)];
+        if let Some(map) = &self.map {
+            lines.push(format!("map {}", map));
+        }
+        lines.push(format!("mode {}", mode_name(self.mode)));
+        for frame in &self.frames {
+            lines.push(format!("frame {}", frame.delta.as_nanos()));
+            for event in &frame.events {
+                lines.push(match event {
+                    InputEvent::Press(key) => format!("press {}", key.variant_name()),
+                    InputEvent::Release(key) => format!("release {}", key.variant_name()),
+                    InputEvent::MousePress(button) => {
+                        format!("mouse-press {}", button.variant_name())
+                    }
+                    InputEvent::MouseRelease(button) => {
+                        format!("mouse-release {}", button.variant_name())
+                    }
+                    InputEvent::MouseMotion(delta) => format!("motion {} {}", delta.x, delta.y),
+                });
+            }
+            let Transform {
+                translation: t,
+                rotation: r,
+                ..
+            } = frame.camera;
+            lines.push(format!(
+                "camera {} {} {} {} {} {} {}",
+                t.x, t.y, t.z, r.x, r.y, r.z, r.w
+            ));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(format!("not an input log, expected {:?}", HEADER));
+        }
+        let mut log = InputLog {
+            seed: 0,
+            map: None,
+            mode: CameraMode::default(),
+            frames: Vec::new(),
+        };
+        for (i, line) in lines {
+            let error = |message: &str| format!("line {}: {}", i + 1, message);
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match command {
+                "" => continue,
+                "seed" => log.seed = rest.parse().map_err(|_| error("bad seed"))?,
+                "map" => log.map = Some(rest.to_string()),
+                "mode" => log.mode = parse_mode(rest).ok_or_else(|| error("bad mode"))?,
+                "frame" => log.frames.push(InputFrame {
+                    delta: Duration::from_nanos(rest.parse().map_err(|_| error("bad delta"))?),
+                    events: Vec::new(),
+                    camera: Transform::IDENTITY,
+                }),
+                _ => {
+                    let Some(frame) = log.frames.last_mut() else {
+                        return Err(error("event before the first frame"));
+                    };
+                    let bad = || error(&format!("bad {} {:?}", command, rest));
+                    let event = match command {
+                        "camera" => {
+                            let v = parse_floats::<7>(rest).ok_or_else(bad)?;
+                            frame.camera = Transform::from_xyz(v[0], v[1], v[2])
+                                .with_rotation(Quat::from_xyzw(v[3], v[4], v[5], v[6]));
+                            continue;
+                        }
+                        "motion" => {
+                            let [x, y] = parse_floats::<2>(rest).ok_or_else(bad)?;
+                            InputEvent::MouseMotion(Vec2::new(x, y))
+                        }
+                        "press" => InputEvent::Press(unit_variant(rest).ok_or_else(bad)?),
+                        "release" => InputEvent::Release(unit_variant(rest).ok_or_else(bad)?),
+                        "mouse-press" => {
+                            InputEvent::MousePress(unit_variant(rest).ok_or_else(bad)?)
+                        }
+                        "mouse-release" => {
+                            InputEvent::MouseRelease(unit_variant(rest).ok_or_else(bad)?)
+                        }
+                        _ => return Err(error(&format!("unknown command {:?}", command))),
+                    };
+                    frame.events.push(event);
+                }
+            }
+        }
+        Ok(log)
+    }
+}
+
+fn mode_name(mode: CameraMode) -> &'static str {
+    match mode {
+        CameraMode::Orbit => "orbit",
+        CameraMode::Fly => "fly",
+        CameraMode::Walk => "walk",
+    }
+}
+
+fn parse_mode(name: &str) -> Option<CameraMode> {
+    match name {
+        "orbit" => Some(CameraMode::Orbit),
+        "fly" => Some(CameraMode::Fly),
+        "walk" => Some(CameraMode::Walk),
+        _ => None,
+    }
+}
+
+fn parse_floats<const N: usize>(text: &str) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+    let mut words = text.split_whitespace();
+    for value in values.iter_mut() {
+        *value = words.next()?.parse().ok()?;
+    }
+    words.next().is_none().then_some(values)
+}
+
+/// The unit variant of a reflected enum such as [KeyCode] named `name`.
+fn unit_variant<T: FromReflect>(name: &str) -> Option<T> {
+    T::from_reflect(&DynamicEnum::new(name.to_string(), DynamicVariant::Unit))
+}
+
+/// Whether a key or button can be written to a log. Keys the platform didn't
+/// identify carry native codes, and are left out.
+fn is_unit<T: Enum>(value: &T) -> bool {
+    value.variant_type() == VariantType::Unit
+}
+
+/// Waits for the map to reload, so recording and replay start from the
+/// same state.
+#[derive(Debug, Clone, Copy)]
+enum Start {
+    /// Waiting for a map other than this root to finish loading.
+    Loading(Option<Entity>),
+    Started,
+}
+
+impl Start {
+    fn reload(maps: &mut MapManager, map: Option<&str>) -> Self {
+        if let Some(map) = map {
+            maps.changelevel(map);
+        }
+        Self::Loading(maps.root())
+    }
+
+    /// Whether input is being recorded or replayed, moving on once the map
+    /// has loaded.
+    fn update(&mut self, maps: &MapManager, summaries: &Query<&MapSummary>) -> bool {
+        if let Self::Loading(old_root) = *self {
+            let loaded = maps.root().is_some_and(|root| summaries.contains(root));
+            if maps.root() == old_root || !loaded {
+                return false;
+            }
+            *self = Self::Started;
+        }
+        true
+    }
+}
+
+/// Input being recorded. Insert it as a resource to start recording, and
+/// remove it to stop.
+#[derive(Resource)]
+pub struct InputRecorder {
+    log: InputLog,
+    start: Start,
+}
+
+impl InputRecorder {
+    /// Reloads the current map and records from when it has loaded.
+    pub fn start(maps: &mut MapManager, mode: CameraMode, debug_rng: &DebugRng) -> Self {
+        let map = maps.current().map(str::to_string);
+        Self {
+            start: Start::reload(maps, map.as_deref()),
+            log: InputLog {
+                seed: debug_rng.seed(),
+                map,
+                mode,
+                frames: Vec::new(),
+            },
+        }
+    }
+
+    pub fn log(&self) -> &InputLog {
+        &self.log
+    }
+}
+
+/// A log being replayed. Insert it as a resource to start replaying; it
+/// removes itself once done.
+#[derive(Resource)]
+pub struct InputReplay {
+    log: InputLog,
+    start: Option<Start>,
+    /// Index of the frame being replayed.
+    frame: Option<usize>,
+    /// Keys and buttons held by the replay.
+    held_keys: Vec<KeyCode>,
+    held_buttons: Vec<MouseButton>,
+    /// First frame where the camera strayed from the log.
+    diverged: Option<usize>,
+}
+
+impl InputReplay {
+    pub fn new(log: InputLog) -> Self {
+        Self {
+            log,
+            start: None,
+            frame: None,
+            held_keys: Vec::new(),
+            held_buttons: Vec::new(),
+            diverged: None,
+        }
+    }
+
+    /// First frame where the replay didn't match the log, so far.
+    pub fn diverged(&self) -> Option<usize> {
+        self.diverged
+    }
+}
+
+/// Starts and stops recording on F10, and replays the last recording on
+/// Shift+F10.
+fn replay_key(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut maps: ResMut<MapManager>,
+    mode: Res<CameraMode>,
+    debug_rng: Res<DebugRng>,
+    recorder: Option<Res<InputRecorder>>,
+    replay: Option<Res<InputReplay>>,
+) {
+    if !keys.just_pressed(KeyCode::F10) || replay.is_some() {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        if recorder.is_some() {
+            return;
+        }
+        match InputLog::load(LOG_PATH) {
+            Ok(log) => {
+                info!("Replaying {} frames of input", log.frames.len());
+                commands.insert_resource(InputReplay::new(log));
+            }
+            Err(err) => error!("Could not replay input: {}", err),
+        }
+        return;
+    }
+    match recorder {
+        Some(recorder) => {
+            let log = recorder.log();
+            match log.save(LOG_PATH) {
+                Ok(()) => info!("Saved {} frames of input to {}", log.frames.len(), LOG_PATH),
+                Err(err) => error!("Could not save input to {}: {}", LOG_PATH, err),
+            }
+            commands.remove_resource::<InputRecorder>();
+        }
+        None => {
+            commands.insert_resource(InputRecorder::start(&mut maps, *mode, &debug_rng));
+        }
+    }
+}
+
+/// Adds this frame's input and camera to the recording.
+#[allow(clippy::too_many_arguments)]
+fn record_frame(
+    recorder: Option<ResMut<InputRecorder>>,
+    maps: Res<MapManager>,
+    summaries: Query<&MapSummary>,
+    time: Res<Time<Real>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    cameras: Query<&Interpolated, With<Camera>>,
+) {
+    let delta: Vec2 = motion.read().map(|event| event.delta).sum();
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+    if !recorder.start.update(&maps, &summaries) {
+        return;
+    }
+    // F10 starts and stops recording, so it would stop the replay
+    let keys_used = |key: &&KeyCode| is_unit(*key) && **key != KeyCode::F10;
+    let mut events: Vec<InputEvent> = keys
+        .get_just_pressed()
+        .filter(keys_used)
+        .map(|&key| InputEvent::Press(key))
+        .collect();
+    events.extend(
+        keys.get_just_released()
+            .filter(keys_used)
+            .map(|&key| InputEvent::Release(key)),
+    );
+    events.extend(
+        buttons
+            .get_just_pressed()
+            .filter(|button| is_unit(*button))
+            .map(|&button| InputEvent::MousePress(button)),
+    );
+    events.extend(
+        buttons
+            .get_just_released()
+            .filter(|button| is_unit(*button))
+            .map(|&button| InputEvent::MouseRelease(button)),
+    );
+    if delta != Vec2::ZERO {
+        events.push(InputEvent::MouseMotion(delta));
+    }
+    let camera = cameras
+        .iter()
+        .next()
+        .map_or(Transform::IDENTITY, |camera| camera.current);
+    recorder.log.frames.push(InputFrame {
+        delta: time.delta(),
+        events,
+        camera,
+    });
+}
+
+/// Moves on to the next frame of the replay, once the map has loaded, and
+/// gives it the logged duration.
+fn replay_frame_time(
+    mut commands: Commands,
+    replay: Option<ResMut<InputReplay>>,
+    mut maps: ResMut<MapManager>,
+    summaries: Query<&MapSummary>,
+    mut mode: ResMut<CameraMode>,
+    mut strategy: ResMut<TimeUpdateStrategy>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    let replay = &mut *replay;
+    let start = match replay.start {
+        Some(ref mut start) => start,
+        None => {
+            // Back to the state the recording started in
+            commands.insert_resource(DebugRng::new(Some(replay.log.seed)));
+            *mode = replay.log.mode;
+            replay
+                .start
+                .insert(Start::reload(&mut maps, replay.log.map.as_deref()))
+        }
+    };
+    if !start.update(&maps, &summaries) {
+        return;
+    }
+
+    let next = replay.frame.map_or(0, |frame| frame + 1);
+    match replay.log.frames.get(next) {
+        Some(frame) => {
+            *strategy = TimeUpdateStrategy::ManualDuration(frame.delta);
+            replay.frame = Some(next);
+        }
+        None => {
+            *strategy = TimeUpdateStrategy::Automatic;
+            match replay.diverged {
+                Some(frame) => warn!("Replay diverged from the log at frame {}", frame),
+                None => info!("Replayed {} frames", replay.log.frames.len()),
+            }
+            commands.remove_resource::<InputReplay>();
+        }
+    }
+}
+
+/// Presses and releases what the log does this frame.
+fn replay_input(
+    replay: Option<ResMut<InputReplay>>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut buttons: ResMut<ButtonInput<MouseButton>>,
+    mut motion: EventWriter<MouseMotion>,
+) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    let replay = &mut *replay;
+    let Some(frame) = replay.frame.and_then(|frame| replay.log.frames.get(frame)) else {
+        return;
+    };
+    for event in &frame.events {
+        match *event {
+            InputEvent::Press(key) => {
+                keys.press(key);
+                replay.held_keys.push(key);
+            }
+            InputEvent::Release(key) => {
+                keys.release(key);
+                replay.held_keys.retain(|&held| held != key);
+            }
+            InputEvent::MousePress(button) => {
+                buttons.press(button);
+                replay.held_buttons.push(button);
+            }
+            InputEvent::MouseRelease(button) => {
+                buttons.release(button);
+                replay.held_buttons.retain(|&held| held != button);
+            }
+            InputEvent::MouseMotion(delta) => {
+                motion.send(MouseMotion { delta });
+            }
+        }
+    }
+    // The last frame lets go of everything
+    if replay.frame == Some(replay.log.frames.len() - 1) {
+        for key in replay.held_keys.drain(..) {
+            keys.release(key);
+        }
+        for button in replay.held_buttons.drain(..) {
+            buttons.release(button);
+        }
+    }
+}
+
+/// Compares the camera with the log at the end of each replayed frame.
+fn check_frame(replay: Option<ResMut<InputReplay>>, cameras: Query<&Interpolated, With<Camera>>) {
+    let Some(mut replay) = replay else {
+        return;
+    };
+    let (Some(index), None) = (replay.frame, replay.diverged) else {
+        return;
+    };
+    let (Some(frame), Some(camera)) = (replay.log.frames.get(index), cameras.iter().next()) else {
+        return;
+    };
+    let logged = frame.camera;
+    let camera = camera.current;
+    let matches = camera
+        .translation
+        .abs_diff_eq(logged.translation, CAMERA_TOLERANCE)
+        && camera
+            .rotation
+            .abs_diff_eq(logged.rotation, CAMERA_TOLERANCE);
+    if !matches {
+        replay.diverged = Some(index);
+    }
+}
+
+fn report_input_stats(
+    mut stats: ResMut<OverlayStats>,
+    recorder: Option<Res<InputRecorder>>,
+    replay: Option<Res<InputReplay>>,
+) {
+    match (recorder, replay) {
+        (Some(recorder), _) => stats.set(
+            "input",
+            Message::new("input.recording").arg(recorder.log.frames.len()),
+        ),
+        (_, Some(replay)) => stats.set(
+            "input",
+            Message::new("input.replaying")
+                .arg(replay.frame.map_or(0, |frame| frame + 1))
+                .arg(replay.log.frames.len()),
+        ),
+        (None, None) => stats.remove("input"),
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{maps::MapManager, render::OverlayStats};
+
+/// Seeds the random numbers of debug effects, so what they draw can be
+/// reproduced for bug reports and comparison screenshots.
+///
+/// The generator starts over from the seed whenever a map loads, so the same
+/// seed gives the same output on each load of the same map. Without a seed,
+/// one is picked at random, logged and shown in the overlay.
+pub struct DebugRngPlugin {
+    pub seed: Option<u32>,
+}
+
+impl Plugin for DebugRngPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DebugRng::new(self.seed))
+            .add_systems(First, reseed_on_map_change);
+    }
+}
+
+/// Random numbers for debug effects, such as the directions of debug rays or
+/// the colors of particles. Anything nondeterministic that ends up on screen
+/// should draw from here rather than `thread_rng`.
+#[derive(Resource)]
+pub struct DebugRng {
+    seed: u32,
+    rng: StdRng,
+}
+
+impl DebugRng {
+    pub fn new(seed: Option<u32>) -> Self {
+        let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+        info!("debug seed {}", seed);
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed.into()),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Starts the sequence over from the seed.
+    pub fn reset(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed.into());
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+fn reseed_on_map_change(
+    mut debug_rng: ResMut<DebugRng>,
+    mut stats: ResMut<OverlayStats>,
+    maps: Res<MapManager>,
+    mut root: Local<Option<Entity>>,
+) {
+    if *root != maps.root() {
+        *root = maps.root();
+        debug_rng.reset();
+        stats.set("seed", debug_rng.seed().to_string());
+    }
+}
@@ -0,0 +1,323 @@
+//! Spectator sessions: several web viewers connected to the same relay see
+//! each other's cameras as frusta, and can follow a presenter's camera, to
+//! review a map together.
+//!
+//! The page owns the WebSocket, see `?session=` in index.html, and passes
+//! messages through [session_receive_js] and [session_outgoing_js]. The
+//! relay, `specrelay` in q2-tools, forwards each message to the other
+//! viewers of the same session path.
+
+use std::sync::Mutex;
+
+use bevy::{prelude::*, utils::HashMap};
+use wasm_bindgen::prelude::*;
+
+use crate::sim::{interpolate, Interpolated};
+
+/// Seconds between camera updates sent to the session.
+const SEND_INTERVAL: f32 = 0.1;
+
+/// Seconds after which a viewer not heard from has left.
+const PEER_TIMEOUT: f32 = 3.0;
+
+/// Length of the drawn frusta, in map units.
+const FRUSTUM_LENGTH: f32 = 48.0;
+
+/// Name the page joined the session with, once its socket is open.
+static JOINED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Messages from the relay, read on the next frame.
+static INBOX: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Messages for the page to send to the relay.
+static OUTBOX: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Shares the main camera with the other viewers of a session and draws
+/// theirs. Idle until the page joins a session.
+///
+/// The console takes `session_present <0|1>`, which asks the others to
+/// follow this camera, and `session_follow <0|1>`, which follows the
+/// presenter's.
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpectatorSession>()
+            .add_systems(
+                Update,
+                (join_session, receive_messages, send_camera, draw_peers).chain(),
+            )
+            .add_systems(PostUpdate, follow_presenter.before(interpolate));
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct SpectatorSession {
+    /// Random id of this viewer in the session.
+    pub id: u32,
+    /// Name shown to the others, once the page has joined a session.
+    pub name: Option<String>,
+    /// Whether the others are asked to follow this camera.
+    pub presenting: bool,
+    /// Whether the camera follows the presenter's, when there is one.
+    pub follow: bool,
+    pub peers: HashMap<u32, Peer>,
+    since_sent: f32,
+}
+
+impl Default for SpectatorSession {
+    fn default() -> Self {
+        Self {
+            id: rand::random(),
+            name: None,
+            presenting: false,
+            follow: true,
+            peers: HashMap::default(),
+            since_sent: 0.0,
+        }
+    }
+}
+
+/// Another viewer of the session.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    pub name: String,
+    pub presenting: bool,
+    /// The camera as last sent.
+    pub camera: Transform,
+    /// The camera as drawn, easing towards `camera` between updates.
+    pub shown: Transform,
+    /// Vertical field of view, in radians.
+    pub fov: f32,
+    /// Time it was last heard from, in seconds since startup.
+    pub last_seen: f32,
+}
+
+impl SpectatorSession {
+    /// The peer the others follow, if any is presenting.
+    pub fn presenter(&self) -> Option<&Peer> {
+        self.peers.values().find(|peer| peer.presenting)
+    }
+
+    /// Runs a console command: `session_present <0|1>` or
+    /// `session_follow <0|1>`.
+    ///
+    /// ```
+    /// # use q2_viewer::session::SpectatorSession;
+    /// let mut session = SpectatorSession::default();
+    /// session.run_command("session_present 1").unwrap();
+    /// assert!(session.presenting);
+    /// assert!(session.run_command("session_follow yes").is_err());
+    /// ```
+    pub fn run_command(&mut self, line: &str) -> Result<(), String> {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            ["session_present", "0"] => self.presenting = false,
+            ["session_present", "1"] => {
+                self.presenting = true;
+                self.follow = false;
+            }
+            ["session_present", ..] => return Err("usage: session_present <0|1>".to_string()),
+            ["session_follow", "0"] => self.follow = false,
+            ["session_follow", "1"] => self.follow = true,
+            ["session_follow", ..] => return Err("usage: session_follow <0|1>".to_string()),
+            _ => return Err(format!("unknown command {:?}", line)),
+        }
+        Ok(())
+    }
+}
+
+/// A camera update, the one message of a session, as a line of text:
+/// `cam <id> <presenting> <x y z> <rotation x y z w> <fov> <name>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraMessage {
+    pub id: u32,
+    pub presenting: bool,
+    pub transform: Transform,
+    pub fov: f32,
+    pub name: String,
+}
+
+impl CameraMessage {
+    /// ```
+    /// # use bevy::prelude::*;
+    /// # use q2_viewer::session::CameraMessage;
+    /// let message = CameraMessage {
+    ///     id: 7,
+    ///     presenting: true,
+    ///     transform: Transform::from_xyz(1.0, 2.0, 3.0),
+    ///     fov: 0.75,
+    ///     name: "map review".to_string(),
+    /// };
+    /// assert_eq!(CameraMessage::parse(&message.to_line()), Some(message));
+    /// assert_eq!(CameraMessage::parse("cam 7 1 1 2"), None);
+    /// ```
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        if words.next()? != "cam" {
+            return None;
+        }
+        let id = words.next()?.parse().ok()?;
+        let presenting = words.next()? == "1";
+        let mut values = [0f32; 8];
+        for value in values.iter_mut() {
+            *value = words.next()?.parse().ok()?;
+        }
+        let [x, y, z, rx, ry, rz, rw, fov] = values;
+        let rotation = Quat::from_xyzw(rx, ry, rz, rw);
+        if !rotation.is_finite() || rotation.length_squared() < 1e-6 {
+            return None;
+        }
+        Some(Self {
+            id,
+            presenting,
+            transform: Transform::from_xyz(x, y, z).with_rotation(rotation.normalize()),
+            fov,
+            name: words.collect::<Vec<_>>().join(" "),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        let Vec3 { x, y, z } = self.transform.translation;
+        let [rx, ry, rz, rw] = self.transform.rotation.to_array();
+        format!(
+            "cam {} {} {} {} {} {} {} {} {} {} {}",
+            self.id, self.presenting as u8, x, y, z, rx, ry, rz, rw, self.fov, self.name
+        )
+    }
+}
+
+/// Joins the session, once the page's socket is open, as `name`.
+#[wasm_bindgen(js_name = session_join)]
+pub fn session_join_js(name: &str) {
+    *JOINED.lock().unwrap() = Some(name.to_string());
+}
+
+/// Hands messages from the relay to the viewer, one per line.
+#[wasm_bindgen(js_name = session_receive)]
+pub fn session_receive_js(text: &str) {
+    INBOX
+        .lock()
+        .unwrap()
+        .extend(text.lines().map(str::to_string));
+}
+
+/// Takes the messages to send to the relay, one per line, or an empty
+/// string when there are none.
+#[wasm_bindgen(js_name = session_outgoing)]
+pub fn session_outgoing_js() -> String {
+    std::mem::take(&mut *OUTBOX.lock().unwrap()).join("\n")
+}
+
+fn join_session(mut session: ResMut<SpectatorSession>) {
+    if let Some(name) = JOINED.lock().unwrap().take() {
+        info!("Joined the session as {:?}", name);
+        session.name = Some(name);
+    }
+}
+
+fn receive_messages(mut session: ResMut<SpectatorSession>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    let inbox = std::mem::take(&mut *INBOX.lock().unwrap());
+    for message in inbox.iter().filter_map(|line| CameraMessage::parse(line)) {
+        if message.id == session.id {
+            continue;
+        }
+        let peer = session.peers.entry(message.id).or_insert_with(|| {
+            info!("{:?} joined the session", message.name);
+            Peer {
+                name: String::new(),
+                presenting: false,
+                camera: message.transform,
+                shown: message.transform,
+                fov: message.fov,
+                last_seen: now,
+            }
+        });
+        peer.name = message.name;
+        peer.presenting = message.presenting;
+        peer.camera = message.transform;
+        peer.fov = message.fov;
+        peer.last_seen = now;
+    }
+
+    session.peers.retain(|_, peer| {
+        let present = now - peer.last_seen < PEER_TIMEOUT;
+        if !present {
+            info!("{:?} left the session", peer.name);
+        }
+        present
+    });
+    // Ease towards the last update, which arrive a few times a second
+    let ease = 1.0 - (-10.0 * time.delta_seconds()).exp();
+    for peer in session.peers.values_mut() {
+        peer.shown.translation = peer.shown.translation.lerp(peer.camera.translation, ease);
+        peer.shown.rotation = peer.shown.rotation.slerp(peer.camera.rotation, ease);
+    }
+}
+
+fn send_camera(
+    mut session: ResMut<SpectatorSession>,
+    cameras: Query<(&Transform, &Projection), (With<Camera3d>, With<Interpolated>)>,
+    time: Res<Time>,
+) {
+    let Some(name) = session.name.clone() else {
+        return;
+    };
+    session.since_sent += time.delta_seconds();
+    if session.since_sent < SEND_INTERVAL {
+        return;
+    }
+    session.since_sent = 0.0;
+    let Ok((transform, projection)) = cameras.get_single() else {
+        return;
+    };
+    let fov = match projection {
+        Projection::Perspective(perspective) => perspective.fov,
+        Projection::Orthographic(_) => 0.0,
+    };
+    let message = CameraMessage {
+        id: session.id,
+        presenting: session.presenting,
+        transform: *transform,
+        fov,
+        name,
+    };
+    OUTBOX.lock().unwrap().push(message.to_line());
+}
+
+/// Draws the frustum of each other viewer, the presenter's in yellow.
+fn draw_peers(session: Res<SpectatorSession>, mut gizmos: Gizmos) {
+    for peer in session.peers.values() {
+        let color = match peer.presenting {
+            true => Color::srgb(1.0, 0.9, 0.2),
+            false => Color::srgb(0.3, 0.8, 1.0),
+        };
+        let transform = peer.shown;
+        let half_height = (peer.fov.max(0.1) / 2.0).tan() * FRUSTUM_LENGTH;
+        let half_width = half_height * 16.0 / 9.0;
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| {
+            transform.transform_point(Vec3::new(x * half_width, y * half_height, -FRUSTUM_LENGTH))
+        });
+        for (i, &corner) in corners.iter().enumerate() {
+            gizmos.line(transform.translation, corner, color);
+            gizmos.line(corner, corners[(i + 1) % 4], color);
+        }
+    }
+}
+
+/// Holds the main camera on the presenter's while following. Runs after the
+/// fixed steps, so it wins over the camera controller.
+fn follow_presenter(
+    session: Res<SpectatorSession>,
+    mut cameras: Query<&mut Interpolated, With<Camera>>,
+) {
+    if !session.follow || session.presenting {
+        return;
+    }
+    let Some(presenter) = session.presenter() else {
+        return;
+    };
+    for mut interpolated in cameras.iter_mut() {
+        *interpolated = Interpolated::new(presenter.shown);
+    }
+}
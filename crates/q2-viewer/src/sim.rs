@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+
+/// Rate of the [FixedUpdate] schedule that moves things in the world.
+pub const SIMULATION_HZ: f64 = 60.0;
+
+/// Runs camera animation, texture animation, lightstyles and movement on a
+/// fixed timestep, so they behave the same at 30 and 240 fps.
+///
+/// Simulation systems go in [FixedUpdate] and move entities by writing
+/// [Interpolated::current]. The rendered [Transform] is blended between the
+/// last two simulation steps, so motion stays smooth when the frame rate
+/// isn't a multiple of the simulation rate.
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+            .add_systems(FixedFirst, store_previous)
+            .add_systems(
+                PostUpdate,
+                interpolate.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// The simulated transform of an entity at the last two fixed steps.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Interpolated {
+    pub previous: Transform,
+    pub current: Transform,
+}
+
+impl Interpolated {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+            current: transform,
+        }
+    }
+
+    /// The transform `t` of the way from the previous step to the current.
+    pub fn lerp(&self, t: f32) -> Transform {
+        Transform {
+            translation: self.previous.translation.lerp(self.current.translation, t),
+            rotation: self.previous.rotation.slerp(self.current.rotation, t),
+            scale: self.previous.scale.lerp(self.current.scale, t),
+        }
+    }
+}
+
+fn store_previous(mut query: Query<&mut Interpolated>) {
+    for mut interpolated in query.iter_mut() {
+        interpolated.previous = interpolated.current;
+    }
+}
+
+pub(crate) fn interpolate(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&Interpolated, &mut Transform)>,
+) {
+    let t = time.overstep_fraction();
+    for (interpolated, mut transform) in query.iter_mut() {
+        *transform = interpolated.lerp(t);
+    }
+}
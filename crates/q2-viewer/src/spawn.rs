@@ -0,0 +1,93 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use q2_formats::bsp38::prelude::Entity as MapEntity;
+
+use crate::asset::BSP38Asset;
+
+type SpawnHook = Box<dyn Fn(&MapEntity, &mut Commands) + Send + Sync>;
+
+/// Spawn hooks for map entities, keyed by classname.
+///
+/// Once a map finishes loading, every hook registered for an entity's
+/// classname is called with that entity, in registration order:
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use q2_viewer::spawn::{ClassnameRegistry, ClassnameSpawnPlugin};
+/// App::new()
+///     .add_plugins(ClassnameSpawnPlugin)
+///     .world_mut()
+///     .resource_mut::<ClassnameRegistry>()
+///     .register("info_player_start", |entity, commands| {
+///         info!("Player start at {:?}", entity.get("origin"));
+///         commands.spawn(SpatialBundle::default());
+///     });
+/// ```
+#[derive(Resource, Default)]
+pub struct ClassnameRegistry {
+    hooks: HashMap<String, Vec<SpawnHook>>,
+}
+
+impl ClassnameRegistry {
+    pub fn register(
+        &mut self,
+        classname: impl Into<String>,
+        hook: impl Fn(&MapEntity, &mut Commands) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.hooks
+            .entry(classname.into())
+            .or_default()
+            .push(Box::new(hook));
+        self
+    }
+
+    pub fn is_registered(&self, classname: &str) -> bool {
+        self.hooks.contains_key(classname)
+    }
+
+    /// Calls the hooks of each entity, returning how many entities had one.
+    pub fn spawn_all(&self, entities: &[MapEntity], commands: &mut Commands) -> usize {
+        let mut spawned = 0;
+        for entity in entities {
+            if let Some(hooks) = self.hooks.get(&entity.classname) {
+                for hook in hooks {
+                    hook(entity, commands);
+                }
+                spawned += 1;
+            }
+        }
+        spawned
+    }
+}
+
+pub struct ClassnameSpawnPlugin;
+
+impl Plugin for ClassnameSpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClassnameRegistry>()
+            .add_systems(Update, spawn_map_entities);
+    }
+}
+
+fn spawn_map_entities(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<BSP38Asset>>,
+    registry: Res<ClassnameRegistry>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        let Some(asset) = assets.get(*id) else {
+            continue;
+        };
+        let entities = asset.bsp.read_entities().unwrap_or_default();
+        let spawned = registry.spawn_all(&entities, &mut commands);
+        debug!(
+            "Spawned {} of {} map entities from registered classnames",
+            spawned,
+            entities.len()
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use bevy::{
+    app::App,
+    pbr::Lightmap,
+    prelude::{default, *},
+    utils::{HashMap, Instant},
+    DefaultPlugins,
+};
+use wasm_bindgen::prelude::*;
+
+use q2_formats::bsp38::{
+    contents::MASK_SOLID,
+    prelude::{AreaPortalState, Tracer, VisMatrix},
+    surface::SurfaceFlags,
+};
+
+use crate::{
+    accessibility::{Accessibility, AccessibilityPlugin},
+    annotations::AnnotationPlugin,
+    asset::{BSP38Asset, BSP38AssetLoader, MapSummary, WorldMesh},
+    audio::ReverbZonePlugin,
+    benchmark::BenchmarkPlugin,
+    camera::CameraControllerPlugin,
+    grid::GridPlugin,
+    journal::JournalPlugin,
+    labels::EntityLabelPlugin,
+    locale::{LocalePlugin, Message},
+    maps::{BrushEntity, BrushModel, MapInstance, MapManager, MapManagerPlugin, MapScoped},
+    md2::{Md2AnimationPlugin, Md2Asset, Md2AssetLoader},
+    measure::MeasurePlugin,
+    memory::MemoryStatsPlugin,
+    pak::{PakAssetPlugin, ASSET_ROOT},
+    pcx::PcxAssetLoader,
+    player::PlayerPlugin,
+    quality::{QualityPlugin, QualityProfile},
+    rebuild::RebuildPlugin,
+    render::{texture_path, InstancedAssets, OverlayStats, RenderPlugin},
+    seed::{DebugRng, DebugRngPlugin},
+    session::SessionPlugin,
+    sim::{Interpolated, SimulationPlugin},
+    spawn::ClassnameSpawnPlugin,
+    targets::TargetGraphPlugin,
+    tga::TgaAssetLoader,
+    wal::{TextureOverrides, WalAssetLoader},
+    window::WindowModePlugin,
+    work::WorkQueuePlugin,
+};
+
+#[derive(Resource, Default)]
+struct State {
+    count: usize,
+}
+
+/// Settings of [start_with] that can't change once the app runs. From
+/// JavaScript:
+///
+/// ```js
+/// const options = new mod.StartOptions();
+/// options.asset_root = 'https://cdn.example.com/q2/';
+/// options.seed = 1234;
+/// options.language = 'de';
+/// options.reduced_motion = true;
+/// options.ui_scale = 1.5;
+/// options.device_memory = navigator.deviceMemory;
+/// options.msaa = 1;
+/// options.resolution_scale = 0.5;
+/// options.map = 'q2dm2';
+/// options.benchmark = 30;
+/// mod.start_with('app-canvas', options);
+/// ```
+#[wasm_bindgen(getter_with_clone)]
+#[derive(Debug, Clone)]
+pub struct StartOptions {
+    /// Folder, or on the web base URL, maps and other assets are read from.
+    /// Relative URLs resolve against the page.
+    pub asset_root: String,
+    /// Seed of the random numbers of debug effects, see [DebugRng]. Picked
+    /// at random when not set.
+    pub seed: Option<u32>,
+    /// Language of on-screen text, read from `lang/<language>.lang`, see
+    /// [LocalePlugin]. English when not set.
+    pub language: Option<String>,
+    /// Holds the camera still, see [Accessibility] for these settings.
+    pub reduced_motion: bool,
+    /// Draws the HUD in high contrast.
+    pub high_contrast: bool,
+    /// Factor of the size of the UI.
+    pub ui_scale: f32,
+    /// Keeps lightstyles from flashing.
+    pub safe_lightstyles: bool,
+    /// Quality profile, `high` or `low`, see [QualityPlugin]. Picked for the
+    /// device when not set.
+    pub quality: Option<String>,
+    /// Memory of the device in GiB, from `navigator.deviceMemory`, used to
+    /// pick the quality profile.
+    pub device_memory: Option<f32>,
+    /// Samples of multi-sample anti-aliasing, 1, 2, 4 or 8, instead of the
+    /// quality profile's.
+    pub msaa: Option<u32>,
+    /// Factor of the window size the world renders at, from 0.25 to 2,
+    /// instead of the quality profile's.
+    pub resolution_scale: Option<f32>,
+    /// Map shown first, instead of the first of the rotation.
+    pub map: Option<String>,
+    /// Seconds to benchmark the first map for, see [BenchmarkPlugin].
+    pub benchmark: Option<f32>,
+}
+
+#[wasm_bindgen]
+impl StartOptions {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self {
+            asset_root: ASSET_ROOT.to_string(),
+            seed: None,
+            language: None,
+            reduced_motion: false,
+            high_contrast: false,
+            ui_scale: 1.0,
+            safe_lightstyles: false,
+            quality: None,
+            device_memory: None,
+            msaa: None,
+            resolution_scale: None,
+            map: None,
+            benchmark: None,
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn start(canvas_id: &str) {
+    start_with(canvas_id, StartOptions::new());
+}
+
+#[wasm_bindgen]
+pub fn start_with(canvas_id: &str, options: StartOptions) {
+    let id = format!("#{}", canvas_id);
+
+    let profile = match options.quality.as_deref() {
+        None => None,
+        Some("high") => Some(QualityProfile::High),
+        Some("low") => Some(QualityProfile::Low),
+        Some(other) => {
+            warn!("Unknown quality profile {:?}, picking one", other);
+            None
+        }
+    };
+
+    let mut app = App::new();
+    // Replaces the default asset source, so it goes before DefaultPlugins
+    app.add_plugins(PakAssetPlugin {
+        root: options.asset_root,
+    });
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            canvas: Some(id.into()),
+            ..default()
+        }),
+        ..default()
+    }))
+    .init_asset::<BSP38Asset>()
+    .register_type::<BSP38Asset>()
+    .register_type::<MapSummary>()
+    .init_asset_loader::<BSP38AssetLoader>()
+    .init_asset_loader::<PcxAssetLoader>()
+    .init_asset_loader::<TgaAssetLoader>()
+    .init_asset::<Md2Asset>()
+    .init_asset_loader::<Md2AssetLoader>()
+    .add_plugins(Md2AnimationPlugin)
+    .add_plugins(RenderPlugin)
+    .add_plugins(SimulationPlugin)
+    .add_plugins(WindowModePlugin)
+    .add_plugins(WorkQueuePlugin)
+    .add_plugins(ClassnameSpawnPlugin)
+    .add_plugins(TargetGraphPlugin)
+    .add_plugins(EntityLabelPlugin)
+    .add_plugins(MapManagerPlugin)
+    .add_plugins(ReverbZonePlugin)
+    .add_plugins(MeasurePlugin)
+    .add_plugins(GridPlugin)
+    .add_plugins(MemoryStatsPlugin)
+    .add_plugins(JournalPlugin)
+    .add_plugins(SessionPlugin)
+    .add_plugins(AnnotationPlugin)
+    .add_plugins(RebuildPlugin)
+    .add_plugins(CameraControllerPlugin)
+    .add_plugins(PlayerPlugin)
+    .add_plugins(DebugRngPlugin { seed: options.seed })
+    .add_plugins(LocalePlugin {
+        language: options.language,
+    })
+    .add_plugins(QualityPlugin {
+        profile,
+        device_memory: options.device_memory,
+        msaa: options.msaa,
+        resolution_scale: options.resolution_scale,
+    })
+    .add_plugins(AccessibilityPlugin {
+        settings: Accessibility {
+            reduced_motion: options.reduced_motion,
+            high_contrast: options.high_contrast,
+            ui_scale: options.ui_scale,
+            safe_lightstyles: options.safe_lightstyles,
+        },
+    })
+    .init_resource::<State>()
+    .add_systems(Startup, (setup_camera, setup_lighting))
+    .add_systems(
+        Update,
+        (
+            update_assets, //
+            update_raycast.after(update_assets),
+        ),
+    );
+    if let Some(map) = options.map {
+        let mut maps = app.world_mut().resource_mut::<MapManager>();
+        maps.rotation.retain(|other| *other != map);
+        maps.rotation.insert(0, map);
+    }
+    if let Some(seconds) = options.benchmark {
+        app.add_plugins(BenchmarkPlugin { seconds });
+    }
+    // Shares the sizes of replaced textures with streaming
+    let overrides = TextureOverrides::default();
+    app.insert_resource(overrides.clone())
+        .register_asset_loader(WalAssetLoader::default().with_overrides(overrides));
+    #[cfg(not(target_arch = "wasm32"))]
+    app.add_plugins((
+        crate::record::RecorderPlugin,
+        crate::replay::InputReplayPlugin,
+        crate::tour::TourPlugin,
+    ));
+    app.run();
+}
+
+/// The decompressed PVS of a map, on its [MapInstance] entity.
+#[derive(Component)]
+pub struct Pvs(pub VisMatrix);
+
+/// Point queries and brush traces through the BSP tree of a map, on its
+/// [MapInstance] entity.
+#[derive(Component)]
+pub struct MapTracer(pub Tracer);
+
+/// Which area portals of a map are open, and the area of each PVS cluster,
+/// on its [MapInstance] entity. Areas behind closed portals are culled.
+#[derive(Component)]
+pub struct MapAreas {
+    pub portals: AreaPortalState,
+    pub cluster_areas: Vec<usize>,
+}
+
+/// A world mesh holding the faces of one texture in one or more PVS clusters.
+#[derive(Component)]
+pub struct WorldBatch {
+    pub clusters: Vec<i16>,
+    /// The grid cell of the faces, if the world was split into chunks.
+    pub chunk: Option<[i32; 3]>,
+    pub texture: String,
+    /// Surface flags of the texture, see [q2_formats::bsp38::surface].
+    pub flags: u32,
+    /// Whether the batch holds the texture's translucent faces, see
+    /// [MeshBuilder::build_translucent_batches](q2_formats::bsp38::prelude::MeshBuilder::build_translucent_batches).
+    pub translucent: bool,
+}
+
+/// Where the camera starts, and returns to when the map changes.
+pub(crate) fn camera_start() -> Transform {
+    Transform::from_xyz(-1275.0, 1300.0, 1250.0).looking_at(Vec3::ZERO, Vec3::Z)
+}
+
+fn setup_camera(mut commands: Commands) {
+    let transform = camera_start();
+    commands.spawn((
+        Camera3dBundle {
+            projection: Projection::Perspective(PerspectiveProjection {
+                near: 0.1,     // Set the near clipping plane
+                far: 10_000.0, // Set the far clipping plane
+                ..default()
+            }),
+            transform,
+            ..default()
+        },
+        Interpolated::new(transform),
+        SpatialListener::default(),
+    ));
+}
+
+/// Lighting shared by every map. The lights of each map are spawned by
+/// [MapLightPlugin](crate::render::MapLightPlugin).
+fn setup_lighting(mut commands: Commands) {
+    let light_direction = Vec3::new(-1.0, -1.0, -1.0).normalize();
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            illuminance: 100000.0,
+            shadows_enabled: false,
+            ..default()
+        },
+        transform: Transform::from_rotation(Quat::from_rotation_arc(Vec3::NEG_Z, light_direction)),
+        ..default()
+    });
+
+    if false {
+        commands.insert_resource(AmbientLight {
+            color: Color::WHITE,
+            ..default()
+        });
+    }
+}
+
+/// Spawns the world of each map instance once its asset has loaded, as
+/// children of the instance's root entity.
+#[allow(clippy::too_many_arguments)]
+fn update_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut state: ResMut<State>,
+    mut instanced: ResMut<InstancedAssets>,
+    mut stats: ResMut<OverlayStats>,
+    mut instances: Query<(Entity, &mut MapInstance)>,
+    bsp38_assets: Res<Assets<BSP38Asset>>,
+    asset_server: Res<AssetServer>,
+) {
+    for (root, mut instance) in instances.iter_mut() {
+        if instance.spawned {
+            continue;
+        }
+        let Some(asset) = bsp38_assets.get(&instance.handle) else {
+            continue;
+        };
+        info!("Asset loaded: {}", asset);
+        debug!("{:#?}", asset.bsp);
+        instance.spawned = true;
+        state.count = 0;
+        let mut timings = asset.timings.clone();
+        let mut children = Vec::new();
+
+        children.push(
+            commands
+                .spawn(PbrBundle {
+                    mesh: meshes.add(Circle::new(2000.0)),
+                    material: materials.add(Color::WHITE),
+                    transform: Transform::from_rotation(Quat::from_rotation_x(
+                        0.0, //-std::f32::consts::FRAC_PI_2,
+                    )),
+                    ..default()
+                })
+                .id(),
+        );
+
+        let vertices = asset.bsp.read_vertices().unwrap_or_default();
+
+        // Decode every PVS row up front so culling is a bit lookup
+        let pvs = timings.time("vis", || asset.bsp.read_vis_matrix().unwrap_or_default());
+        stats.set(
+            "pvs",
+            Message::new("pvs.summary")
+                .arg(pvs.num_clusters())
+                .arg(format!("{:.1}", pvs.memory_bytes() as f32 / 1024.0)),
+        );
+        commands.entity(root).insert((
+            Pvs(pvs),
+            MapTracer(asset.bsp.tracer()),
+            MapAreas {
+                portals: asset.bsp.area_portal_state(),
+                cluster_areas: asset.bsp.cluster_areas(),
+            },
+            asset.summary.clone(),
+        ));
+
+        let textures = asset.bsp.unique_textures();
+        stats.set(
+            "textures",
+            Message::new("textures.summary")
+                .arg(textures.len())
+                .arg(textures.iter().filter(|t| t.faces == 0).count()),
+        );
+
+        let offset = asset.world_offset();
+
+        // One mesh per (cluster, texture) batch, sharing a material per
+        // texture, showing its WAL image once that loads
+        let _spawn = info_span!("load_stage", stage = "spawn").entered();
+        let spawn_start = Instant::now();
+        let mut texture_materials: HashMap<(String, bool), Handle<StandardMaterial>> =
+            HashMap::new();
+        // A batch can hold faces from several atlas pages, but draws with one
+        let lightmap = match asset.lightmaps.as_slice() {
+            [page] => Some(page.clone()),
+            [] => None,
+            pages => {
+                warn!("{} lightmap pages, drawing without lightmaps", pages.len());
+                None
+            }
+        };
+        let mut spawn_batch = |commands: &mut Commands, batch: &WorldMesh, transform: Transform| {
+            let flags = textures
+                .iter()
+                .find(|t| t.name == batch.texture)
+                .map_or(0, |t| t.flags);
+            let material = texture_materials
+                .entry((batch.texture.clone(), batch.translucent))
+                .or_insert_with(|| {
+                    let (alpha, alpha_mode) = match batch.translucent {
+                        true => (
+                            SurfaceFlags::from_bits_retain(flags).alpha(),
+                            AlphaMode::Blend,
+                        ),
+                        false => (1.0, AlphaMode::Opaque),
+                    };
+                    let texture = texture_path(&instance.name, &batch.texture);
+                    materials.add(StandardMaterial {
+                        base_color: Color::WHITE.with_alpha(alpha),
+                        base_color_texture: Some(asset_server.load(texture)),
+                        alpha_mode,
+                        ..default()
+                    })
+                })
+                .clone();
+
+            let mut entity = commands.spawn((
+                PbrBundle {
+                    mesh: batch.mesh.clone(),
+                    material,
+                    transform,
+                    ..default()
+                },
+                WorldBatch {
+                    clusters: batch.clusters.clone(),
+                    chunk: batch.chunk,
+                    texture: batch.texture.clone(),
+                    flags,
+                    translucent: batch.translucent,
+                },
+            ));
+            if let Some(image) = &lightmap {
+                entity.insert(Lightmap {
+                    image: image.clone(),
+                    uv_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+                });
+            }
+            entity.id()
+        };
+        for batch in &asset.meshes {
+            children.push(spawn_batch(
+                &mut commands,
+                batch,
+                Transform::from_translation(offset),
+            ));
+        }
+
+        // Inline models get their own transform, so movers can animate them
+        for index in 0..asset.bsp.read_models().unwrap_or_default().len() {
+            let entity = asset
+                .summary
+                .entities
+                .iter()
+                .find(|entity| index > 0 && entity.model == Some(index));
+            // Models turning about an origin are compiled around it
+            let origin = entity.and_then(|entity| entity.origin).unwrap_or_default();
+            let model = commands
+                .spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(offset + origin)),
+                    BrushModel { index },
+                ))
+                .id();
+            if let Some(entity) = entity {
+                commands.entity(model).insert(BrushEntity {
+                    classname: entity.classname.clone(),
+                    targetname: entity.targetname.clone(),
+                });
+            }
+            let batches: Vec<Entity> = index
+                .checked_sub(1)
+                .and_then(|i| asset.inline_models.get(i))
+                .into_iter()
+                .flatten()
+                .map(|batch| spawn_batch(&mut commands, batch, Transform::IDENTITY))
+                .collect();
+            commands.entity(model).push_children(&batches);
+            children.push(model);
+        }
+
+        for v in vertices.chunks(3) {
+            let transform = Transform::from_translation(Vec3::from_slice(v) + offset);
+            let marker = commands.spawn(instanced.bundle(MARKER, transform, || {
+                marker_assets(&mut meshes, &mut materials)
+            }));
+            children.push(marker.id());
+        }
+        commands.entity(root).push_children(&children);
+        timings.0.push(("spawn", spawn_start.elapsed()));
+        stats.set("load", timings.to_string());
+        commands.entity(root).insert(timings);
+    }
+}
+
+/// Instancing key for the small red debug cubes.
+const MARKER: &str = "marker";
+
+fn marker_assets(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+    (
+        meshes.add(Cuboid::new(10.0, 10.0, 10.0)),
+        materials.add(Color::srgb(1.0, 0.15, 0.15)),
+    )
+}
+
+/// Traces random rays through the brushes of the maps, from -5000 to 5000
+/// in world space, and marks the first few hits with a cube.
+fn update_raycast(
+    mut state: ResMut<State>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut instanced: ResMut<InstancedAssets>,
+    mut debug_rng: ResMut<DebugRng>,
+    maps: Query<(&MapTracer, &MapSummary, &GlobalTransform)>,
+) {
+    use rand::Rng;
+
+    if state.count >= 5 {
+        return;
+    }
+    let rng = debug_rng.rng();
+    let p1 = Vec3::new(
+        rng.gen_range(-5000.0..5000.0),
+        rng.gen_range(-5000.0..5000.0),
+        rng.gen_range(-5000.0..5000.0),
+    )
+    .normalize()
+        * 5000.0;
+    let p2 = Vec3::new(
+        rng.gen_range(-5000.0..5000.0),
+        rng.gen_range(-5000.0..5000.0),
+        rng.gen_range(-5000.0..5000.0),
+    )
+    .normalize()
+        * 5000.0;
+
+    for (tracer, summary, root) in maps.iter() {
+        let to_local = root.affine().inverse();
+        let start = to_local.transform_point3(p1) - summary.world_offset();
+        let end = to_local.transform_point3(p2) - summary.world_offset();
+        let trace = tracer
+            .0
+            .trace_ray(start.to_array(), end.to_array(), MASK_SOLID);
+        if trace.fraction == 1.0 || trace.start_solid {
+            continue;
+        }
+        info!("Hit: {:?}", trace);
+        state.count += 1;
+
+        let pos = root.transform_point(Vec3::from(trace.end) + summary.world_offset());
+        commands.spawn((
+            instanced.bundle(MARKER, Transform::from_translation(pos), || {
+                marker_assets(&mut meshes, &mut materials)
+            }),
+            MapScoped,
+        ));
+    }
+}
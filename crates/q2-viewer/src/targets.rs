@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+use q2_formats::bsp38::prelude::{LinkKind, TargetGraph};
+
+use crate::asset::BSP38Asset;
+
+/// The target graph of the most recently loaded map, with entity positions
+/// in world space for drawing.
+#[derive(Resource, Default)]
+pub struct MapTargets {
+    pub graph: TargetGraph,
+    /// World position of each entity, or None for entities without an origin
+    /// (such as brush entities).
+    pub positions: Vec<Option<Vec3>>,
+    /// Draw the links as gizmo lines. Toggled with the T key.
+    pub show: bool,
+}
+
+pub struct TargetGraphPlugin;
+
+impl Plugin for TargetGraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapTargets>()
+            .add_systems(Update, (build_target_graph, toggle_links, draw_links));
+    }
+}
+
+fn build_target_graph(
+    mut events: EventReader<AssetEvent<BSP38Asset>>,
+    mut targets: ResMut<MapTargets>,
+    assets: Res<Assets<BSP38Asset>>,
+) {
+    for event in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = event else {
+            continue;
+        };
+        let Some(asset) = assets.get(*id) else {
+            continue;
+        };
+        let entities = asset.bsp.read_entities().unwrap_or_default();
+        let offset = asset.world_offset();
+
+        targets.graph = TargetGraph::new(&entities);
+        targets.positions = entities
+            .iter()
+            .map(|entity| entity.origin().map(|p| Vec3::from(p) + offset))
+            .collect();
+        for link in targets.graph.unresolved() {
+            warn!(
+                "Entity {} ({}) has {} {:?} but nothing has that targetname",
+                link.from,
+                entities[link.from].classname,
+                link.kind.key(),
+                link.name
+            );
+        }
+    }
+}
+
+fn toggle_links(keys: Res<ButtonInput<KeyCode>>, mut targets: ResMut<MapTargets>) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        targets.show = !targets.show;
+    }
+}
+
+fn draw_links(targets: Res<MapTargets>, mut gizmos: Gizmos) {
+    if !targets.show {
+        return;
+    }
+    for link in targets.graph.links() {
+        let (Some(Some(from)), Some(Some(to))) = (
+            targets.positions.get(link.from),
+            targets.positions.get(link.to),
+        ) else {
+            continue;
+        };
+        let color = match link.kind {
+            LinkKind::Target => Color::srgb(1.0, 0.85, 0.2),
+            LinkKind::KillTarget => Color::srgb(1.0, 0.2, 0.2),
+            LinkKind::PathTarget => Color::srgb(0.3, 0.8, 1.0),
+        };
+        gizmos.arrow(*from, *to, color);
+    }
+}
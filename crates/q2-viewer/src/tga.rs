@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use thiserror::Error;
+
+use q2_formats::tga::{Tga, TgaError};
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum TgaAssetLoaderError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid TGA: {0}")]
+    Tga(#[from] TgaError),
+}
+
+/// Loads .tga images, such as the `env/` skies, as RGBA [Image]s.
+///
+/// As in the engine, a .tga next to a .wal or .pcx of the same name takes
+/// its place, see [read_override].
+#[derive(Default)]
+pub struct TgaAssetLoader;
+
+impl AssetLoader for TgaAssetLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = TgaAssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Image, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(tga_image(&Tga::from_bytes(&bytes)?, 1))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tga"]
+    }
+}
+
+/// The .tga image overriding the image at `path`, if there is one next to
+/// it. One that doesn't decode is skipped with a warning.
+pub async fn read_override(path: &Path, load_context: &mut LoadContext<'_>) -> Option<Tga> {
+    let path = path.with_extension("tga");
+    let bytes = load_context.read_asset_bytes(path.clone()).await.ok()?;
+    Tga::from_bytes(&bytes)
+        .inspect_err(|err| warn!("{}: {}, not using it", path.display(), err))
+        .ok()
+}
+
+/// `tga` as an RGBA [Image] with up to `mip_levels` mip levels, each half
+/// the size of the one before.
+pub fn tga_image(tga: &Tga, mip_levels: u32) -> Image {
+    let size = Extent3d {
+        width: tga.width,
+        height: tga.height,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image::new(
+        size,
+        TextureDimension::D2,
+        tga.rgba.clone(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    let levels = mip_levels.min(size.max_mips(TextureDimension::D2));
+    let (mut level, mut width, mut height) = (tga.rgba.clone(), tga.width, tga.height);
+    for _ in 1..levels {
+        (level, width, height) = downsample(&level, width, height);
+        image.data.extend_from_slice(&level);
+    }
+    image.texture_descriptor.mip_level_count = levels;
+    image
+}
+
+/// Halves an RGBA image, averaging each 2x2 block of texels.
+fn downsample(rgba: &[u8], width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    let texel = |x: u32, y: u32| {
+        let at = ((y.min(height - 1) * width + x.min(width - 1)) * 4) as usize;
+        &rgba[at..at + 4]
+    };
+    let mut out = Vec::with_capacity((half_width * half_height * 4) as usize);
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let block = [
+                texel(2 * x, 2 * y),
+                texel(2 * x + 1, 2 * y),
+                texel(2 * x, 2 * y + 1),
+                texel(2 * x + 1, 2 * y + 1),
+            ];
+            out.extend((0..4).map(|c| {
+                let sum: u32 = block.iter().map(|texel| texel[c] as u32).sum();
+                ((sum + 2) / 4) as u8
+            }));
+        }
+    }
+    (out, half_width, half_height)
+}
@@ -0,0 +1,329 @@
+//! Screenshot tours: the camera stops at each waypoint of a map, a screenshot
+//! is saved at each stop, and a JSON manifest and an HTML gallery of them are
+//! written next to the images.
+//!
+//! Only available on native, since it writes to disk. F8 tours the current
+//! map into `tours/<map>/`, along its `path_corner` chains, or around the
+//! whole map if it has none.
+
+use std::{f32::consts::TAU, fmt::Write as _, io, path::PathBuf};
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+
+use q2_formats::bsp38::prelude::{Entity as MapEntity, LinkKind, TargetGraph};
+
+use crate::{
+    asset::BSP38Asset,
+    framing::{frame_map, OVERVIEW_PITCH},
+    maps::{MapInstance, MapManager},
+    sim::{interpolate, Interpolated},
+};
+
+/// Height of the camera above a `path_corner`, which sits near the floor
+/// for the monsters and trains walking the path.
+const EYE_HEIGHT: f32 = 48.0;
+
+/// Stops of a tour around the whole map, when it has no path corners.
+const OVERVIEW_STOPS: usize = 8;
+
+/// Tours the map while a [Tour] resource exists, then removes it.
+pub struct TourPlugin;
+
+impl Plugin for TourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (tour_key, advance_tour).chain())
+            .add_systems(PostUpdate, drive_camera.before(interpolate));
+    }
+}
+
+/// A waypoint of a [Tour].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TourStop {
+    /// Caption of the screenshot.
+    pub name: String,
+    pub transform: Transform,
+}
+
+/// Stops along the `path_corner` chains of `entities`, moved by `offset`
+/// into the world. Each chain starts at a corner no other corner leads to
+/// (or at its first corner, for loops) and follows their `target` keys,
+/// looking towards the next corner.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use q2_formats::bsp38::prelude::parse_entities;
+/// # use q2_viewer::tour::path_corner_stops;
+/// let entities = parse_entities(
+///     r#"{ "classname" "path_corner" "targetname" "b" "origin" "100 0 0" }
+///        { "classname" "path_corner" "targetname" "a" "target" "b" "origin" "0 0 0" }"#,
+/// );
+/// let stops = path_corner_stops(&entities, Vec3::ZERO);
+/// let names: Vec<&str> = stops.iter().map(|stop| stop.name.as_str()).collect();
+/// assert_eq!(names, ["a", "b"]);
+/// assert_eq!(stops[0].transform.translation, Vec3::new(0.0, 0.0, 48.0));
+/// assert!(stops[0].transform.forward().x > 0.99);
+/// ```
+pub fn path_corner_stops(entities: &[MapEntity], offset: Vec3) -> Vec<TourStop> {
+    let graph = TargetGraph::new(entities);
+    let is_corner =
+        |i: usize| entities[i].classname == "path_corner" && entities[i].origin().is_some();
+    let next = |i: usize| {
+        graph
+            .targets_of(i)
+            .find(|link| link.kind == LinkKind::Target && is_corner(link.to))
+            .map(|link| link.to)
+    };
+    let corners: Vec<usize> = (0..entities.len()).filter(|&i| is_corner(i)).collect();
+    let mut starts: Vec<usize> = corners
+        .iter()
+        .copied()
+        .filter(|&i| !corners.iter().any(|&from| next(from) == Some(i)))
+        .collect();
+    // Loops have no start, so each begins at its first corner
+    starts.extend(corners.iter().copied());
+
+    let mut visited = vec![false; entities.len()];
+    let mut order = Vec::new();
+    for start in starts {
+        let mut corner = Some(start);
+        while let Some(i) = corner.filter(|&i| !visited[i]) {
+            visited[i] = true;
+            order.push(i);
+            corner = next(i);
+        }
+    }
+
+    let position = |i: usize| {
+        Vec3::from(entities[i].origin().unwrap_or_default()) + offset + Vec3::Z * EYE_HEIGHT
+    };
+    order
+        .iter()
+        .enumerate()
+        .map(|(k, &i)| {
+            let eye = position(i);
+            // Look along the path, or on from the previous corner at its end
+            let ahead = match (next(i), k.checked_sub(1).map(|k| order[k])) {
+                (Some(to), _) => position(to) - eye,
+                (None, Some(from)) => eye - position(from),
+                (None, None) => Vec3::X,
+            };
+            let ahead = Vec3::new(ahead.x, ahead.y, 0.0).try_normalize();
+            let name = entities[i]
+                .targetname()
+                .map_or_else(|| format!("path_corner {}", k + 1), str::to_string);
+            TourStop {
+                name,
+                transform: Transform::from_translation(eye)
+                    .looking_to(ahead.unwrap_or(Vec3::X), Vec3::Z),
+            }
+        })
+        .collect()
+}
+
+/// A tour in progress. Insert it as a resource to start it; it removes
+/// itself once every screenshot has been requested.
+#[derive(Resource)]
+pub struct Tour {
+    /// Name of the map, for the manifest.
+    map: String,
+    stops: Vec<TourStop>,
+    dir: PathBuf,
+    /// Frames to wait at each stop before the screenshot, so textures have
+    /// time to stream in.
+    pub settle_frames: usize,
+    /// Index of the current stop.
+    stop: usize,
+    /// Frames spent at the current stop.
+    frame: usize,
+}
+
+impl Tour {
+    /// A tour of `map` along `stops`, saving into `dir`, which is created if
+    /// missing.
+    pub fn new(map: &str, stops: Vec<TourStop>, dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            map: map.to_string(),
+            stops,
+            dir,
+            settle_frames: 30,
+            stop: 0,
+            frame: 0,
+        })
+    }
+
+    fn file_name(index: usize) -> String {
+        format!("stop_{:03}.png", index)
+    }
+
+    /// The manifest of the tour: the map, and the image, caption, position
+    /// and rotation of each stop.
+    pub fn manifest_json(&self) -> String {
+        let mut json = format!("{{\n  \"map\": {},\n  \"stops\": [", json_string(&self.map));
+        for (i, stop) in self.stops.iter().enumerate() {
+            let [x, y, z] = stop.transform.translation.to_array();
+            let [qx, qy, qz, qw] = stop.transform.rotation.to_array();
+            let _ = write!(
+                json,
+                "{}\n    {{ \"image\": {}, \"name\": {}, \"position\": [{}, {}, {}], \"rotation\": [{}, {}, {}, {}] }}",
+                if i == 0 { "" } else { "," },
+                json_string(&Self::file_name(i)),
+                json_string(&stop.name),
+                x, y, z, qx, qy, qz, qw,
+            );
+        }
+        json.push_str("\n  ]\n}\n");
+        json
+    }
+
+    /// A page showing the screenshots with their captions.
+    pub fn gallery_html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>body {{ font-family: sans-serif; background: #111; color: #eee; }} \
+             figure {{ display: inline-block; margin: 8px; }} img {{ width: 480px; }}</style>\n\
+             </head>\n<body>\n<h1>{}</h1>\n",
+            html_escape(&self.map),
+            html_escape(&self.map)
+        );
+        for (i, stop) in self.stops.iter().enumerate() {
+            let _ = writeln!(
+                html,
+                "<figure><img src=\"{}\" alt=\"{name}\"><figcaption>{name}</figcaption></figure>",
+                Self::file_name(i),
+                name = html_escape(&stop.name),
+            );
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn write_manifest(&self) -> io::Result<()> {
+        std::fs::write(self.dir.join("tour.json"), self.manifest_json())?;
+        std::fs::write(self.dir.join("index.html"), self.gallery_html())
+    }
+}
+
+/// `text` as a JSON string literal.
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Tours the current map on F8.
+fn tour_key(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    maps: Res<MapManager>,
+    instances: Query<&MapInstance>,
+    assets: Res<Assets<BSP38Asset>>,
+    cameras: Query<&Projection, With<Interpolated>>,
+    tour: Option<Res<Tour>>,
+) {
+    if !keys.just_pressed(KeyCode::F8) || tour.is_some() {
+        return;
+    }
+    let Some(asset) = maps
+        .root()
+        .and_then(|root| instances.get(root).ok())
+        .and_then(|instance| assets.get(&instance.handle))
+    else {
+        warn!("No map loaded to tour");
+        return;
+    };
+    let mut stops = path_corner_stops(
+        &asset.bsp.read_entities().unwrap_or_default(),
+        asset.world_offset(),
+    );
+    if stops.is_empty() {
+        let projection = match cameras.iter().next() {
+            Some(Projection::Perspective(perspective)) => perspective.clone(),
+            _ => PerspectiveProjection::default(),
+        };
+        let bounds = asset.summary.world_bounds();
+        stops = (0..OVERVIEW_STOPS)
+            .map(|i| TourStop {
+                name: format!("overview {}", i + 1),
+                transform: frame_map(
+                    &bounds,
+                    OVERVIEW_PITCH,
+                    i as f32 / OVERVIEW_STOPS as f32 * TAU,
+                    &projection,
+                ),
+            })
+            .collect();
+    }
+
+    let name = maps.current().unwrap_or("map");
+    match Tour::new(name, stops, PathBuf::from("tours").join(name)) {
+        Ok(tour) => {
+            info!("Touring {} stops of {}", tour.stops.len(), name);
+            commands.insert_resource(tour);
+        }
+        Err(err) => error!("Could not start tour: {}", err),
+    }
+}
+
+/// Takes the screenshot of each stop once it has settled, and writes the
+/// manifest after the last one.
+fn advance_tour(
+    mut commands: Commands,
+    tour: Option<ResMut<Tour>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+) {
+    let Some(mut tour) = tour else {
+        return;
+    };
+    if tour.stop >= tour.stops.len() {
+        match tour.write_manifest() {
+            Ok(()) => info!("Wrote tour of {} to {}", tour.map, tour.dir.display()),
+            Err(err) => error!("Could not write tour manifest: {}", err),
+        }
+        commands.remove_resource::<Tour>();
+        return;
+    }
+    tour.frame += 1;
+    if tour.frame < tour.settle_frames {
+        return;
+    }
+    let Some(window) = windows.iter().next() else {
+        return;
+    };
+    let path = tour.dir.join(Tour::file_name(tour.stop));
+    // Only one screenshot can be pending per window, so retry next frame
+    if screenshots.save_screenshot_to_disk(window, path).is_ok() {
+        tour.stop += 1;
+        tour.frame = 0;
+    }
+}
+
+/// Holds the main camera at the current stop. Runs after the fixed steps,
+/// so it wins over whatever animates the camera there.
+fn drive_camera(tour: Option<Res<Tour>>, mut cameras: Query<&mut Interpolated, With<Camera>>) {
+    let Some(stop) = tour.as_ref().and_then(|tour| tour.stops.get(tour.stop)) else {
+        return;
+    };
+    for mut interpolated in cameras.iter_mut() {
+        *interpolated = Interpolated::new(stop.transform);
+    }
+}
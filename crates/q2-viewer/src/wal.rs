@@ -0,0 +1,160 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{ImageAddressMode, ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    },
+    utils::HashMap,
+};
+use thiserror::Error;
+
+use q2_formats::wal::{Palette, Wal, WalError, MIP_LEVELS};
+
+use crate::tga::{read_override, tga_image};
+
+/// Where the palette lives, relative to the game directory holding
+/// `textures/`.
+const PALETTE_PATH: &str = "pics/colormap.pcx";
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum WalAssetLoaderError {
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid WAL: {0}")]
+    Wal(#[from] WalError),
+}
+
+/// Loads .wal textures as RGBA [Image]s with all four mip levels, colored
+/// through the palette of `pics/colormap.pcx` next to the `textures/` folder
+/// the texture is in (also inside a .pak, see [crate::pak]). Without a
+/// palette the textures load in grayscale.
+///
+/// The images repeat, as faces tile their textures. Face UVs from
+/// [q2_formats::bsp38::BSP38::read_faces] are in texels, so materials scale
+/// them by the image size.
+///
+/// As in the engine, a .tga of the same name next to the .wal replaces it,
+/// usually at a higher resolution. Its UVs are still in texels of the .wal,
+/// recorded in [TextureOverrides].
+#[derive(Default)]
+pub struct WalAssetLoader {
+    /// Palettes by path, read once per game directory.
+    palettes: Mutex<HashMap<PathBuf, Arc<Palette>>>,
+    overrides: TextureOverrides,
+}
+
+/// Sizes of the .wal textures a .tga has replaced, by asset path, shared
+/// with the [WalAssetLoader] so materials can scale face UVs by them rather
+/// than by the size of the image.
+#[derive(Resource, Clone, Default)]
+pub struct TextureOverrides(Arc<Mutex<HashMap<PathBuf, UVec2>>>);
+
+impl TextureOverrides {
+    /// Size of the .wal at `path`, if a .tga has replaced it.
+    pub fn wal_size(&self, path: &Path) -> Option<UVec2> {
+        self.0.lock().unwrap().get(path).copied()
+    }
+}
+
+impl WalAssetLoader {
+    /// Records the textures replaced by a .tga in `overrides`.
+    pub fn with_overrides(mut self, overrides: TextureOverrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    async fn palette(&self, texture: &Path, load_context: &mut LoadContext<'_>) -> Arc<Palette> {
+        let game_dir: PathBuf = texture
+            .components()
+            .take_while(|c| !c.as_os_str().eq_ignore_ascii_case("textures"))
+            .collect();
+        let path = game_dir.join(PALETTE_PATH);
+        let cached = self.palettes.lock().unwrap().get(&path).cloned();
+        if let Some(palette) = cached {
+            return palette;
+        }
+
+        let palette = match load_context.read_asset_bytes(path.clone()).await {
+            Ok(bytes) => Palette::from_pcx(&bytes).unwrap_or_else(|err| {
+                warn!("{}: {}, using a grayscale palette", path.display(), err);
+                Palette::grayscale()
+            }),
+            Err(err) => {
+                warn!("{}, using a grayscale palette", err);
+                Palette::grayscale()
+            }
+        };
+        let palette = Arc::new(palette);
+        self.palettes.lock().unwrap().insert(path, palette.clone());
+        palette
+    }
+}
+
+impl AssetLoader for WalAssetLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = WalAssetLoaderError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<Image, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let wal = Wal::from_bytes(&bytes)?;
+        let path = load_context.path().to_path_buf();
+        let sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            address_mode_v: ImageAddressMode::Repeat,
+            mipmap_filter: ImageFilterMode::Linear,
+            ..ImageSamplerDescriptor::linear()
+        });
+
+        if let Some(tga) = read_override(&path, load_context).await {
+            let mut image = tga_image(&tga, MIP_LEVELS as u32);
+            image.sampler = sampler;
+            self.overrides
+                .0
+                .lock()
+                .unwrap()
+                .insert(path, UVec2::new(wal.width, wal.height));
+            return Ok(image);
+        }
+
+        let palette = self.palette(&path, load_context).await;
+
+        let size = Extent3d {
+            width: wal.width,
+            height: wal.height,
+            depth_or_array_layers: 1,
+        };
+        let mut image = Image::new(
+            size,
+            TextureDimension::D2,
+            wal.to_rgba(0, &palette),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        // Mip levels follow each other in the image data
+        for level in 1..MIP_LEVELS {
+            image.data.extend(wal.to_rgba(level, &palette));
+        }
+        image.texture_descriptor.mip_level_count = MIP_LEVELS as u32;
+        image.sampler = sampler;
+        Ok(image)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wal"]
+    }
+}
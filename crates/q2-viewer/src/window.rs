@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+
+/// Size used when the canvas size can't be read.
+const FALLBACK_RESOLUTION: (f32, f32) = (1280.0, 720.0);
+
+/// Sets up the primary window and toggles fullscreen with F11.
+///
+/// On the web, fullscreen puts the canvas in browser fullscreen and renders
+/// at the screen's size, so the HUD stays crisp instead of being scaled up.
+/// Pages can also call the exported `toggle_fullscreen(canvas_id)`, e.g.
+/// from a button.
+pub struct WindowModePlugin;
+
+impl Plugin for WindowModePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_window)
+            .add_systems(Update, toggle_fullscreen);
+        #[cfg(target_arch = "wasm32")]
+        app.add_systems(Update, platform::sync_fullscreen_size);
+    }
+}
+
+fn setup_window(mut windows: Query<&mut Window>) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        warn!("No primary window to set up");
+        return;
+    };
+    platform::setup(&mut window);
+}
+
+fn toggle_fullscreen(keys: Res<ButtonInput<KeyCode>>, mut windows: Query<&mut Window>) {
+    if !keys.just_pressed(KeyCode::F11) {
+        return;
+    }
+    if let Ok(mut window) = windows.get_single_mut() {
+        platform::toggle_fullscreen(&mut window);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use bevy::prelude::*;
+    use wasm_bindgen::JsCast;
+    use web_sys::HtmlCanvasElement;
+
+    use super::FALLBACK_RESOLUTION;
+
+    /// Sizes the window to match the canvas it renders into.
+    pub fn setup(window: &mut Window) {
+        let size = window
+            .canvas
+            .as_deref()
+            .ok_or_else(|| "no canvas selector".to_string())
+            .and_then(|selector| canvas_size(selector.trim_start_matches('#')));
+        let (width, height) = size.unwrap_or_else(|err| {
+            warn!(
+                "Could not read the canvas size ({}), using {}x{}",
+                err, FALLBACK_RESOLUTION.0, FALLBACK_RESOLUTION.1
+            );
+            FALLBACK_RESOLUTION
+        });
+
+        window.resolution.set(width, height);
+        window.resizable = false;
+    }
+
+    fn canvas_size(id: &str) -> Result<(f32, f32), String> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or_else(|| "no document".to_string())?;
+        let canvas = document
+            .get_element_by_id(id)
+            .ok_or_else(|| format!("no element with id {:?}", id))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| format!("element {:?} is not a canvas", id))?;
+        Ok((canvas.width() as f32, canvas.height() as f32))
+    }
+
+    /// Fullscreens the canvas through the browser rather than winit, which
+    /// needs to happen while handling the key press.
+    pub fn toggle_fullscreen(window: &mut Window) {
+        let selector = window.canvas.as_deref().unwrap_or_default();
+        super::toggle_fullscreen_js(selector.trim_start_matches('#'));
+    }
+
+    /// Matches the render size to the screen while the canvas is fullscreen,
+    /// restoring the canvas size afterwards.
+    pub fn sync_fullscreen_size(
+        mut windows: Query<&mut Window>,
+        mut windowed: Local<Option<(f32, f32)>>,
+    ) {
+        let Ok(mut window) = windows.get_single_mut() else {
+            return;
+        };
+        let Some(browser) = web_sys::window() else {
+            return;
+        };
+        let fullscreen = browser
+            .document()
+            .and_then(|document| document.fullscreen_element())
+            .is_some();
+
+        match *windowed {
+            None if fullscreen => {
+                *windowed = Some((window.width(), window.height()));
+                let size = |value: Result<wasm_bindgen::JsValue, _>| {
+                    value.ok().and_then(|v| v.as_f64()).unwrap_or(0.0) as f32
+                };
+                let (width, height) = (size(browser.inner_width()), size(browser.inner_height()));
+                if width > 0.0 && height > 0.0 {
+                    window.resolution.set(width, height);
+                }
+            }
+            Some((width, height)) if !fullscreen => {
+                window.resolution.set(width, height);
+                *windowed = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Toggles browser fullscreen for the canvas with the given id.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(js_name = toggle_fullscreen)]
+pub fn toggle_fullscreen_js(canvas_id: &str) {
+    let Some(document) = web_sys::window().and_then(|window| window.document()) else {
+        return;
+    };
+    if document.fullscreen_element().is_some() {
+        document.exit_fullscreen();
+        return;
+    }
+    let result = document
+        .get_element_by_id(canvas_id)
+        .ok_or_else(|| format!("no element with id {:?}", canvas_id))
+        .and_then(|canvas| {
+            canvas
+                .request_fullscreen()
+                .map_err(|err| format!("{:?}", err))
+        });
+    if let Err(err) = result {
+        warn!("Could not enter fullscreen: {}", err);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use bevy::{prelude::*, window::WindowMode};
+
+    use super::FALLBACK_RESOLUTION;
+
+    /// Native windows have no canvas: keep the size winit opens the window
+    /// with, falling back to a default if it reports none.
+    pub fn setup(window: &mut Window) {
+        if window.width() <= 0.0 || window.height() <= 0.0 {
+            window
+                .resolution
+                .set(FALLBACK_RESOLUTION.0, FALLBACK_RESOLUTION.1);
+        }
+        window.resizable = true;
+        info!("Window size {}x{}", window.width(), window.height());
+    }
+
+    pub fn toggle_fullscreen(window: &mut Window) {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::Instant;
+
+use crate::render::OverlayStats;
+
+/// Result of running one step of a [Job].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    /// The job has more work and wants to be stepped again.
+    Pending,
+    Done,
+}
+
+/// A long-running piece of work split into small steps.
+///
+/// Each step should do a bounded amount of work (a few milliseconds at most),
+/// since the queue can only check its budget between steps.
+pub trait Job: Send + Sync + 'static {
+    fn step(&mut self, world: &mut World) -> JobStatus;
+}
+
+impl<F> Job for F
+where
+    F: FnMut(&mut World) -> JobStatus + Send + Sync + 'static,
+{
+    fn step(&mut self, world: &mut World) -> JobStatus {
+        self(world)
+    }
+}
+
+/// Background jobs processed in order under a per-frame time budget, so long
+/// map processing (lightmap packing, navmesh generation, thumbnails) never
+/// hitches the render loop.
+#[derive(Resource)]
+pub struct WorkQueue {
+    pub budget: Duration,
+    jobs: VecDeque<Box<dyn Job>>,
+}
+
+impl Default for WorkQueue {
+    fn default() -> Self {
+        Self {
+            budget: Duration::from_millis(4),
+            jobs: VecDeque::new(),
+        }
+    }
+}
+
+impl WorkQueue {
+    pub fn push(&mut self, job: impl Job) {
+        self.jobs.push_back(Box::new(job));
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+pub struct WorkQueuePlugin;
+
+impl Plugin for WorkQueuePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorkQueue>()
+            .add_systems(Update, process_work_queue);
+    }
+}
+
+/// Steps queued jobs until the frame budget runs out. At least one step runs
+/// every frame so work always progresses.
+fn process_work_queue(world: &mut World) {
+    let start = Instant::now();
+    let (mut jobs, budget) = {
+        let mut queue = world.resource_mut::<WorkQueue>();
+        (std::mem::take(&mut queue.jobs), queue.budget)
+    };
+
+    while let Some(job) = jobs.front_mut() {
+        if job.step(world) == JobStatus::Done {
+            jobs.pop_front();
+        }
+        if start.elapsed() >= budget {
+            break;
+        }
+    }
+
+    // Jobs queued by the steps above run after the ones already waiting
+    let mut queue = world.resource_mut::<WorkQueue>();
+    jobs.append(&mut queue.jobs);
+    queue.jobs = jobs;
+
+    let pending = queue.len();
+    let mut stats = world.resource_mut::<OverlayStats>();
+    if pending > 0 {
+        stats.set("jobs", pending.to_string());
+    } else {
+        stats.remove("jobs");
+    }
+}
@@ -1,3 +0,0 @@
-mod bsp38;
-mod render;
-mod start;